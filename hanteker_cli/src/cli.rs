@@ -1,9 +1,30 @@
-use clap::{Args, CommandFactory, Parser, Subcommand};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{ArgEnum, ArgGroup, Args, CommandFactory, Parser, Subcommand};
 use clap_complete::Shell;
 
+use hanteker_lib::capture::AcquisitionMode;
+use hanteker_lib::detect::Detect;
 use hanteker_lib::device::cfg::{
     AwgType, Coupling, DeviceFunction, Probe, Scale, TimeScale, TriggerMode, TriggerSlope,
 };
+use hanteker_lib::dsp::{Decimation, FilterSpec, Window};
+use hanteker_lib::encode::Protocol;
+use hanteker_lib::export::ExportFormat;
+use hanteker_lib::math::MathExpr;
+use hanteker_lib::measure::Stat;
+use hanteker_lib::models::hantek2d42::{
+    awg_frequency_max, AWG_AMPLITUDE_MAX, AWG_DUTY_MAX, AWG_DUTY_MIN, AWG_FREQUENCY_MIN,
+    AWG_OFFSET_MAX, CAPTURE_PACKET,
+};
+
+use crate::exit::{machine_error, EXIT_USAGE};
+use crate::rotate::parse_size;
+use crate::sinks::SinkSpec;
+use crate::udev::RULE_PATH;
+
+const NUM_CHANNELS: usize = 2;
 
 /// A cli tool to interface with Hantek oscilloscope
 #[derive(Parser, Debug)]
@@ -16,6 +37,44 @@ pub(crate) struct Cli {
     #[clap(long, default_value_t = 1000)]
     pub(crate) timeout: u64,
 
+    /// Timeout of sending commands in milliseconds, defaults to --timeout. Low to fail fast
+    #[clap(long)]
+    pub(crate) write_timeout: Option<u64>,
+
+    /// Timeout of reading capture data in milliseconds, defaults to --timeout. Long time scales
+    /// need more
+    #[clap(long)]
+    pub(crate) read_timeout: Option<u64>,
+
+    /// Bytes of a capture read with each capture command, a multiple of 64. Sizes other than 64
+    /// are experimental, see the bench subcommand
+    #[clap(long, default_value_t = CAPTURE_PACKET)]
+    pub(crate) capture_packet: usize,
+
+    /// Use the device with this serial number, or at this bus:address as printed by `status`,
+    /// e.g. 001:004, when several are connected. Given more than once, the command runs on each
+    /// device in parallel, every line of output prefixed with the device. Paths written to must
+    /// then have {device} in them, replaced by the device
+    #[clap(long = "device", value_name = "DEVICE", multiple_occurrences = true)]
+    pub(crate) devices: Vec<String>,
+
+    /// Run the command on every device connected in parallel, see --device
+    #[clap(long, conflicts_with = "devices")]
+    pub(crate) all_devices: bool,
+
+    /// Claim this USB interface, and use its bulk endpoints, rather than the one found to own
+    /// the bulk endpoints. For unusual firmware
+    #[clap(long)]
+    pub(crate) interface: Option<u8>,
+
+    /// Retries of a USB transfer failing with a stall or a timeout
+    #[clap(long, default_value_t = 0)]
+    pub(crate) usb_retries: u32,
+
+    /// Wait before the first retry of a USB transfer, doubled on every further one
+    #[clap(long, default_value = "10ms", parse(try_from_str = humantime::parse_duration))]
+    pub(crate) usb_backoff: Duration,
+
     /// Specify multiple time to increase log level from info
     #[clap(short, long, parse(from_occurrences))]
     pub(crate) verbose: usize,
@@ -24,9 +83,42 @@ pub(crate) struct Cli {
     #[clap(short, long, parse(from_occurrences))]
     pub(crate) silent: usize,
 
+    /// Log levels of single targets on top of --verbose and --silent, comma separated, e.g.
+    /// hanteker::usb=trace,hanteker::capture=debug. The lib logs to hanteker::usb,
+    /// hanteker::capture, hanteker::awg and hanteker::device
     #[clap(long)]
-    /// Suppress warnings about UI quirks
+    pub(crate) log_filter: Option<String>,
+
+    /// Json writes a line per record with its time, level, target and message
+    #[clap(long, arg_enum, default_value = "text")]
+    pub(crate) log_format: LogFormat,
+
+    /// Append the log to this file instead of writing it to stderr
+    #[clap(long)]
+    pub(crate) log_file: Option<PathBuf>,
+
+    /// On failure only write a json object with the exit code, its class and the message to
+    /// stderr. The exit codes are the same either way, see the README
+    #[clap(long, alias = "quiet-errors")]
+    pub(crate) machine: bool,
+
+    #[clap(long)]
+    /// Suppress warnings about UI quirks, `print --quirks` lists them
     pub(crate) no_quirks: bool,
+
+    /// Print the latency of each command sent to the device to stderr when done
+    #[clap(long)]
+    pub(crate) timing: bool,
+
+    /// Log every command sent to the device, with the names of its codes where known, and the
+    /// length of every read
+    #[clap(long)]
+    pub(crate) trace_usb: bool,
+
+    /// On Ctrl-C or another terminating signal only release the interface, leaving the scope
+    /// and AWG running. They are still stopped on a crash
+    #[clap(long)]
+    pub(crate) keep_running: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -41,7 +133,51 @@ pub(crate) enum Commands {
     Channel(ChannelCli),
 
     /// Capture scope channels
-    Capture(CaptureCli),
+    Capture(Box<CaptureCli>),
+
+    /// Capture a channel and print measurements of it
+    Measure(MeasureCli),
+
+    /// Count pulses of a channel over a gate time, printing frequency and duty cycle
+    Counter(CounterCli),
+
+    /// Capture a channel and print its frequency spectrum
+    Spectrum(SpectrumCli),
+
+    /// Sweep settings as declared in a TOML plan, measuring a channel at each point
+    Sweep(SweepCli),
+
+    /// Capture and decode a serial protocol
+    Decode(DecodeCli),
+
+    /// Sweep the AWG through a circuit and print its gain and phase at each frequency as CSV
+    Bode(BodeCli),
+
+    /// Set every time scale and channel scale, reporting the ones the device rejects
+    Verify(VerifyCli),
+
+    /// Time captures and print the samples per second achieved, for each size of the reads
+    Bench(BenchCli),
+
+    /// Guided check of probe compensation on a square wave from the generator
+    #[clap(alias = "probe-comp")]
+    ProbeCheck(ProbeCheckCli),
+
+    /// Block until a channel crosses a level, exits with 124 on timeout
+    Wait(WaitCli),
+
+    /// Plot captured channels in the terminal
+    Plot(PlotCli),
+
+    /// Interactive front panel with live waveform and settings
+    Tui(TuiCli),
+
+    /// Expose the device over the network
+    Serve(ServeCli),
+
+    /// Send a command by its codes and hex dump what goes back and forth, for exploring the
+    /// protocol
+    Raw(RawCli),
 
     /// Operate on AWG function of the device
     Awg(AwgCli),
@@ -49,12 +185,39 @@ pub(crate) enum Commands {
     /// Print device info
     Print(PrintCli),
 
+    /// Check the device is connected, can be claimed and answers a capture, exiting non-zero
+    /// with a one line reason if not
+    Status(StatusCli),
+
+    /// Snapshot the config, or diff two snapshots
+    Config(ConfigCli),
+
+    /// Run the commands of a script in turn, opening the device once. A line holds a
+    /// subcommand with its options, `sleep <duration>` pauses and `#` starts a comment
+    Run(RunCli),
+
+    /// Wait for the device to be connected and apply a profile to it, again on every
+    /// reconnect, until interrupted
+    Watch(WatchCli),
+
+    /// Run a rhai script driving the device, for sequences with loops and conditions
+    #[cfg(feature = "rhai")]
+    Script(ScriptCli),
+
     /// Generate shell completion script.
     Shell(ShellCli),
+
+    /// Print the udev rule letting non-root users open the device on Linux
+    SetupUdev(SetupUdevCli),
 }
 
 #[derive(Args, Debug)]
 pub(crate) struct DeviceCli {
+    /// USB reset of the device before anything else, to recover one that stopped answering
+    /// without power cycling it
+    #[clap(long)]
+    pub(crate) reset: bool,
+
     #[clap(long)]
     pub(crate) start: bool,
 
@@ -71,12 +234,17 @@ pub(crate) struct ScopeCli {
     #[clap(short, long)]
     pub(crate) force_mode: bool,
 
-    #[clap(long, arg_enum)]
+    #[clap(long, parse(try_from_str = parse_time_scale))]
     pub(crate) time_scale: Option<TimeScale>,
 
     #[clap(long)]
     pub(crate) time_offset: Option<f32>,
 
+    /// Trigger point in percent of the screen width from the left, 50 being the center. The
+    /// signal left of it is from before the trigger
+    #[clap(long, conflicts_with = "time-offset")]
+    pub(crate) trigger_position: Option<f32>,
+
     #[clap(long, value_name = "CHANNEL")]
     pub(crate) trigger_source: Option<usize>,
 
@@ -86,8 +254,14 @@ pub(crate) struct ScopeCli {
     #[clap(long, arg_enum)]
     pub(crate) trigger_mode: Option<TriggerMode>,
 
-    #[clap(long)]
+    /// Trigger level in volts, needs the trigger source and its scale known, e.g. set in the
+    /// same invocation
+    #[clap(long, allow_hyphen_values = true)]
     pub(crate) trigger_level: Option<f32>,
+
+    /// Trigger level in percent of the screen height from the bottom, needs nothing else known
+    #[clap(long, conflicts_with = "trigger-level")]
+    pub(crate) trigger_level_percent: Option<f32>,
 }
 
 #[derive(Args, Debug)]
@@ -96,8 +270,9 @@ pub(crate) struct ChannelCli {
     #[clap(short, long)]
     pub(crate) force_mode: bool,
 
-    #[clap(short, long, possible_values = ["1", "2"])]
-    pub(crate) channel: usize,
+    /// Channel to set, 1, 2 or all of them. May be repeated
+    #[clap(short, long, required = true, parse(try_from_str = parse_channel_selection))]
+    pub(crate) channel: Vec<ChannelSelection>,
 
     #[clap(long, group = "channel-status")]
     pub(crate) enable: bool,
@@ -111,12 +286,17 @@ pub(crate) struct ChannelCli {
     #[clap(long, arg_enum)]
     pub(crate) probe: Option<Probe>,
 
-    #[clap(long, arg_enum)]
+    #[clap(long, parse(try_from_str = parse_scale))]
     pub(crate) scale: Option<Scale>,
 
-    #[clap(long)]
+    /// Offset in volts, needs the scale set in the same invocation
+    #[clap(long, allow_hyphen_values = true, conflicts_with = "offset-raw")]
     pub(crate) offset: Option<f32>,
 
+    /// Offset as the device takes it, 0..=200 with 100 at the center of the screen
+    #[clap(long)]
+    pub(crate) offset_raw: Option<u8>,
+
     #[clap(long, group = "bandwidth-limit-status")]
     pub(crate) enable_bandwidth_limit: bool,
 
@@ -124,7 +304,31 @@ pub(crate) struct ChannelCli {
     pub(crate) disable_bandwidth_limit: bool,
 }
 
+impl ChannelCli {
+    /// Every channel selected, sorted.
+    pub(crate) fn channels(&self) -> Vec<usize> {
+        let mut channels: Vec<usize> = self
+            .channel
+            .iter()
+            .flat_map(|it| match it {
+                ChannelSelection::One(channel_no) => *channel_no..=*channel_no,
+                ChannelSelection::All => 1..=NUM_CHANNELS,
+            })
+            .collect();
+        channels.sort_unstable();
+        channels.dedup();
+        channels
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChannelSelection {
+    One(usize),
+    All,
+}
+
 #[derive(Args, Debug)]
+#[clap(group(ArgGroup::new("gate_level").requires("gate-channel")))]
 pub(crate) struct CaptureCli {
     /// Set device to scope mode before running any other command
     #[clap(short, long)]
@@ -139,10 +343,805 @@ pub(crate) struct CaptureCli {
     /// Defaults to infinity
     #[clap(short, long)]
     pub(crate) num_captures: Option<usize>,
+
+    /// Format written to stdout, csv and jsonl have a row per sample in volts where the
+    /// channel's scale is known, framed has each capture's raw samples after a header telling
+    /// their channels, scales and time, f32le has the samples as interleaved little-endian
+    /// float32 volts at the sample rate printed to stderr, e.g. for GNU Radio
+    #[clap(long, arg_enum, default_value = "raw")]
+    pub(crate) format: ExportFormat,
+
+    /// Write to this file instead of stdout
+    #[clap(short, long)]
+    pub(crate) output: Option<PathBuf>,
+
+    /// Where to write the captures instead of stdout, may be repeated to write to several at
+    /// once: stdout, file:<path>, tcp-connect:<host:port> to connect to, tcp-listen:<host:port>
+    /// to accept a client on, ws:<host:port> to serve WebSocket clients on, or null
+    #[clap(long, parse(try_from_str = parse_sink), conflicts_with = "output")]
+    pub(crate) sink: Vec<SinkSpec>,
+
+    /// Record to this HDF5 file instead of stdout, roll mode included: a dataset of raw samples
+    /// per channel, grown a chunk at a time, with the volts per count and the sample rate as
+    /// attributes. See hanteker_cli/src/recording.rs for the layout
+    #[cfg(feature = "hdf5")]
+    #[clap(
+        long,
+        conflicts_with_all = &[
+            "format", "output", "sink", "max-size", "mode", "decimate", "math", "segments", "xy",
+            "gate-channel"
+        ]
+    )]
+    pub(crate) hdf5: Option<PathBuf>,
+
+    /// Rotate the output file, or the file sinks, once grown past this size, e.g. 500M
+    #[clap(long, parse(try_from_str = parse_size))]
+    pub(crate) max_size: Option<u64>,
+
+    /// Rotated files to keep, as the output file name with .1, .2, ... appended
+    #[clap(long, default_value_t = 1, requires = "max-size")]
+    pub(crate) rotate: usize,
+
+    /// What to do when stdout can't keep up with the captures: block the capture until it does,
+    /// or drop whole captures while a few are already waiting for it
+    #[clap(long, arg_enum, default_value = "block", conflicts_with = "output")]
+    pub(crate) on_backpressure: Backpressure,
+
+    /// Time scale, needed for dead time reporting. Set on the device before capturing
+    #[clap(long, parse(try_from_str = parse_time_scale))]
+    pub(crate) time_scale: Option<TimeScale>,
+
+    /// Scale of the captured channels, needed for volts and for masks. Set on the device before
+    /// capturing
+    #[clap(long, parse(try_from_str = parse_scale))]
+    pub(crate) scale: Option<Scale>,
+
+    /// Acquisition mode done in software: normal, average:<N> for the mean of every N captures,
+    /// which must be triggered, or hires:<N> for the mean of every N adjacent samples. Csv and
+    /// jsonl keep the resolution gained, raw and framed have whole counts
+    #[clap(
+        long,
+        default_value = "normal",
+        parse(try_from_str = parse_mode),
+        conflicts_with_all = &["roll", "decimate", "gate-channel"]
+    )]
+    pub(crate) mode: AcquisitionMode,
+
+    /// Filter every captured channel, e.g. lowpass:10k, highpass:50, bandpass:1k-5k or
+    /// average:8. Needs the time scale, may be repeated to chain filters
+    #[clap(long, parse(try_from_str = parse_filter))]
+    pub(crate) filter: Vec<FilterSpec>,
+
+    /// Write the min and max of every given number of samples rather than each sample, e.g.
+    /// envelope:64. Glitches still show in a fraction of the output
+    #[clap(
+        long,
+        parse(try_from_str = parse_decimation),
+        conflicts_with_all = &["math", "roll", "max-size", "gate-channel"]
+    )]
+    pub(crate) decimate: Option<Decimation>,
+
+    /// Add a column computed from the captured channels in volts, e.g. "ch1 - 2*ch2". Needs
+    /// csv or jsonl, may be repeated
+    #[clap(long, parse(try_from_str = parse_math))]
+    pub(crate) math: Vec<MathExpr>,
+
+    /// Record at a roll mode time scale, 100ms per division or slower, appending samples as they
+    /// come with their wall clock time. Writes csv, the time in seconds since the Unix epoch
+    #[clap(long, conflicts_with_all = &["format", "max-size", "gate-channel"])]
+    pub(crate) roll: bool,
+
+    /// Arm the single trigger this many times, recording the capture each trigger took as a
+    /// segment of its own: csv and jsonl number the segments in a leading column and start the
+    /// time over at each, framed tells the time of each in its header. Captures that didn't
+    /// trigger aren't recorded
+    #[clap(
+        long,
+        conflicts_with_all = &["num-captures", "roll", "decimate", "gate-channel", "xy", "max-size"]
+    )]
+    pub(crate) segments: Option<usize>,
+
+    /// Write channel 2 against channel 1 rather than against time, csv rows of (x, y) pairs in
+    /// volts where the channel's scale is known, e.g. for Lissajous figures. Needs both channels
+    #[clap(
+        long,
+        conflicts_with_all = &["format", "roll", "decimate", "math", "max-size", "gate-channel"]
+    )]
+    pub(crate) xy: bool,
+
+    /// Print acquisition dead time and inter-chunk latency to stderr when done
+    #[clap(long)]
+    pub(crate) stats: bool,
+
+    /// Print the rate samples and bytes come in at, and the USB retries, short reads and
+    /// captures dropped for a slow stdout, to stderr every given time while capturing, e.g. 5s
+    #[clap(long, parse(try_from_str = humantime::parse_duration))]
+    pub(crate) stats_interval: Option<Duration>,
+
+    /// Write the statistics of --stats-interval to this file instead, a JSON object per line
+    #[clap(long, requires = "stats-interval")]
+    pub(crate) stats_json: Option<PathBuf>,
+
+    /// Report glitches or runts on every captured channel to stderr as they're found, e.g.
+    /// glitch:100ns for pulses narrower than 100ns or runt:0.8..2.0 for pulses crossing one of
+    /// the thresholds in volts but not the other. Glitches need the time scale, runts the scale.
+    /// May be repeated
+    #[clap(
+        long,
+        parse(try_from_str = parse_detect),
+        conflicts_with_all = &["decimate", "gate-channel"]
+    )]
+    pub(crate) detect: Vec<Detect>,
+
+    /// Test every capture against the limits in this mask file, see --make-mask. Prints the
+    /// number of failed captures and violating samples to stderr when done
+    #[clap(long, conflicts_with_all = &["roll", "decimate", "gate-channel"])]
+    pub(crate) mask: Option<PathBuf>,
+
+    /// Exit with code 9 if any capture violated the mask
+    #[clap(long, requires = "mask")]
+    pub(crate) fail_on_violation: bool,
+
+    /// Write a mask around the first capture of every channel to this file, the capture being
+    /// the golden one. Needs the scale of the channels
+    #[clap(long, conflicts_with_all = &["mask", "roll", "decimate", "gate-channel"])]
+    pub(crate) make_mask: Option<PathBuf>,
+
+    /// Volts the limits made by --make-mask are past the golden capture
+    #[clap(long, default_value_t = 0.1)]
+    pub(crate) mask_margin: f32,
+
+    /// Samples either side of each golden sample the limits made by --make-mask also cover, so
+    /// that a bit of jitter passes
+    #[clap(long, default_value_t = 2)]
+    pub(crate) mask_spread: usize,
+
+    /// Only record samples taken while this channel is past the gate level
+    #[clap(long, possible_values = ["1", "2"], requires = "gate_level")]
+    pub(crate) gate_channel: Option<usize>,
+
+    /// Gate is open while the gate channel is above this many volts
+    #[clap(long, group = "gate_level", allow_hyphen_values = true)]
+    pub(crate) gate_above: Option<f32>,
+
+    /// Gate is open while the gate channel is below this many volts
+    #[clap(long, group = "gate_level", allow_hyphen_values = true)]
+    pub(crate) gate_below: Option<f32>,
+
+    /// Scale of the gate channel, needed to compare it against the level. Set on the device
+    /// before capturing
+    #[clap(long, parse(try_from_str = parse_scale), requires = "gate-channel")]
+    pub(crate) gate_scale: Option<Scale>,
+}
+
+#[derive(ArgEnum, Debug, Clone)]
+pub(crate) enum Backpressure {
+    Block,
+    Drop,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct MeasureCli {
+    /// Set device to scope mode before running any other command
+    #[clap(short, long)]
+    pub(crate) force_mode: bool,
+
+    #[clap(short, long, possible_values = ["1", "2"])]
+    pub(crate) channel: usize,
+
+    /// Comma separated list of measurements to print
+    #[clap(
+        long,
+        arg_enum,
+        use_value_delimiter = true,
+        default_value = "vpp,vrms,mean,freq"
+    )]
+    pub(crate) stat: Vec<Stat>,
+
+    /// Channel scale, needed to convert samples to volts. Set on the device before measuring
+    #[clap(long, parse(try_from_str = parse_scale))]
+    pub(crate) scale: Option<Scale>,
+
+    /// Time scale, needed for frequency, period and duty. Set on the device before measuring
+    #[clap(long, parse(try_from_str = parse_time_scale))]
+    pub(crate) time_scale: Option<TimeScale>,
+
+    #[clap(long, default_value_t = 1000)]
+    pub(crate) capture_chunk: usize,
+
+    /// Repeat the measurement every given milliseconds, defaults to measuring once
+    #[clap(short, long, value_name = "MILLIS")]
+    pub(crate) interval: Option<u64>,
+
+    /// Number of measurements when repeating, defaults to infinity
+    #[clap(short, long)]
+    pub(crate) num_measurements: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct CounterCli {
+    /// Set device to scope mode before running any other command
+    #[clap(short, long)]
+    pub(crate) force_mode: bool,
+
+    #[clap(short, long, possible_values = ["1", "2"])]
+    pub(crate) channel: usize,
+
+    /// Time over which captures are accumulated for each reading, e.g. 1s
+    #[clap(long, default_value = "1s", parse(try_from_str = humantime::parse_duration))]
+    pub(crate) gate: Duration,
+
+    /// Level in volts at which pulses are counted, defaults to the mid level of each capture
+    #[clap(long, allow_hyphen_values = true)]
+    pub(crate) threshold: Option<f32>,
+
+    /// Channel scale, needed to convert samples to volts. Set on the device before counting
+    #[clap(long, parse(try_from_str = parse_scale))]
+    pub(crate) scale: Option<Scale>,
+
+    /// Time scale, needed to know the sample rate. Set on the device before counting
+    #[clap(long, parse(try_from_str = parse_time_scale))]
+    pub(crate) time_scale: Option<TimeScale>,
+
+    #[clap(long, default_value_t = 1000)]
+    pub(crate) capture_chunk: usize,
+
+    /// Number of readings, defaults to infinity
+    #[clap(short, long)]
+    pub(crate) num_readings: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct SweepCli {
+    /// Set device to scope mode before running any other command
+    #[clap(short, long)]
+    pub(crate) force_mode: bool,
+
+    /// Print rows as CSV as soon as they're measured, instead of a table at the end
+    #[clap(long)]
+    pub(crate) csv: bool,
+
+    pub(crate) plan: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct DecodeSpiCli {
+    /// Set device to scope mode before running any other command
+    #[clap(short, long)]
+    pub(crate) force_mode: bool,
+
+    #[clap(long, possible_values = ["1", "2"], default_value_t = 1)]
+    pub(crate) clock: usize,
+
+    #[clap(long, possible_values = ["1", "2"], default_value_t = 2)]
+    pub(crate) data: usize,
+
+    /// Clock idles high
+    #[clap(long)]
+    pub(crate) cpol: bool,
+
+    /// Data is sampled on the second clock edge instead of the first
+    #[clap(long)]
+    pub(crate) cpha: bool,
+
+    /// Logic level threshold in volts, defaults to halfway between the lowest and highest sample
+    /// of each channel
+    #[clap(long, allow_hyphen_values = true)]
+    pub(crate) threshold: Option<f32>,
+
+    /// Scale of both channels, needed to convert samples to volts. Set on the device before
+    /// capturing
+    #[clap(long, parse(try_from_str = parse_scale))]
+    pub(crate) scale: Option<Scale>,
+
+    /// Time scale, needed to know the sample rate. Set on the device before capturing
+    #[clap(long, parse(try_from_str = parse_time_scale))]
+    pub(crate) time_scale: Option<TimeScale>,
+
+    #[clap(long, default_value_t = 4000)]
+    pub(crate) capture_chunk: usize,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct DecodeI2cCli {
+    /// Set device to scope mode before running any other command
+    #[clap(short, long)]
+    pub(crate) force_mode: bool,
+
+    #[clap(long, possible_values = ["1", "2"], default_value_t = 1)]
+    pub(crate) scl: usize,
+
+    #[clap(long, possible_values = ["1", "2"], default_value_t = 2)]
+    pub(crate) sda: usize,
+
+    /// Logic level threshold in volts, defaults to halfway between the lowest and highest sample
+    /// of each channel
+    #[clap(long, allow_hyphen_values = true)]
+    pub(crate) threshold: Option<f32>,
+
+    /// Scale of both channels, needed to convert samples to volts. Set on the device before
+    /// capturing
+    #[clap(long, parse(try_from_str = parse_scale))]
+    pub(crate) scale: Option<Scale>,
+
+    /// Time scale, needed to know the sample rate. Set on the device before capturing
+    #[clap(long, parse(try_from_str = parse_time_scale))]
+    pub(crate) time_scale: Option<TimeScale>,
+
+    #[clap(long, default_value_t = 4000)]
+    pub(crate) capture_chunk: usize,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct BodeCli {
+    /// Set device to scope mode before running any other command
+    #[clap(short, long)]
+    pub(crate) force_mode: bool,
+
+    /// Channel on the input of the circuit, where the AWG output goes, e.g. ch1
+    #[clap(long, parse(try_from_str = parse_channel))]
+    pub(crate) input: usize,
+
+    /// Channel on the output of the circuit, e.g. ch2
+    #[clap(long, parse(try_from_str = parse_channel))]
+    pub(crate) output: usize,
+
+    /// First frequency in Hz, k and M suffixes accepted
+    #[clap(long, parse(try_from_str = parse_frequency))]
+    pub(crate) from: f32,
+
+    /// Last frequency in Hz, k and M suffixes accepted
+    #[clap(long, parse(try_from_str = parse_frequency))]
+    pub(crate) to: f32,
+
+    /// Number of frequencies, both ends included
+    #[clap(long, default_value_t = 20)]
+    pub(crate) steps: usize,
+
+    /// Space the frequencies linearly instead of logarithmically
+    #[clap(long)]
+    pub(crate) linear: bool,
+
+    /// Settling time at each frequency before capturing
+    #[clap(long, default_value = "200ms", parse(try_from_str = humantime::parse_duration))]
+    pub(crate) dwell: Duration,
+
+    /// Scale of the input channel, needed to convert samples to volts. Set on the device before
+    /// measuring
+    #[clap(long, parse(try_from_str = parse_scale))]
+    pub(crate) input_scale: Option<Scale>,
+
+    /// Scale of the output channel, needed to convert samples to volts. Set on the device
+    /// before measuring
+    #[clap(long, parse(try_from_str = parse_scale))]
+    pub(crate) output_scale: Option<Scale>,
+
+    /// Samples captured per channel at each frequency
+    #[clap(long, default_value_t = 1000)]
+    pub(crate) capture_chunk: usize,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct DecodeCli {
+    #[clap(subcommand)]
+    pub(crate) sub_commands: DecodeCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum DecodeCommands {
+    /// Print the bytes of an 8N1 UART line, idle high
+    Uart(DecodeUartCli),
+
+    /// Print the transactions of an SPI bus, clock and data on the two channels
+    Spi(DecodeSpiCli),
+
+    /// Print the transactions of an I2C bus, SCL and SDA on the two channels
+    I2c(DecodeI2cCli),
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct DecodeUartCli {
+    /// Set device to scope mode before running any other command
+    #[clap(short, long)]
+    pub(crate) force_mode: bool,
+
+    #[clap(short, long, possible_values = ["1", "2"])]
+    pub(crate) channel: usize,
+
+    /// Bits per second
+    #[clap(long)]
+    pub(crate) baud: f32,
+
+    /// Logic level threshold in volts, defaults to halfway between the lowest and highest sample
+    #[clap(long, allow_hyphen_values = true)]
+    pub(crate) threshold: Option<f32>,
+
+    /// Channel scale, needed to convert samples to volts. Set on the device before capturing
+    #[clap(long, parse(try_from_str = parse_scale))]
+    pub(crate) scale: Option<Scale>,
+
+    /// Time scale, needed to know the sample rate. Set on the device before capturing
+    #[clap(long, parse(try_from_str = parse_time_scale))]
+    pub(crate) time_scale: Option<TimeScale>,
+
+    #[clap(long, default_value_t = 4000)]
+    pub(crate) capture_chunk: usize,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct VerifyCli {
+    /// Set device to scope mode before running any other command
+    #[clap(short, long)]
+    pub(crate) force_mode: bool,
+
+    #[clap(short, long, possible_values = ["1", "2"], default_values = &["1", "2"])]
+    pub(crate) channel: Vec<usize>,
+
+    /// Print the matrix as CSV instead of a table
+    #[clap(long)]
+    pub(crate) csv: bool,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct BenchCli {
+    /// Set device to scope mode before running any other command
+    #[clap(short, long)]
+    pub(crate) force_mode: bool,
+
+    #[clap(short, long, possible_values = ["1", "2"], default_values = &["1"])]
+    pub(crate) channel: Vec<usize>,
+
+    #[clap(long, default_value_t = 4000)]
+    pub(crate) capture_chunk: usize,
+
+    /// Captures timed for each size
+    #[clap(short, long, default_value_t = 10)]
+    pub(crate) num_captures: usize,
+
+    /// Comma separated sizes of the reads to compare, in bytes, defaults to --capture-packet
+    #[clap(long, use_value_delimiter = true)]
+    pub(crate) packet: Vec<usize>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct ProbeCheckCli {
+    /// Set device to scope mode before running any other command
+    #[clap(short, long)]
+    pub(crate) force_mode: bool,
+
+    #[clap(short, long, possible_values = ["1", "2"])]
+    pub(crate) channel: usize,
+
+    #[clap(long, parse(try_from_str = parse_scale), default_value = "v1")]
+    pub(crate) scale: Scale,
+
+    /// Time scale, the default shows a few periods of the 1kHz square wave
+    #[clap(long, parse(try_from_str = parse_time_scale), default_value = "us100")]
+    pub(crate) time_scale: TimeScale,
+
+    /// Leave the generator alone, for checking against a square wave from another source
+    #[clap(long)]
+    pub(crate) no_awg: bool,
+
+    #[clap(long, default_value_t = 1000.0)]
+    pub(crate) frequency: f32,
+
+    #[clap(long, default_value_t = 2.0)]
+    pub(crate) amplitude: f32,
+
+    #[clap(long, default_value_t = 1000)]
+    pub(crate) capture_chunk: usize,
+
+    /// Number of captures the result is averaged over
+    #[clap(short, long, default_value_t = 4)]
+    pub(crate) num_captures: usize,
+
+    /// Don't wait for the probe to be connected
+    #[clap(short, long)]
+    pub(crate) yes: bool,
+}
+
+#[derive(ArgEnum, Debug, Clone)]
+pub(crate) enum SpectrumFormat {
+    Csv,
+    Bars,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct SpectrumCli {
+    /// Set device to scope mode before running any other command
+    #[clap(short, long)]
+    pub(crate) force_mode: bool,
+
+    #[clap(short, long, possible_values = ["1", "2"])]
+    pub(crate) channel: usize,
+
+    #[clap(short, long, arg_enum, default_value = "hann")]
+    pub(crate) window: Window,
+
+    /// Number of samples to transform, must be a power of two
+    #[clap(short, long, default_value_t = 4096)]
+    pub(crate) points: usize,
+
+    /// Channel scale, needed to convert samples to volts. Set on the device before capturing
+    #[clap(long, parse(try_from_str = parse_scale))]
+    pub(crate) scale: Option<Scale>,
+
+    /// Time scale, needed to know the sample rate. Set on the device before capturing
+    #[clap(long, parse(try_from_str = parse_time_scale))]
+    pub(crate) time_scale: Option<TimeScale>,
+
+    #[clap(long, arg_enum, default_value = "csv")]
+    pub(crate) format: SpectrumFormat,
+
+    /// Number of rows of the bar chart, bins are grouped by their maximum
+    #[clap(long, default_value_t = 32)]
+    pub(crate) rows: usize,
+}
+
+#[derive(Args, Debug)]
+#[clap(group(ArgGroup::new("level").required(true)))]
+pub(crate) struct WaitCli {
+    /// Set device to scope mode before running any other command
+    #[clap(short, long)]
+    pub(crate) force_mode: bool,
+
+    #[clap(short, long, possible_values = ["1", "2"])]
+    pub(crate) channel: usize,
+
+    /// Wait for the measurement to go above this level
+    #[clap(long, group = "level", allow_hyphen_values = true)]
+    pub(crate) above: Option<f32>,
+
+    /// Wait for the measurement to go below this level
+    #[clap(long, group = "level", allow_hyphen_values = true)]
+    pub(crate) below: Option<f32>,
+
+    /// Measurement compared against the level
+    #[clap(long, arg_enum, default_value = "mean")]
+    pub(crate) stat: Stat,
+
+    /// Give up after this long, e.g. 60s or 500ms. Defaults to waiting forever
+    #[clap(short, long, parse(try_from_str = humantime::parse_duration))]
+    pub(crate) timeout: Option<Duration>,
+
+    /// Pause between captures
+    #[clap(long, default_value = "100ms", parse(try_from_str = humantime::parse_duration))]
+    pub(crate) poll: Duration,
+
+    /// Channel scale, needed to convert samples to volts. Set on the device before waiting
+    #[clap(long, parse(try_from_str = parse_scale))]
+    pub(crate) scale: Option<Scale>,
+
+    /// Time scale, needed for frequency, period and duty. Set on the device before waiting
+    #[clap(long, parse(try_from_str = parse_time_scale))]
+    pub(crate) time_scale: Option<TimeScale>,
+
+    #[clap(long, default_value_t = 1000)]
+    pub(crate) capture_chunk: usize,
 }
 
 #[derive(Args, Debug)]
-pub(crate) struct PrintCli {}
+pub(crate) struct PlotCli {
+    /// Set device to scope mode before running any other command
+    #[clap(short, long)]
+    pub(crate) force_mode: bool,
+
+    #[clap(short, long, possible_values = ["1", "2"], required = true)]
+    pub(crate) channel: Vec<usize>,
+
+    /// Channel scale, only used to annotate the plot. Set on the device before plotting
+    #[clap(long, parse(try_from_str = parse_scale))]
+    pub(crate) scale: Option<Scale>,
+
+    /// Time scale, only used to annotate the plot. Set on the device before plotting
+    #[clap(long, parse(try_from_str = parse_time_scale))]
+    pub(crate) time_scale: Option<TimeScale>,
+
+    #[clap(long, default_value_t = 1000)]
+    pub(crate) capture_chunk: usize,
+
+    /// Plot width in characters
+    #[clap(long, default_value_t = 100)]
+    pub(crate) width: usize,
+
+    /// Plot height in characters
+    #[clap(long, default_value_t = 24)]
+    pub(crate) height: usize,
+
+    /// Pause between redraws
+    #[clap(long, default_value = "200ms", parse(try_from_str = humantime::parse_duration))]
+    pub(crate) refresh: Duration,
+
+    /// Draw a single capture and exit instead of updating live
+    #[clap(long)]
+    pub(crate) once: bool,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct TuiCli {
+    /// Set device to scope mode before running any other command
+    #[clap(short, long)]
+    pub(crate) force_mode: bool,
+
+    #[clap(short, long, possible_values = ["1", "2"], default_values = &["1", "2"])]
+    pub(crate) channel: Vec<usize>,
+
+    /// Initial scale of the channels
+    #[clap(long, parse(try_from_str = parse_scale))]
+    pub(crate) scale: Option<Scale>,
+
+    /// Initial time scale
+    #[clap(long, parse(try_from_str = parse_time_scale))]
+    pub(crate) time_scale: Option<TimeScale>,
+
+    #[clap(long, default_value_t = 1000)]
+    pub(crate) capture_chunk: usize,
+
+    /// Pause between captures
+    #[clap(long, default_value = "200ms", parse(try_from_str = humantime::parse_duration))]
+    pub(crate) refresh: Duration,
+
+    /// Start out plotting channel 2 against channel 1, toggled with x
+    #[clap(long)]
+    pub(crate) xy: bool,
+}
+
+#[derive(Args, Debug)]
+#[clap(group(ArgGroup::new("protocol").required(true)))]
+pub(crate) struct ServeCli {
+    /// Serve SCPI commands over raw TCP, for VISA based tooling
+    #[clap(long, group = "protocol")]
+    pub(crate) scpi: bool,
+
+    /// Serve REST endpoints for the settings and a WebSocket streaming captures
+    #[clap(long, group = "protocol")]
+    pub(crate) http: bool,
+
+    /// Address to listen on
+    #[clap(long, default_value = "127.0.0.1")]
+    pub(crate) bind: String,
+
+    /// Port to listen on, defaults to 5025 for SCPI and 8080 for HTTP
+    #[clap(short, long)]
+    pub(crate) port: Option<u16>,
+
+    /// Samples per channel in each frame captured over HTTP
+    #[clap(long, default_value_t = 1000)]
+    pub(crate) capture_chunk: usize,
+
+    /// Announce the service over mDNS as LXI instruments do, for VISA tooling and the like to
+    /// discover. Needs --bind to an address reachable on the network
+    #[clap(long)]
+    pub(crate) mdns: bool,
+
+    /// Serve Prometheus metrics at /metrics on this address, e.g. :9187 for every interface
+    #[clap(long)]
+    pub(crate) metrics: Option<String>,
+}
+
+#[derive(ArgEnum, Debug, Clone, PartialEq, Eq)]
+pub(crate) enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(ArgEnum, Debug, Clone)]
+pub(crate) enum PrintFormat {
+    Text,
+    Json,
+    Yaml,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct StatusCli {}
+
+#[derive(Args, Debug)]
+pub(crate) struct RawCli {
+    /// Function code, e.g. 0x0000 for scope settings or 0x0002 for the AWG
+    #[clap(long, parse(try_from_str = parse_func))]
+    pub(crate) func: u16,
+
+    /// Command code within the function, e.g. 0x13
+    #[clap(long, parse(try_from_str = parse_cmd))]
+    pub(crate) cmd: u8,
+
+    /// Value bytes as colon separated hex, e.g. 01:00:00:00
+    #[clap(long, default_value = "00:00:00:00", parse(try_from_str = parse_val))]
+    pub(crate) val: [u8; 4],
+
+    /// Read a response of up to this many bytes after sending
+    #[clap(long, value_name = "N")]
+    pub(crate) read: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct PrintCli {
+    /// Text is for humans, json and yaml also include the config snapshot
+    #[clap(long, arg_enum, default_value = "text")]
+    pub(crate) format: PrintFormat,
+
+    /// Same as --format json
+    #[clap(long, conflicts_with = "format")]
+    pub(crate) json: bool,
+
+    /// Only list the known firmware bugs of the connected device
+    #[clap(long, conflicts_with_all = &["format", "json"])]
+    pub(crate) quirks: bool,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct ConfigCli {
+    #[clap(subcommand)]
+    pub(crate) sub_commands: ConfigCommands,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum ConfigCommands {
+    /// Print the config as JSON
+    ///
+    /// The device can't be read back, only what was set through this process is known. `GET
+    /// /api/snapshot` of `serve --http` snapshots a running session.
+    Snapshot(ConfigSnapshotCli),
+
+    /// Print the fields that differ between two snapshots, no device needed
+    ///
+    /// Given a single profile instead, print what applying it would change to the config known
+    /// to this process.
+    Diff(ConfigDiffCli),
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct ConfigSnapshotCli {
+    /// Write to this file instead of stdout
+    #[clap(short, long)]
+    pub(crate) output: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct ConfigDiffCli {
+    /// Snapshot taken first, or the profile to compare against when alone
+    pub(crate) before: PathBuf,
+
+    /// Snapshot taken second
+    pub(crate) after: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct RunCli {
+    /// Script to run, e.g. test.hntk
+    pub(crate) script: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct WatchCli {
+    /// Profile to apply, a TOML config with only the fields to set, see `config diff`
+    #[clap(short, long)]
+    pub(crate) profile: PathBuf,
+
+    /// How often to look for the device while it's away, and check it's still there once
+    /// connected
+    #[clap(long, default_value = "1s", parse(try_from_str = humantime::parse_duration))]
+    pub(crate) poll: Duration,
+}
+
+#[cfg(feature = "rhai")]
+#[derive(Args, Debug)]
+pub(crate) struct ScriptCli {
+    /// Script to run, e.g. test.rhai
+    pub(crate) script: PathBuf,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct SetupUdevCli {
+    /// Write the rule to /etc/udev/rules.d and reload udev, needs root
+    #[clap(long)]
+    pub(crate) install: bool,
+
+    /// Where to install the rule
+    #[clap(long, requires = "install", default_value = RULE_PATH)]
+    pub(crate) path: PathBuf,
+}
 
 #[derive(Args, Debug)]
 pub(crate) struct ShellCli {
@@ -162,19 +1161,25 @@ pub(crate) struct AwgCli {
     #[clap(short, long, arg_enum)]
     pub(crate) r#type: Option<AwgType>,
 
-    #[clap(long)]
+    /// Frequency in Hz, up to 25MHz for sine, 10MHz for square and arbitrary, 1MHz for ramp
+    /// and trapezoid
+    #[clap(long, validator = awg_frequency)]
     pub(crate) frequency: Option<f32>,
 
-    #[clap(short, long)]
+    /// Amplitude in volts, within ±3.5V
+    #[clap(short, long, allow_hyphen_values = true, validator = awg_amplitude)]
     pub(crate) amplitude: Option<f32>,
 
-    #[clap(short, long)]
+    /// Offset in volts, within ±3.5V
+    #[clap(short, long, allow_hyphen_values = true, validator = awg_offset)]
     pub(crate) offset: Option<f32>,
 
-    #[clap(long)]
+    /// Duty cycle of the square waveform in percent, 1 to 99
+    #[clap(long, validator = awg_duty)]
     pub(crate) duty_square: Option<f32>,
 
-    #[clap(long)]
+    /// Duty cycle of the ramp waveform in percent, 1 to 99
+    #[clap(long, validator = awg_duty)]
     pub(crate) duty_ramp: Option<f32>,
 
     #[clap(long)]
@@ -191,6 +1196,77 @@ pub(crate) struct AwgCli {
 
     #[clap(long)]
     pub(crate) start: bool,
+
+    /// Stop again after running for this long, e.g. 100ms
+    #[clap(
+        long,
+        requires = "start",
+        conflicts_with_all = &["stop", "cycles"],
+        parse(try_from_str = humantime::parse_duration)
+    )]
+    pub(crate) burst: Option<Duration>,
+
+    /// Stop again after this many periods of the waveform, the frequency must be given
+    #[clap(long, requires_all = &["start", "frequency"], conflicts_with = "stop")]
+    pub(crate) cycles: Option<u32>,
+
+    #[clap(subcommand)]
+    pub(crate) sub_commands: Option<AwgCommands>,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum AwgCommands {
+    /// Step the frequency over a range, printing each one as it's set. Runs after the other
+    /// options, e.g. `awg --start sweep ...`
+    Sweep(AwgSweepCli),
+
+    /// Code bytes as a UART, Manchester or PWM waveform to load into an arb slot, and set the
+    /// frequency playing it at the baud rate
+    Encode(AwgEncodeCli),
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct AwgSweepCli {
+    /// First frequency in Hz, k and M suffixes accepted
+    #[clap(long, parse(try_from_str = parse_frequency))]
+    pub(crate) from: f32,
+
+    /// Last frequency in Hz, k and M suffixes accepted
+    #[clap(long, parse(try_from_str = parse_frequency))]
+    pub(crate) to: f32,
+
+    /// Number of frequencies, both ends included
+    #[clap(long, default_value_t = 10)]
+    pub(crate) steps: usize,
+
+    /// Time spent on each frequency
+    #[clap(long, default_value = "100ms", parse(try_from_str = humantime::parse_duration))]
+    pub(crate) dwell: Duration,
+
+    /// Space the frequencies logarithmically instead of linearly
+    #[clap(long)]
+    pub(crate) log: bool,
+}
+
+#[derive(Args, Debug)]
+pub(crate) struct AwgEncodeCli {
+    #[clap(long, arg_enum)]
+    pub(crate) protocol: Protocol,
+
+    /// Bits per second
+    #[clap(long)]
+    pub(crate) baud: f32,
+
+    /// Bytes to send, as UTF-8
+    #[clap(long)]
+    pub(crate) data: String,
+
+    #[clap(long, default_value_t = 16)]
+    pub(crate) samples_per_bit: usize,
+
+    /// Write the samples to this file, one per line, instead of stdout
+    #[clap(short, long)]
+    pub(crate) output: Option<PathBuf>,
 }
 
 pub(crate) fn cli_command() -> clap::Command<'static> {
@@ -198,5 +1274,188 @@ pub(crate) fn cli_command() -> clap::Command<'static> {
 }
 
 pub(crate) fn cli_parse() -> Cli {
-    Cli::parse()
+    match Cli::try_parse() {
+        Ok(cli) => cli,
+        // Help and version are "errors" too, printed to stdout.
+        Err(e) if e.use_stderr() && machine_requested() => {
+            let message = e.to_string();
+            let message = message.lines().next().unwrap_or_default();
+            eprintln!(
+                "{}",
+                machine_error(EXIT_USAGE, message.trim_start_matches("error: "))
+            );
+            std::process::exit(EXIT_USAGE);
+        }
+        Err(e) => e.exit(),
+    }
+}
+
+/// The arguments couldn't be parsed, so --machine is looked for by itself.
+fn machine_requested() -> bool {
+    std::env::args_os().any(|it| it == "--machine" || it == "--quiet-errors")
+}
+
+// ============================================================= VALIDATORS
+
+fn within(value: &str, min: f32, max: f32, unit: &str) -> Result<(), String> {
+    let value: f32 = value
+        .parse()
+        .map_err(|_| format!("not a number: {}", value))?;
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(format!("must be within {}..={} {}", min, max, unit))
+    }
+}
+
+/// Against the fastest waveform, the type may be given separately. The limit of the type is
+/// checked by the lib once it's known.
+fn awg_frequency(value: &str) -> Result<(), String> {
+    within(
+        value,
+        AWG_FREQUENCY_MIN,
+        awg_frequency_max(&AwgType::Sin),
+        "Hz",
+    )
+}
+
+fn awg_amplitude(value: &str) -> Result<(), String> {
+    within(value, -AWG_AMPLITUDE_MAX, AWG_AMPLITUDE_MAX, "V")
+}
+
+fn awg_offset(value: &str) -> Result<(), String> {
+    within(value, -AWG_OFFSET_MAX, AWG_OFFSET_MAX, "V")
+}
+
+fn awg_duty(value: &str) -> Result<(), String> {
+    within(value, AWG_DUTY_MIN, AWG_DUTY_MAX, "%")
+}
+
+/// Hz, with an optional k or M suffix, e.g. `10k`.
+fn parse_frequency(value: &str) -> Result<f32, String> {
+    let (number, multiplier) = match value.strip_suffix(['k', 'K']) {
+        Some(number) => (number, 1e3),
+        None => match value.strip_suffix('M') {
+            Some(number) => (number, 1e6),
+            None => (value, 1.0),
+        },
+    };
+    number
+        .parse::<f32>()
+        .map(|it| it * multiplier)
+        .map_err(|_| format!("not a frequency, e.g. 10k: {}", value))
+}
+
+/// Channel number, optionally prefixed with `ch`, e.g. `ch1`.
+fn parse_channel(value: &str) -> Result<usize, String> {
+    let number = value
+        .strip_prefix("ch")
+        .or_else(|| value.strip_prefix("CH"))
+        .unwrap_or(value);
+    match number.parse::<usize>() {
+        Ok(channel_no) if (1..=2).contains(&channel_no) => Ok(channel_no),
+        _ => Err(format!("not a channel, e.g. ch1 or 2: {}", value)),
+    }
+}
+
+/// Hex with a 0x prefix, e.g. `0x13`, decimal otherwise.
+fn parse_code(value: &str) -> Result<u32, String> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => value.parse(),
+    }
+    .map_err(|_| format!("not a code, e.g. 0x13: {}", value))
+}
+
+fn parse_func(value: &str) -> Result<u16, String> {
+    u16::try_from(parse_code(value)?).map_err(|_| format!("func is 16 bits: {}", value))
+}
+
+fn parse_cmd(value: &str) -> Result<u8, String> {
+    u8::try_from(parse_code(value)?).map_err(|_| format!("cmd is 8 bits: {}", value))
+}
+
+/// Colon separated hex bytes, e.g. `01:00:00:00`.
+fn parse_val(value: &str) -> Result<[u8; 4], String> {
+    value
+        .split(':')
+        .map(|it| u8::from_str_radix(it, 16))
+        .collect::<Result<Vec<_>, _>>()
+        .ok()
+        .and_then(|it| it.try_into().ok())
+        .ok_or_else(|| format!("not 4 hex bytes, e.g. 01:00:00:00: {}", value))
+}
+
+fn parse_channel_selection(value: &str) -> Result<ChannelSelection, String> {
+    match value.parse() {
+        Ok(channel_no @ 1..=NUM_CHANNELS) => Ok(ChannelSelection::One(channel_no)),
+        _ if value == "all" => Ok(ChannelSelection::All),
+        _ => Err(format!("not a channel, 1, 2 or all: {}", value)),
+    }
+}
+
+/// A scale by name, e.g. mv200, or volts per division rounded to the nearest scale, e.g. 0.2.
+fn parse_scale(value: &str) -> Result<Scale, String> {
+    value
+        .parse::<Scale>()
+        .ok()
+        .or_else(|| Scale::from_volts_per_div(value.parse().ok()?))
+        .ok_or_else(|| {
+            format!(
+                "not a scale, one of {}, or volts per division, e.g. 0.2: {}",
+                names(Scale::my_options()),
+                value
+            )
+        })
+}
+
+/// A time scale by name, e.g. us100, or seconds per division rounded to the nearest time scale,
+/// e.g. 1e-4.
+fn parse_time_scale(value: &str) -> Result<TimeScale, String> {
+    value
+        .parse::<TimeScale>()
+        .ok()
+        .or_else(|| TimeScale::from_seconds_per_div(value.parse().ok()?))
+        .ok_or_else(|| {
+            format!(
+                "not a time scale, one of {}, or seconds per division, e.g. 1e-4: {}",
+                names(TimeScale::my_options()),
+                value
+            )
+        })
+}
+
+fn names<T>(options: Vec<(String, T)>) -> String {
+    options
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn parse_filter(value: &str) -> Result<FilterSpec, String> {
+    FilterSpec::parse(value).map_err(|e| e.to_string())
+}
+
+fn parse_mode(value: &str) -> Result<AcquisitionMode, String> {
+    AcquisitionMode::parse(value).map_err(|e| e.to_string())
+}
+
+fn parse_decimation(value: &str) -> Result<Decimation, String> {
+    Decimation::parse(value).map_err(|e| e.to_string())
+}
+
+fn parse_detect(value: &str) -> Result<Detect, String> {
+    Detect::parse(value).map_err(|e| e.to_string())
+}
+
+fn parse_math(value: &str) -> Result<MathExpr, String> {
+    MathExpr::parse(value).map_err(|e| e.to_string())
+}
+
+fn parse_sink(value: &str) -> Result<SinkSpec, String> {
+    SinkSpec::parse(value)
 }
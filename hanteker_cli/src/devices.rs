@@ -0,0 +1,274 @@
+//! Picking a device out of several connected, and running a command on more than one of them.
+//!
+//! A device is named by its serial number, or by where it is on the bus when it has none. A
+//! command for several devices is run by a copy of this process for each, with the same command
+//! line and the device it's for in [`DEVICE_ENV`], in parallel. Every line they write is
+//! prefixed with their device, e.g. `[001:004] ready: ...`.
+//!
+//! Files written would be written by every copy, so their paths must have [`DEVICE_PLACEHOLDER`]
+//! in them, each copy replacing it with its device. Binary captures can't go to stdout, the
+//! prefixes would corrupt them.
+
+use std::env;
+use std::ffi::OsString;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Context;
+use log::{debug, warn};
+
+use hanteker_lib::models::hantek2d42::Hantek2D42;
+
+use crate::cli::{AwgCli, AwgCommands, Cli, Commands, ConfigCli, ConfigCommands};
+use crate::exit::{ExitStatus, EXIT_DEVICE_NOT_FOUND, EXIT_FAILURE, EXIT_USAGE};
+use crate::failsafe::{on_signals, EXIT_INTERRUPTED};
+use crate::sinks::{self, SinkSpec};
+
+/// Device a copy of the process runs the command on.
+const DEVICE_ENV: &str = "HANTEKER_DEVICE";
+
+/// Replaced in the command line of each copy by the name of its device, `:` becoming `-`, so
+/// that each one writes a file of its own, e.g. `--output capture-{device}.bin`.
+pub(crate) const DEVICE_PLACEHOLDER: &str = "{device}";
+
+/// Whether the command is for several devices, run by [`run_on_each`].
+pub(crate) fn is_multi(cli: &Cli) -> bool {
+    env::var_os(DEVICE_ENV).is_none() && (cli.all_devices || cli.devices.len() > 1)
+}
+
+/// The single device the command is for, `None` if it's for whichever one is connected.
+pub(crate) fn selected(cli: &Cli) -> Option<String> {
+    match env::var(DEVICE_ENV) {
+        Ok(name) => Some(name),
+        Err(_) => match cli.devices.as_slice() {
+            [name] => Some(name.clone()),
+            _ => None,
+        },
+    }
+}
+
+/// Serial number of the device, or its bus and address.
+pub(crate) fn name_of(hantek: &Hantek2D42) -> String {
+    let usb = &hantek.usb;
+    match usb.get_serial() {
+        Ok(Some(serial)) if !serial.trim().is_empty() => serial.trim().to_string(),
+        _ => format!("{:03}:{:03}", usb.device.bus_number(), usb.device.address()),
+    }
+}
+
+/// The device named so, out of every one connected.
+pub(crate) fn open_named<'a>(
+    context: &'a libusb::Context,
+    timeout: Duration,
+    name: &str,
+) -> anyhow::Result<Hantek2D42<'a>> {
+    let mut found = vec![];
+    for hantek in Hantek2D42::open_all(context, timeout)? {
+        let it = name_of(&hantek);
+        if it == name {
+            return Ok(hantek);
+        }
+        found.push(it);
+    }
+    Err(ExitStatus::new(
+        EXIT_DEVICE_NOT_FOUND,
+        format!("no device {}, connected: {}", name, names(&found)),
+    )
+    .into())
+}
+
+/// Runs the command on each device asked for, exiting with the code of the first one failing.
+pub(crate) fn run_on_each(cli: &Cli) -> anyhow::Result<()> {
+    check_outputs(cli)?;
+    let interrupted = on_signals()?;
+    let names = {
+        let context = libusb::Context::new()?;
+        let connected: Vec<String> =
+            Hantek2D42::open_all(&context, Duration::from_millis(cli.timeout))?
+                .iter()
+                .map(name_of)
+                .collect();
+        if cli.all_devices {
+            if connected.is_empty() {
+                return Err(ExitStatus::new(EXIT_DEVICE_NOT_FOUND, "no device connected").into());
+            }
+            connected
+        } else {
+            if let Some(missing) = cli.devices.iter().find(|it| !connected.contains(it)) {
+                return Err(ExitStatus::new(
+                    EXIT_DEVICE_NOT_FOUND,
+                    format!("no device {}, connected: {}", missing, names(&connected)),
+                )
+                .into());
+            }
+            cli.devices.clone()
+        }
+    };
+    debug!("running on devices: {}", names.join(", "));
+
+    let exe = env::current_exe().context("finding the executable to run for each device")?;
+    let codes = thread::scope(|scope| {
+        let runs: Vec<_> = names
+            .iter()
+            .map(|name| {
+                let exe = &exe;
+                scope.spawn(move || run_one(exe, name))
+            })
+            .collect();
+        runs.into_iter()
+            .zip(&names)
+            .map(|(run, name)| {
+                let code = run
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("output thread panicked")))
+                    .unwrap_or_else(|e| {
+                        warn!("[{}] {:#}", name, e);
+                        EXIT_FAILURE
+                    });
+                (name, code)
+            })
+            .collect::<Vec<_>>()
+    });
+
+    if interrupted.load(std::sync::atomic::Ordering::SeqCst) {
+        return Err(ExitStatus::new(EXIT_INTERRUPTED, "interrupted").into());
+    }
+    let failed: Vec<_> = codes.iter().filter(|(_, code)| *code != 0).collect();
+    match failed.first() {
+        None => Ok(()),
+        Some((_, code)) => Err(ExitStatus::new(
+            *code,
+            format!(
+                "failed on {} of {} devices: {}",
+                failed.len(),
+                codes.len(),
+                failed
+                    .iter()
+                    .map(|(name, code)| format!("{} (exit {})", name, code))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        )
+        .into()),
+    }
+}
+
+/// Fails if the copies would write over each other: to the same file or address, or binary
+/// to stdout.
+fn check_outputs(cli: &Cli) -> anyhow::Result<()> {
+    let usage = |message: String| Err(ExitStatus::new(EXIT_USAGE, message).into());
+    let mut files: Vec<(&str, PathBuf)> = vec![];
+    match &cli.sub_commands {
+        Commands::Capture(capture) => {
+            let file = if capture.sink.is_empty() {
+                "--output "
+            } else {
+                "--sink file:"
+            };
+            for spec in sinks::specs(capture) {
+                match spec {
+                    SinkSpec::File(path) => files.push((file, path)),
+                    SinkSpec::TcpServer(addr) | SinkSpec::WebSocket(addr) => {
+                        return usage(format!("every device would listen on {}", addr))
+                    }
+                    SinkSpec::Stdout
+                        if !(capture.roll || capture.xy || sinks::is_text(&capture.format)) =>
+                    {
+                        return usage(format!(
+                            "--format {} to stdout would be corrupted by the device prefixes, write each \
+                             device to a file of its own, e.g. --output capture-{}.bin",
+                            capture.format.to_string().to_lowercase(),
+                            DEVICE_PLACEHOLDER
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            #[cfg(feature = "hdf5")]
+            files.extend(capture.hdf5.clone().map(|it| ("--hdf5 ", it)));
+            files.extend(capture.stats_json.clone().map(|it| ("--stats-json ", it)));
+            files.extend(capture.make_mask.clone().map(|it| ("--make-mask ", it)));
+        }
+        Commands::Config(ConfigCli {
+            sub_commands: ConfigCommands::Snapshot(snapshot),
+        }) => files.extend(snapshot.output.clone().map(|it| ("--output ", it))),
+        Commands::Awg(AwgCli {
+            sub_commands: Some(AwgCommands::Encode(encode)),
+            ..
+        }) => files.extend(encode.output.clone().map(|it| ("--output ", it))),
+        _ => {}
+    }
+    match files
+        .iter()
+        .find(|(_, path)| !path.to_string_lossy().contains(DEVICE_PLACEHOLDER))
+    {
+        Some((option, path)) => usage(format!(
+            "{}{} would be written by every device, put {} in it for a file each",
+            option,
+            path.display(),
+            DEVICE_PLACEHOLDER
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The command line of the process, [`DEVICE_PLACEHOLDER`] replaced by the device.
+fn args_for(name: &str) -> Vec<OsString> {
+    let name = name.replace(':', "-");
+    env::args_os()
+        .skip(1)
+        .map(|arg| match arg.to_str() {
+            Some(text) => text.replace(DEVICE_PLACEHOLDER, &name).into(),
+            None => arg,
+        })
+        .collect()
+}
+
+/// Exit code of the copy of the process run for the device.
+fn run_one(exe: &std::path::Path, name: &str) -> anyhow::Result<i32> {
+    let mut child = Command::new(exe)
+        .args(args_for(name))
+        .env(DEVICE_ENV, name)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("starting the command for {}", name))?;
+
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+    thread::scope(|scope| {
+        scope.spawn(|| prefix_lines(stdout, name, &mut io::stdout()));
+        scope.spawn(|| prefix_lines(stderr, name, &mut io::stderr()));
+    });
+    let status = child.wait()?;
+    // Killed by a signal has no code, it's what an interrupted run exits with anyway.
+    Ok(status.code().unwrap_or(EXIT_INTERRUPTED))
+}
+
+fn prefix_lines(from: impl Read, name: &str, to: &mut impl Write) {
+    for line in BufReader::new(from).split(b'\n') {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        // One write per line, so lines of different devices don't interleave.
+        let mut prefixed = format!("[{}] ", name).into_bytes();
+        prefixed.extend_from_slice(&line);
+        prefixed.push(b'\n');
+        if to.write_all(&prefixed).is_err() {
+            break;
+        }
+    }
+}
+
+fn names(names: &[String]) -> String {
+    if names.is_empty() {
+        "none".to_string()
+    } else {
+        names.join(", ")
+    }
+}
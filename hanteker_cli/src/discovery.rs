@@ -0,0 +1,96 @@
+//! mDNS announcements of `serve --mdns`, the way LXI instruments make them, so that discovery of
+//! VISA tooling and mDNS browsers finds the bridged device without being told its address.
+//!
+//! SCPI is announced as `_scpi-raw._tcp` and HTTP as `_http._tcp` and `_lxi._tcp`, with the
+//! identity of the device in TXT records. Only raw socket SCPI is served, VXI-11 and HiSLIP
+//! discovery is not answered.
+
+use std::net::IpAddr;
+
+use anyhow::{bail, Context};
+use log::{debug, info};
+use mdns_sd::{ServiceDaemon, ServiceInfo};
+
+use hanteker_lib::models::hantek2d42::Hantek2D42;
+
+pub(crate) const SCPI_SERVICES: [&str; 1] = ["_scpi-raw._tcp.local."];
+pub(crate) const HTTP_SERVICES: [&str; 2] = ["_http._tcp.local.", "_lxi._tcp.local."];
+
+/// Announces the services until dropped, then says goodbye to the network.
+pub(crate) struct Announcement {
+    daemon: ServiceDaemon,
+}
+
+/// Announces the services on `port`, at `bind` or at every address of the host if unspecified.
+pub(crate) fn announce(
+    hantek: &Hantek2D42,
+    services: &[&str],
+    bind: &str,
+    port: u16,
+) -> anyhow::Result<Announcement> {
+    let ip: IpAddr = bind
+        .parse()
+        .with_context(|| format!("announcing needs --bind to be an address, got {}", bind))?;
+    if ip.is_loopback() {
+        bail!("--mdns needs --bind to an address others can reach, e.g. 0.0.0.0");
+    }
+
+    let manufacturer = hantek
+        .usb
+        .get_manufacturer()
+        .unwrap_or_else(|_| "Hantek".to_string());
+    let model = hantek
+        .usb
+        .get_product()
+        .unwrap_or_else(|_| "2D42".to_string());
+    let serial = hantek.usb.get_serial().ok().flatten();
+    let properties = [
+        ("txtvers", "1".to_string()),
+        ("Manufacturer", manufacturer.clone()),
+        ("Model", model.clone()),
+        (
+            "SerialNumber",
+            serial.clone().unwrap_or_else(|| "0".to_string()),
+        ),
+        (
+            "FirmwareVersion",
+            format!("hanteker-{}", env!("CARGO_PKG_VERSION")),
+        ),
+    ];
+    // Names must be unique on the network, the serial tells two bridges apart.
+    let (name, host) = match &serial {
+        Some(serial) => (
+            format!("{} {} {}", manufacturer, model, serial),
+            format!("hanteker-{}.local.", serial),
+        ),
+        None => (
+            format!("{} {}", manufacturer, model),
+            "hanteker.local.".to_string(),
+        ),
+    };
+
+    let daemon = ServiceDaemon::new().context("starting mDNS")?;
+    for service in services {
+        let info = if ip.is_unspecified() {
+            ServiceInfo::new(service, &name, &host, (), port, &properties[..])
+                .map(ServiceInfo::enable_addr_auto)
+        } else {
+            ServiceInfo::new(service, &name, &host, ip, port, &properties[..])
+        }
+        .with_context(|| format!("describing {}", service))?;
+        debug!("announcing {}", info.get_fullname());
+        daemon
+            .register(info)
+            .with_context(|| format!("announcing {}", service))?;
+    }
+    info!("announcing {} over mDNS as {}", services.join(", "), name);
+    Ok(Announcement { daemon })
+}
+
+impl Drop for Announcement {
+    fn drop(&mut self) {
+        if let Err(e) = self.daemon.shutdown() {
+            debug!("stopping mDNS: {}", e);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Exit codes, one per class of error, stable so that scripts can rely on them:
+//!
+//! | code | class              |                                                            |
+//! |------|--------------------|------------------------------------------------------------|
+//! | 1    | `failure`          | anything not classified below                              |
+//! | 2    | `usage`            | bad command line                                           |
+//! | 3    | `device_not_found` | no device, or more than one, is connected                  |
+//! | 4    | `device_access`    | the device can't be opened or claimed, e.g. permissions    |
+//! | 5    | `usb_timeout`      | a USB transfer timed out                                   |
+//! | 6    | `usb`              | any other USB failure, e.g. the device was unplugged       |
+//! | 7    | `invalid_argument` | a value the device doesn't take, or one missing to set it  |
+//! | 8    | `capture`          | the device stopped sending capture data                    |
+//! | 9    | `mask_violation`   | a capture violated the mask, with `--fail-on-violation`    |
+//! | 124  | `timeout`          | `wait` gave up before observing the condition              |
+//! | 130  | `interrupted`      | killed by a signal                                         |
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+use hanteker_lib::device::usb::HantekUsbError;
+use hanteker_lib::models::hantek2d42::Hantek2D42Error;
+use serde_json::json;
+
+use crate::failsafe::EXIT_INTERRUPTED;
+
+pub(crate) const EXIT_FAILURE: i32 = 1;
+pub(crate) const EXIT_USAGE: i32 = 2;
+pub(crate) const EXIT_DEVICE_NOT_FOUND: i32 = 3;
+pub(crate) const EXIT_DEVICE_ACCESS: i32 = 4;
+pub(crate) const EXIT_USB_TIMEOUT: i32 = 5;
+pub(crate) const EXIT_USB: i32 = 6;
+pub(crate) const EXIT_INVALID_ARGUMENT: i32 = 7;
+pub(crate) const EXIT_CAPTURE: i32 = 8;
+pub(crate) const EXIT_MASK_VIOLATION: i32 = 9;
+
+/// `wait` gave up before observing the condition, same code as coreutils' `timeout`.
+pub(crate) const EXIT_TIMEOUT: i32 = 124;
+
+/// An outcome that scripts need to tell apart from a generic failure. Returned by handlers
+/// and turned into the exit code by `main`, after the device is released.
+#[derive(Debug)]
+pub(crate) struct ExitStatus {
+    pub(crate) code: i32,
+    pub(crate) message: String,
+}
+
+impl ExitStatus {
+    pub(crate) fn new(code: i32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for ExitStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl Error for ExitStatus {}
+
+/// Name of the class of an exit code, as in the table above.
+pub(crate) fn class_of(code: i32) -> &'static str {
+    match code {
+        EXIT_USAGE => "usage",
+        EXIT_DEVICE_NOT_FOUND => "device_not_found",
+        EXIT_DEVICE_ACCESS => "device_access",
+        EXIT_USB_TIMEOUT => "usb_timeout",
+        EXIT_USB => "usb",
+        EXIT_INVALID_ARGUMENT => "invalid_argument",
+        EXIT_CAPTURE => "capture",
+        EXIT_MASK_VIOLATION => "mask_violation",
+        EXIT_TIMEOUT => "timeout",
+        EXIT_INTERRUPTED => "interrupted",
+        _ => "failure",
+    }
+}
+
+/// Exit code of the first error in the chain that has a class of its own.
+pub(crate) fn code_of(error: &anyhow::Error) -> i32 {
+    error
+        .chain()
+        .find_map(|it| {
+            if let Some(status) = it.downcast_ref::<ExitStatus>() {
+                return Some(status.code);
+            }
+            if let Some(e) = it.downcast_ref::<Hantek2D42Error>() {
+                return device_code(e);
+            }
+            if let Some(e) = it.downcast_ref::<HantekUsbError>() {
+                return usb_code(e);
+            }
+            it.downcast_ref::<libusb::Error>().map(libusb_code)
+        })
+        .unwrap_or(EXIT_FAILURE)
+}
+
+fn device_code(error: &Hantek2D42Error) -> Option<i32> {
+    match error {
+        // The usb error is next in the chain.
+        Hantek2D42Error::HantekUsbError { .. } => None,
+        Hantek2D42Error::ChannelScaleUnknown { .. }
+        | Hantek2D42Error::TriggerSourceUnknown
+        | Hantek2D42Error::InvalidArgument { .. } => Some(EXIT_INVALID_ARGUMENT),
+        Hantek2D42Error::IncompleteCapture { .. } => Some(EXIT_CAPTURE),
+        Hantek2D42Error::CaptureCancelled { .. } => Some(EXIT_INTERRUPTED),
+        _ => Some(EXIT_FAILURE),
+    }
+}
+
+fn usb_code(error: &HantekUsbError) -> Option<i32> {
+    match error {
+        HantekUsbError::NoDeviceFound { .. } | HantekUsbError::TooManyDevicesFound { .. } => {
+            Some(EXIT_DEVICE_NOT_FOUND)
+        }
+        HantekUsbError::OpenUsbDeviceError { .. }
+        | HantekUsbError::UsbInterfaceClaimError { .. }
+        | HantekUsbError::InterfaceAlreadyClaimed { .. } => Some(EXIT_DEVICE_ACCESS),
+        HantekUsbError::Interrupted => Some(EXIT_INTERRUPTED),
+        // The libusb error is next in the chain.
+        _ if error.source().is_some() => None,
+        _ => Some(EXIT_USB),
+    }
+}
+
+fn libusb_code(error: &libusb::Error) -> i32 {
+    match error {
+        libusb::Error::Timeout => EXIT_USB_TIMEOUT,
+        libusb::Error::Access => EXIT_DEVICE_ACCESS,
+        _ => EXIT_USB,
+    }
+}
+
+/// The single line written to stderr on failure with `--machine`.
+pub(crate) fn machine_error(code: i32, message: &str) -> String {
+    json!({
+        "code": code,
+        "class": class_of(code),
+        "message": message,
+    })
+    .to_string()
+}
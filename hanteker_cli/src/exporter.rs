@@ -0,0 +1,203 @@
+//! Prometheus metrics of `serve --metrics`, scraped from a thread of their own while the server
+//! owns the device: capture reads and bytes, USB errors, retries and short reads, whether the
+//! device is connected and when it last captured, so that a stalled acquisition can be alerted
+//! on. The device doesn't send DMM readings over USB, only whether DMM is its function is known.
+//!
+//! Values are taken from the device between requests, an idle server reports the last ones.
+
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::bail;
+use log::{debug, info};
+use tiny_http::{Header, Response, Server};
+
+use hanteker_lib::device::cfg::DeviceFunction;
+use hanteker_lib::models::hantek2d42::Hantek2D42;
+
+use crate::scpi::ScpiError;
+
+#[derive(Debug, Clone)]
+struct State {
+    capture_reads: u64,
+    capture_bytes: u64,
+    usb_errors: u64,
+    usb_retries: u64,
+    short_reads: u64,
+    connected: bool,
+    last_capture: Option<SystemTime>,
+    function: Option<DeviceFunction>,
+}
+
+/// Shares what the server observes with the metrics thread, does nothing if not listening.
+#[derive(Clone, Default)]
+pub(crate) struct Exporter {
+    state: Option<Arc<Mutex<State>>>,
+}
+
+impl Exporter {
+    /// Serves the metrics on `addr`, all interfaces if it's only a port as in `:9187`.
+    pub(crate) fn listen(addr: &str) -> anyhow::Result<Self> {
+        let addr = match addr.strip_prefix(':') {
+            Some(port) => format!("0.0.0.0:{}", port),
+            None => addr.to_string(),
+        };
+        let server = match Server::http(&addr) {
+            Ok(server) => server,
+            Err(e) => bail!("could not listen on {}: {}", addr, e),
+        };
+        info!("serving metrics on http://{}/metrics", addr);
+
+        let state = Arc::new(Mutex::new(State {
+            capture_reads: 0,
+            capture_bytes: 0,
+            usb_errors: 0,
+            usb_retries: 0,
+            short_reads: 0,
+            connected: true,
+            last_capture: None,
+            function: None,
+        }));
+        {
+            let state = Arc::clone(&state);
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    let response = match request.url() {
+                        "/metrics" => {
+                            let state = state.lock().unwrap().clone();
+                            let content_type = "text/plain; version=0.0.4";
+                            Response::from_string(exposition(&state)).with_header(
+                                Header::from_bytes(&b"Content-Type"[..], content_type).unwrap(),
+                            )
+                        }
+                        _ => Response::from_string("not found").with_status_code(404),
+                    };
+                    if let Err(e) = request.respond(response) {
+                        debug!("metrics scrape failed: {}", e);
+                    }
+                }
+            });
+        }
+        Ok(Self { state: Some(state) })
+    }
+
+    /// Takes the counters of the device, a capture read since the last time being a capture.
+    pub(crate) fn observe(&self, hantek: &Hantek2D42) {
+        let state = match &self.state {
+            Some(state) => state,
+            None => return,
+        };
+        let metrics = hantek.metrics();
+        let (reads, bytes) = metrics
+            .commands
+            .get(&("reading capture", None))
+            .map_or((0, 0), |it| (it.count, it.bytes));
+
+        let mut state = state.lock().unwrap();
+        if reads > state.capture_reads {
+            state.last_capture = Some(SystemTime::now());
+            state.connected = true;
+        }
+        state.capture_reads = reads;
+        state.capture_bytes = bytes;
+        state.usb_retries = hantek.usb.retries();
+        state.short_reads = metrics.short_reads;
+        state.function = hantek.get_config().device_function.clone();
+    }
+
+    /// Counts the error if USB failed, the device is disconnected if it's gone.
+    pub(crate) fn failed(&self, error: &ScpiError) {
+        let state = match &self.state {
+            Some(state) => state,
+            None => return,
+        };
+        let mut state = state.lock().unwrap();
+        match error.code {
+            -240 => state.usb_errors += 1,
+            -241 => {
+                state.usb_errors += 1;
+                state.connected = false;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The metrics in the text format of Prometheus.
+fn exposition(state: &State) -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, f64)]| {
+        writeln!(out, "# HELP hanteker_{} {}", name, help).unwrap();
+        writeln!(out, "# TYPE hanteker_{} {}", name, kind).unwrap();
+        for (labels, value) in samples {
+            writeln!(out, "hanteker_{}{} {}", name, labels, value).unwrap();
+        }
+    };
+
+    metric(
+        "capture_reads_total",
+        "counter",
+        "Capture reads from the device.",
+        &[("", state.capture_reads as f64)],
+    );
+    metric(
+        "capture_bytes_total",
+        "counter",
+        "Bytes of capture data read from the device.",
+        &[("", state.capture_bytes as f64)],
+    );
+    metric(
+        "usb_errors_total",
+        "counter",
+        "Device commands failed by USB.",
+        &[("", state.usb_errors as f64)],
+    );
+    metric(
+        "usb_retries_total",
+        "counter",
+        "USB transfers retried after a stall or timeout.",
+        &[("", state.usb_retries as f64)],
+    );
+    metric(
+        "short_reads_total",
+        "counter",
+        "Capture reads the device answered with less than asked for.",
+        &[("", state.short_reads as f64)],
+    );
+    metric(
+        "device_connected",
+        "gauge",
+        "Whether the device is connected, as of the last command.",
+        &[("", if state.connected { 1.0 } else { 0.0 })],
+    );
+    if let Some(last) = state.last_capture {
+        let seconds = last.duration_since(UNIX_EPOCH).unwrap_or_default();
+        metric(
+            "last_capture_timestamp_seconds",
+            "gauge",
+            "When capture data was last read from the device.",
+            &[("", seconds.as_secs_f64())],
+        );
+    }
+    if let Some(function) = &state.function {
+        let labels: Vec<_> = DeviceFunction::my_iter()
+            .map(|it| {
+                let value = if it == *function { 1.0 } else { 0.0 };
+                (
+                    format!("{{function=\"{}\"}}", it.to_string().to_lowercase()),
+                    value,
+                )
+            })
+            .collect();
+        let samples: Vec<_> = labels.iter().map(|(l, v)| (l.as_str(), *v)).collect();
+        metric(
+            "device_function",
+            "gauge",
+            "Function the device was last set to, scope, awg or dmm.",
+            &samples,
+        );
+    }
+    out
+}
@@ -0,0 +1,87 @@
+//! Best-effort cleanup when a command doesn't run to completion, be it a panic or a signal.
+//! Leaving the generator driving a circuit after a crashed script can damage hardware, so the
+//! AWG output and the acquisition are stopped, and the interface released.
+
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use hanteker_lib::models::hantek2d42::Hantek2D42;
+use log::{error, warn};
+use signal_hook::consts::TERM_SIGNALS;
+use signal_hook::flag;
+
+/// Killed by a signal, same code as shells report for SIGINT.
+pub(crate) const EXIT_INTERRUPTED: i32 = 130;
+
+/// Makes the terminating signals set the returned flag, which the device checks before each
+/// transfer. A second signal, e.g. while the cleanup hangs, exits right away.
+pub(crate) fn on_signals() -> io::Result<Arc<AtomicBool>> {
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let mut signals = TERM_SIGNALS.to_vec();
+    #[cfg(unix)]
+    signals.push(signal_hook::consts::SIGHUP);
+
+    for signal in signals {
+        // Registered first, so it only fires once the flag is already set.
+        flag::register_conditional_shutdown(signal, EXIT_INTERRUPTED, Arc::clone(&interrupted))?;
+        flag::register(signal, Arc::clone(&interrupted))?;
+    }
+    Ok(interrupted)
+}
+
+/// Stops the device if dropped while panicking or after a signal, unless asked to keep it
+/// running after a signal.
+pub(crate) struct Failsafe<'h, 'a> {
+    hantek: &'h mut Hantek2D42<'a>,
+    interrupted: Arc<AtomicBool>,
+    keep_running: bool,
+}
+
+impl<'h, 'a> Failsafe<'h, 'a> {
+    pub(crate) fn new(
+        hantek: &'h mut Hantek2D42<'a>,
+        interrupted: Arc<AtomicBool>,
+        keep_running: bool,
+    ) -> Self {
+        hantek.usb.set_interrupt(Some(Arc::clone(&interrupted)));
+        Self {
+            hantek,
+            interrupted,
+            keep_running,
+        }
+    }
+
+    pub(crate) fn hantek(&mut self) -> &mut Hantek2D42<'a> {
+        self.hantek
+    }
+}
+
+impl Drop for Failsafe<'_, '_> {
+    fn drop(&mut self) {
+        // Lets the cleanup through, a further signal exits without waiting for it.
+        self.hantek.usb.set_interrupt(None);
+
+        let interrupted = self.interrupted.load(Ordering::SeqCst);
+        let panicking = thread::panicking();
+        if !interrupted && !panicking {
+            return;
+        }
+
+        if self.keep_running && !panicking {
+            warn!("command did not complete, leaving awg and scope running");
+        } else {
+            warn!("command did not complete, stopping awg and scope");
+            if let Err(e) = self.hantek.awg_stop() {
+                error!("failsafe could not stop awg: {}", e);
+            }
+            if let Err(e) = self.hantek.stop() {
+                error!("failsafe could not stop scope: {}", e);
+            }
+        }
+        if let Err(e) = self.hantek.usb.release() {
+            error!("failsafe could not release interface: {}", e);
+        }
+    }
+}
@@ -1,14 +1,61 @@
-use std::{env, io};
-use std::fmt::Display;
+use std::{env, fs, io};
 use std::io::Write;
+use std::net::TcpListener;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
-use anyhow::bail;
+use anyhow::{bail, Context};
 use clap_complete::generate;
-use hanteker_lib::device::cfg::DeviceFunction;
-use hanteker_lib::models::hantek2d42::Hantek2D42;
-use log::{error, warn};
+use hanteker_lib::awg::{AwgBurst, AwgSweep};
+use hanteker_lib::batch::{CommandBatch, Setting};
+use hanteker_lib::bode::Bode;
+use hanteker_lib::capture::{
+    is_roll_mode, sample_rate, Acquisition, AcquisitionStats, BufferPool, CaptureFrame,
+    CaptureHandle, Gate,
+};
+use hanteker_lib::compensation::{self, Compensation};
+use hanteker_lib::decode::spi::SpiMode;
+use hanteker_lib::decode::{self, i2c, spi, uart};
+use hanteker_lib::detect::Detect;
+use hanteker_lib::device::cfg::{
+    AwgType, ConfigChange, Coupling, DeviceFunction, Scale, TimeScale, TriggerMode,
+};
+use hanteker_lib::device::cmd::RawCommand;
+use hanteker_lib::dsp::{spectrum, Decimation, Filter, SpectrumBin};
+use hanteker_lib::encode::encode;
+use hanteker_lib::export::{ExportFormat, FrameSink, RollCsvSink, XyCsvSink};
+use hanteker_lib::logging::AWG;
+use hanteker_lib::mask::{test_frame, Mask, MaskStats};
+use hanteker_lib::measure::{measure, EdgeCounter, Measurements, Stat};
+use hanteker_lib::models::hantek2d42::{DecodedCommand, Hantek2D42};
+use hanteker_lib::quirks::Quirk;
+use hanteker_lib::verify::{verify_scales, Outcome};
+use log::{debug, error, info, warn};
+use serde_json::json;
 
-use crate::cli::{AwgCli, CaptureCli, ChannelCli, Cli, cli_command, DeviceCli, ScopeCli, ShellCli};
+use crate::cli::{
+    AwgCli, AwgCommands, AwgEncodeCli, AwgSweepCli, BenchCli, BodeCli, CaptureCli, ChannelCli, Cli,
+    cli_command, ConfigDiffCli, ConfigSnapshotCli, CounterCli, DecodeI2cCli, DecodeSpiCli,
+    DecodeUartCli, DeviceCli, MeasureCli, PlotCli, PrintCli, PrintFormat, ProbeCheckCli, RawCli,
+    ScopeCli, ServeCli, SetupUdevCli, ShellCli, SpectrumCli, SpectrumFormat, StatusCli, SweepCli,
+    TuiCli, VerifyCli, WaitCli,
+};
+use crate::discovery;
+use crate::exit::{ExitStatus, EXIT_MASK_VIOLATION, EXIT_TIMEOUT};
+use crate::exporter::Exporter;
+use crate::health::Health;
+use crate::http;
+use crate::mask;
+use crate::output::keep_writing;
+use crate::plot;
+use crate::profile;
+use crate::scpi;
+use crate::sinks;
+use crate::snapshot;
+use crate::sweep::{csv_line, print_table, Plan};
+use crate::tui::Dashboard;
+use crate::udev;
 
 pub(crate) fn handle_shell(_parent: &Cli, s: &ShellCli) {
     let name = match &s.name_override {
@@ -18,157 +65,1618 @@ pub(crate) fn handle_shell(_parent: &Cli, s: &ShellCli) {
     generate(s.shell, &mut cli_command(), name, &mut io::stdout());
 }
 
-pub(crate) fn handle_print(_parent: &Cli, hantek: &mut Hantek2D42) -> anyhow::Result<()> {
-    println!("{}", hantek.usb.pretty_printed_device_info());
+pub(crate) fn handle_setup_udev(_parent: &Cli, cli: &SetupUdevCli) -> anyhow::Result<()> {
+    if cli.install {
+        udev::install(&cli.path)
+    } else {
+        print!("{}", udev::rule());
+        Ok(())
+    }
+}
+
+pub(crate) fn handle_print(
+    _parent: &Cli,
+    cli: &PrintCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    let format = if cli.json {
+        PrintFormat::Json
+    } else {
+        cli.format.clone()
+    };
+    if cli.quirks {
+        for quirk in hantek.quirks() {
+            println!("{}: {}", quirk.my_to_string(), quirk.description());
+        }
+        return Ok(());
+    }
+    match format {
+        PrintFormat::Text => println!("{}", hantek.usb.pretty_printed_device_info()),
+        PrintFormat::Json => println!("{}", serde_json::to_string_pretty(&device_info(hantek))?),
+        PrintFormat::Yaml => print!("{}", serde_yaml::to_string(&device_info(hantek))?),
+    }
+    Ok(())
+}
+
+/// Opening and claiming the device is done by then, what's left to check is that it answers.
+pub(crate) fn handle_status(
+    _parent: &Cli,
+    _cli: &StatusCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    hantek
+        .capture(&[1], 64)
+        .context("device doesn't answer a capture")?;
+
+    let usb = &hantek.usb;
+    println!(
+        "ready: {} bus={:03} address={:03} release={} serial={}",
+        usb.get_product()
+            .unwrap_or_else(|_| format!("{:04x}:{:04x}", usb.vid(), usb.pid())),
+        usb.device.bus_number(),
+        usb.device.address(),
+        usb.device_release(),
+        usb.get_serial()
+            .ok()
+            .flatten()
+            .unwrap_or_else(|| "none".to_string()),
+    );
+    Ok(())
+}
+
+/// Strings that can't be read are `null`, the rest is still worth having.
+fn device_info(hantek: &Hantek2D42) -> serde_json::Value {
+    let usb = &hantek.usb;
+    let manufacturer = usb.get_manufacturer().map_err(|e| warn!("{}", e)).ok();
+    let product = usb.get_product().map_err(|e| warn!("{}", e)).ok();
+    let serial = usb.get_serial().map_err(|e| warn!("{}", e)).ok().flatten();
+    json!({
+        "vid": format!("{:04x}", usb.vid()),
+        "pid": format!("{:04x}", usb.pid()),
+        "bus": usb.device.bus_number(),
+        "address": usb.device.address(),
+        "speed": usb.speed(),
+        "device_release": usb.device_release(),
+        "manufacturer": manufacturer,
+        "product": product,
+        "serial": serial,
+        "quirks": hantek
+            .quirks()
+            .iter()
+            .map(|it| it.my_to_string().to_string())
+            .collect::<Vec<_>>(),
+        "config": snapshot::snapshot(hantek.get_config()),
+    })
+}
+
+pub(crate) fn handle_raw(
+    _parent: &Cli,
+    cli: &RawCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    let cmd: RawCommand = Hantek2D42::command_builder(cli.func)
+        .set_cmd(cli.cmd)
+        .set_val_u8(cli.val[0], cli.val[1], cli.val[2], cli.val[3])
+        .build()?;
+    println!("# {}", DecodedCommand::from(cmd));
+    print!("{}", hex_dump('>', &cmd));
+    let written = hantek.send_raw(&cmd)?;
+    if written != cmd.len() {
+        warn!("only {} of {} bytes written", written, cmd.len());
+    }
+
+    if let Some(length) = cli.read {
+        let mut response = vec![0; length];
+        let read = hantek.read_raw(&mut response)?;
+        print!("{}", hex_dump('<', &response[..read]));
+        if read == 0 {
+            println!("< (nothing)");
+        }
+    }
+    Ok(())
+}
+
+/// Lines of 16 bytes, each prefixed with the direction and its offset.
+fn hex_dump(direction: char, bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(idx, line)| {
+            let hex: Vec<String> = line.iter().map(|it| format!("{:02x}", it)).collect();
+            format!("{} {:04x}  {}\n", direction, idx * 16, hex.join(" "))
+        })
+        .collect()
+}
+
+pub(crate) fn handle_config_snapshot(
+    _parent: &Cli,
+    cli: &ConfigSnapshotCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    let snapshot = snapshot::snapshot(hantek.get_config());
+    match &cli.output {
+        Some(path) => snapshot::write(path, &snapshot)?,
+        None => println!("{}", serde_json::to_string_pretty(&snapshot)?),
+    }
+    Ok(())
+}
+
+pub(crate) fn handle_config_diff(_parent: &Cli, cli: &ConfigDiffCli) -> anyhow::Result<()> {
+    let before = snapshot::read(&cli.before)?;
+    let after = match &cli.after {
+        Some(after) => snapshot::read(after)?,
+        None => unreachable!(),
+    };
+    let changes = snapshot::diff(&before, &after);
+    if changes.is_empty() {
+        println!("no differences");
+    }
+    for change in changes {
+        println!("{}", change);
+    }
+    Ok(())
+}
+
+/// What applying the profile would change, leaving out the fields it doesn't set.
+pub(crate) fn handle_config_diff_profile(
+    _parent: &Cli,
+    cli: &ConfigDiffCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    let profile = profile::read(&cli.before)?;
+    let changes: Vec<ConfigChange> = hantek
+        .get_config()
+        .diff(&profile)
+        .into_iter()
+        .filter(ConfigChange::is_set_after)
+        .collect();
+    if changes.is_empty() {
+        println!("no differences");
+    }
+    for change in changes {
+        println!("{}", change);
+    }
+    Ok(())
+}
+
+pub(crate) fn handle_device(
+    _parent: &Cli,
+    cli: &DeviceCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    if cli.start && cli.stop {
+        bail!("must not specify start and stop at the same time.");
+    }
+
+    if cli.reset {
+        hantek.reset()?;
+        info!("device reset");
+    }
+
+    if cli.start {
+        hantek.start()?;
+    }
+    if cli.stop {
+        hantek.stop()?;
+    }
+
+    if let Some(mode) = &cli.mode {
+        hantek.set_device_function(mode.clone())?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn handle_scope(
+    _parent: &Cli,
+    cli: &ScopeCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    if cli.force_mode {
+        hantek.set_device_function(DeviceFunction::Scope)?;
+    }
+
+    if let Some(time_scale) = &cli.time_scale {
+        hantek.set_time_scale(time_scale.clone())?;
+    }
+    if let Some(time_offset) = &cli.time_offset {
+        hantek.set_time_offset_with_auto_adjustment(*time_offset)?;
+    }
+    if let Some(trigger_position) = &cli.trigger_position {
+        hantek.set_trigger_position(*trigger_position)?;
+    }
+
+    if let Some(trigger_source) = &cli.trigger_source {
+        hantek.set_trigger_source(*trigger_source)?;
+    }
+    if let Some(trigger_level) = &cli.trigger_level {
+        hantek.set_trigger_level_volts(*trigger_level, None)?;
+    }
+    if let Some(percent) = &cli.trigger_level_percent {
+        hantek.set_trigger_level_percent(*percent)?;
+    }
+    if let Some(trigger_slope) = &cli.trigger_slope {
+        hantek.set_trigger_slope(trigger_slope.clone())?;
+    }
+    if let Some(trigger_mode) = &cli.trigger_mode {
+        hantek.set_trigger_mode(trigger_mode.clone())?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn handle_channel(
+    _parent: &Cli,
+    cli: &ChannelCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    if cli.force_mode {
+        hantek.set_device_function(DeviceFunction::Scope)?;
+    }
+
+    let channels = cli.channels();
+    let mut batch = CommandBatch::new();
+    for channel_no in channels.iter().copied() {
+        if cli.enable {
+            batch.push(Setting::ChannelEnabled(channel_no, true));
+        }
+        if cli.disable {
+            batch.push(Setting::ChannelEnabled(channel_no, false));
+        }
+        if cli.enable_bandwidth_limit {
+            batch.push(Setting::ChannelBandwidthLimit(channel_no, true));
+        }
+        if cli.disable_bandwidth_limit {
+            batch.push(Setting::ChannelBandwidthLimit(channel_no, false));
+        }
+        if let Some(coupling) = &cli.coupling {
+            batch.push(Setting::ChannelCoupling(channel_no, coupling.clone()));
+        }
+        if let Some(probe) = &cli.probe {
+            batch.push(Setting::ChannelProbe(channel_no, probe.clone()));
+        }
+        if let Some(scale) = &cli.scale {
+            batch.push(Setting::ChannelScale(channel_no, scale.clone()));
+        }
+        if let Some(offset) = cli.offset_raw {
+            batch.push(Setting::ChannelOffset(channel_no, offset));
+        }
+    }
+    hantek.apply_batch(&batch)?;
+
+    // In volts it depends on the scale, so it's only converted once the scale is set.
+    if let Some(offset) = &cli.offset {
+        for channel_no in channels {
+            hantek.set_channel_offset_with_auto_adjustment(channel_no, *offset)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn handle_capture(
+    _parent: &Cli,
+    cli: &CaptureCli,
+    hantek: &mut Hantek2D42,
+    handle: &CaptureHandle,
+) -> anyhow::Result<()> {
+    if cli.channel.is_empty() {
+        error!("at least one channel must be specified.");
+        std::process::exit(1);
+    }
+
+    if cli.capture_chunk < 64 {
+        error!("minimum length of chunks=64, asked for={}", cli.capture_chunk);
+        std::process::exit(1);
+    }
+
+    if cli.xy && !(cli.channel.contains(&1) && cli.channel.contains(&2)) {
+        bail!("xy pairs channel 1 with channel 2, capture both");
+    }
+
+    if cli.force_mode {
+        hantek.set_device_function(DeviceFunction::Scope)?;
+    }
+
+    let gate = match cli.gate_channel {
+        None => None,
+        Some(gate_channel) => {
+            if cli.channel.len() != 1 || cli.channel[0] == gate_channel {
+                bail!("gated capture records a single channel other than the gate channel");
+            }
+            if let Some(scale) = &cli.gate_scale {
+                hantek.set_channel_scale(gate_channel, scale.clone())?;
+            }
+            if hantek
+                .get_config()
+                .channel(gate_channel)
+                .and_then(|it| it.scale())
+                .is_none()
+            {
+                bail!(
+                    "scale of channel {} is unknown, specify it with --gate-scale",
+                    gate_channel
+                );
+            }
+            Some(Gate {
+                channel_no: gate_channel,
+                level: cli.gate_above.or(cli.gate_below).unwrap(),
+                above: cli.gate_above.is_some(),
+            })
+        }
+    };
+
+    if !cli.math.is_empty() {
+        if let ExportFormat::Raw | ExportFormat::Framed | ExportFormat::F32le = cli.format {
+            bail!("math columns need csv or jsonl format");
+        }
+        for expr in &cli.math {
+            if let Some(channel_no) = expr.channels().iter().find(|it| !cli.channel.contains(it)) {
+                bail!("{} uses channel {} which isn't captured", expr, channel_no);
+            }
+        }
+    }
+
+    if let Some(time_scale) = &cli.time_scale {
+        hantek.set_time_scale(time_scale.clone())?;
+    }
+    if let Some(scale) = &cli.scale {
+        for channel_no in &cli.channel {
+            hantek.set_channel_scale(*channel_no, scale.clone())?;
+        }
+    }
+
+    let mut filters = vec![];
+    if !cli.filter.is_empty() {
+        if gate.is_some() {
+            bail!("filters can't be combined with gated capture, its samples aren't evenly spaced");
+        }
+        let sample_rate = match &hantek.get_config().time_scale {
+            Some(time_scale) => sample_rate(time_scale),
+            None => bail!("time scale is unknown, specify it with --time-scale"),
+        };
+        for channel_no in &cli.channel {
+            for spec in &cli.filter {
+                filters.push((*channel_no, Filter::new(spec, sample_rate)?));
+            }
+        }
+    }
+
+    for detect in &cli.detect {
+        let config = hantek.get_config();
+        match detect {
+            Detect::Glitch(_) if config.time_scale.is_none() => {
+                bail!("time scale is unknown, specify it with --time-scale")
+            }
+            Detect::Runt(..) => {
+                if let Some(channel_no) = cli
+                    .channel
+                    .iter()
+                    .find(|it| config.channel(**it).and_then(|ch| ch.scale()).is_none())
+                {
+                    bail!(
+                        "scale of channel {} is unknown, specify it with --scale",
+                        channel_no
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut detections = vec![0; cli.detect.len()];
+
+    match &hantek.get_config().time_scale {
+        Some(time_scale) if cli.roll && !is_roll_mode(time_scale) => bail!(
+            "{} isn't a roll mode time scale, it starts at 100ms",
+            time_scale
+        ),
+        None if cli.roll => bail!("time scale is unknown, specify it with --time-scale"),
+        Some(time_scale) if !cli.roll && is_roll_mode(time_scale) => {
+            warn!("the device rolls at {}, see --roll", time_scale)
+        }
+        _ => {}
+    }
+    // Recordings take roll mode captures frame by frame, as any other.
+    #[cfg(feature = "hdf5")]
+    let recording = cli.hdf5.is_some();
+    #[cfg(not(feature = "hdf5"))]
+    let recording = false;
+    if cli.roll && !recording {
+        return capture_roll(cli, &mut filters, &mut detections, hantek, handle);
+    }
+
+    if let Some(Decimation::Envelope(samples_per_bucket)) = cli.decimate {
+        if let ExportFormat::Framed | ExportFormat::F32le = cli.format {
+            bail!("envelopes need raw, csv or jsonl format");
+        }
+        return capture_envelope(cli, samples_per_bucket, &mut filters, hantek, handle);
+    }
+
+    if let ExportFormat::F32le = cli.format {
+        if gate.is_some() {
+            bail!("f32le streams need evenly spaced samples, gated capture has none");
+        }
+        let config = hantek.get_config();
+        for channel_no in &cli.channel {
+            if config
+                .channel(*channel_no)
+                .and_then(|it| it.scale())
+                .is_none()
+            {
+                bail!(
+                    "scale of channel {} is unknown, specify it with --scale",
+                    channel_no
+                );
+            }
+        }
+        // Nothing in the stream tells its rate, whatever reads it has to be told.
+        match &config.time_scale {
+            Some(time_scale) => eprintln!(
+                "f32le: {} channels interleaved, {} samples/s each",
+                cli.channel.len(),
+                sample_rate(time_scale)
+            ),
+            None => bail!("time scale is unknown, specify it with --time-scale"),
+        }
+    }
+
+    let masks = match &cli.mask {
+        Some(path) => mask::read(path)?,
+        None => vec![],
+    };
+    let masked: Vec<usize> = match &cli.make_mask {
+        Some(_) => cli.channel.clone(),
+        None => masks.iter().map(|it| it.channel_no).collect(),
+    };
+    for channel_no in masked {
+        if !cli.channel.contains(&channel_no) {
+            bail!("the mask has channel {} which isn't captured", channel_no);
+        }
+        if hantek
+            .get_config()
+            .channel(channel_no)
+            .and_then(|it| it.scale())
+            .is_none()
+        {
+            bail!(
+                "scale of channel {} is unknown, specify it with --scale",
+                channel_no
+            );
+        }
+    }
+    let mut mask_stats = MaskStats::default();
+
+    let mut health = Health::new(cli)?;
+    let mut sink: Box<dyn FrameSink> = if cli.xy {
+        Box::new(XyCsvSink::new(sinks::writer(cli, &mut health, true)?, 1, 2))
+    } else if cli.segments.is_some() {
+        let text = sinks::is_text(&cli.format);
+        cli.format
+            .segment_sink(sinks::writer(cli, &mut health, text)?, cli.math.clone())
+    } else {
+        sinks::frame_sink(cli, &mut health)?
+    };
+    let mut stats = AcquisitionStats::default();
+    let mut pool = BufferPool::new(1);
+    let mut acquisition = Acquisition::new(cli.mode.clone());
+
+    if cli.segments.is_some() {
+        hantek.set_trigger_mode(TriggerMode::Single)?;
+    }
+
+    let mut captures = 0;
+    let num_captures = cli.segments.or(cli.num_captures);
+    while num_captures.is_none_or(|num| captures < num) {
+        let mut captured = match capture_chunk(cli, &gate, &mut stats, &mut pool, hantek, handle) {
+            Ok(captured) => captured,
+            // Interrupted, what was written so far stays a complete set of rows.
+            Err(e) if handle.is_cancelled() => {
+                debug!("capture stopped: {}", e);
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        health.record(&captured, hantek)?;
+        if !acquisition.apply(&mut captured) {
+            pool.put(captured.raw);
+            continue;
+        }
+        for (channel_no, filter) in &mut filters {
+            captured.filter_channel(*channel_no, filter);
+        }
+        detect(cli, &mut detections, &captured);
+        if let (Some(path), 0) = (&cli.make_mask, captures) {
+            make_mask(cli, path, &captured)?;
+        }
+        if !masks.is_empty() {
+            // Scales were checked above, they don't change during the capture.
+            let result = test_frame(&masks, &captured).unwrap_or_default();
+            if let Some(idx) = result.first_violation {
+                warn!(
+                    "capture {} violates the mask at sample {}, {} samples above and {} below",
+                    captures, idx, result.above, result.below
+                );
+            }
+            mask_stats.record(&result);
+        }
+        let written = sink.write_frame(&captured).and_then(|_| sink.flush());
+        if !keep_writing(written)? {
+            break;
+        }
+        pool.put(captured.raw);
+        captures += 1;
+    }
+    print_stats(cli, &stats);
+    print_detections(cli, &detections);
+
+    if cli.mask.is_some() {
+        eprintln!("mask: {}", mask_stats);
+        if cli.fail_on_violation && !mask_stats.passed() {
+            return Err(ExitStatus::new(
+                EXIT_MASK_VIOLATION,
+                format!(
+                    "{} of {} captures violated the mask",
+                    mask_stats.failed, mask_stats.captures
+                ),
+            )
+            .into());
+        }
+    }
+    Ok(())
+}
+
+/// Reports every event found in the capture to stderr, counting them by detection.
+fn detect(cli: &CaptureCli, counts: &mut [usize], frame: &CaptureFrame) {
+    for (detect, count) in cli.detect.iter().zip(counts) {
+        for channel_no in &frame.channels {
+            // What the detections need was checked before capturing.
+            for it in detect.scan(frame, *channel_no).unwrap_or_default() {
+                *count += 1;
+                let timestamp = match it.timestamp {
+                    Some(time) => humantime::format_rfc3339_micros(time).to_string(),
+                    None => "?".to_string(),
+                };
+                let width = match it.width {
+                    Some(width) => humantime::format_duration(width).to_string(),
+                    None => "?".to_string(),
+                };
+                eprintln!(
+                    "{} ch{} {} at {} sample={} width={}",
+                    detect.name(),
+                    channel_no,
+                    if it.high { "high" } else { "low" },
+                    timestamp,
+                    it.sample,
+                    width
+                );
+            }
+        }
+    }
+}
+
+fn print_detections(cli: &CaptureCli, counts: &[usize]) {
+    if !cli.detect.is_empty() {
+        let counts: Vec<String> = cli
+            .detect
+            .iter()
+            .zip(counts)
+            .map(|(detect, count)| format!("{}={}", detect, count))
+            .collect();
+        eprintln!("detected: {}", counts.join(" "));
+    }
+}
+
+/// Writes a mask around the golden capture, of every captured channel.
+fn make_mask(cli: &CaptureCli, path: &Path, golden: &CaptureFrame) -> anyhow::Result<()> {
+    let masks: Vec<Mask> = golden
+        .channels
+        .iter()
+        .map(|channel_no| {
+            // Scales were checked before capturing.
+            let volts = golden.channel_volts(*channel_no).unwrap_or_default();
+            Mask::around(*channel_no, &volts, cli.mask_margin, cli.mask_spread)
+        })
+        .collect();
+    mask::write(path, &masks)?;
+    info!(
+        "wrote mask of channels {:?} to {}",
+        golden.channels,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Appends captures as they come, each sample with its wall clock time.
+fn capture_roll(
+    cli: &CaptureCli,
+    filters: &mut [(usize, Filter)],
+    detections: &mut [usize],
+    hantek: &mut Hantek2D42,
+    handle: &CaptureHandle,
+) -> anyhow::Result<()> {
+    let mut health = Health::new(cli)?;
+    let mut sink = RollCsvSink::new(sinks::writer(cli, &mut health, true)?, cli.math.clone());
+    let mut stats = AcquisitionStats::default();
+    let mut pool = BufferPool::new(1);
+
+    let mut captures = 0;
+    while cli.num_captures.is_none_or(|num| captures < num) {
+        let mut captured = match capture_chunk(cli, &None, &mut stats, &mut pool, hantek, handle) {
+            Ok(captured) => captured,
+            Err(e) if handle.is_cancelled() => {
+                debug!("capture stopped: {}", e);
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        let received = SystemTime::now();
+        health.record(&captured, hantek)?;
+        for (channel_no, filter) in filters.iter_mut() {
+            captured.filter_channel(*channel_no, filter);
+        }
+        detect(cli, detections, &captured);
+        let written = sink
+            .write_frame(&captured, received)
+            .and_then(|_| sink.flush());
+        if !keep_writing(written)? {
+            break;
+        }
+        pool.put(captured.raw);
+        captures += 1;
+    }
+    print_stats(cli, &stats);
+    print_detections(cli, detections);
+    Ok(())
+}
+
+/// Captures written out as their envelope, the min and max of every run of samples.
+fn capture_envelope(
+    cli: &CaptureCli,
+    samples_per_bucket: usize,
+    filters: &mut [(usize, Filter)],
+    hantek: &mut Hantek2D42,
+    handle: &CaptureHandle,
+) -> anyhow::Result<()> {
+    let mut health = Health::new(cli)?;
+    let text = sinks::is_text(&cli.format);
+    let mut sink = cli
+        .format
+        .envelope_sink(sinks::writer(cli, &mut health, text)?);
+    let mut stats = AcquisitionStats::default();
+    let mut pool = BufferPool::new(1);
+
+    let mut captures = 0;
+    while cli.num_captures.is_none_or(|num| captures < num) {
+        let mut captured = match capture_chunk(cli, &None, &mut stats, &mut pool, hantek, handle) {
+            Ok(captured) => captured,
+            Err(e) if handle.is_cancelled() => {
+                debug!("capture stopped: {}", e);
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        health.record(&captured, hantek)?;
+        for (channel_no, filter) in filters.iter_mut() {
+            captured.filter_channel(*channel_no, filter);
+        }
+        let envelope = captured.envelope(samples_per_bucket);
+        pool.put(captured.raw);
+        let written = sink.write_envelope(&envelope).and_then(|_| sink.flush());
+        if !keep_writing(written)? {
+            break;
+        }
+        captures += 1;
+    }
+    print_stats(cli, &stats);
+    Ok(())
+}
+
+/// Wait between captures while waiting for a segment to trigger.
+const SEGMENT_POLL: Duration = Duration::from_millis(10);
+
+/// Samples to write out for a single capture, only those taken while the gate is open when
+/// gating. Gated samples aren't evenly spaced, so they go out without a time scale. The samples
+/// are in a buffer of the pool, to be put back once written out.
+fn capture_chunk(
+    cli: &CaptureCli,
+    gate: &Option<Gate>,
+    stats: &mut AcquisitionStats,
+    pool: &mut BufferPool,
+    hantek: &mut Hantek2D42,
+    handle: &CaptureHandle,
+) -> anyhow::Result<CaptureFrame> {
+    let frame = match gate {
+        None if cli.segments.is_some() => {
+            let frame =
+                hantek.capture_segment(&cli.channel, cli.capture_chunk, SEGMENT_POLL, handle)?;
+            debug!(
+                "segment triggered by {}",
+                humantime::format_rfc3339_micros(frame.started_at)
+            );
+            frame
+        }
+        None => hantek.capture_frame_pooled(&cli.channel, cli.capture_chunk, handle, pool)?,
+        Some(gate) => hantek.capture_frame_pooled(
+            &[cli.channel[0], gate.channel_no],
+            cli.capture_chunk,
+            handle,
+            pool,
+        )?,
+    };
+    stats.record(&frame);
+    debug!(
+        "captured in {:?}, gap={:?} dead={:?}",
+        frame.acquisition_time,
+        frame.gap,
+        frame.dead_time()
+    );
+
+    match gate {
+        None => Ok(frame),
+        Some(gate) => {
+            let channel_no = cli.channel[0];
+            let gated = CaptureFrame {
+                channels: vec![channel_no],
+                scales: vec![frame.scale(channel_no).cloned()],
+                time_scale: None,
+                raw: frame.gated_raw(channel_no, gate).unwrap_or_default().into(),
+                fine: None,
+                ..frame
+            };
+            pool.put(frame.raw);
+            Ok(gated)
+        }
+    }
+}
+
+fn print_stats(cli: &CaptureCli, stats: &AcquisitionStats) {
+    if cli.stats {
+        eprintln!("{}", stats);
+    }
+}
+
+pub(crate) fn handle_measure(
+    _parent: &Cli,
+    cli: &MeasureCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    if cli.capture_chunk < 64 {
+        bail!(
+            "minimum length of chunks=64, asked for={}",
+            cli.capture_chunk
+        );
+    }
+
+    if cli.force_mode {
+        hantek.set_device_function(DeviceFunction::Scope)?;
+    }
+
+    if let Some(scale) = &cli.scale {
+        hantek.set_channel_scale(cli.channel, scale.clone())?;
+    }
+    if let Some(time_scale) = &cli.time_scale {
+        hantek.set_time_scale(time_scale.clone())?;
+    }
+
+    let mut count = 0;
+    loop {
+        let frame = hantek.capture_frame(&[cli.channel], cli.capture_chunk)?;
+        let samples = match frame.channel_volts(cli.channel) {
+            Some(samples) => samples,
+            None => bail!(
+                "scale of channel {} is unknown, specify it with --scale",
+                cli.channel
+            ),
+        };
+        let measurements = match measure(&samples, frame.sample_rate()) {
+            Some(measurements) => measurements,
+            None => bail!("nothing captured"),
+        };
+        println!("{}", format_measurements(&cli.stat, &measurements));
+
+        count += 1;
+        match cli.interval {
+            None => break,
+            Some(_) if cli.num_measurements == Some(count) => break,
+            Some(interval) => thread::sleep(Duration::from_millis(interval)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Only the captured time counts, the gate is the wall time over which captures are taken. The
+/// share of it actually captured is printed along, a low one makes for a rough reading.
+pub(crate) fn handle_counter(
+    _parent: &Cli,
+    cli: &CounterCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    if cli.capture_chunk < 64 {
+        bail!(
+            "minimum length of chunks=64, asked for={}",
+            cli.capture_chunk
+        );
+    }
+
+    if cli.force_mode {
+        hantek.set_device_function(DeviceFunction::Scope)?;
+    }
+
+    if let Some(scale) = &cli.scale {
+        hantek.set_channel_scale(cli.channel, scale.clone())?;
+    }
+    if let Some(time_scale) = &cli.time_scale {
+        hantek.set_time_scale(time_scale.clone())?;
+    }
+
+    let mut readings = 0;
+    while cli.num_readings.is_none_or(|num| readings < num) {
+        let mut counter = EdgeCounter::new();
+        let started = Instant::now();
+        while started.elapsed() < cli.gate {
+            let frame = hantek.capture_frame(&[cli.channel], cli.capture_chunk)?;
+            let samples = match frame.channel_volts(cli.channel) {
+                Some(samples) => samples,
+                None => bail!(
+                    "scale of channel {} is unknown, specify it with --scale",
+                    cli.channel
+                ),
+            };
+            let sample_rate = match frame.sample_rate() {
+                Some(sample_rate) => sample_rate,
+                None => bail!("time scale is unknown, specify it with --time-scale"),
+            };
+            counter.record(&samples, sample_rate, cli.threshold);
+        }
+
+        let show = |value: Option<f32>| value.map_or("?".to_string(), |it| format!("{:.4}", it));
+        println!(
+            "pulses={} freq={}Hz duty={}% captured={:.1}%",
+            counter.pulses,
+            show(counter.frequency()),
+            show(counter.duty()),
+            100.0 * counter.seconds / started.elapsed().as_secs_f64()
+        );
+        readings += 1;
+    }
+
+    Ok(())
+}
+
+fn format_measurements(stats: &[Stat], measurements: &Measurements) -> String {
+    stats
+        .iter()
+        .map(|stat| match measurements.get(stat) {
+            Some(value) => format!("{}={:.4}{}", stat, value, stat.unit()),
+            None => format!("{}=?", stat),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub(crate) fn handle_sweep(
+    _parent: &Cli,
+    cli: &SweepCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    let plan = Plan::load(&cli.plan)?;
+
+    if cli.force_mode {
+        hantek.set_device_function(DeviceFunction::Scope)?;
+    }
+
+    info!("sweeping {} points", plan.num_points());
+    if cli.csv {
+        println!("{}", csv_line(&plan.header()));
+        plan.run(hantek, |row| println!("{}", csv_line(&row)))
+    } else {
+        let mut rows = vec![];
+        plan.run(hantek, |row| {
+            debug!("sweep point: {}", row.join(" "));
+            rows.push(row);
+        })?;
+        print_table(&plan.header(), &rows);
+        Ok(())
+    }
+}
+
+const VERIFY_COLUMNS: [&str; 6] = [
+    "device_release",
+    "setting",
+    "channel",
+    "value",
+    "outcome",
+    "error",
+];
+
+pub(crate) fn handle_bench(
+    parent: &Cli,
+    cli: &BenchCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    if cli.capture_chunk < 64 {
+        bail!(
+            "minimum length of chunks=64, asked for={}",
+            cli.capture_chunk
+        );
+    }
+    if cli.num_captures == 0 {
+        bail!("need at least one capture to time");
+    }
+
+    if cli.force_mode {
+        hantek.set_device_function(DeviceFunction::Scope)?;
+    }
+
+    let packets = if cli.packet.is_empty() {
+        vec![parent.capture_packet]
+    } else {
+        cli.packet.clone()
+    };
+    let header: Vec<String> = [
+        "packet",
+        "captures",
+        "samples",
+        "seconds",
+        "samples_per_sec",
+        "bytes_per_sec",
+        "error",
+    ]
+    .iter()
+    .map(|it| it.to_string())
+    .collect();
+
+    let mut buffer = vec![0; cli.capture_chunk * cli.channel.len()];
+    let mut rows = vec![];
+    for packet in packets {
+        hantek.set_capture_packet(packet)?;
+        // The first capture of a run may wait for the device to fill its buffer.
+        let warm_up = hantek.capture_into(&cli.channel, &mut buffer);
+
+        let started = Instant::now();
+        let mut captures = 0;
+        let mut error = warm_up.err().map(|e| e.to_string());
+        while error.is_none() && captures < cli.num_captures {
+            match hantek.capture_into(&cli.channel, &mut buffer) {
+                Ok(()) => captures += 1,
+                Err(e) => error = Some(e.to_string()),
+            }
+        }
+        let seconds = started.elapsed().as_secs_f64();
+
+        let samples = captures * cli.capture_chunk;
+        let bytes = samples * cli.channel.len();
+        rows.push(vec![
+            packet.to_string(),
+            captures.to_string(),
+            samples.to_string(),
+            format!("{:.3}", seconds),
+            format!("{:.0}", samples as f64 / seconds),
+            format!("{:.0}", bytes as f64 / seconds),
+            error.unwrap_or_else(|| "-".to_string()),
+        ]);
+    }
+    hantek.set_capture_packet(parent.capture_packet)?;
+
+    print_table(&header, &rows);
     Ok(())
 }
 
-pub(crate) fn handle_device(
+pub(crate) fn handle_verify(
     _parent: &Cli,
-    cli: &DeviceCli,
+    cli: &VerifyCli,
     hantek: &mut Hantek2D42,
 ) -> anyhow::Result<()> {
-    if cli.start && cli.stop {
-        bail!("must not specify start and stop at the same time.");
+    if cli.force_mode {
+        hantek.set_device_function(DeviceFunction::Scope)?;
     }
 
-    if cli.start {
-        hantek.start()?;
+    let matrix = verify_scales(hantek, &cli.channel)?;
+    let header: Vec<String> = VERIFY_COLUMNS.iter().map(|it| it.to_string()).collect();
+    let rows: Vec<Vec<String>> = matrix
+        .entries
+        .iter()
+        .map(|entry| {
+            vec![
+                matrix.device_release.clone(),
+                entry.setting.to_string(),
+                entry
+                    .channel_no
+                    .map_or_else(|| "-".to_string(), |it| it.to_string()),
+                entry.value.clone(),
+                entry.outcome.to_string(),
+                match &entry.outcome {
+                    Outcome::Rejected(error) => error.clone(),
+                    Outcome::Accepted => "-".to_string(),
+                },
+            ]
+        })
+        .collect();
+
+    if cli.csv {
+        println!("{}", csv_line(&header));
+        for row in &rows {
+            println!("{}", csv_line(row));
+        }
+    } else {
+        print_table(&header, &rows);
     }
-    if cli.stop {
-        hantek.stop()?;
+
+    let rejected = matrix.rejected().count();
+    if rejected > 0 {
+        warn!("{} of {} values rejected", rejected, matrix.entries.len());
     }
+    // Nothing reads settings back from the device, so silently remapped values go unnoticed.
+    info!("accepted values are unconfirmed, the device offers no read-back");
 
-    if let Some(mode) = &cli.mode {
-        hantek.set_device_function(mode.clone())?;
+    Ok(())
+}
+
+pub(crate) fn handle_probe_check(
+    _parent: &Cli,
+    cli: &ProbeCheckCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    if cli.capture_chunk < 64 {
+        bail!(
+            "minimum length of chunks=64, asked for={}",
+            cli.capture_chunk
+        );
+    }
+    if cli.num_captures == 0 {
+        bail!("need at least one capture");
+    }
+
+    if !cli.yes {
+        if cli.no_awg {
+            eprintln!(
+                "Connect the probe of channel {} to the square wave,",
+                cli.channel
+            );
+        } else {
+            eprintln!(
+                "Connect the probe of channel {} to the generator output,",
+                cli.channel
+            );
+        }
+        eprintln!("set its switch to x10 and press enter.");
+        io::stdin().read_line(&mut String::new())?;
+    }
+
+    if cli.force_mode {
+        hantek.set_device_function(DeviceFunction::Scope)?;
+    }
+
+    if !cli.no_awg {
+        hantek.set_awg_type(AwgType::Square)?;
+        hantek.set_awg_frequency(cli.frequency)?;
+        hantek.set_awg_amplitude(cli.amplitude)?;
+        hantek.set_awg_offset(0.0)?;
+        hantek.awg_start()?;
+    }
+
+    // AC coupling would tilt the flat tops on its own and mask the probe.
+    hantek.set_channel_coupling(cli.channel, Coupling::DC)?;
+    hantek.set_channel_scale(cli.channel, cli.scale.clone())?;
+    hantek.set_time_scale(cli.time_scale.clone())?;
+
+    let mut reports = vec![];
+    for _ in 0..cli.num_captures {
+        let frame = hantek.capture_frame(&[cli.channel], cli.capture_chunk)?;
+        let samples = frame.channel_volts(cli.channel).unwrap_or_default();
+        match compensation::analyze(&samples) {
+            Some(report) => {
+                debug!(
+                    "overshoot={:.1}% rounding={:.1}% half_periods={}",
+                    report.overshoot, report.rounding, report.half_periods
+                );
+                reports.push(report);
+            }
+            None => warn!("no complete half period of a square wave in capture"),
+        }
+    }
+    if reports.is_empty() {
+        bail!(
+            "no square wave found on channel {}, check the probe and the time scale",
+            cli.channel
+        );
+    }
+
+    let count = reports.len() as f32;
+    let amplitude = reports.iter().map(|it| it.amplitude).sum::<f32>() / count;
+    let overshoot = reports.iter().map(|it| it.overshoot).sum::<f32>() / count;
+    let rounding = reports.iter().map(|it| it.rounding).sum::<f32>() / count;
+    let verdict = Compensation::judge(overshoot, rounding);
+    let rise_times: Vec<f32> = reports.iter().filter_map(|it| it.rise_time).collect();
+
+    print!(
+        "amplitude={:.3}V overshoot={:.1}% rounding={:.1}%",
+        amplitude, overshoot, rounding
+    );
+    if !rise_times.is_empty() {
+        let samples = rise_times.iter().sum::<f32>() / rise_times.len() as f32;
+        let seconds = samples / sample_rate(&cli.time_scale);
+        print!(" rise_time={:.3}us", 1e6 * seconds);
     }
+    println!(" verdict={}", verdict);
+    println!(
+        "{}",
+        match verdict {
+            Compensation::Good => "The corners are square, the probe is compensated.",
+            Compensation::Over =>
+                "The corners spike past the flat top, the probe is over-compensated: \
+                 turn its trimmer to lower the capacitance until the spikes are gone.",
+            Compensation::Under =>
+                "The corners are rounded, the probe is under-compensated: \
+                 turn its trimmer to raise the capacitance until the corners are square.",
+        }
+    );
 
     Ok(())
 }
 
-pub(crate) fn handle_scope(
+pub(crate) fn handle_spectrum(
     _parent: &Cli,
-    cli: &ScopeCli,
+    cli: &SpectrumCli,
     hantek: &mut Hantek2D42,
 ) -> anyhow::Result<()> {
+    if cli.points < 64 || !cli.points.is_power_of_two() {
+        bail!(
+            "number of points must be a power of two and at least 64, asked for={}",
+            cli.points
+        );
+    }
+
     if cli.force_mode {
         hantek.set_device_function(DeviceFunction::Scope)?;
     }
 
+    if let Some(scale) = &cli.scale {
+        hantek.set_channel_scale(cli.channel, scale.clone())?;
+    }
     if let Some(time_scale) = &cli.time_scale {
         hantek.set_time_scale(time_scale.clone())?;
     }
-    if let Some(time_offset) = &cli.time_offset {
-        hantek.set_time_offset_with_auto_adjustment(*time_offset)?;
+
+    let frame = hantek.capture_frame(&[cli.channel], cli.points)?;
+    let samples = match frame.channel_volts(cli.channel) {
+        Some(samples) => samples,
+        None => bail!(
+            "scale of channel {} is unknown, specify it with --scale",
+            cli.channel
+        ),
+    };
+    let sample_rate = match frame.sample_rate() {
+        Some(sample_rate) => sample_rate,
+        None => bail!("time scale is unknown, specify it with --time-scale"),
+    };
+
+    let bins = spectrum(&samples, sample_rate, &cli.window);
+    match cli.format {
+        SpectrumFormat::Csv => {
+            println!("frequency_hz,magnitude_dbv");
+            for bin in bins {
+                println!("{},{:.2}", bin.frequency, bin.magnitude_dbv);
+            }
+        }
+        SpectrumFormat::Bars => print_spectrum_bars(&bins, cli.rows.max(1)),
     }
 
-    if let Some(trigger_source) = &cli.trigger_source {
-        hantek.set_trigger_source(*trigger_source)?;
+    Ok(())
+}
+
+pub(crate) fn handle_decode_uart(
+    _parent: &Cli,
+    cli: &DecodeUartCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    if cli.force_mode {
+        hantek.set_device_function(DeviceFunction::Scope)?;
     }
-    if let Some(trigger_level) = &cli.trigger_level {
-        hantek.set_trigger_level_with_auto_adjustment(*trigger_level)?;
+
+    let (levels, sample_rate) = capture_levels(
+        hantek,
+        &[cli.channel],
+        &cli.scale,
+        &cli.time_scale,
+        cli.threshold,
+        cli.capture_chunk,
+    )?;
+    for byte in uart::decode(&levels[0], sample_rate, cli.baud)? {
+        println!(
+            "{:.6} 0x{:02x} {}{}",
+            byte.time,
+            byte.value,
+            printable(byte.value),
+            if byte.framing_error {
+                " framing error"
+            } else {
+                ""
+            }
+        );
     }
-    if let Some(trigger_slope) = &cli.trigger_slope {
-        hantek.set_trigger_slope(trigger_slope.clone())?;
+
+    Ok(())
+}
+
+pub(crate) fn handle_decode_spi(
+    _parent: &Cli,
+    cli: &DecodeSpiCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    if cli.clock == cli.data {
+        bail!("clock and data must be on different channels");
     }
-    if let Some(trigger_mode) = &cli.trigger_mode {
-        hantek.set_trigger_mode(trigger_mode.clone())?;
+    if cli.force_mode {
+        hantek.set_device_function(DeviceFunction::Scope)?;
+    }
+
+    let (levels, sample_rate) = capture_levels(
+        hantek,
+        &[cli.clock, cli.data],
+        &cli.scale,
+        &cli.time_scale,
+        cli.threshold,
+        cli.capture_chunk,
+    )?;
+    let mode = SpiMode {
+        cpol: cli.cpol,
+        cpha: cli.cpha,
+    };
+    for transaction in spi::decode(&levels[0], &levels[1], sample_rate, &mode) {
+        let bytes: Vec<String> = transaction
+            .bytes
+            .iter()
+            .map(|it| format!("{:02x}", it))
+            .collect();
+        println!(
+            "{:.6} {}{}",
+            transaction.time,
+            bytes.join(" "),
+            if transaction.trailing_bits > 0 {
+                format!(" +{} bits", transaction.trailing_bits)
+            } else {
+                "".to_string()
+            }
+        );
     }
 
     Ok(())
 }
 
-pub(crate) fn handle_channel(
+pub(crate) fn handle_decode_i2c(
     _parent: &Cli,
-    cli: &ChannelCli,
+    cli: &DecodeI2cCli,
     hantek: &mut Hantek2D42,
 ) -> anyhow::Result<()> {
+    if cli.scl == cli.sda {
+        bail!("SCL and SDA must be on different channels");
+    }
     if cli.force_mode {
         hantek.set_device_function(DeviceFunction::Scope)?;
     }
 
-    if cli.enable {
-        hantek.enable_channel(cli.channel)?;
+    let (levels, sample_rate) = capture_levels(
+        hantek,
+        &[cli.scl, cli.sda],
+        &cli.scale,
+        &cli.time_scale,
+        cli.threshold,
+        cli.capture_chunk,
+    )?;
+    for transaction in i2c::decode(&levels[0], &levels[1], sample_rate) {
+        let mut line = format!("{:.6} S", transaction.time);
+        if let Some(address) = transaction.address {
+            line.push_str(&format!(
+                " 0x{:02x}{} {}",
+                address,
+                if transaction.read { "R" } else { "W" },
+                ack(transaction.address_ack)
+            ));
+        }
+        for byte in &transaction.data {
+            line.push_str(&format!(" {:02x} {}", byte.value, ack(byte.ack)));
+        }
+        if transaction.stopped {
+            line.push_str(" P");
+        }
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+/// Captures the channels, in order, as logic levels along with the sample rate.
+fn capture_levels(
+    hantek: &mut Hantek2D42,
+    channels: &[usize],
+    scale: &Option<Scale>,
+    time_scale: &Option<TimeScale>,
+    threshold: Option<f32>,
+    num_samples: usize,
+) -> anyhow::Result<(Vec<Vec<bool>>, f32)> {
+    for channel_no in channels {
+        if let Some(scale) = scale {
+            hantek.set_channel_scale(*channel_no, scale.clone())?;
+        }
     }
-    if cli.disable {
-        hantek.disable_channel(cli.channel)?;
+    if let Some(time_scale) = time_scale {
+        hantek.set_time_scale(time_scale.clone())?;
     }
 
-    if cli.enable_bandwidth_limit {
-        hantek.channel_enable_bandwidth_limit(cli.channel)?;
+    let frame = hantek.capture_frame(channels, num_samples)?;
+    let sample_rate = match frame.sample_rate() {
+        Some(sample_rate) => sample_rate,
+        None => bail!("time scale is unknown, specify it with --time-scale"),
+    };
+    let mut levels = vec![];
+    for channel_no in channels {
+        let samples = match frame.channel_volts(*channel_no) {
+            Some(samples) => samples,
+            None => bail!(
+                "scale of channel {} is unknown, specify it with --scale",
+                channel_no
+            ),
+        };
+        let threshold = match threshold.or_else(|| decode::midpoint(&samples)) {
+            Some(threshold) => threshold,
+            None => bail!("nothing captured"),
+        };
+        levels.push(decode::digital(&samples, threshold));
     }
-    if cli.disable_bandwidth_limit {
-        hantek.channel_disable_bandwidth_limit(cli.channel)?;
+    Ok((levels, sample_rate))
+}
+
+fn ack(ack: bool) -> &'static str {
+    if ack {
+        "ACK"
+    } else {
+        "NACK"
     }
+}
 
-    if let Some(probe) = &cli.probe {
-        hantek.set_channel_probe(cli.channel, probe.clone())?;
+fn printable(value: u8) -> char {
+    if value.is_ascii_graphic() || value == b' ' {
+        value as char
+    } else {
+        '.'
     }
+}
 
-    if let Some(scale) = &cli.scale {
-        hantek.set_channel_scale(cli.channel, scale.clone())?;
+pub(crate) fn handle_bode(
+    _parent: &Cli,
+    cli: &BodeCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    if cli.force_mode {
+        hantek.set_device_function(DeviceFunction::Scope)?;
     }
 
-    if let Some(offset) = &cli.offset {
-        hantek.set_channel_offset_with_auto_adjustment(cli.channel, *offset)?;
+    for (channel_no, scale, flag) in [
+        (cli.input, &cli.input_scale, "--input-scale"),
+        (cli.output, &cli.output_scale, "--output-scale"),
+    ] {
+        hantek.enable_channel(channel_no)?;
+        if let Some(scale) = scale {
+            hantek.set_channel_scale(channel_no, scale.clone())?;
+        }
+        if hantek
+            .get_config()
+            .channel(channel_no)
+            .and_then(|it| it.scale())
+            .is_none()
+        {
+            bail!(
+                "scale of channel {} is unknown, specify it with {}",
+                channel_no,
+                flag
+            );
+        }
     }
 
-    Ok(())
+    let bode = Bode {
+        sweep: AwgSweep {
+            from: cli.from,
+            to: cli.to,
+            steps: cli.steps,
+            logarithmic: !cli.linear,
+            dwell: cli.dwell,
+        },
+        input: cli.input,
+        output: cli.output,
+        num_samples: cli.capture_chunk,
+    };
+    hantek.awg_start()?;
+
+    let out = io::stdout();
+    let mut out = out.lock();
+    writeln!(out, "frequency,gain,gain_db,phase")?;
+    let mut written = Ok(());
+    bode.run(hantek, |point| {
+        if written.is_ok() {
+            written = writeln!(
+                out,
+                "{},{:.4},{:.2},{:.1}",
+                point.frequency, point.gain, point.gain_db, point.phase
+            )
+            .and_then(|_| out.flush());
+        }
+    })?;
+    Ok(written?)
 }
 
-pub(crate) fn handle_capture(
+fn print_spectrum_bars(bins: &[SpectrumBin], rows: usize) {
+    const WIDTH: f32 = 60.0;
+    const FLOOR_DBV: f32 = -100.0;
+
+    let per_row = bins.len().div_ceil(rows).max(1);
+    let grouped: Vec<(f32, f32)> = bins
+        .chunks(per_row)
+        .map(|chunk| {
+            let peak = chunk
+                .iter()
+                .map(|it| it.magnitude_dbv)
+                .fold(f32::NEG_INFINITY, f32::max);
+            (chunk[0].frequency, peak)
+        })
+        .collect();
+    let top = grouped.iter().map(|it| it.1).fold(FLOOR_DBV, f32::max);
+
+    for (frequency, magnitude) in grouped {
+        let ratio = (magnitude.max(FLOOR_DBV) - FLOOR_DBV) / (top - FLOOR_DBV).max(f32::EPSILON);
+        println!(
+            "{:>12.1} Hz {:>8.2} dBV |{}",
+            frequency,
+            magnitude,
+            "#".repeat((ratio * WIDTH).round() as usize)
+        );
+    }
+}
+
+pub(crate) fn handle_wait(
     _parent: &Cli,
-    cli: &CaptureCli,
+    cli: &WaitCli,
     hantek: &mut Hantek2D42,
 ) -> anyhow::Result<()> {
-    if cli.channel.is_empty() {
-        error!("at least one channel must be specified.");
-        std::process::exit(1);
+    if cli.capture_chunk < 64 {
+        bail!(
+            "minimum length of chunks=64, asked for={}",
+            cli.capture_chunk
+        );
+    }
+
+    if cli.force_mode {
+        hantek.set_device_function(DeviceFunction::Scope)?;
+    }
+
+    if let Some(scale) = &cli.scale {
+        hantek.set_channel_scale(cli.channel, scale.clone())?;
     }
+    if let Some(time_scale) = &cli.time_scale {
+        hantek.set_time_scale(time_scale.clone())?;
+    }
+
+    let started = Instant::now();
+    loop {
+        let frame = hantek.capture_frame(&[cli.channel], cli.capture_chunk)?;
+        let samples = match frame.channel_volts(cli.channel) {
+            Some(samples) => samples,
+            None => bail!(
+                "scale of channel {} is unknown, specify it with --scale",
+                cli.channel
+            ),
+        };
+        let value = measure(&samples, frame.sample_rate()).and_then(|it| it.get(&cli.stat));
+
+        let reached = match (value, cli.above, cli.below) {
+            (Some(value), Some(above), _) => value > above,
+            (Some(value), _, Some(below)) => value < below,
+            _ => false,
+        };
+        if reached {
+            println!("{}={}{}", cli.stat, value.unwrap(), cli.stat.unit());
+            return Ok(());
+        }
+
+        if let Some(timeout) = cli.timeout {
+            if started.elapsed() >= timeout {
+                return Err(ExitStatus::new(
+                    EXIT_TIMEOUT,
+                    format!(
+                        "timed out after {} waiting for {} of channel {}",
+                        humantime::format_duration(timeout),
+                        cli.stat,
+                        cli.channel
+                    ),
+                )
+                .into());
+            }
+        }
+
+        thread::sleep(cli.poll);
+    }
+}
 
+pub(crate) fn handle_plot(
+    _parent: &Cli,
+    cli: &PlotCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
     if cli.capture_chunk < 64 {
-        error!("minimum length of chunks=64, asked for={}", cli.capture_chunk);
-        std::process::exit(1);
+        bail!(
+            "minimum length of chunks=64, asked for={}",
+            cli.capture_chunk
+        );
+    }
+    if cli.width < 2 || cli.height < 2 {
+        bail!("plot must be at least 2x2 characters");
     }
 
     if cli.force_mode {
         hantek.set_device_function(DeviceFunction::Scope)?;
     }
 
+    if let Some(scale) = &cli.scale {
+        for channel_no in &cli.channel {
+            hantek.set_channel_scale(*channel_no, scale.clone())?;
+        }
+    }
+    if let Some(time_scale) = &cli.time_scale {
+        hantek.set_time_scale(time_scale.clone())?;
+    }
+
     let out = std::io::stdout();
     let mut lock = out.lock();
+    loop {
+        let frame = hantek.capture_frame(&cli.channel, cli.capture_chunk)?;
+        let rendered = plot::render(&frame, cli.width, cli.height);
 
-    match cli.num_captures {
-        None => {
-            loop {
-                let captured = hantek
-                    .capture(&cli.channel, cli.capture_chunk)
-                    .expect("capture failed");
-                if lock.write_all(&captured).is_err() || lock.flush().is_err() {
-                    // Probably stream closed.
-                    std::process::exit(0);
-                }
-            }
+        if cli.once {
+            write!(lock, "{}", rendered)?;
+            return Ok(());
         }
-        Some(num) => {
-            for _ in 0..num {
-                let captured = hantek.capture(&cli.channel, cli.capture_chunk);
 
-                if let Err(e) = captured {
-                    // Cast to make CLion happy.
-                    error!("error: {}", &e as &dyn Display);
-                    std::process::exit(1);
-                }
+        if write!(lock, "{}{}", plot::CLEAR_SCREEN, rendered).is_err() || lock.flush().is_err() {
+            // Probably stream closed.
+            return Ok(());
+        }
+        thread::sleep(cli.refresh);
+    }
+}
 
-                let captured = captured.unwrap();
-                if lock.write_all(&captured).is_err() || lock.flush().is_err() {
-                    // Probably stream closed.
-                    std::process::exit(0);
-                }
-            }
-            Ok(())
+pub(crate) fn handle_tui(
+    _parent: &Cli,
+    cli: &TuiCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    if cli.capture_chunk < 64 {
+        bail!(
+            "minimum length of chunks=64, asked for={}",
+            cli.capture_chunk
+        );
+    }
+
+    if cli.force_mode {
+        hantek.set_device_function(DeviceFunction::Scope)?;
+    }
+
+    if let Some(scale) = &cli.scale {
+        for channel_no in &cli.channel {
+            hantek.set_channel_scale(*channel_no, scale.clone())?;
         }
     }
+    if let Some(time_scale) = &cli.time_scale {
+        hantek.set_time_scale(time_scale.clone())?;
+    }
+
+    let mut channels = cli.channel.clone();
+    channels.sort_unstable();
+    channels.dedup();
+    if cli.xy && channels != [1, 2] {
+        bail!("xy pairs channel 1 with channel 2, capture both");
+    }
+    Dashboard::new(channels, cli.capture_chunk, cli.refresh, cli.xy).run(hantek)
 }
 
 pub(crate) fn handle_awg(
@@ -206,13 +1714,8 @@ pub(crate) fn handle_awg(
 
     if cli.offset.is_some() {
         hantek.set_awg_offset(cli.offset.unwrap())?;
-        if !parent.no_quirks {
-            // Had me scratching my head for a while wondering why...
-            warn!(
-                "The offset in the UI will not be updated properly, but it is set. \
-                   This is a bug in the device firmware."
-            );
-        }
+        // Had me scratching my head for a while wondering why...
+        warn_quirk(parent, hantek, Quirk::AwgOffsetNotShown);
     }
 
     if cli.duty_square.is_some() {
@@ -232,23 +1735,121 @@ pub(crate) fn handle_awg(
     }
 
     if cli.start {
-        hantek.awg_start()?;
-        if !parent.no_quirks {
-            warn!(
-                "The running status in the UI will not be updated properly, but it is set. \
-               This is a bug in the device firmware."
-            );
+        let burst = match (cli.burst, cli.cycles) {
+            (Some(duration), _) => Some(AwgBurst::Duration(duration)),
+            (_, Some(cycles)) => Some(AwgBurst::Cycles(cycles)),
+            _ => None,
+        };
+        match burst {
+            Some(burst) => {
+                let ran = burst.run(hantek)?;
+                info!("awg stopped after {:?}", ran);
+            }
+            None => hantek.awg_start()?,
         }
+        warn_quirk(parent, hantek, Quirk::AwgRunningNotShown);
     }
     if cli.stop {
         hantek.awg_stop()?;
-        if !parent.no_quirks {
-            warn!(
-                "The running status in the UI will not be updated properly, but it is set. \
-               This is a bug in the device firmware."
-            );
+        warn_quirk(parent, hantek, Quirk::AwgRunningNotShown);
+    }
+
+    match &cli.sub_commands {
+        Some(AwgCommands::Sweep(sweep)) => handle_awg_sweep(sweep, hantek)?,
+        Some(AwgCommands::Encode(encode)) => handle_awg_encode(encode, hantek)?,
+        None => {}
+    }
+
+    Ok(())
+}
+
+fn warn_quirk(parent: &Cli, hantek: &Hantek2D42, quirk: Quirk) {
+    if !parent.no_quirks && hantek.has_quirk(quirk) {
+        warn!(target: AWG, "{}", quirk.description());
+    }
+}
+
+fn handle_awg_sweep(cli: &AwgSweepCli, hantek: &mut Hantek2D42) -> anyhow::Result<()> {
+    let sweep = AwgSweep {
+        from: cli.from,
+        to: cli.to,
+        steps: cli.steps,
+        logarithmic: cli.log,
+        dwell: cli.dwell,
+    };
+
+    let out = io::stdout();
+    let mut out = out.lock();
+    writeln!(out, "step,frequency")?;
+    let mut written = Ok(());
+    sweep.run(hantek, |step, frequency| {
+        if written.is_ok() {
+            written = writeln!(out, "{},{}", step, frequency).and_then(|_| out.flush());
+        }
+    })?;
+    Ok(written?)
+}
+
+fn handle_awg_encode(cli: &AwgEncodeCli, hantek: &mut Hantek2D42) -> anyhow::Result<()> {
+    let waveform = encode(&cli.protocol, cli.data.as_bytes(), cli.samples_per_bit)?;
+    let mut content = String::new();
+    for sample in &waveform.samples {
+        content.push_str(&format!("{}\n", sample));
+    }
+    match &cli.output {
+        Some(path) => {
+            fs::write(path, content).with_context(|| format!("writing {}", path.display()))?
         }
+        None => io::stdout().write_all(content.as_bytes())?,
     }
 
+    let frequency = waveform.frequency(cli.baud);
+    hantek.set_awg_frequency(frequency)?;
+    info!(
+        "{} samples, {} bits per period, awg frequency set to {} Hz. Load the samples into an \
+        arb slot and select it with --type",
+        waveform.samples.len(),
+        waveform.num_bits,
+        frequency
+    );
     Ok(())
 }
+
+pub(crate) fn handle_serve(
+    _parent: &Cli,
+    cli: &ServeCli,
+    hantek: &mut Hantek2D42,
+) -> anyhow::Result<()> {
+    let metrics = match &cli.metrics {
+        Some(addr) => Exporter::listen(addr)?,
+        None => Exporter::default(),
+    };
+    if cli.http {
+        if cli.capture_chunk < 64 {
+            bail!(
+                "minimum length of chunks=64, asked for={}",
+                cli.capture_chunk
+            );
+        }
+        let port = cli.port.unwrap_or(http::PORT);
+        let server = match tiny_http::Server::http((cli.bind.as_str(), port)) {
+            Ok(server) => server,
+            Err(e) => bail!("could not listen on {}:{}: {}", cli.bind, port, e),
+        };
+        info!("serving HTTP on {}:{}", cli.bind, port);
+        let _announcement = cli
+            .mdns
+            .then(|| discovery::announce(hantek, &discovery::HTTP_SERVICES, &cli.bind, port))
+            .transpose()?;
+        http::serve(&server, hantek, &metrics, cli.capture_chunk)
+    } else {
+        let port = cli.port.unwrap_or(scpi::PORT);
+        let listener = TcpListener::bind((cli.bind.as_str(), port))?;
+        info!("serving SCPI on {}", listener.local_addr()?);
+        let _announcement = cli
+            .mdns
+            .then(|| discovery::announce(hantek, &discovery::SCPI_SERVICES, &cli.bind, port))
+            .transpose()?;
+        scpi::serve(&listener, hantek, &metrics)
+    }
+}
@@ -0,0 +1,187 @@
+//! Whether a streaming capture keeps up, reported every `--stats-interval` while it runs: the
+//! rate samples and bytes come in at, and the USB retries, short reads and frames dropped for a
+//! slow stdout in the interval. Lines go to stderr, or as JSON to the `--stats-json` file.
+//!
+//! Reports are made by a thread of their own on a timer, an interval no capture came in is
+//! reported with nothing in it rather than not at all, that being when the acquisition stalls.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use anyhow::Context;
+use serde_json::json;
+
+use hanteker_lib::capture::CaptureFrame;
+use hanteker_lib::models::hantek2d42::Hantek2D42;
+
+use crate::cli::CaptureCli;
+use crate::output::{DroppedFrames, QueuedStdout};
+
+/// Totals since the start of the capture, an interval is the difference of two.
+#[derive(Debug, Clone, Default)]
+struct Totals {
+    frames: u64,
+    samples: u64,
+    bytes: u64,
+    retries: u64,
+    short_reads: u64,
+    dropped: u64,
+}
+
+/// What the capture shares with the thread reporting on it.
+#[derive(Default)]
+struct State {
+    /// Totals as of the last frame, the dropped frames aside.
+    totals: Totals,
+    dropped: Option<DroppedFrames>,
+    /// Why reporting stopped, given back to the capture.
+    failed: Option<io::Error>,
+}
+
+pub(crate) struct Health {
+    state: Arc<Mutex<State>>,
+    /// Stops the reporter once dropped, `None` without an interval.
+    stop: Option<Sender<()>>,
+    reporter: Option<JoinHandle<()>>,
+}
+
+impl Health {
+    /// Reports nothing without an interval.
+    pub(crate) fn new(cli: &CaptureCli) -> anyhow::Result<Self> {
+        let state = Arc::new(Mutex::new(State::default()));
+        let interval = match cli.stats_interval {
+            Some(interval) => interval,
+            None => {
+                return Ok(Self {
+                    state,
+                    stop: None,
+                    reporter: None,
+                })
+            }
+        };
+        let json = match &cli.stats_json {
+            Some(path) => Some(BufWriter::new(
+                File::create(path).with_context(|| format!("creating {}", path.display()))?,
+            )),
+            None => None,
+        };
+
+        let (stop, stopped) = mpsc::channel();
+        let now = Instant::now();
+        let mut reporter = Reporter {
+            json,
+            started: now,
+            last: (Totals::default(), now),
+        };
+        let reporter = {
+            let state = Arc::clone(&state);
+            thread::spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    let totals = {
+                        let state = state.lock().unwrap();
+                        let mut totals = state.totals.clone();
+                        totals.dropped = state.dropped.as_ref().map_or(0, |it| it.get() as u64);
+                        totals
+                    };
+                    if let Err(e) = reporter.report(totals) {
+                        state.lock().unwrap().failed = Some(e);
+                        return;
+                    }
+                }
+            })
+        };
+        Ok(Self {
+            state,
+            stop: Some(stop),
+            reporter: Some(reporter),
+        })
+    }
+
+    /// Counts the frames stdout drops into the reports.
+    pub(crate) fn watch(&mut self, stdout: &QueuedStdout) {
+        self.state.lock().unwrap().dropped = Some(stdout.dropped_frames());
+    }
+
+    /// Counts the frame into the interval in progress, failing if reporting did.
+    pub(crate) fn record(&mut self, frame: &CaptureFrame, hantek: &Hantek2D42) -> io::Result<()> {
+        if self.stop.is_none() {
+            return Ok(());
+        }
+        let metrics = hantek.metrics();
+        let mut state = self.state.lock().unwrap();
+        if let Some(e) = state.failed.take() {
+            return Err(e);
+        }
+        let totals = &mut state.totals;
+        totals.frames += 1;
+        totals.samples += frame.num_samples() as u64;
+        totals.bytes = metrics
+            .commands
+            .get(&("reading capture", None))
+            .map_or(0, |it| it.bytes);
+        totals.retries = hantek.usb.retries();
+        totals.short_reads = metrics.short_reads;
+        Ok(())
+    }
+}
+
+impl Drop for Health {
+    fn drop(&mut self) {
+        self.stop = None;
+        if let Some(reporter) = self.reporter.take() {
+            reporter.join().ok();
+        }
+    }
+}
+
+/// Writes a report every interval, on the thread of its own.
+struct Reporter {
+    json: Option<BufWriter<File>>,
+    started: Instant,
+    /// Totals at the end of the previous interval, and when it ended.
+    last: (Totals, Instant),
+}
+
+impl Reporter {
+    fn report(&mut self, totals: Totals) -> io::Result<()> {
+        let now = Instant::now();
+        let last = &self.last.0;
+        let seconds = now.duration_since(self.last.1).as_secs_f64();
+        let frames = totals.frames - last.frames;
+        let samples_per_sec = (totals.samples - last.samples) as f64 / seconds;
+        // Metrics are taken at the end when timing, they only ever grow until then.
+        let bytes_per_sec = totals.bytes.saturating_sub(last.bytes) as f64 / seconds;
+        let retries = totals.retries.saturating_sub(last.retries);
+        let short_reads = totals.short_reads.saturating_sub(last.short_reads);
+        let dropped = totals.dropped.saturating_sub(last.dropped);
+
+        match &mut self.json {
+            Some(out) => {
+                let line = json!({
+                    "elapsed": now.duration_since(self.started).as_secs_f64(),
+                    "interval": seconds,
+                    "frames": frames,
+                    "samples_per_sec": samples_per_sec,
+                    "bytes_per_sec": bytes_per_sec,
+                    "usb_retries": retries,
+                    "short_reads": short_reads,
+                    "dropped_frames": dropped,
+                });
+                writeln!(out, "{}", line)?;
+                out.flush()?;
+            }
+            None => {
+                eprintln!(
+                    "stats: frames={} samples/s={:.0} bytes/s={:.0} usb_retries={} short_reads={} dropped={}",
+                    frames, samples_per_sec, bytes_per_sec, retries, short_reads, dropped
+                );
+            }
+        }
+        self.last = (totals, now);
+        Ok(())
+    }
+}
@@ -0,0 +1,589 @@
+//! REST endpoints for the settings and a WebSocket streaming capture frames, so browser
+//! dashboards and remote monitoring don't need to talk USB.
+//!
+//! - `GET /api/options`: every setting with its value space and unit.
+//! - `GET /api/settings`: the config as known to the lib, `null` for whatever was never set.
+//! - `PUT /api/settings`: applies the given subset of the same document and returns the result.
+//! - `GET /api/snapshot`: every field of the config, as written by `config snapshot`.
+//! - `GET /api/capture?channels=1,2`: a single frame as JSON.
+//! - `GET /api/errors`: the device errors since the last call, oldest first, with the codes
+//!   `:SYSTem:ERRor?` of SCPI has for them. Errors answered also carry their code.
+//! - `GET /api/stream?channels=1,2&format=json|binary`: WebSocket upgrade, then frames back to
+//!   back. Binary frames are the number of channels, the channel numbers and then the signed raw
+//!   samples interleaved in that order, one byte each.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use hanteker_lib::capture::CaptureFrame;
+use hanteker_lib::device::cfg::{
+    option_catalog, AwgType, Coupling, DeviceFunction, HantekConfig, Probe, Scale, TimeScale,
+    TriggerMode, TriggerSlope, ValueSpace,
+};
+use hanteker_lib::models::hantek2d42::{Hantek2D42, Hantek2D42Error};
+use log::{debug, info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, ReadWrite, Request, Response, Server};
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::Role;
+use tungstenite::{Message, WebSocket};
+
+use crate::exporter::Exporter;
+use crate::scpi::{ErrorQueue, ScpiError};
+use crate::snapshot;
+
+/// Port used when none is given.
+pub(crate) const PORT: u16 = 8080;
+
+const NUM_CHANNELS: usize = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamFormat {
+    Json,
+    Binary,
+}
+
+/// A WebSocket client and what it asked to be streamed.
+struct Stream {
+    socket: WebSocket<Box<dyn ReadWrite + Send>>,
+    channels: Vec<usize>,
+    format: StreamFormat,
+}
+
+/// Error answered to the client, with the status code telling bad requests from device failures.
+struct ApiError {
+    status: u16,
+    message: String,
+    /// Device errors, as queued for `/api/errors`.
+    queued: Option<ScpiError>,
+}
+
+impl ApiError {
+    fn bad_request(message: impl ToString) -> Self {
+        Self {
+            status: 400,
+            message: message.to_string(),
+            queued: None,
+        }
+    }
+
+    fn not_found() -> Self {
+        Self {
+            status: 404,
+            message: "not found".to_string(),
+            queued: None,
+        }
+    }
+
+    /// Values the lib rejects before sending are the client's fault, settings it needs first a
+    /// conflict, a device gone unavailable, a command that couldn't be built ours and the rest
+    /// the device's.
+    fn device(error: Hantek2D42Error) -> Self {
+        let error = anyhow::Error::new(error);
+        let queued = ScpiError::device(&error);
+        let status = match queued.code {
+            -224 | -222 => 400,
+            -221 => 409,
+            -241 => 503,
+            -200 => 500,
+            _ => 502,
+        };
+        Self {
+            status,
+            message: format!("{:#}", error),
+            queued: Some(queued),
+        }
+    }
+}
+
+/// Serves requests one at a time on the calling thread, which owns the device. While any
+/// stream is open, a frame is captured and sent to the streams between requests.
+pub(crate) fn serve(
+    server: &Server,
+    hantek: &mut Hantek2D42,
+    metrics: &Exporter,
+    capture_chunk: usize,
+) -> anyhow::Result<()> {
+    let mut streams: Vec<Stream> = vec![];
+    let mut errors = ErrorQueue::default();
+    loop {
+        let request = if streams.is_empty() {
+            Some(server.recv()?)
+        } else {
+            server.try_recv()?
+        };
+        if let Some(request) = request {
+            if let Some(stream) = handle(request, hantek, &mut errors, metrics, capture_chunk)? {
+                streams.push(stream);
+            }
+        }
+
+        if !streams.is_empty() {
+            broadcast(&mut streams, hantek, metrics, capture_chunk);
+        }
+        metrics.observe(hantek);
+    }
+}
+
+fn handle(
+    mut request: Request,
+    hantek: &mut Hantek2D42,
+    errors: &mut ErrorQueue,
+    metrics: &Exporter,
+    capture_chunk: usize,
+) -> std::io::Result<Option<Stream>> {
+    let (path, query) = match request.url().split_once('?') {
+        Some((path, query)) => (path.to_string(), query.to_string()),
+        None => (request.url().to_string(), String::new()),
+    };
+    let params: HashMap<&str, &str> = query
+        .split('&')
+        .filter_map(|it| it.split_once('='))
+        .collect();
+    debug!("http request: {} {}", request.method(), request.url());
+
+    let result = match (request.method(), path.as_str()) {
+        (Method::Get, "/api/options") => Ok(options()),
+        (Method::Get, "/api/settings") => Ok(settings(hantek.get_config())),
+        (Method::Get, "/api/snapshot") => Ok(snapshot::snapshot(hantek.get_config())),
+        (Method::Put, "/api/settings") => {
+            let mut body = String::new();
+            match request.as_reader().read_to_string(&mut body) {
+                Ok(_) => update(hantek, &body).map(|_| settings(hantek.get_config())),
+                Err(e) => Err(ApiError::bad_request(e)),
+            }
+        }
+        (Method::Get, "/api/capture") => channels(&params).and_then(|channels| {
+            hantek
+                .capture_frame(&channels, capture_chunk)
+                .map(|frame| frame_json(&frame))
+                .map_err(ApiError::device)
+        }),
+        (Method::Get, "/api/errors") => Ok(drain(errors)),
+        (Method::Get, "/api/stream") => return upgrade(request, &params),
+        _ => Err(ApiError::not_found()),
+    };
+
+    let (status, body) = match result {
+        Ok(body) => (200, body),
+        Err(e) => {
+            debug!("http error: {} {}", e.status, e.message);
+            match e.queued {
+                Some(queued) => {
+                    let body = json!({ "error": e.message, "code": queued.code });
+                    metrics.failed(&queued);
+                    errors.push(queued);
+                    (e.status, body)
+                }
+                None => (e.status, json!({ "error": e.message })),
+            }
+        }
+    };
+    request.respond(
+        Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(header("Content-Type", "application/json")),
+    )?;
+    Ok(None)
+}
+
+fn drain(errors: &mut ErrorQueue) -> Value {
+    let mut drained = vec![];
+    while let Some(e) = errors.pop() {
+        drained.push(json!({ "code": e.code, "message": e.message }));
+    }
+    json!({ "errors": drained })
+}
+
+fn upgrade(request: Request, params: &HashMap<&str, &str>) -> std::io::Result<Option<Stream>> {
+    let key = request
+        .headers()
+        .iter()
+        .find(|it| it.field.equiv("Sec-WebSocket-Key"))
+        .map(|it| it.value.to_string());
+    let parsed = key
+        .ok_or_else(|| ApiError::bad_request("not a websocket request"))
+        .and_then(|key| Ok((key, stream_format(params)?, channels(params)?)));
+    let (key, format, channels) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            request.respond(
+                Response::from_string(json!({ "error": e.message }).to_string())
+                    .with_status_code(e.status)
+                    .with_header(header("Content-Type", "application/json")),
+            )?;
+            return Ok(None);
+        }
+    };
+
+    let response = Response::empty(101)
+        .with_header(header("Upgrade", "websocket"))
+        .with_header(header(
+            "Sec-WebSocket-Accept",
+            &derive_accept_key(key.as_bytes()),
+        ));
+    let socket = request.upgrade("websocket", response);
+    info!("stream opened, channels={:?} format={:?}", channels, format);
+    Ok(Some(Stream {
+        socket: WebSocket::from_raw_socket(socket, Role::Server, None),
+        channels,
+        format,
+    }))
+}
+
+/// Captures every channel any stream asked for and sends the frame to all of them, dropping
+/// the ones that went away.
+fn broadcast(
+    streams: &mut Vec<Stream>,
+    hantek: &mut Hantek2D42,
+    metrics: &Exporter,
+    capture_chunk: usize,
+) {
+    let mut channels: Vec<usize> = streams
+        .iter()
+        .flat_map(|it| it.channels.iter().copied())
+        .collect();
+    channels.sort_unstable();
+    channels.dedup();
+
+    let frame = match hantek.capture_frame(&channels, capture_chunk) {
+        Ok(frame) => frame,
+        Err(e) => {
+            let e = anyhow::Error::new(e);
+            metrics.failed(&ScpiError::device(&e));
+            warn!("capture failed, closing streams: {:#}", e);
+            for mut stream in streams.drain(..) {
+                stream.socket.close(None).ok();
+            }
+            return;
+        }
+    };
+
+    let json = frame_json(&frame).to_string();
+    let binary = frame_binary(&frame);
+    streams.retain_mut(|stream| {
+        let message = match stream.format {
+            StreamFormat::Json => Message::Text(json.clone()),
+            StreamFormat::Binary => Message::Binary(binary.clone()),
+        };
+        match stream.socket.send(message) {
+            Ok(()) => true,
+            Err(e) => {
+                info!("stream closed: {}", e);
+                false
+            }
+        }
+    });
+}
+
+fn stream_format(params: &HashMap<&str, &str>) -> Result<StreamFormat, ApiError> {
+    match params.get("format").copied().unwrap_or("json") {
+        "json" => Ok(StreamFormat::Json),
+        "binary" => Ok(StreamFormat::Binary),
+        other => Err(ApiError::bad_request(format!("unknown format: {}", other))),
+    }
+}
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).expect("valid header")
+}
+
+fn channels(params: &HashMap<&str, &str>) -> Result<Vec<usize>, ApiError> {
+    let channels = params.get("channels").copied().unwrap_or("1,2");
+    let mut parsed = vec![];
+    for channel in channels.split(',') {
+        match channel.parse() {
+            Ok(channel_no @ 1..=NUM_CHANNELS) => parsed.push(channel_no),
+            _ => {
+                return Err(ApiError::bad_request(format!(
+                    "invalid channel: {}",
+                    channel
+                )))
+            }
+        }
+    }
+    parsed.sort_unstable();
+    parsed.dedup();
+    Ok(parsed)
+}
+
+fn show<T: ToString>(value: &Option<T>) -> Value {
+    value
+        .as_ref()
+        .map_or(Value::Null, |it| Value::String(it.to_string()))
+}
+
+fn options() -> Value {
+    let options: Vec<Value> = option_catalog()
+        .into_iter()
+        .map(|option| {
+            let values = match option.values {
+                ValueSpace::Bool => json!({ "kind": "bool" }),
+                ValueSpace::Channel => json!({ "kind": "channel", "min": 1, "max": NUM_CHANNELS }),
+                ValueSpace::Number { min, max } => {
+                    json!({ "kind": "number", "min": min, "max": max })
+                }
+                ValueSpace::Choice(choices) => json!({
+                    "kind": "choice",
+                    "choices": choices
+                        .into_iter()
+                        .map(|it| json!({ "name": it.name, "value": it.value }))
+                        .collect::<Vec<_>>(),
+                }),
+            };
+            json!({
+                "name": option.name,
+                "per_channel": option.per_channel,
+                "values": values,
+                "unit": option.unit,
+                "description": option.description,
+            })
+        })
+        .collect();
+    json!(options)
+}
+
+fn settings(config: &HantekConfig) -> Value {
+    let channels: serde_json::Map<String, Value> = config
+        .channels()
+        .map(|(channel_no, channel)| {
+            (
+                channel_no.to_string(),
+                json!({
+                    "enabled": channel.enabled,
+                    "scale": show(&channel.scale),
+                    "coupling": show(&channel.coupling),
+                    "probe": show(&channel.probe),
+                    "offset": channel.offset,
+                }),
+            )
+        })
+        .collect();
+
+    json!({
+        "device_function": show(&config.device_function),
+        "running": config.running_status.as_ref().map(|it| it.is_start()),
+        "time_scale": show(&config.time_scale),
+        "channels": channels,
+        "trigger": {
+            "source": config.trigger_source_channel,
+            "slope": show(&config.trigger_slope),
+            "mode": show(&config.trigger_mode),
+            "level": config.trigger_level,
+        },
+        "awg": {
+            "type": show(&config.awg_type),
+            "frequency": config.awg_frequency,
+            "amplitude": config.awg_amplitude,
+            "offset": config.awg_offset,
+            "running": config.awg_running_status.as_ref().map(|it| it.is_start()),
+        },
+    })
+}
+
+fn frame_json(frame: &CaptureFrame) -> Value {
+    let mut raw = serde_json::Map::new();
+    let mut volts = serde_json::Map::new();
+    for channel_no in &frame.channels {
+        let samples: Vec<i8> = frame
+            .channel_raw(*channel_no)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|it| it as i8)
+            .collect();
+        raw.insert(channel_no.to_string(), json!(samples));
+        volts.insert(
+            channel_no.to_string(),
+            json!(frame.channel_volts(*channel_no)),
+        );
+    }
+
+    json!({
+        "channels": frame.channels,
+        "time_scale": show(&frame.time_scale),
+        "sample_rate": frame.sample_rate(),
+        "raw": raw,
+        "volts": volts,
+    })
+}
+
+fn frame_binary(frame: &CaptureFrame) -> Vec<u8> {
+    let mut binary = Vec::with_capacity(1 + frame.channels.len() + frame.raw.len());
+    binary.push(frame.channels.len() as u8);
+    binary.extend(frame.channels.iter().map(|it| *it as u8));
+    binary.extend(&frame.raw);
+    binary
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct SettingsUpdate {
+    device_function: Option<String>,
+    running: Option<bool>,
+    time_scale: Option<String>,
+    channels: HashMap<usize, ChannelUpdate>,
+    trigger: TriggerUpdate,
+    awg: AwgUpdate,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct ChannelUpdate {
+    enabled: Option<bool>,
+    scale: Option<String>,
+    coupling: Option<String>,
+    probe: Option<String>,
+    /// Raw value, 0..=200 over the 8 vertical divisions.
+    offset: Option<u8>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct TriggerUpdate {
+    source: Option<usize>,
+    slope: Option<String>,
+    mode: Option<String>,
+    /// Raw value, 0..=200 over the 8 vertical divisions.
+    level: Option<u8>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct AwgUpdate {
+    #[serde(rename = "type")]
+    awg_type: Option<String>,
+    frequency: Option<f32>,
+    amplitude: Option<f32>,
+    offset: Option<f32>,
+    running: Option<bool>,
+}
+
+fn parse<T: FromStr>(value: &Option<String>, what: &str) -> Result<Option<T>, ApiError> {
+    value
+        .as_ref()
+        .map(|it| {
+            it.parse()
+                .map_err(|_| ApiError::bad_request(format!("invalid {}: {}", what, it)))
+        })
+        .transpose()
+}
+
+/// Validates the whole update before applying any of it, so a typo doesn't leave the device
+/// half configured. A device error midway still does.
+fn update(hantek: &mut Hantek2D42, body: &str) -> Result<(), ApiError> {
+    let update: SettingsUpdate = serde_json::from_str(body).map_err(ApiError::bad_request)?;
+
+    let device_function: Option<DeviceFunction> =
+        parse(&update.device_function, "device function")?;
+    let time_scale: Option<TimeScale> = parse(&update.time_scale, "time scale")?;
+    let mut channels = vec![];
+    for (channel_no, channel) in &update.channels {
+        if !(1..=NUM_CHANNELS).contains(channel_no) {
+            return Err(ApiError::bad_request(format!(
+                "invalid channel: {}",
+                channel_no
+            )));
+        }
+        let scale: Option<Scale> = parse(&channel.scale, "scale")?;
+        let coupling: Option<Coupling> = parse(&channel.coupling, "coupling")?;
+        let probe: Option<Probe> = parse(&channel.probe, "probe")?;
+        channels.push((*channel_no, channel, scale, coupling, probe));
+    }
+    channels.sort_unstable_by_key(|it| it.0);
+    if let Some(source) = update.trigger.source {
+        if !(1..=NUM_CHANNELS).contains(&source) {
+            return Err(ApiError::bad_request(format!(
+                "invalid trigger source: {}",
+                source
+            )));
+        }
+    }
+    let slope: Option<TriggerSlope> = parse(&update.trigger.slope, "trigger slope")?;
+    let mode: Option<TriggerMode> = parse(&update.trigger.mode, "trigger mode")?;
+    let awg_type: Option<AwgType> = parse(&update.awg.awg_type, "awg type")?;
+
+    if let Some(function) = device_function {
+        hantek
+            .set_device_function(function)
+            .map_err(ApiError::device)?;
+    }
+    if let Some(time_scale) = time_scale {
+        hantek
+            .set_time_scale(time_scale)
+            .map_err(ApiError::device)?;
+    }
+    for (channel_no, channel, scale, coupling, probe) in channels {
+        match channel.enabled {
+            Some(true) => hantek.enable_channel(channel_no),
+            Some(false) => hantek.disable_channel(channel_no),
+            None => Ok(()),
+        }
+        .map_err(ApiError::device)?;
+        if let Some(scale) = scale {
+            hantek
+                .set_channel_scale(channel_no, scale)
+                .map_err(ApiError::device)?;
+        }
+        if let Some(coupling) = coupling {
+            hantek
+                .set_channel_coupling(channel_no, coupling)
+                .map_err(ApiError::device)?;
+        }
+        if let Some(probe) = probe {
+            hantek
+                .set_channel_probe(channel_no, probe)
+                .map_err(ApiError::device)?;
+        }
+        if let Some(offset) = channel.offset {
+            hantek
+                .set_channel_offset(channel_no, offset)
+                .map_err(ApiError::device)?;
+        }
+    }
+
+    if let Some(source) = update.trigger.source {
+        hantek
+            .set_trigger_source(source)
+            .map_err(ApiError::device)?;
+    }
+    if let Some(slope) = slope {
+        hantek.set_trigger_slope(slope).map_err(ApiError::device)?;
+    }
+    if let Some(mode) = mode {
+        hantek.set_trigger_mode(mode).map_err(ApiError::device)?;
+    }
+    if let Some(level) = update.trigger.level {
+        hantek.set_trigger_level(level).map_err(ApiError::device)?;
+    }
+
+    if let Some(awg_type) = awg_type {
+        hantek.set_awg_type(awg_type).map_err(ApiError::device)?;
+    }
+    if let Some(frequency) = update.awg.frequency {
+        hantek
+            .set_awg_frequency(frequency)
+            .map_err(ApiError::device)?;
+    }
+    if let Some(amplitude) = update.awg.amplitude {
+        hantek
+            .set_awg_amplitude(amplitude)
+            .map_err(ApiError::device)?;
+    }
+    if let Some(offset) = update.awg.offset {
+        hantek.set_awg_offset(offset).map_err(ApiError::device)?;
+    }
+    match update.awg.running {
+        Some(true) => hantek.awg_start(),
+        Some(false) => hantek.awg_stop(),
+        None => Ok(()),
+    }
+    .map_err(ApiError::device)?;
+
+    match update.running {
+        Some(true) => hantek.start(),
+        Some(false) => hantek.stop(),
+        None => Ok(()),
+    }
+    .map_err(ApiError::device)
+}
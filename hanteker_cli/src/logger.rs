@@ -0,0 +1,82 @@
+//! Log output for long unattended runs: json lines, or plain text, appended to a file or written
+//! to stderr. Interactive runs keep the colored output of pretty_env_logger.
+
+use std::fs::OpenOptions;
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use env_logger::filter::{Builder, Filter};
+use log::{Log, Metadata, Record};
+use serde_json::json;
+
+use crate::cli::LogFormat;
+
+pub(crate) struct Logger {
+    filter: Filter,
+    format: LogFormat,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Logger {
+    /// Filters as in RUST_LOG, e.g. `INFO,hanteker::usb=trace`.
+    pub(crate) fn new(filters: &str, format: LogFormat, file: Option<&Path>) -> io::Result<Self> {
+        let out: Box<dyn Write + Send> = match file {
+            Some(path) => Box::new(LineWriter::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            )),
+            None => Box::new(io::stderr()),
+        };
+        Ok(Self {
+            filter: Builder::new().parse(filters).build(),
+            format,
+            out: Mutex::new(out),
+        })
+    }
+
+    pub(crate) fn init(self) -> Result<(), log::SetLoggerError> {
+        log::set_max_level(self.filter.filter());
+        log::set_boxed_logger(Box::new(self))
+    }
+
+    fn line(&self, record: &Record) -> String {
+        let time = humantime::format_rfc3339_millis(SystemTime::now());
+        match self.format {
+            LogFormat::Text => format!(
+                "{} {:<5} {} {}",
+                time,
+                record.level(),
+                record.target(),
+                record.args()
+            ),
+            LogFormat::Json => json!({
+                "time": time.to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            })
+            .to_string(),
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.filter.matches(record) {
+            return;
+        }
+        let line = self.line(record);
+        let mut out = self.out.lock().unwrap_or_else(|it| it.into_inner());
+        // Nowhere left to report a failing log to.
+        let _ = writeln!(out, "{}", line);
+    }
+
+    fn flush(&self) {
+        let _ = self.out.lock().unwrap_or_else(|it| it.into_inner()).flush();
+    }
+}
@@ -1,22 +1,69 @@
 #![cfg_attr(not(debug_assertions), deny(warnings))]
 
-use std::time::Duration;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use anyhow::Context;
+use log::{debug, error, info};
 use pretty_env_logger::formatted_builder;
 
-use hanteker_lib::models::hantek2d42::Hantek2D42;
+use hanteker_lib::capture::CaptureHandle;
+use hanteker_lib::device::usb::{RetryConfig, Transfer};
+use hanteker_lib::logging::USB;
+use hanteker_lib::metrics::Metrics;
+use hanteker_lib::models::hantek2d42::{DecodedCommand, Hantek2D42};
 
-use crate::cli::{cli_parse, Cli, Commands};
+use crate::cli::{
+    cli_parse, Cli, Commands, ConfigCli, ConfigCommands, ConfigDiffCli, DecodeCommands, LogFormat,
+    RunCli,
+};
+use crate::exit::{code_of, machine_error, ExitStatus};
+use crate::failsafe::{on_signals, Failsafe, EXIT_INTERRUPTED};
 use crate::handler::{
-    handle_awg, handle_capture, handle_channel, handle_device, handle_print, handle_scope,
-    handle_shell,
+    handle_awg, handle_bench, handle_bode, handle_capture, handle_channel, handle_config_diff,
+    handle_config_diff_profile, handle_config_snapshot, handle_counter, handle_decode_i2c,
+    handle_decode_spi, handle_decode_uart, handle_device, handle_measure, handle_plot,
+    handle_print, handle_probe_check, handle_raw, handle_scope, handle_serve, handle_setup_udev,
+    handle_shell, handle_spectrum, handle_status, handle_sweep, handle_tui, handle_verify,
+    handle_wait,
 };
+use crate::logger::Logger;
+use crate::script::Step;
+#[cfg(feature = "rhai")]
+use crate::scripting::handle_script;
+use crate::watch::handle_watch;
 
 mod cli;
+mod devices;
+mod discovery;
+mod exit;
+mod exporter;
+mod failsafe;
 mod handler;
+mod health;
+mod http;
+mod logger;
+mod mask;
+mod output;
+mod plot;
+mod profile;
+#[cfg(feature = "hdf5")]
+mod recording;
+mod rotate;
+mod scpi;
+mod script;
+#[cfg(feature = "rhai")]
+mod scripting;
+mod sinks;
+mod snapshot;
+mod sweep;
+mod tui;
+mod udev;
+mod watch;
 
-fn init_log(silent: usize, verbose: usize) {
-    let filter = match (silent, verbose) {
+fn init_log(cli: &Cli) -> anyhow::Result<()> {
+    let level = match (cli.silent, cli.verbose) {
         (1, _) => "WARN",
         (2, _) => "ERROR",
         (s, _) if s > 2 => "",
@@ -24,25 +71,72 @@ fn init_log(silent: usize, verbose: usize) {
         (_, v) if v >= 2 => "TRACE",
         _ => "INFO",
     };
+    let filter = match &cli.log_filter {
+        Some(targets) => format!("{},{}", level, targets),
+        None => level.to_string(),
+    };
 
-    let mut builder = formatted_builder();
-    builder.parse_filters(filter);
-    builder.init();
+    if cli.log_format == LogFormat::Text && cli.log_file.is_none() {
+        let mut builder = formatted_builder();
+        builder.parse_filters(&filter);
+        builder.init();
+    } else {
+        Logger::new(&filter, cli.log_format.clone(), cli.log_file.as_deref())?.init()?;
+    }
+    Ok(())
 }
 
-fn main() -> anyhow::Result<()> {
+fn main() {
     let cli = cli_parse();
+    if let Err(e) = run(&cli) {
+        let code = code_of(&e);
+        if cli.machine {
+            eprintln!("{}", machine_error(code, &format!("{:#}", e)));
+        } else if let Some(status) = e.downcast_ref::<ExitStatus>() {
+            error!("{}", status);
+        } else if let Commands::Status(_) = &cli.sub_commands {
+            println!("not ready: {:#}", e);
+        } else {
+            eprintln!("Error: {:?}", e);
+        }
+        std::process::exit(code);
+    }
+}
 
-    init_log(cli.silent, cli.verbose);
+fn run(cli: &Cli) -> anyhow::Result<()> {
+    init_log(cli)?;
 
     if let Commands::Shell(sub) = &cli.sub_commands {
-        handle_shell(&cli, sub);
+        handle_shell(cli, sub);
+    } else if let Commands::SetupUdev(sub) = &cli.sub_commands {
+        handle_setup_udev(cli, sub)?;
+    } else if let Commands::Config(ConfigCli {
+        sub_commands: ConfigCommands::Diff(sub @ ConfigDiffCli { after: Some(_), .. }),
+    }) = &cli.sub_commands
+    {
+        handle_config_diff(cli, sub)?;
+    } else if devices::is_multi(cli) {
+        devices::run_on_each(cli)?;
+    } else if let Commands::Watch(sub) = &cli.sub_commands {
+        handle_watch(cli, sub)?;
     } else {
+        let interrupted = on_signals()?;
         let context = libusb::Context::new()?;
-        let mut hantek = Hantek2D42::open(&context, Duration::from_millis(cli.timeout))?;
-        hantek.usb.claim()?;
-        let cmd_result = handle_usb_command(&cli, &mut hantek);
+        let mut hantek = open_device(cli, &context)?;
+        let started = Instant::now();
+        let cmd_result = {
+            let mut failsafe =
+                Failsafe::new(&mut hantek, Arc::clone(&interrupted), cli.keep_running);
+            let handle = CaptureHandle::with_flag(Arc::clone(&interrupted));
+            handle_usb_command(cli, &cli.sub_commands, failsafe.hantek(), &handle)
+        };
+        if cli.timing {
+            print_timing(started.elapsed(), &hantek.take_metrics());
+        }
         let release_result = hantek.usb.release();
+        if interrupted.load(Ordering::SeqCst) {
+            return Err(ExitStatus::new(EXIT_INTERRUPTED, "interrupted").into());
+        }
         cmd_result?;
         release_result?;
     }
@@ -50,16 +144,129 @@ fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn handle_usb_command(cli: &Cli, hantek: &mut Hantek2D42) -> anyhow::Result<()> {
-    match &cli.sub_commands {
+/// Opens and claims the device, set up as the global options say.
+fn open_device<'a>(cli: &Cli, context: &'a libusb::Context) -> anyhow::Result<Hantek2D42<'a>> {
+    let timeout = Duration::from_millis(cli.timeout);
+    let mut hantek = match devices::selected(cli) {
+        Some(name) => devices::open_named(context, timeout, &name)?,
+        None => Hantek2D42::open(context, timeout)?,
+    };
+    hantek.usb.set_timeouts(
+        Duration::from_millis(cli.write_timeout.unwrap_or(cli.timeout)),
+        Duration::from_millis(cli.read_timeout.unwrap_or(cli.timeout)),
+    );
+    hantek.usb.set_retry(RetryConfig {
+        max_retries: cli.usb_retries,
+        backoff: cli.usb_backoff,
+    });
+    hantek.set_capture_packet(cli.capture_packet)?;
+    if cli.trace_usb {
+        hantek.usb.set_trace(Some(Box::new(trace_transfer)));
+    }
+    match cli.interface {
+        Some(interface) => hantek.usb.claim_interface(interface)?,
+        None => hantek.usb.claim()?,
+    }
+    Ok(hantek)
+}
+
+/// Reads are logged by their length only, capture data would drown everything else.
+fn trace_transfer(transfer: &Transfer) {
+    match transfer {
+        Transfer::Write { data, .. } => match DecodedCommand::try_from(*data) {
+            Ok(cmd) => info!(target: USB, "usb > {}", cmd),
+            Err(_) => info!(target: USB, "usb > {:02x?}", data),
+        },
+        Transfer::Read {
+            requested, data, ..
+        } => info!(target: USB, "usb < {} of {} bytes", data.len(), requested),
+    }
+}
+
+/// Wall time not spent in transfers is the host's, a large share of it points at the host side
+/// rather than the device.
+fn print_timing(wall: Duration, metrics: &Metrics) {
+    eprintln!("{}", metrics);
+    eprintln!(
+        "wall time={:?} outside transfers={:?}",
+        wall,
+        wall.saturating_sub(metrics.total())
+    );
+}
+
+/// The global options are the ones of `cli`, the subcommand is `command`, so the lines of a
+/// script share them.
+fn handle_usb_command(
+    cli: &Cli,
+    command: &Commands,
+    hantek: &mut Hantek2D42,
+    handle: &CaptureHandle,
+) -> anyhow::Result<()> {
+    match command {
         Commands::Awg(sub) => handle_awg(cli, sub, hantek)?,
         Commands::Device(sub) => handle_device(cli, sub, hantek)?,
         Commands::Scope(sub) => handle_scope(cli, sub, hantek)?,
-        Commands::Print(_) => handle_print(cli, hantek)?,
+        Commands::Print(sub) => handle_print(cli, sub, hantek)?,
+        Commands::Status(sub) => handle_status(cli, sub, hantek)?,
         Commands::Channel(sub) => handle_channel(cli, sub, hantek)?,
-        Commands::Capture(sub) => handle_capture(cli, sub, hantek)?,
-        Commands::Shell(_) => unreachable!(),
+        Commands::Capture(sub) => handle_capture(cli, sub, hantek, handle)?,
+        Commands::Measure(sub) => handle_measure(cli, sub, hantek)?,
+        Commands::Counter(sub) => handle_counter(cli, sub, hantek)?,
+        Commands::Sweep(sub) => handle_sweep(cli, sub, hantek)?,
+        Commands::Bode(sub) => handle_bode(cli, sub, hantek)?,
+        Commands::Decode(sub) => match &sub.sub_commands {
+            DecodeCommands::Uart(sub) => handle_decode_uart(cli, sub, hantek)?,
+            DecodeCommands::Spi(sub) => handle_decode_spi(cli, sub, hantek)?,
+            DecodeCommands::I2c(sub) => handle_decode_i2c(cli, sub, hantek)?,
+        },
+        Commands::Verify(sub) => handle_verify(cli, sub, hantek)?,
+        Commands::Bench(sub) => handle_bench(cli, sub, hantek)?,
+        Commands::ProbeCheck(sub) => handle_probe_check(cli, sub, hantek)?,
+        Commands::Spectrum(sub) => handle_spectrum(cli, sub, hantek)?,
+        Commands::Wait(sub) => handle_wait(cli, sub, hantek)?,
+        Commands::Plot(sub) => handle_plot(cli, sub, hantek)?,
+        Commands::Tui(sub) => handle_tui(cli, sub, hantek)?,
+        Commands::Serve(sub) => handle_serve(cli, sub, hantek)?,
+        Commands::Raw(sub) => handle_raw(cli, sub, hantek)?,
+        Commands::Config(sub) => match &sub.sub_commands {
+            ConfigCommands::Snapshot(sub) => handle_config_snapshot(cli, sub, hantek)?,
+            ConfigCommands::Diff(sub @ ConfigDiffCli { after: Some(_), .. }) => {
+                handle_config_diff(cli, sub)?
+            }
+            ConfigCommands::Diff(sub) => handle_config_diff_profile(cli, sub, hantek)?,
+        },
+        Commands::Run(sub) => handle_run(cli, sub, hantek, handle)?,
+        #[cfg(feature = "rhai")]
+        Commands::Script(sub) => handle_script(cli, sub, hantek, handle)?,
+        // Opens the device on its own, see run and the scripts.
+        Commands::Watch(_) => unreachable!(),
+        Commands::Shell(sub) => handle_shell(cli, sub),
+        Commands::SetupUdev(sub) => handle_setup_udev(cli, sub)?,
     }
 
     Ok(())
 }
+
+/// Stops at the first line failing, or once interrupted.
+fn handle_run(
+    cli: &Cli,
+    sub: &RunCli,
+    hantek: &mut Hantek2D42,
+    handle: &CaptureHandle,
+) -> anyhow::Result<()> {
+    let lines = script::read(&sub.script)?;
+    for line in &lines {
+        if handle.is_cancelled() {
+            break;
+        }
+        debug!("{}:{}: {}", sub.script.display(), line.number, line.text);
+        match &line.step {
+            Step::Sleep(duration) => script::sleep(*duration, handle),
+            Step::Command(command) => handle_usb_command(cli, command, hantek, handle)
+                .with_context(|| {
+                    format!("{}:{}: {}", sub.script.display(), line.number, line.text)
+                })?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! Mask files, the limits of each masked channel in volts by position of the sample in the
+//! capture. Written by `capture --make-mask` from a golden capture, or by hand:
+//!
+//! ```toml
+//! [[channel]]
+//! channel = 1
+//! lower = [-0.1, 0.4, 0.9]
+//! upper = [0.1, 0.6, 1.1]
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use hanteker_lib::mask::Mask;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaskFile {
+    channel: Vec<ChannelMask>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ChannelMask {
+    channel: usize,
+    lower: Vec<f32>,
+    upper: Vec<f32>,
+}
+
+pub(crate) fn read(path: &Path) -> anyhow::Result<Vec<Mask>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let file: MaskFile =
+        toml::from_str(&content).with_context(|| format!("parsing {}", path.display()))?;
+
+    let mut masks: Vec<Mask> = vec![];
+    for it in file.channel {
+        if !(1..=2).contains(&it.channel) {
+            bail!("{}: no such channel: {}", path.display(), it.channel);
+        }
+        if masks.iter().any(|mask| mask.channel_no == it.channel) {
+            bail!("{}: channel {} is masked twice", path.display(), it.channel);
+        }
+        if it.lower.len() != it.upper.len() || it.lower.is_empty() {
+            bail!(
+                "{}: channel {} must have as many lower as upper limits, at least one",
+                path.display(),
+                it.channel
+            );
+        }
+        masks.push(Mask {
+            channel_no: it.channel,
+            lower: it.lower,
+            upper: it.upper,
+        });
+    }
+    if masks.is_empty() {
+        bail!("{}: no channel is masked", path.display());
+    }
+    Ok(masks)
+}
+
+pub(crate) fn write(path: &Path, masks: &[Mask]) -> anyhow::Result<()> {
+    let file = MaskFile {
+        channel: masks
+            .iter()
+            .map(|it| ChannelMask {
+                channel: it.channel_no,
+                lower: it.lower.clone(),
+                upper: it.upper.clone(),
+            })
+            .collect(),
+    };
+    let content = toml::to_string(&file)?;
+    fs::write(path, content).with_context(|| format!("writing {}", path.display()))
+}
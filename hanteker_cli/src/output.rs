@@ -0,0 +1,196 @@
+//! Captures written to stdout by a thread of their own, so that a reader falling behind holds
+//! back the capture only as far as [`Backpressure`] says.
+//!
+//! Whatever is written between two flushes goes out as one piece, the capture loops flush once
+//! per frame, so frames dropped for a slow reader are dropped whole. The first one is never
+//! dropped, it carries the header of the row formats. Once stdout closes, e.g. `| head`, the
+//! capture stops; a line of how many frames went out and how many were dropped is printed to
+//! stderr either way.
+
+use std::io::{self, Write};
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+use anyhow::Context;
+use log::debug;
+
+use crate::cli::Backpressure;
+
+/// Frames waiting for stdout at most before backpressure applies.
+const QUEUED_FRAMES: usize = 8;
+
+#[derive(Default)]
+struct Shared {
+    written: AtomicUsize,
+    dropped: AtomicUsize,
+    closed: AtomicBool,
+    error: Mutex<Option<io::Error>>,
+}
+
+impl Shared {
+    /// Why stdout can't be written anymore, `None` while it can.
+    fn failure(&self) -> Option<io::Error> {
+        if self.closed.load(Ordering::SeqCst) {
+            return Some(io::ErrorKind::BrokenPipe.into());
+        }
+        let error = self.error.lock().unwrap();
+        error
+            .as_ref()
+            .map(|it| io::Error::new(it.kind(), it.to_string()))
+    }
+}
+
+/// Frames dropped by a [`QueuedStdout`] so far, readable while it's boxed into a sink.
+#[derive(Clone)]
+pub(crate) struct DroppedFrames(Arc<Shared>);
+
+impl DroppedFrames {
+    pub(crate) fn get(&self) -> usize {
+        self.0.dropped.load(Ordering::SeqCst)
+    }
+}
+
+pub(crate) struct QueuedStdout {
+    policy: Backpressure,
+    frame: Vec<u8>,
+    sender: Option<SyncSender<Vec<u8>>>,
+    writer: Option<JoinHandle<()>>,
+    shared: Arc<Shared>,
+}
+
+impl QueuedStdout {
+    pub(crate) fn spawn(policy: Backpressure) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Vec<u8>>(QUEUED_FRAMES);
+        let shared = Arc::new(Shared::default());
+        let writer = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || {
+                let mut out = io::stdout().lock();
+                for frame in receiver {
+                    match out.write_all(&frame).and_then(|_| out.flush()) {
+                        Ok(()) => {
+                            shared.written.fetch_add(1, Ordering::SeqCst);
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                            shared.closed.store(true, Ordering::SeqCst);
+                            break;
+                        }
+                        Err(e) => {
+                            *shared.error.lock().unwrap() = Some(e);
+                            break;
+                        }
+                    }
+                }
+            })
+        };
+        Self {
+            policy,
+            frame: vec![],
+            sender: Some(sender),
+            writer: Some(writer),
+            shared,
+        }
+    }
+
+    pub(crate) fn dropped_frames(&self) -> DroppedFrames {
+        DroppedFrames(Arc::clone(&self.shared))
+    }
+
+    /// Hands the frame written so far to the writer thread, or drops it if the policy says so.
+    fn send(&mut self) -> io::Result<()> {
+        let frame = mem::take(&mut self.frame);
+        let sender = self.sender.as_ref().expect("sender is only taken on drop");
+        let first = self.shared.written.load(Ordering::SeqCst) == 0
+            && self.shared.dropped.load(Ordering::SeqCst) == 0;
+        let sent = match self.policy {
+            Backpressure::Drop if !first => match sender.try_send(frame) {
+                Ok(()) => Ok(()),
+                Err(TrySendError::Full(_)) => {
+                    let dropped = self.shared.dropped.fetch_add(1, Ordering::SeqCst) + 1;
+                    debug!("stdout is behind, dropped frame, {} so far", dropped);
+                    Ok(())
+                }
+                Err(TrySendError::Disconnected(_)) => Err(()),
+            },
+            _ => sender.send(frame).map_err(|_| ()),
+        };
+        // Disconnected only once the writer thread stopped on a failure.
+        sent.map_err(|_| {
+            self.shared
+                .failure()
+                .unwrap_or_else(|| io::Error::other("stdout writer stopped"))
+        })
+    }
+}
+
+impl Write for QueuedStdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(e) = self.shared.failure() {
+            return Err(e);
+        }
+        self.frame.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(e) = self.shared.failure() {
+            return Err(e);
+        }
+        if self.frame.is_empty() {
+            return Ok(());
+        }
+        self.send()
+    }
+}
+
+/// Waits for the frames queued to go out, then reports how many did.
+impl Drop for QueuedStdout {
+    fn drop(&mut self) {
+        if !self.frame.is_empty() && self.shared.failure().is_none() {
+            self.send().ok();
+        }
+        drop(self.sender.take());
+        if let Some(writer) = self.writer.take() {
+            writer.join().ok();
+        }
+
+        let shared = &self.shared;
+        let closed = if shared.closed.load(Ordering::SeqCst) {
+            ", stdout was closed by its reader"
+        } else {
+            ""
+        };
+        eprintln!(
+            "stdout: {} frames written, {} dropped{}",
+            shared.written.load(Ordering::SeqCst),
+            shared.dropped.load(Ordering::SeqCst),
+            closed
+        );
+    }
+}
+
+/// Whether the error is the other end going away, e.g. the reader of stdout or a TCP peer.
+pub(crate) fn is_disconnect(error: &io::Error) -> bool {
+    matches!(
+        error.kind(),
+        io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+    )
+}
+
+/// Whether to go on capturing after writing a frame out: `false` once the reader of the output
+/// went away, which ends the capture as if it was complete. Any other failure is an error.
+pub(crate) fn keep_writing(written: io::Result<()>) -> anyhow::Result<bool> {
+    match written {
+        Ok(()) => Ok(true),
+        Err(e) if is_disconnect(&e) => {
+            debug!("output closed, stopping the capture");
+            Ok(false)
+        }
+        Err(e) => Err(e).context("writing the capture out"),
+    }
+}
@@ -0,0 +1,177 @@
+use hanteker_lib::capture::{CaptureFrame, COUNTS_PER_DIVISION};
+
+/// Vertical divisions on the device screen, half above and half below the channel's zero.
+const DIVISIONS: f32 = 8.0;
+
+/// Move the cursor home and clear the terminal, for redrawing in place.
+pub(crate) const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+const RESET_COLOR: &str = "\x1b[0m";
+const CHANNEL_COLORS: [&str; 2] = ["\x1b[33m", "\x1b[36m"];
+const BOTH_COLOR: &str = "\x1b[37m";
+
+/// Monochrome pixel grid backed by unicode braille characters, each character holds a 2x4
+/// block of dots.
+pub(crate) struct BrailleCanvas {
+    width: usize,
+    height: usize,
+    cells: Vec<u8>,
+}
+
+impl BrailleCanvas {
+    /// Size is in characters, the canvas has `2 * width` by `4 * height` dots.
+    pub(crate) fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![0; width * height],
+        }
+    }
+
+    pub(crate) fn dots_wide(&self) -> usize {
+        self.width * 2
+    }
+
+    pub(crate) fn dots_high(&self) -> usize {
+        self.height * 4
+    }
+
+    pub(crate) fn set(&mut self, x: usize, y: usize) {
+        if x >= self.dots_wide() || y >= self.dots_high() {
+            return;
+        }
+
+        const BITS: [[u8; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+        self.cells[(y / 4) * self.width + x / 2] |= BITS[x % 2][y % 4];
+    }
+
+    /// Vertical run of dots between two rows, used to join consecutive samples.
+    pub(crate) fn vline(&mut self, x: usize, y0: usize, y1: usize) {
+        for y in y0.min(y1)..=y0.max(y1) {
+            self.set(x, y);
+        }
+    }
+
+    fn cell(&self, column: usize, row: usize) -> u8 {
+        self.cells[row * self.width + column]
+    }
+}
+
+fn braille(bits: u8) -> char {
+    char::from_u32(0x2800 + bits as u32).unwrap_or(' ')
+}
+
+/// Dot row of a raw sample, full scale being the 8 divisions of the device screen.
+fn sample_row(raw: u8, dots_high: usize) -> usize {
+    let half = DIVISIONS / 2.0 * COUNTS_PER_DIVISION;
+    let counts = ((raw as i8) as f32).clamp(-half, half);
+    let ratio = (half - counts) / (2.0 * half);
+    (ratio * (dots_high - 1) as f32).round() as usize
+}
+
+pub(crate) fn draw_trace(canvas: &mut BrailleCanvas, samples: &[u8]) {
+    if samples.is_empty() {
+        return;
+    }
+
+    let columns = canvas.dots_wide();
+    let rows = canvas.dots_high();
+    let mut previous: Option<(usize, usize)> = None;
+    for (idx, sample) in samples.iter().enumerate() {
+        let x = if samples.len() > 1 {
+            idx * (columns - 1) / (samples.len() - 1)
+        } else {
+            0
+        };
+        let y = sample_row(*sample, rows);
+        match previous {
+            Some((px, py)) if px == x || px + 1 == x => canvas.vline(x, py, y),
+            _ => canvas.set(x, y),
+        }
+        previous = Some((x, y));
+    }
+}
+
+fn draw_zero_line(canvas: &mut BrailleCanvas) {
+    let y = canvas.dots_high() / 2;
+    for x in (0..canvas.dots_wide()).step_by(4) {
+        canvas.set(x, y);
+    }
+}
+
+/// Header line describing the vertical and horizontal scales of the frame.
+pub(crate) fn annotations(frame: &CaptureFrame) -> String {
+    let mut parts: Vec<String> = frame
+        .channels
+        .iter()
+        .map(|channel_no| match frame.scale(*channel_no) {
+            Some(scale) => format!("CH{} {}/div", channel_no, scale),
+            None => format!("CH{} ?/div", channel_no),
+        })
+        .collect();
+    parts.push(match &frame.time_scale {
+        Some(time_scale) => format!("time {}/div", time_scale),
+        None => "time ?/div".to_string(),
+    });
+    parts.push(format!("samples={}", frame.num_samples()));
+    parts.join("  ")
+}
+
+/// Render every channel of the frame into a `width` x `height` characters plot, each channel
+/// in its own color.
+pub(crate) fn render(frame: &CaptureFrame, width: usize, height: usize) -> String {
+    let canvases: Vec<BrailleCanvas> = frame
+        .channels
+        .iter()
+        .map(|channel_no| {
+            let mut canvas = BrailleCanvas::new(width, height);
+            draw_trace(
+                &mut canvas,
+                &frame.channel_raw(*channel_no).unwrap_or_default(),
+            );
+            canvas
+        })
+        .collect();
+    let mut grid = BrailleCanvas::new(width, height);
+    draw_zero_line(&mut grid);
+
+    let mut out = String::new();
+    out.push_str(&annotations(frame));
+    out.push('\n');
+    for row in 0..height {
+        let label = if row == 0 {
+            "+4div"
+        } else if row == height / 2 {
+            "    0"
+        } else if row == height - 1 {
+            "-4div"
+        } else {
+            ""
+        };
+        out.push_str(&format!("{:>5} ", label));
+
+        for column in 0..width {
+            let traces: Vec<(usize, u8)> = canvases
+                .iter()
+                .enumerate()
+                .map(|(idx, canvas)| (idx, canvas.cell(column, row)))
+                .filter(|(_, bits)| *bits != 0)
+                .collect();
+            match traces.len() {
+                0 => out.push(braille(grid.cell(column, row))),
+                1 => {
+                    let (idx, bits) = traces[0];
+                    out.push_str(CHANNEL_COLORS[idx % CHANNEL_COLORS.len()]);
+                    out.push(braille(bits));
+                    out.push_str(RESET_COLOR);
+                }
+                _ => {
+                    out.push_str(BOTH_COLOR);
+                    out.push(braille(traces.iter().fold(0, |acc, it| acc | it.1)));
+                    out.push_str(RESET_COLOR);
+                }
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,40 @@
+//! Profiles, configs saved as TOML with only the fields to apply set, channels in order:
+//!
+//! ```toml
+//! time_scale = "us100"
+//! trigger_source_channel = 1
+//!
+//! [[channels]]
+//! enabled = true
+//! scale = "v1"
+//!
+//! [[channels]]
+//! enabled = false
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use hanteker_lib::device::cfg::{ChannelConfig, HantekConfig};
+
+const NUM_CHANNELS: usize = 2;
+
+pub(crate) fn read(path: &Path) -> anyhow::Result<HantekConfig> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let mut profile: HantekConfig =
+        toml::from_str(&content).with_context(|| format!("parsing {}", path.display()))?;
+    if profile.num_channels() > NUM_CHANNELS {
+        bail!(
+            "{}: {} channels, the device has {}",
+            path.display(),
+            profile.num_channels(),
+            NUM_CHANNELS
+        );
+    }
+    profile
+        .channels
+        .resize_with(NUM_CHANNELS, ChannelConfig::default);
+    Ok(profile)
+}
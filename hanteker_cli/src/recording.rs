@@ -0,0 +1,162 @@
+//! HDF5 recordings of `capture --hdf5`, with the CLI built with `--features hdf5`, for
+//! acquisitions too long for csv: every channel is a dataset of its raw samples, grown a chunk at
+//! a time as captures come, so a recording of gigabytes is written in the memory of a capture.
+//!
+//! A channel's dataset `ch<N>` has the samples in signed counts, its attribute `volts_per_count`
+//! turning them into volts when its scale is known. The file's attributes have the
+//! `sample_rate` and `seconds_per_division` when the time scale is known. Captures aren't back
+//! to back, `frame_start` has the first sample of each and `frame_time` its wall clock time in
+//! seconds since the Unix epoch. The file opens in MATLAB with `h5read`.
+
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use hdf5::{Dataset, File, H5Type, Location};
+
+use hanteker_lib::capture::{counts_to_volts, CaptureFrame};
+use hanteker_lib::device::cfg::Scale;
+use hanteker_lib::export::FrameSink;
+
+/// Samples per chunk of a dataset, what HDF5 reads and writes at once.
+const CHUNK: usize = 64 * 1024;
+
+/// Writes every capture to an HDF5 file. Every frame must have the channels, scales and sample
+/// rate of the first one, they're attributes of the recording.
+pub(crate) struct Hdf5Sink {
+    file: File,
+    recording: Option<Recording>,
+    /// A channel's samples de-interleaved, kept between frames.
+    buf: Vec<i8>,
+}
+
+struct Recording {
+    channels: Vec<usize>,
+    scales: Vec<Option<Scale>>,
+    sample_rate: Option<f32>,
+    samples: Vec<Dataset>,
+    frame_start: Dataset,
+    frame_time: Dataset,
+    /// Samples of each channel and captures written so far.
+    len: usize,
+    frames: usize,
+}
+
+impl Hdf5Sink {
+    pub(crate) fn create(path: &Path) -> anyhow::Result<Self> {
+        Ok(Self {
+            file: File::create(path)?,
+            recording: None,
+            buf: vec![],
+        })
+    }
+
+    /// Creates the datasets of the recording, described by its first frame.
+    fn start(&self, frame: &CaptureFrame) -> hdf5::Result<Recording> {
+        let mut samples = vec![];
+        for (channel_no, scale) in frame.channels.iter().zip(&frame.scales) {
+            let dataset = growing::<i8>(&self.file, &format!("ch{}", channel_no))?;
+            if let Some(scale) = scale {
+                attr(&dataset, "volts_per_count", counts_to_volts(1.0, scale))?;
+                attr(&dataset, "volts_per_division", scale.volts_per_division())?;
+            }
+            samples.push(dataset);
+        }
+        if let Some(time_scale) = &frame.time_scale {
+            attr(&self.file, "seconds_per_division", time_scale.raw_value())?;
+        }
+        if let Some(sample_rate) = frame.sample_rate() {
+            attr(&self.file, "sample_rate", sample_rate)?;
+        }
+
+        Ok(Recording {
+            channels: frame.channels.clone(),
+            scales: frame.scales.clone(),
+            sample_rate: frame.sample_rate(),
+            samples,
+            frame_start: growing::<u64>(&self.file, "frame_start")?,
+            frame_time: growing::<f64>(&self.file, "frame_time")?,
+            len: 0,
+            frames: 0,
+        })
+    }
+}
+
+impl FrameSink for Hdf5Sink {
+    fn write_frame(&mut self, frame: &CaptureFrame) -> io::Result<()> {
+        if self.recording.is_none() {
+            self.recording = Some(self.start(frame).map_err(io_error)?);
+        }
+        let recording = self.recording.as_mut().unwrap();
+        if recording.channels != frame.channels
+            || recording.scales != frame.scales
+            || recording.sample_rate != frame.sample_rate()
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "frame has channels {:?} with scales {:?} at {:?} samples/s, the recording was started with {:?} with scales {:?} at {:?}",
+                    frame.channels,
+                    frame.scales,
+                    frame.sample_rate(),
+                    recording.channels,
+                    recording.scales,
+                    recording.sample_rate
+                ),
+            ));
+        }
+
+        let samples = recording.len..recording.len + frame.num_samples();
+        for (idx, dataset) in recording.samples.iter().enumerate() {
+            self.buf.clear();
+            self.buf.extend(
+                frame
+                    .raw
+                    .iter()
+                    .skip(idx)
+                    .step_by(frame.channels.len())
+                    .map(|it| *it as i8),
+            );
+            append(dataset, &self.buf, samples.clone()).map_err(io_error)?;
+        }
+        let time = frame
+            .started_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        let frames = recording.frames..recording.frames + 1;
+        append(
+            &recording.frame_start,
+            &[samples.start as u64],
+            frames.clone(),
+        )
+        .map_err(io_error)?;
+        append(&recording.frame_time, &[time], frames).map_err(io_error)?;
+        recording.len = samples.end;
+        recording.frames += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush().map_err(io_error)
+    }
+}
+
+/// A one dimensional dataset, empty and chunked to grow without bound.
+fn growing<T: H5Type>(file: &File, name: &str) -> hdf5::Result<Dataset> {
+    file.new_dataset::<T>().chunk(CHUNK).shape(0..).create(name)
+}
+
+fn append<T: H5Type>(dataset: &Dataset, data: &[T], at: Range<usize>) -> hdf5::Result<()> {
+    dataset.resize(at.end)?;
+    dataset.write_slice(data, at)
+}
+
+fn attr<T: H5Type>(location: &Location, name: &str, value: T) -> hdf5::Result<()> {
+    location.new_attr::<T>().create(name)?.write_scalar(&value)
+}
+
+fn io_error(e: hdf5::Error) -> io::Error {
+    io::Error::other(e)
+}
@@ -0,0 +1,146 @@
+//! Writing captures to a file that is rotated once it grows past a size, so acquisitions running
+//! for hours keep a bounded amount of disk.
+//!
+//! The file being written keeps its name, older ones get `.1`, `.2`, ... appended, `.1` being
+//! the most recent. Files are only rotated between frames, and each one is complete on its own:
+//! the row formats start over with their header, and with the sample count at zero.
+
+use std::cell::Cell;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use hanteker_lib::capture::CaptureFrame;
+use hanteker_lib::export::{ExportFormat, FrameSink};
+use hanteker_lib::math::MathExpr;
+use log::info;
+
+pub(crate) struct RotatingSink {
+    format: ExportFormat,
+    math: Vec<MathExpr>,
+    path: PathBuf,
+    max_size: Option<u64>,
+    keep: usize,
+    written: Rc<Cell<u64>>,
+    current: Box<dyn FrameSink>,
+}
+
+impl RotatingSink {
+    /// Truncates the file if it exists, as a shell redirection would.
+    pub(crate) fn create(
+        format: ExportFormat,
+        math: Vec<MathExpr>,
+        path: PathBuf,
+        max_size: Option<u64>,
+        keep: usize,
+    ) -> io::Result<Self> {
+        let written = Rc::new(Cell::new(0));
+        let current = open(&format, &math, &path, &written)?;
+        Ok(Self {
+            format,
+            math,
+            path,
+            max_size,
+            keep,
+            written,
+            current,
+        })
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let oldest = numbered(&self.path, self.keep);
+        if oldest.exists() {
+            fs::remove_file(&oldest)?;
+        }
+        for idx in (1..=self.keep).rev() {
+            let from = numbered(&self.path, idx - 1);
+            if from.exists() {
+                fs::rename(&from, numbered(&self.path, idx))?;
+            }
+        }
+        info!(
+            "rotated {} after {} bytes",
+            self.path.display(),
+            self.written.get()
+        );
+
+        self.written.set(0);
+        self.current = open(&self.format, &self.math, &self.path, &self.written)?;
+        Ok(())
+    }
+}
+
+impl FrameSink for RotatingSink {
+    fn write_frame(&mut self, frame: &CaptureFrame) -> io::Result<()> {
+        self.current.write_frame(frame)?;
+        self.current.flush()?;
+        match self.max_size {
+            Some(max_size) if self.written.get() >= max_size => self.rotate(),
+            _ => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.current.flush()
+    }
+}
+
+fn open(
+    format: &ExportFormat,
+    math: &[MathExpr],
+    path: &Path,
+    written: &Rc<Cell<u64>>,
+) -> io::Result<Box<dyn FrameSink>> {
+    let file = File::create(path)?;
+    let out = Counting {
+        inner: file,
+        written: Rc::clone(written),
+    };
+    Ok(format.sink_with_math(BufWriter::new(out), math.to_vec()))
+}
+
+/// The path itself for 0, otherwise the path with the number appended.
+fn numbered(path: &Path, idx: usize) -> PathBuf {
+    if idx == 0 {
+        return path.to_path_buf();
+    }
+    let mut name = path.as_os_str().to_os_string();
+    name.push(format!(".{}", idx));
+    PathBuf::from(name)
+}
+
+/// Counts the bytes that reached the file, the buffered ones are counted once flushed.
+struct Counting<W: Write> {
+    inner: W,
+    written: Rc<Cell<u64>>,
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written.set(self.written.get() + written as u64);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Size with an optional binary suffix, e.g. `500M` or `2G`.
+pub(crate) fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (number, multiplier) = match value.char_indices().last() {
+        Some((idx, 'K')) | Some((idx, 'k')) => (&value[..idx], 1 << 10),
+        Some((idx, 'M')) | Some((idx, 'm')) => (&value[..idx], 1 << 20),
+        Some((idx, 'G')) | Some((idx, 'g')) => (&value[..idx], 1 << 30),
+        _ => (value, 1),
+    };
+    match number.parse::<u64>() {
+        Ok(number) if number > 0 => number
+            .checked_mul(multiplier)
+            .ok_or_else(|| format!("too large: {}", value)),
+        _ => Err(format!("not a size, e.g. 500M: {}", value)),
+    }
+}
@@ -0,0 +1,823 @@
+//! A subset of SCPI over a raw TCP socket, enough for VISA based tooling (pyvisa, LabVIEW) to
+//! drive the device like a bench scope.
+//!
+//! Commands are newline terminated and several of them can be joined with `;`, each one taking
+//! its full path from the root. Mnemonics are accepted in their short or long form in any case,
+//! queries answer with the short form.
+//!
+//! Failures never drop the connection, they go to the error queue read with `:SYSTem:ERRor?`
+//! and set the class of the error in the standard event status register. `*STB?`, `*ESR?`,
+//! `*ESE` and `*SRE` report them the IEEE 488.2 way: error available, event summary and master
+//! summary bits in the status byte.
+
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::io::{BufRead, BufReader, Write};
+use std::mem;
+use std::net::{TcpListener, TcpStream};
+
+use hanteker_lib::capture::{sample_rate, COUNTS_PER_DIVISION};
+use hanteker_lib::device::cfg::{
+    AwgType, Coupling, Probe, Scale, TimeScale, TriggerMode, TriggerSlope,
+};
+use hanteker_lib::models::hantek2d42::{Hantek2D42, Hantek2D42Error};
+use log::{debug, info, warn};
+
+use crate::exit::{code_of, EXIT_DEVICE_ACCESS, EXIT_DEVICE_NOT_FOUND, EXIT_USB, EXIT_USB_TIMEOUT};
+use crate::exporter::Exporter;
+
+/// Conventional port of SCPI over raw TCP.
+pub(crate) const PORT: u16 = 5025;
+
+/// Errors kept for `:SYSTem:ERRor?`. Once full, the newest one is replaced by a queue overflow.
+const ERROR_QUEUE_LEN: usize = 16;
+
+/// What `:SYSTem:ERRor?` answers with an empty queue.
+const NO_ERROR: &str = "0,\"No error\"";
+
+/// Bits of the standard event status register, `*ESR?`.
+const ESR_OPERATION_COMPLETE: u8 = 1 << 0;
+const ESR_QUERY_ERROR: u8 = 1 << 2;
+const ESR_DEVICE_ERROR: u8 = 1 << 3;
+const ESR_EXECUTION_ERROR: u8 = 1 << 4;
+const ESR_COMMAND_ERROR: u8 = 1 << 5;
+
+/// Bits of the status byte, `*STB?`.
+const STB_ERROR_AVAILABLE: u8 = 1 << 2;
+const STB_EVENT_SUMMARY: u8 = 1 << 5;
+const STB_MASTER_SUMMARY: u8 = 1 << 6;
+
+/// Points returned by `:WAVeform:DATA?` until set with `:WAVeform:POINts`.
+const DEFAULT_POINTS: usize = 1000;
+
+const COUPLINGS: [(&str, Coupling); 3] = [
+    ("AC", Coupling::AC),
+    ("DC", Coupling::DC),
+    ("GND", Coupling::GND),
+];
+const PROBES: [(&str, Probe); 4] = [
+    ("1", Probe::X1),
+    ("10", Probe::X10),
+    ("100", Probe::X100),
+    ("1000", Probe::X1000),
+];
+const TRIGGER_MODES: [(&str, TriggerMode); 3] = [
+    ("AUTO", TriggerMode::Auto),
+    ("NORMal", TriggerMode::Normal),
+    ("SINGle", TriggerMode::Single),
+];
+const TRIGGER_SLOPES: [(&str, TriggerSlope); 3] = [
+    ("POSitive", TriggerSlope::Rising),
+    ("NEGative", TriggerSlope::Falling),
+    ("EITHer", TriggerSlope::Both),
+];
+const AWG_TYPES: [(&str, AwgType); 8] = [
+    ("SQUare", AwgType::Square),
+    ("RAMP", AwgType::Ramp),
+    ("SINusoid", AwgType::Sin),
+    ("TRAPezoid", AwgType::Trap),
+    ("ARB1", AwgType::Arb1),
+    ("ARB2", AwgType::Arb2),
+    ("ARB3", AwgType::Arb3),
+    ("ARB4", AwgType::Arb4),
+];
+const FORMATS: [(&str, WaveformFormat); 2] = [
+    ("BYTE", WaveformFormat::Byte),
+    ("ASCii", WaveformFormat::Ascii),
+];
+
+/// Entry of the error queue, formatted the way `:SYSTem:ERRor?` reports it.
+#[derive(Debug)]
+pub(crate) struct ScpiError {
+    pub(crate) code: i32,
+    pub(crate) message: String,
+}
+
+impl ScpiError {
+    fn new(code: i32, message: &str, detail: impl Display) -> Self {
+        Self {
+            code,
+            message: format!("{};{}", message, detail),
+        }
+    }
+
+    fn undefined_header(header: &str) -> Self {
+        Self::new(-113, "Undefined header", header)
+    }
+
+    fn suffix_out_of_range(header: &str) -> Self {
+        Self::new(-114, "Header suffix out of range", header)
+    }
+
+    fn missing_parameter(header: &str) -> Self {
+        Self::new(-109, "Missing parameter", header)
+    }
+
+    fn invalid_character(detail: &str) -> Self {
+        Self::new(-101, "Invalid character", detail)
+    }
+
+    fn data_type(value: &str) -> Self {
+        Self::new(-104, "Data type error", value)
+    }
+
+    fn illegal_value(value: &str) -> Self {
+        Self::new(-224, "Illegal parameter value", value)
+    }
+
+    fn out_of_range(value: &str) -> Self {
+        Self::new(-222, "Data out of range", value)
+    }
+
+    /// Failure of a device command: out of range or a settings conflict if rejected before
+    /// sending, a hardware error if USB failed and missing hardware if the device is gone.
+    pub(crate) fn execution(error: Hantek2D42Error) -> Self {
+        Self::device(&anyhow::Error::new(error))
+    }
+
+    /// Same as [`Self::execution`], for a device error with context.
+    pub(crate) fn device(error: &anyhow::Error) -> Self {
+        let unplugged = error.chain().any(|it| {
+            matches!(
+                it.downcast_ref::<libusb::Error>(),
+                Some(libusb::Error::NoDevice)
+            )
+        });
+        let (code, message) = match error.downcast_ref::<Hantek2D42Error>() {
+            Some(Hantek2D42Error::InvalidArgument { .. }) => (-222, "Data out of range"),
+            // Commands are built of values checked first, failing to is a bug of ours.
+            Some(Hantek2D42Error::CommandBuildError { .. }) => (-200, "Execution error"),
+            Some(
+                Hantek2D42Error::ChannelScaleUnknown { .. } | Hantek2D42Error::TriggerSourceUnknown,
+            ) => (-221, "Settings conflict"),
+            Some(Hantek2D42Error::IncompleteCapture { .. }) => (-230, "Data corrupt or stale"),
+            _ => match code_of(error) {
+                code if unplugged || code == EXIT_DEVICE_NOT_FOUND => (-241, "Hardware missing"),
+                EXIT_DEVICE_ACCESS | EXIT_USB_TIMEOUT | EXIT_USB => (-240, "Hardware error"),
+                _ => (-200, "Execution error"),
+            },
+        };
+        Self::new(code, message, format!("{:#}", error))
+    }
+
+    fn queue_overflow() -> Self {
+        Self {
+            code: -350,
+            message: "Queue overflow".to_string(),
+        }
+    }
+
+    /// Bit of the standard event status register the class of the error sets.
+    fn event(&self) -> u8 {
+        match self.code {
+            -199..=-100 => ESR_COMMAND_ERROR,
+            -299..=-200 => ESR_EXECUTION_ERROR,
+            -399..=-300 => ESR_DEVICE_ERROR,
+            -499..=-400 => ESR_QUERY_ERROR,
+            _ => 0,
+        }
+    }
+
+    /// Queried setting that was never set, nothing can be read back from the device.
+    fn unknown(what: &str) -> Self {
+        Self::new(-230, "Data stale", format!("{} is unknown until set", what))
+    }
+}
+
+impl Display for ScpiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},\"{}\"", self.code, self.message.replace('"', "\"\""))
+    }
+}
+
+/// Errors in the order they happened, as `:SYSTem:ERRor?` reads them.
+#[derive(Default)]
+pub(crate) struct ErrorQueue {
+    errors: VecDeque<ScpiError>,
+}
+
+impl ErrorQueue {
+    /// Queues the error, or replaces the newest one with a queue overflow once full.
+    pub(crate) fn push(&mut self, error: ScpiError) {
+        if self.errors.len() < ERROR_QUEUE_LEN {
+            self.errors.push_back(error);
+        } else if let Some(newest) = self.errors.back_mut() {
+            *newest = ScpiError::queue_overflow();
+        }
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<ScpiError> {
+        self.errors.pop_front()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.errors.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.errors.clear();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WaveformFormat {
+    /// Signed raw samples in a definite length block.
+    Byte,
+    /// Comma separated volts.
+    Ascii,
+}
+
+/// A single level of a command header, e.g. `CHAN1` of `:CHAN1:SCAL`.
+struct Node {
+    mnemonic: String,
+    suffix: Option<usize>,
+}
+
+impl Node {
+    fn parse(node: &str) -> Self {
+        let node = node.to_ascii_uppercase();
+        let digits = node.len() - node.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+        let (mnemonic, suffix) = node.split_at(node.len() - digits);
+        Self {
+            mnemonic: mnemonic.to_string(),
+            suffix: suffix.parse().ok(),
+        }
+    }
+
+    fn is(&self, long: &str) -> bool {
+        self.suffix.is_none() && matches(&self.mnemonic, long)
+    }
+}
+
+/// Whether `value` (upper case) is the short or long form of `long`, the short form being its
+/// upper case letters.
+fn matches(value: &str, long: &str) -> bool {
+    value == short(long) || value == long.to_ascii_uppercase()
+}
+
+fn short(long: &str) -> String {
+    long.chars().filter(|c| !c.is_ascii_lowercase()).collect()
+}
+
+fn path(nodes: &[Node], longs: &[&str]) -> bool {
+    nodes.len() == longs.len() && nodes.iter().zip(longs).all(|(node, long)| node.is(long))
+}
+
+/// Accepts connections one after the other, the device can't be shared between clients.
+pub(crate) fn serve(
+    listener: &TcpListener,
+    hantek: &mut Hantek2D42,
+    metrics: &Exporter,
+) -> anyhow::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let peer = stream.peer_addr()?;
+        info!("client connected: {}", peer);
+        match Session::new(metrics.clone()).run(stream, hantek) {
+            Ok(()) => info!("client disconnected: {}", peer),
+            Err(e) => warn!("client dropped: {}, error={}", peer, e),
+        }
+    }
+    Ok(())
+}
+
+/// State of a single client, SCPI keeps it between commands.
+struct Session {
+    errors: ErrorQueue,
+    /// Standard event status register and its enable mask.
+    events: u8,
+    events_enabled: u8,
+    /// Enable mask of the status byte for the master summary.
+    status_enabled: u8,
+    metrics: Exporter,
+    source: usize,
+    points: usize,
+    format: WaveformFormat,
+}
+
+impl Session {
+    fn new(metrics: Exporter) -> Self {
+        Self {
+            errors: ErrorQueue::default(),
+            events: 0,
+            events_enabled: 0,
+            status_enabled: 0,
+            metrics,
+            source: 1,
+            points: DEFAULT_POINTS,
+            format: WaveformFormat::Byte,
+        }
+    }
+
+    fn run(&mut self, stream: TcpStream, hantek: &mut Hantek2D42) -> std::io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).split(b'\n') {
+            let line = line?;
+            let line = match std::str::from_utf8(&line) {
+                Ok(line) => line.trim_end_matches('\r'),
+                Err(e) => {
+                    self.push_error(ScpiError::invalid_character(&e.to_string()));
+                    continue;
+                }
+            };
+            let response = self.execute(hantek, line);
+            self.metrics.observe(hantek);
+            if !response.is_empty() {
+                writer.write_all(&response)?;
+                writer.write_all(b"\n")?;
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs every command of the line, joining the responses of the queries with `;`. A failed
+    /// command goes to the error queue and the rest of the line still runs.
+    fn execute(&mut self, hantek: &mut Hantek2D42, line: &str) -> Vec<u8> {
+        let mut responses = vec![];
+        for command in line.split(';').map(str::trim).filter(|it| !it.is_empty()) {
+            debug!("scpi command: {}", command);
+            match self.command(hantek, command) {
+                Ok(Some(response)) => responses.push(response),
+                Ok(None) => {}
+                Err(e) => self.push_error(e),
+            }
+        }
+        responses.join(&b';')
+    }
+
+    fn push_error(&mut self, error: ScpiError) {
+        debug!("scpi error: {}", error);
+        self.events |= error.event();
+        self.metrics.failed(&error);
+        self.errors.push(error);
+    }
+
+    /// The status byte, summarizing the error queue and the enabled events.
+    fn status_byte(&self) -> u8 {
+        let mut status = 0;
+        if !self.errors.is_empty() {
+            status |= STB_ERROR_AVAILABLE;
+        }
+        if self.events & self.events_enabled != 0 {
+            status |= STB_EVENT_SUMMARY;
+        }
+        if status & self.status_enabled != 0 {
+            status |= STB_MASTER_SUMMARY;
+        }
+        status
+    }
+
+    fn command(
+        &mut self,
+        hantek: &mut Hantek2D42,
+        command: &str,
+    ) -> Result<Option<Vec<u8>>, ScpiError> {
+        let (header, args) = command
+            .split_once(char::is_whitespace)
+            .unwrap_or((command, ""));
+        let args: Vec<&str> = args
+            .split(',')
+            .map(str::trim)
+            .filter(|it| !it.is_empty())
+            .collect();
+        let query = header.ends_with('?');
+        let nodes: Vec<Node> = header
+            .trim_end_matches('?')
+            .trim_start_matches(':')
+            .split(':')
+            .map(Node::parse)
+            .collect();
+        let arg = || {
+            args.first()
+                .copied()
+                .ok_or_else(|| ScpiError::missing_parameter(header))
+        };
+
+        if let Some(channel) = nodes.first().filter(|it| matches(&it.mnemonic, "CHANnel")) {
+            let channel_no = channel_no(channel.suffix.unwrap_or(1), header)?;
+            return self.channel_command(hantek, channel_no, &nodes[1..], query, arg, header);
+        }
+
+        let config = hantek.get_config();
+        let response = if path(&nodes, &["*IDN"]) && query {
+            Some(identify(hantek))
+        } else if path(&nodes, &["*OPC"]) {
+            // Commands complete before the next one is read.
+            if query {
+                Some("1".to_string())
+            } else {
+                self.events |= ESR_OPERATION_COMPLETE;
+                None
+            }
+        } else if path(&nodes, &["*WAI"]) {
+            None
+        } else if path(&nodes, &["*CLS"]) {
+            self.errors.clear();
+            self.events = 0;
+            None
+        } else if path(&nodes, &["*ESR"]) && query {
+            Some(mem::take(&mut self.events).to_string())
+        } else if path(&nodes, &["*ESE"]) {
+            if query {
+                Some(self.events_enabled.to_string())
+            } else {
+                self.events_enabled = parse_register(arg()?)?;
+                None
+            }
+        } else if path(&nodes, &["*SRE"]) {
+            if query {
+                Some(self.status_enabled.to_string())
+            } else {
+                // The master summary bit can't be enabled, it's the summary of the others.
+                self.status_enabled = parse_register(arg()?)? & !STB_MASTER_SUMMARY;
+                None
+            }
+        } else if path(&nodes, &["*STB"]) && query {
+            Some(self.status_byte().to_string())
+        } else if (path(&nodes, &["SYSTem", "ERRor"]) || path(&nodes, &["SYSTem", "ERRor", "NEXT"]))
+            && query
+        {
+            Some(match self.errors.pop() {
+                Some(e) => e.to_string(),
+                None => NO_ERROR.to_string(),
+            })
+        } else if path(&nodes, &["SYSTem", "ERRor", "COUNt"]) && query {
+            Some(self.errors.len().to_string())
+        } else if path(&nodes, &["SYSTem", "ERRor", "ALL"]) && query {
+            let mut errors = vec![];
+            while let Some(e) = self.errors.pop() {
+                errors.push(e.to_string());
+            }
+            Some(if errors.is_empty() {
+                NO_ERROR.to_string()
+            } else {
+                errors.join(",")
+            })
+        } else if path(&nodes, &["RUN"]) {
+            hantek.start().map_err(ScpiError::execution)?;
+            None
+        } else if path(&nodes, &["STOP"]) {
+            hantek.stop().map_err(ScpiError::execution)?;
+            None
+        } else if path(&nodes, &["TIMebase", "SCALe"]) {
+            if query {
+                let time_scale = config.time_scale.as_ref();
+                let time_scale = time_scale.ok_or_else(|| ScpiError::unknown("time scale"))?;
+                Some(number(time_scale.raw_value()))
+            } else {
+                let time_scale = nearest(TimeScale::my_iter(), TimeScale::raw_value, arg()?)?;
+                hantek
+                    .set_time_scale(time_scale)
+                    .map_err(ScpiError::execution)?;
+                None
+            }
+        } else if path(&nodes, &["TRIGger", "MODE"]) {
+            if query {
+                Some(name(
+                    &TRIGGER_MODES,
+                    config.trigger_mode.as_ref(),
+                    "trigger mode",
+                )?)
+            } else {
+                let mode = choice(&TRIGGER_MODES, arg()?)?;
+                hantek
+                    .set_trigger_mode(mode)
+                    .map_err(ScpiError::execution)?;
+                None
+            }
+        } else if path(&nodes, &["TRIGger", "SLOPe"]) {
+            if query {
+                Some(name(
+                    &TRIGGER_SLOPES,
+                    config.trigger_slope.as_ref(),
+                    "trigger slope",
+                )?)
+            } else {
+                let slope = choice(&TRIGGER_SLOPES, arg()?)?;
+                hantek
+                    .set_trigger_slope(slope)
+                    .map_err(ScpiError::execution)?;
+                None
+            }
+        } else if path(&nodes, &["TRIGger", "SOURce"]) {
+            if query {
+                let source = config.trigger_source_channel;
+                let source = source.ok_or_else(|| ScpiError::unknown("trigger source"))?;
+                Some(format!("CHAN{}", source))
+            } else {
+                let channel_no = channel_arg(arg()?)?;
+                hantek
+                    .set_trigger_source(channel_no)
+                    .map_err(ScpiError::execution)?;
+                None
+            }
+        } else if path(&nodes, &["WAVeform", "SOURce"]) {
+            if query {
+                Some(format!("CHAN{}", self.source))
+            } else {
+                self.source = channel_arg(arg()?)?;
+                None
+            }
+        } else if path(&nodes, &["WAVeform", "POINts"]) {
+            if query {
+                Some(self.points.to_string())
+            } else {
+                let value = arg()?;
+                self.points = match value.parse() {
+                    Ok(points) if points >= 64 => points,
+                    Ok(_) => return Err(ScpiError::out_of_range(value)),
+                    Err(_) => return Err(ScpiError::data_type(value)),
+                };
+                None
+            }
+        } else if path(&nodes, &["WAVeform", "FORMat"]) {
+            if query {
+                Some(name(&FORMATS, Some(&self.format), "format")?)
+            } else {
+                self.format = choice(&FORMATS, arg()?)?;
+                None
+            }
+        } else if path(&nodes, &["WAVeform", "XINCrement"]) && query {
+            let time_scale = config.time_scale.as_ref();
+            let time_scale = time_scale.ok_or_else(|| ScpiError::unknown("time scale"))?;
+            Some(number(1.0 / sample_rate(time_scale)))
+        } else if path(&nodes, &["WAVeform", "YINCrement"]) && query {
+            let scale = channel_scale(hantek, self.source)?;
+            Some(number(scale.raw_value() / COUNTS_PER_DIVISION))
+        } else if path(&nodes, &["WAVeform", "DATA"]) && query {
+            return self.waveform(hantek).map(Some);
+        } else if path(&nodes, &["SOURce", "FUNCtion"]) {
+            if query {
+                Some(name(&AWG_TYPES, config.awg_type.as_ref(), "function")?)
+            } else {
+                let awg_type = choice(&AWG_TYPES, arg()?)?;
+                hantek
+                    .set_awg_type(awg_type)
+                    .map_err(ScpiError::execution)?;
+                None
+            }
+        } else if path(&nodes, &["SOURce", "FREQuency"]) {
+            if query {
+                let frequency = config.awg_frequency;
+                Some(number(
+                    frequency.ok_or_else(|| ScpiError::unknown("frequency"))?,
+                ))
+            } else {
+                let frequency = parse_number(arg()?)?;
+                hantek
+                    .set_awg_frequency(frequency)
+                    .map_err(ScpiError::execution)?;
+                None
+            }
+        } else if path(&nodes, &["SOURce", "VOLTage"]) {
+            if query {
+                let amplitude = config.awg_amplitude;
+                Some(number(
+                    amplitude.ok_or_else(|| ScpiError::unknown("amplitude"))?,
+                ))
+            } else {
+                let amplitude = parse_number(arg()?)?;
+                hantek
+                    .set_awg_amplitude(amplitude)
+                    .map_err(ScpiError::execution)?;
+                None
+            }
+        } else if path(&nodes, &["SOURce", "VOLTage", "OFFSet"]) {
+            if query {
+                let offset = config.awg_offset;
+                Some(number(offset.ok_or_else(|| ScpiError::unknown("offset"))?))
+            } else {
+                let offset = parse_number(arg()?)?;
+                hantek
+                    .set_awg_offset(offset)
+                    .map_err(ScpiError::execution)?;
+                None
+            }
+        } else if path(&nodes, &["OUTPut"]) {
+            if query {
+                let running = config.awg_running_status.as_ref();
+                let running = running.ok_or_else(|| ScpiError::unknown("output"))?;
+                Some(boolean(running.is_start()))
+            } else if parse_boolean(arg()?)? {
+                hantek.awg_start().map_err(ScpiError::execution)?;
+                None
+            } else {
+                hantek.awg_stop().map_err(ScpiError::execution)?;
+                None
+            }
+        } else {
+            return Err(ScpiError::undefined_header(header));
+        };
+
+        Ok(response.map(String::into_bytes))
+    }
+
+    fn channel_command<'c>(
+        &mut self,
+        hantek: &mut Hantek2D42,
+        channel_no: usize,
+        nodes: &[Node],
+        query: bool,
+        arg: impl Fn() -> Result<&'c str, ScpiError>,
+        header: &str,
+    ) -> Result<Option<Vec<u8>>, ScpiError> {
+        let config = hantek.get_config();
+        let response = if path(nodes, &["DISPlay"]) {
+            if query {
+                let enabled = config.channel(channel_no).and_then(|it| it.enabled);
+                Some(boolean(
+                    enabled.ok_or_else(|| ScpiError::unknown("display"))?,
+                ))
+            } else if parse_boolean(arg()?)? {
+                hantek
+                    .enable_channel(channel_no)
+                    .map_err(ScpiError::execution)?;
+                None
+            } else {
+                hantek
+                    .disable_channel(channel_no)
+                    .map_err(ScpiError::execution)?;
+                None
+            }
+        } else if path(nodes, &["SCALe"]) {
+            if query {
+                Some(number(channel_scale(hantek, channel_no)?.raw_value()))
+            } else {
+                let scale = nearest(Scale::my_iter(), Scale::raw_value, arg()?)?;
+                hantek
+                    .set_channel_scale(channel_no, scale)
+                    .map_err(ScpiError::execution)?;
+                None
+            }
+        } else if path(nodes, &["COUPling"]) {
+            if query {
+                let coupling = config.channel(channel_no).and_then(|it| it.coupling());
+                Some(name(&COUPLINGS, coupling, "coupling")?)
+            } else {
+                let coupling = choice(&COUPLINGS, arg()?)?;
+                hantek
+                    .set_channel_coupling(channel_no, coupling)
+                    .map_err(ScpiError::execution)?;
+                None
+            }
+        } else if path(nodes, &["PROBe"]) {
+            if query {
+                let probe = config.channel(channel_no).and_then(|it| it.probe());
+                Some(name(&PROBES, probe, "probe")?)
+            } else {
+                let probe = choice(&PROBES, arg()?)?;
+                hantek
+                    .set_channel_probe(channel_no, probe)
+                    .map_err(ScpiError::execution)?;
+                None
+            }
+        } else {
+            return Err(ScpiError::undefined_header(header));
+        };
+
+        Ok(response.map(String::into_bytes))
+    }
+
+    fn waveform(&self, hantek: &mut Hantek2D42) -> Result<Vec<u8>, ScpiError> {
+        let frame = hantek
+            .capture_frame(&[self.source], self.points)
+            .map_err(ScpiError::execution)?;
+
+        match self.format {
+            WaveformFormat::Byte => {
+                let samples = frame.channel_raw(self.source).unwrap_or_default();
+                let length = samples.len().to_string();
+                let mut block = format!("#{}{}", length.len(), length).into_bytes();
+                block.extend(samples);
+                Ok(block)
+            }
+            WaveformFormat::Ascii => {
+                let samples = frame
+                    .channel_volts(self.source)
+                    .ok_or_else(|| ScpiError::unknown("channel scale"))?;
+                Ok(samples
+                    .iter()
+                    .map(|it| number(*it))
+                    .collect::<Vec<_>>()
+                    .join(",")
+                    .into_bytes())
+            }
+        }
+    }
+}
+
+fn identify(hantek: &Hantek2D42) -> String {
+    let manufacturer = hantek
+        .usb
+        .get_manufacturer()
+        .unwrap_or_else(|_| "Hantek".to_string());
+    let product = hantek
+        .usb
+        .get_product()
+        .unwrap_or_else(|_| "2D42".to_string());
+    format!(
+        "{},{},0,hanteker-{}",
+        manufacturer.replace(',', " "),
+        product.replace(',', " "),
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+fn channel_no(channel_no: usize, header: &str) -> Result<usize, ScpiError> {
+    match channel_no {
+        1 | 2 => Ok(channel_no),
+        _ => Err(ScpiError::suffix_out_of_range(header)),
+    }
+}
+
+/// Channel given as a parameter, `CHAN1` or just `1`.
+fn channel_arg(value: &str) -> Result<usize, ScpiError> {
+    let node = Node::parse(value);
+    if !node.mnemonic.is_empty() && !matches(&node.mnemonic, "CHANnel") {
+        return Err(ScpiError::illegal_value(value));
+    }
+    match node.suffix {
+        Some(channel_no @ 1..=2) => Ok(channel_no),
+        _ => Err(ScpiError::out_of_range(value)),
+    }
+}
+
+fn channel_scale(hantek: &Hantek2D42, channel_no: usize) -> Result<Scale, ScpiError> {
+    hantek
+        .get_config()
+        .channel(channel_no)
+        .and_then(|it| it.scale.clone())
+        .ok_or_else(|| ScpiError::unknown("channel scale"))
+}
+
+fn choice<T: Clone>(options: &[(&str, T)], value: &str) -> Result<T, ScpiError> {
+    let upper = value.to_ascii_uppercase();
+    options
+        .iter()
+        .find(|(long, _)| matches(&upper, long))
+        .map(|(_, it)| it.clone())
+        .ok_or_else(|| ScpiError::illegal_value(value))
+}
+
+fn name<T: PartialEq>(
+    options: &[(&str, T)],
+    value: Option<&T>,
+    what: &str,
+) -> Result<String, ScpiError> {
+    let value = value.ok_or_else(|| ScpiError::unknown(what))?;
+    options
+        .iter()
+        .find(|(_, it)| it == value)
+        .map(|(long, _)| short(long))
+        .ok_or_else(|| ScpiError::unknown(what))
+}
+
+/// The discrete setting closest to the asked value, within 1% of it as the device has no
+/// settings in between to round to.
+fn nearest<T>(
+    mut options: impl Iterator<Item = T>,
+    raw_value: fn(&T) -> f32,
+    value: &str,
+) -> Result<T, ScpiError> {
+    let asked = parse_number(value)?;
+    options
+        .find(|it| (raw_value(it) - asked).abs() <= raw_value(it) * 0.01)
+        .ok_or_else(|| ScpiError::out_of_range(value))
+}
+
+/// A register mask, 0 to 255.
+fn parse_register(value: &str) -> Result<u8, ScpiError> {
+    let mask = parse_number(value)?.round();
+    if !(0.0..=255.0).contains(&mask) {
+        return Err(ScpiError::out_of_range(value));
+    }
+    Ok(mask as u8)
+}
+
+fn parse_number(value: &str) -> Result<f32, ScpiError> {
+    match value.parse::<f32>() {
+        Ok(number) if number.is_finite() => Ok(number),
+        _ => Err(ScpiError::data_type(value)),
+    }
+}
+
+fn parse_boolean(value: &str) -> Result<bool, ScpiError> {
+    match value.to_ascii_uppercase().as_str() {
+        "ON" | "1" => Ok(true),
+        "OFF" | "0" => Ok(false),
+        _ => Err(ScpiError::illegal_value(value)),
+    }
+}
+
+fn number(value: f32) -> String {
+    format!("{:E}", value)
+}
+
+fn boolean(value: bool) -> String {
+    if value { "1" } else { "0" }.to_string()
+}
@@ -0,0 +1,135 @@
+//! Scripts run by the `run` subcommand, a command per line as given on the command line, minus
+//! the global options. For example:
+//!
+//! ```text
+//! # Square wave into channel 1, measured at two time scales.
+//! awg --type square --frequency 1000 --amplitude 2 --start
+//! channel -c 1 --enable --scale v1 --coupling dc
+//! sleep 200ms
+//! scope --time-scale us100
+//! measure -c 1 --stat vpp --stat freq
+//! scope --time-scale us10
+//! measure -c 1 --stat vpp --stat freq
+//! ```
+//!
+//! `#` starts a comment, words are split on whitespace unless quoted with `'` or `"`, and
+//! `sleep <duration>` pauses, e.g. `sleep 100ms` or `sleep 2s`.
+
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, bail, Context};
+use clap::Parser;
+
+use hanteker_lib::capture::CaptureHandle;
+
+use crate::cli::Commands;
+
+/// Longest a sleep goes without checking for an interruption.
+const SLEEP_SLICE: Duration = Duration::from_millis(100);
+
+pub(crate) enum Step {
+    Sleep(Duration),
+    Command(Box<Commands>),
+}
+
+pub(crate) struct Line {
+    /// Counted from 1, as editors do.
+    pub(crate) number: usize,
+    pub(crate) text: String,
+    pub(crate) step: Step,
+}
+
+#[derive(Parser, Debug)]
+#[clap(name = "script", no_binary_name = true)]
+struct ScriptCommand {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+/// Every line is parsed before any is run, a typo further down doesn't leave the device half
+/// way through the script.
+pub(crate) fn read(path: &Path) -> anyhow::Result<Vec<Line>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let mut lines = vec![];
+    for (idx, text) in content.lines().enumerate() {
+        let number = idx + 1;
+        let step = parse_line(text).with_context(|| format!("{}:{}", path.display(), number))?;
+        if let Some(step) = step {
+            lines.push(Line {
+                number,
+                text: text.trim().to_string(),
+                step,
+            });
+        }
+    }
+    Ok(lines)
+}
+
+/// `None` for blank lines and comments.
+fn parse_line(text: &str) -> anyhow::Result<Option<Step>> {
+    let words = split_words(text)?;
+    match words.first().map(String::as_str) {
+        None => Ok(None),
+        Some("sleep") => match &words[1..] {
+            [duration] => {
+                let duration = humantime::parse_duration(duration)
+                    .with_context(|| format!("bad duration {}", duration))?;
+                Ok(Some(Step::Sleep(duration)))
+            }
+            _ => bail!("sleep takes a single duration, e.g. sleep 100ms"),
+        },
+        Some(_) => {
+            let command = ScriptCommand::try_parse_from(&words).map_err(|e| {
+                let message = e.to_string();
+                let message = message.lines().next().unwrap_or_default();
+                anyhow!("{}", message.trim_start_matches("error: "))
+            })?;
+            match command.command {
+                Commands::Run(_) => bail!("a script can't run another script"),
+                Commands::Watch(_) => bail!("watch opens the device itself, it can't be scripted"),
+                _ => {}
+            }
+            Ok(Some(Step::Command(Box::new(command.command))))
+        }
+    }
+}
+
+fn split_words(text: &str) -> anyhow::Result<Vec<String>> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    for c in text.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.get_or_insert_with(String::new).push(c),
+            None if c == '\'' || c == '"' => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            None if c == '#' && word.is_none() => break,
+            None if c.is_whitespace() => words.extend(word.take()),
+            None => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if let Some(q) = quote {
+        bail!("unterminated {} quote", q);
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Returns early once the handle is cancelled.
+pub(crate) fn sleep(duration: Duration, handle: &CaptureHandle) {
+    let until = Instant::now() + duration;
+    loop {
+        let now = Instant::now();
+        if now >= until || handle.is_cancelled() {
+            break;
+        }
+        thread::sleep((until - now).min(SLEEP_SLICE));
+    }
+}
@@ -0,0 +1,226 @@
+//! Rhai scripts run by the `script` subcommand, for test sequences needing loops or conditions
+//! the scripts of `run` can't express. For example:
+//!
+//! ```rhai
+//! set("channel1.scale", "v1");
+//! set("time_scale", "us100");
+//! set("awg.type", "sin");
+//! set("awg.amplitude", 2.0);
+//! awg_start();
+//!
+//! for frequency in [100, 1000, 10000] {
+//!     set("awg.frequency", frequency);
+//!     sleep(200);
+//!     let m = measure(1);
+//!     print(`${frequency} Hz: ${m.vpp} Vpp`);
+//!     assert(m.vpp > 1.5, `amplitude dropped at ${frequency} Hz`);
+//! }
+//! ```
+//!
+//! Besides what rhai has built in, a script has:
+//!
+//! - `set(name, value)`, a setting named as in the plans of `sweep`, e.g. `channel1.scale`,
+//! - `start()`, `stop()`, `awg_start()` and `awg_stop()`,
+//! - `capture(channel, samples)`, an array of the volts of a channel,
+//! - `measure(channel)`, a map of the measurements of a capture of a channel, a stat that
+//!   can't be told being `()`,
+//! - `sleep(ms)` and `assert(condition, message)`, the latter failing the script if the
+//!   condition doesn't hold.
+//!
+//! The device is driven from the thread running the command, the script runs on one of its own
+//! and hands it every call.
+
+use std::fs;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context};
+use rhai::{Array, Dynamic, Engine, EvalAltResult, ImmutableString, Map};
+
+use hanteker_lib::batch::CommandBatch;
+use hanteker_lib::capture::CaptureHandle;
+use hanteker_lib::measure::{measure, Stat};
+use hanteker_lib::models::hantek2d42::Hantek2D42;
+
+use crate::cli::{Cli, ScriptCli};
+use crate::script;
+use crate::sweep::parse_setting;
+
+/// Samples of the captures `measure` takes.
+const MEASURE_SAMPLES: usize = 1000;
+
+type Call = Box<dyn FnOnce(&mut Hantek2D42) + Send>;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+/// Hands calls over to the thread driving the device, waiting for their results.
+#[derive(Clone)]
+struct Device {
+    calls: Sender<Call>,
+}
+
+impl Device {
+    fn call<R: Send + 'static>(
+        &self,
+        call: impl FnOnce(&mut Hantek2D42) -> anyhow::Result<R> + Send + 'static,
+    ) -> ScriptResult<R> {
+        let (result, receiver) = mpsc::sync_channel(1);
+        let call: Call = Box::new(move |hantek| {
+            let _ = result.send(call(hantek));
+        });
+        self.calls
+            .send(call)
+            .map_err(|_| "device is gone".to_string())?;
+        match receiver.recv() {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(e)) => Err(format!("{:#}", e).into()),
+            Err(_) => Err("device is gone".into()),
+        }
+    }
+}
+
+pub(crate) fn handle_script(
+    _parent: &Cli,
+    cli: &ScriptCli,
+    hantek: &mut Hantek2D42,
+    handle: &CaptureHandle,
+) -> anyhow::Result<()> {
+    let source = fs::read_to_string(&cli.script)
+        .with_context(|| format!("reading {}", cli.script.display()))?;
+    let name = cli.script.display().to_string();
+    let (calls, receiver) = mpsc::channel::<Call>();
+    let handle = handle.clone();
+
+    thread::scope(|scope| {
+        let script = scope.spawn(move || {
+            let engine = engine(Device { calls }, handle);
+            match engine.run(&source) {
+                Ok(()) => Ok(()),
+                // Interrupted, which the caller tells on its own.
+                Err(e) if matches!(*e, EvalAltResult::ErrorTerminated(..)) => Ok(()),
+                Err(e) => Err(anyhow!("{}: {}", name, e)),
+            }
+        });
+        // Done once the script is, dropping the engine and every sender along with it.
+        for call in receiver {
+            call(hantek);
+        }
+        script
+            .join()
+            .unwrap_or_else(|_| Err(anyhow!("script thread panicked")))
+    })
+}
+
+fn engine(device: Device, handle: CaptureHandle) -> Engine {
+    let mut engine = Engine::new();
+
+    let interrupted = handle.clone();
+    engine.on_progress(move |_| interrupted.is_cancelled().then_some(Dynamic::UNIT));
+
+    let it = device.clone();
+    engine.register_fn(
+        "set",
+        move |name: ImmutableString, value: Dynamic| -> ScriptResult<()> {
+            let setting = parse_setting(&name, &toml_value(&value)?)
+                .map_err(|e| format!("{}: {:#}", name, e))?;
+            it.call(move |hantek| {
+                let batch: CommandBatch = [setting].into_iter().collect();
+                Ok(hantek.apply_batch(&batch)?)
+            })
+        },
+    );
+
+    let it = device.clone();
+    engine.register_fn("start", move || it.call(|hantek| Ok(hantek.start()?)));
+    let it = device.clone();
+    engine.register_fn("stop", move || it.call(|hantek| Ok(hantek.stop()?)));
+    let it = device.clone();
+    engine.register_fn("awg_start", move || {
+        it.call(|hantek| Ok(hantek.awg_start()?))
+    });
+    let it = device.clone();
+    engine.register_fn("awg_stop", move || it.call(|hantek| Ok(hantek.awg_stop()?)));
+
+    let it = device.clone();
+    engine.register_fn(
+        "capture",
+        move |channel_no: i64, num_samples: i64| -> ScriptResult<Array> {
+            let (volts, _) =
+                it.call(move |hantek| capture_volts(hantek, channel_no, num_samples))?;
+            Ok(volts
+                .into_iter()
+                .map(|it| Dynamic::from(it as f64))
+                .collect())
+        },
+    );
+
+    let it = device;
+    engine.register_fn("measure", move |channel_no: i64| -> ScriptResult<Map> {
+        let (volts, sample_rate) =
+            it.call(move |hantek| capture_volts(hantek, channel_no, MEASURE_SAMPLES as i64))?;
+        let measurements = measure(&volts, sample_rate).ok_or("nothing captured")?;
+        Ok(Stat::my_iter()
+            .map(|stat| {
+                let value = measurements
+                    .get(&stat)
+                    .map_or(Dynamic::UNIT, |it| Dynamic::from(it as f64));
+                (stat.my_to_string().to_string().into(), value)
+            })
+            .collect())
+    });
+
+    engine.register_fn("sleep", move |ms: i64| {
+        script::sleep(Duration::from_millis(ms.max(0) as u64), &handle)
+    });
+    engine.register_fn(
+        "assert",
+        |condition: bool, message: ImmutableString| -> ScriptResult<()> {
+            if condition {
+                Ok(())
+            } else {
+                Err(format!("assertion failed: {}", message).into())
+            }
+        },
+    );
+
+    engine
+}
+
+/// Volts of a capture of the channel, along with its sample rate if known.
+fn capture_volts(
+    hantek: &mut Hantek2D42,
+    channel_no: i64,
+    num_samples: i64,
+) -> anyhow::Result<(Vec<f32>, Option<f32>)> {
+    let channel_no = usize::try_from(channel_no).map_err(|_| anyhow!("bad channel"))?;
+    let num_samples = usize::try_from(num_samples).map_err(|_| anyhow!("bad number of samples"))?;
+    let frame = hantek.capture_frame(&[channel_no], num_samples)?;
+    let volts = frame.channel_volts(channel_no).ok_or_else(|| {
+        anyhow!(
+            "scale of channel {} is unknown, set channel{}.scale first",
+            channel_no,
+            channel_no
+        )
+    })?;
+    Ok((volts, frame.sample_rate()))
+}
+
+/// The value as it would be written in a sweep plan.
+fn toml_value(value: &Dynamic) -> ScriptResult<toml::Value> {
+    if let Some(it) = value.clone().try_cast::<bool>() {
+        Ok(toml::Value::Boolean(it))
+    } else if let Some(it) = value.clone().try_cast::<i64>() {
+        Ok(toml::Value::Integer(it))
+    } else if let Some(it) = value.clone().try_cast::<f64>() {
+        Ok(toml::Value::Float(it))
+    } else if let Some(it) = value.clone().try_cast::<ImmutableString>() {
+        Ok(toml::Value::String(it.to_string()))
+    } else {
+        Err(format!(
+            "expected a bool, number or string, got: {}",
+            value.type_name()
+        )
+        .into())
+    }
+}
@@ -0,0 +1,330 @@
+//! Where captures go, any number of places at once, picked with `--sink`: stdout, a file, a
+//! TCP connection made or accepted, WebSocket clients or nowhere. Each one gets every capture in
+//! the format of `--format`.
+//!
+//! A sink whose other end goes away, e.g. the TCP peer closing, is dropped with a warning and
+//! the others go on; the capture stops once none is left.
+
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::mem;
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::{bail, Context};
+use log::{debug, info, warn};
+use tungstenite::{Message, WebSocket};
+
+use hanteker_lib::capture::CaptureFrame;
+use hanteker_lib::export::{ExportFormat, FrameSink};
+
+use crate::cli::CaptureCli;
+use crate::health::Health;
+use crate::output::{is_disconnect, QueuedStdout};
+#[cfg(feature = "hdf5")]
+use crate::recording::Hdf5Sink;
+use crate::rotate::RotatingSink;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum SinkSpec {
+    Stdout,
+    /// Rotated once past `--max-size`.
+    File(PathBuf),
+    /// Connected to, e.g. `nc -l 9000` listening.
+    Tcp(String),
+    /// Listened on for a single client, e.g. a TCP source block of GNU Radio, the capture starts
+    /// once it connected.
+    TcpServer(String),
+    /// Listened on, every client connected gets the captures from then on, a message each.
+    WebSocket(String),
+    Null,
+}
+
+impl SinkSpec {
+    pub(crate) fn parse(text: &str) -> Result<Self, String> {
+        let bad = || {
+            format!(
+                "invalid sink {}, expected stdout, file:<path>, tcp-connect:<host:port>, tcp-listen:<host:port>, ws:<host:port> or null",
+                text
+            )
+        };
+        match text {
+            "stdout" => return Ok(Self::Stdout),
+            "null" => return Ok(Self::Null),
+            _ => {}
+        }
+        let (kind, value) = text.split_once(':').ok_or_else(bad)?;
+        // Whether tcp: connects or listens isn't obvious, it must be told.
+        if kind == "tcp" {
+            let addr = value.trim_start_matches("//");
+            return Err(format!(
+                "ambiguous sink {}, use tcp-connect:{} to connect or tcp-listen:{} to wait for a client",
+                text, addr, addr
+            ));
+        }
+        if value.is_empty() {
+            return Err(bad());
+        }
+        match kind {
+            "file" => Ok(Self::File(PathBuf::from(value))),
+            "tcp-connect" => Ok(Self::Tcp(value.to_string())),
+            "tcp-listen" => Ok(Self::TcpServer(value.to_string())),
+            "ws" => Ok(Self::WebSocket(value.to_string())),
+            _ => Err(bad()),
+        }
+    }
+}
+
+impl Display for SinkSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stdout => write!(f, "stdout"),
+            Self::File(path) => write!(f, "file:{}", path.display()),
+            Self::Tcp(addr) => write!(f, "tcp-connect:{}", addr),
+            Self::TcpServer(addr) => write!(f, "tcp-listen:{}", addr),
+            Self::WebSocket(addr) => write!(f, "ws:{}", addr),
+            Self::Null => write!(f, "null"),
+        }
+    }
+}
+
+/// The sinks asked for, `--output` being a file sink and stdout the default.
+pub(crate) fn specs(cli: &CaptureCli) -> Vec<SinkSpec> {
+    if !cli.sink.is_empty() {
+        return cli.sink.clone();
+    }
+    match &cli.output {
+        Some(path) => vec![SinkSpec::File(path.clone())],
+        None => vec![SinkSpec::Stdout],
+    }
+}
+
+/// Every sink, each writing frames in the format asked for.
+pub(crate) fn frame_sink(
+    cli: &CaptureCli,
+    health: &mut Health,
+) -> anyhow::Result<Box<dyn FrameSink>> {
+    #[cfg(feature = "hdf5")]
+    if let Some(path) = &cli.hdf5 {
+        let sink =
+            Hdf5Sink::create(path).with_context(|| format!("creating {}", path.display()))?;
+        return Ok(Box::new(sink));
+    }
+    let specs = specs(cli);
+    if cli.max_size.is_some() && !specs.iter().any(|it| matches!(it, SinkSpec::File(_))) {
+        bail!("--max-size needs --output or a file sink");
+    }
+    let mut sinks = vec![];
+    for spec in specs {
+        let sink: Box<dyn FrameSink> = match &spec {
+            SinkSpec::File(path) => Box::new(
+                RotatingSink::create(
+                    cli.format.clone(),
+                    cli.math.clone(),
+                    path.clone(),
+                    cli.max_size,
+                    cli.rotate,
+                )
+                .with_context(|| format!("creating {}", path.display()))?,
+            ),
+            _ => cli.format.sink_with_math(
+                open(&spec, cli, health, is_text(&cli.format))?,
+                cli.math.clone(),
+            ),
+        };
+        sinks.push((spec, sink));
+    }
+    Ok(match sinks.len() {
+        1 => sinks.pop().unwrap().1,
+        _ => Box::new(TeeSink { sinks }),
+    })
+}
+
+/// Every sink as a single writer, for the captures written other than frame by frame. Text
+/// goes to WebSocket clients as text messages rather than binary ones.
+pub(crate) fn writer(
+    cli: &CaptureCli,
+    health: &mut Health,
+    text: bool,
+) -> anyhow::Result<Box<dyn Write>> {
+    let mut writers = vec![];
+    for spec in specs(cli) {
+        let writer = open(&spec, cli, health, text)?;
+        writers.push((spec, writer));
+    }
+    Ok(match writers.len() {
+        1 => writers.pop().unwrap().1,
+        _ => Box::new(TeeWriter { writers }),
+    })
+}
+
+/// Whether the format is text, sent to WebSocket clients as text messages.
+pub(crate) fn is_text(format: &ExportFormat) -> bool {
+    matches!(format, ExportFormat::Csv | ExportFormat::Jsonl)
+}
+
+fn open(
+    spec: &SinkSpec,
+    cli: &CaptureCli,
+    health: &mut Health,
+    text: bool,
+) -> anyhow::Result<Box<dyn Write>> {
+    Ok(match spec {
+        SinkSpec::Stdout => {
+            let stdout = QueuedStdout::spawn(cli.on_backpressure.clone());
+            health.watch(&stdout);
+            Box::new(stdout)
+        }
+        SinkSpec::File(path) => Box::new(BufWriter::new(
+            File::create(path).with_context(|| format!("creating {}", path.display()))?,
+        )),
+        SinkSpec::Tcp(addr) => Box::new(BufWriter::new(
+            TcpStream::connect(addr).with_context(|| format!("connecting to {}", addr))?,
+        )),
+        SinkSpec::TcpServer(addr) => Box::new(BufWriter::new(accept_one(addr)?)),
+        SinkSpec::WebSocket(addr) => Box::new(WebSocketOut::listen(addr, text)?),
+        SinkSpec::Null => Box::new(io::sink()),
+    })
+}
+
+/// Waits for a client to connect, the only one served.
+fn accept_one(addr: &str) -> anyhow::Result<TcpStream> {
+    let listener = TcpListener::bind(addr).with_context(|| format!("listening on {}", addr))?;
+    info!("waiting for a client on {}", addr);
+    let (client, peer) = listener
+        .accept()
+        .with_context(|| format!("accepting a client on {}", addr))?;
+    info!("streaming captures to {}", peer);
+    // Captures are flushed whole, no point holding back their last segment.
+    client.set_nodelay(true).ok();
+    Ok(client)
+}
+
+/// Drops the sinks failing with a disconnect, errors once none is left or on any other failure.
+fn retain_connected<S>(
+    sinks: &mut Vec<(SinkSpec, S)>,
+    mut write: impl FnMut(&mut S) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut failure = None;
+    sinks.retain_mut(|(spec, sink)| match write(sink) {
+        Ok(()) => true,
+        Err(e) if is_disconnect(&e) => {
+            warn!("sink {} went away, dropping it: {}", spec, e);
+            false
+        }
+        Err(e) => {
+            failure.get_or_insert(e);
+            true
+        }
+    });
+    match failure {
+        Some(e) => Err(e),
+        None if sinks.is_empty() => Err(io::ErrorKind::BrokenPipe.into()),
+        None => Ok(()),
+    }
+}
+
+struct TeeSink {
+    sinks: Vec<(SinkSpec, Box<dyn FrameSink>)>,
+}
+
+impl FrameSink for TeeSink {
+    fn write_frame(&mut self, frame: &CaptureFrame) -> io::Result<()> {
+        retain_connected(&mut self.sinks, |it| it.write_frame(frame))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        retain_connected(&mut self.sinks, |it| it.flush())
+    }
+}
+
+struct TeeWriter {
+    writers: Vec<(SinkSpec, Box<dyn Write>)>,
+}
+
+impl Write for TeeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        retain_connected(&mut self.writers, |it| it.write_all(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        retain_connected(&mut self.writers, |it| it.flush())
+    }
+}
+
+/// Sends whatever was written between two flushes to every client as a message, the capture
+/// loops flush once per frame. Clients connect whenever, a frame without any goes nowhere.
+struct WebSocketOut {
+    text: bool,
+    frame: Vec<u8>,
+    clients: Arc<Mutex<Vec<WebSocket<TcpStream>>>>,
+}
+
+impl WebSocketOut {
+    fn listen(addr: &str, text: bool) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind(addr).with_context(|| format!("listening on {}", addr))?;
+        info!("serving captures to WebSocket clients on ws://{}", addr);
+        let clients = Arc::new(Mutex::new(vec![]));
+        {
+            let clients = Arc::clone(&clients);
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            debug!("WebSocket client failed to connect: {}", e);
+                            continue;
+                        }
+                    };
+                    let peer = stream.peer_addr();
+                    match tungstenite::accept(stream) {
+                        Ok(client) => {
+                            info!("WebSocket client connected: {:?}", peer);
+                            clients.lock().unwrap().push(client);
+                        }
+                        Err(e) => debug!("WebSocket handshake with {:?} failed: {}", peer, e),
+                    }
+                }
+            });
+        }
+        Ok(Self {
+            text,
+            frame: vec![],
+            clients,
+        })
+    }
+}
+
+impl Write for WebSocketOut {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.frame.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.frame.is_empty() {
+            return Ok(());
+        }
+        let frame = mem::take(&mut self.frame);
+        let message = if self.text {
+            Message::Text(String::from_utf8_lossy(&frame).into_owned())
+        } else {
+            Message::Binary(frame)
+        };
+        self.clients
+            .lock()
+            .unwrap()
+            .retain_mut(|client| match client.send(message.clone()) {
+                Ok(()) => true,
+                Err(e) => {
+                    info!("WebSocket client went away: {}", e);
+                    false
+                }
+            });
+        Ok(())
+    }
+}
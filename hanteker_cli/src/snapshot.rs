@@ -0,0 +1,195 @@
+//! Snapshots of the config as JSON, and a field by field diff between two of them, to tell
+//! what changed between a working and a broken setup.
+//!
+//! The device can't be read back, a snapshot holds what was set through the process taking it,
+//! `null` for whatever was never set. Snapshots of a long running session are taken from the
+//! HTTP server with `GET /api/snapshot`.
+
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+
+use anyhow::Context;
+use hanteker_lib::device::cfg::{Adjustment, ChannelConfig, HantekConfig, RunningStatus};
+use serde_json::{json, Map, Value};
+
+pub(crate) fn snapshot(config: &HantekConfig) -> Value {
+    let mut fields = Map::new();
+    fields.insert(
+        "timeout_ms".to_string(),
+        json!(config.timeout.map(|it| it.as_millis() as u64)),
+    );
+    fields.insert("device_function".to_string(), show(&config.device_function));
+
+    per_channel(&mut fields, "enabled", config, |it| json!(it.enabled));
+    per_channel(&mut fields, "coupling", config, |it| show(&it.coupling));
+    per_channel(&mut fields, "probe", config, |it| show(&it.probe));
+    per_channel(&mut fields, "scale", config, |it| show(&it.scale));
+    per_channel(&mut fields, "offset", config, |it| json!(it.offset));
+    per_channel(&mut fields, "bandwidth_limit", config, |it| {
+        json!(it.bandwidth_limit)
+    });
+    per_channel(&mut fields, "offset_adjustment", config, |it| {
+        it.offset_adjustment
+            .as_ref()
+            .map_or(Value::Null, adjustment)
+    });
+
+    fields.insert("time_scale".to_string(), show(&config.time_scale));
+    fields.insert("time_offset".to_string(), json!(config.time_offset));
+    fields.insert(
+        "time_offset_adjustment".to_string(),
+        config
+            .time_offset_adjustment
+            .as_ref()
+            .map_or(Value::Null, adjustment),
+    );
+
+    fields.insert("running".to_string(), running(&config.running_status));
+    fields.insert(
+        "trigger.source".to_string(),
+        json!(config.trigger_source_channel),
+    );
+    fields.insert("trigger.slope".to_string(), show(&config.trigger_slope));
+    fields.insert("trigger.mode".to_string(), show(&config.trigger_mode));
+    fields.insert("trigger.level".to_string(), json!(config.trigger_level));
+    fields.insert(
+        "trigger.level_adjustment".to_string(),
+        config
+            .trigger_level_adjustment
+            .as_ref()
+            .map_or(Value::Null, adjustment),
+    );
+
+    fields.insert("awg.type".to_string(), show(&config.awg_type));
+    fields.insert("awg.frequency".to_string(), json!(config.awg_frequency));
+    fields.insert("awg.amplitude".to_string(), json!(config.awg_amplitude));
+    fields.insert("awg.offset".to_string(), json!(config.awg_offset));
+    fields.insert("awg.duty_square".to_string(), json!(config.awg_duty_square));
+    fields.insert("awg.duty_ramp".to_string(), json!(config.awg_duty_ramp));
+    fields.insert(
+        "awg.duty_trap".to_string(),
+        config.awg_duty_trap.as_ref().map_or(
+            Value::Null,
+            |it| json!({ "high": it.high, "low": it.low, "rise": it.rise }),
+        ),
+    );
+    fields.insert(
+        "awg.running".to_string(),
+        running(&config.awg_running_status),
+    );
+
+    Value::Object(fields)
+}
+
+fn per_channel(
+    fields: &mut Map<String, Value>,
+    name: &str,
+    config: &HantekConfig,
+    to_json: impl Fn(&ChannelConfig) -> Value,
+) {
+    for (channel_no, channel) in config.channels() {
+        fields.insert(format!("channel{}.{}", channel_no, name), to_json(channel));
+    }
+}
+
+fn show<T: Display>(value: &Option<T>) -> Value {
+    value.as_ref().map_or(Value::Null, show_some)
+}
+
+fn show_some<T: Display>(value: &T) -> Value {
+    Value::String(value.to_string())
+}
+
+fn adjustment(value: &Adjustment) -> Value {
+    json!({ "upper": value.upper, "lower": value.lower })
+}
+
+fn running(value: &Option<RunningStatus>) -> Value {
+    json!(value.as_ref().map(|it| it.is_start()))
+}
+
+pub(crate) fn read(path: &Path) -> anyhow::Result<Value> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    serde_json::from_str(&content).with_context(|| format!("parsing {}", path.display()))
+}
+
+pub(crate) fn write(path: &Path, snapshot: &Value) -> anyhow::Result<()> {
+    let content = serde_json::to_string_pretty(snapshot)?;
+    fs::write(path, content + "\n").with_context(|| format!("writing {}", path.display()))
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) struct Change {
+    pub(crate) field: String,
+    /// Missing from the first snapshot when `None`.
+    pub(crate) before: Option<Value>,
+    /// Missing from the second snapshot when `None`.
+    pub(crate) after: Option<Value>,
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show = |value: &Option<Value>| match value {
+            Some(value) => value.to_string(),
+            None => "(absent)".to_string(),
+        };
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.field,
+            show(&self.before),
+            show(&self.after)
+        )
+    }
+}
+
+/// Fields whose values differ, sorted by name. Nested objects are compared field by field,
+/// named by their path joined with dots, so any JSON document diffs, e.g. `GET /api/settings`.
+pub(crate) fn diff(before: &Value, after: &Value) -> Vec<Change> {
+    let mut before_fields = Map::new();
+    flatten("", before, &mut before_fields);
+    let mut after_fields = Map::new();
+    flatten("", after, &mut after_fields);
+
+    let mut changes: Vec<Change> = before_fields
+        .iter()
+        .filter(|(field, value)| after_fields.get(*field) != Some(value))
+        .map(|(field, value)| Change {
+            field: field.clone(),
+            before: Some(value.clone()),
+            after: after_fields.get(field).cloned(),
+        })
+        .collect();
+    changes.extend(
+        after_fields
+            .iter()
+            .filter(|(field, _)| !before_fields.contains_key(*field))
+            .map(|(field, value)| Change {
+                field: field.clone(),
+                before: None,
+                after: Some(value.clone()),
+            }),
+    );
+    changes.sort_by(|a, b| a.field.cmp(&b.field));
+    changes
+}
+
+fn flatten(prefix: &str, value: &Value, into: &mut Map<String, Value>) {
+    match value {
+        Value::Object(fields) if !fields.is_empty() => {
+            for (name, value) in fields {
+                let path = if prefix.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}.{}", prefix, name)
+                };
+                flatten(&path, value, into);
+            }
+        }
+        _ => {
+            into.insert(prefix.to_string(), value.clone());
+        }
+    }
+}
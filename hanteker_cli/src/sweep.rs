@@ -0,0 +1,404 @@
+//! Sweeps over any combination of settings declared in a TOML plan, measuring a channel at
+//! each point. For example:
+//!
+//! ```toml
+//! channel = 1
+//! stats = ["vpp", "freq"]
+//! settle = "200ms"
+//!
+//! [setup]
+//! "channel1.scale" = "v1"
+//! time_scale = "us100"
+//!
+//! [[sweep]]
+//! setting = "awg.frequency"
+//! values = [100, 1000, 10000]
+//!
+//! [[sweep]]
+//! setting = "awg.amplitude"
+//! start = 0.5
+//! stop = 2.5
+//! step = 0.5
+//! ```
+//!
+//! The first sweep is the outermost loop. Settings are named `time_scale`, `channelN.enabled`,
+//! `channelN.scale`, `channelN.coupling`, `channelN.probe`, `channelN.offset`,
+//! `trigger.source`, `trigger.slope`, `trigger.mode`, `trigger.level`, `awg.type`,
+//! `awg.frequency`, `awg.amplitude` and `awg.offset`. Offset and trigger level take the raw
+//! 0..=200 value.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context};
+use hanteker_lib::batch::{CommandBatch, Setting};
+use hanteker_lib::measure::{measure, Stat};
+use hanteker_lib::models::hantek2d42::Hantek2D42;
+use log::debug;
+use serde::Deserialize;
+
+const NUM_CHANNELS: usize = 2;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlanFile {
+    channel: usize,
+    #[serde(default = "default_stats")]
+    stats: Vec<String>,
+    #[serde(default = "default_capture_chunk")]
+    capture_chunk: usize,
+    /// Number of captures averaged at each point.
+    #[serde(default = "default_captures")]
+    captures: usize,
+    /// Wait after changing settings before capturing, as understood by humantime.
+    settle: Option<String>,
+    #[serde(default)]
+    setup: BTreeMap<String, toml::Value>,
+    sweep: Vec<AxisFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AxisFile {
+    setting: String,
+    #[serde(default)]
+    values: Vec<toml::Value>,
+    start: Option<f64>,
+    stop: Option<f64>,
+    step: Option<f64>,
+}
+
+fn default_stats() -> Vec<String> {
+    vec!["vpp".to_string(), "vrms".to_string(), "freq".to_string()]
+}
+
+fn default_capture_chunk() -> usize {
+    1000
+}
+
+fn default_captures() -> usize {
+    1
+}
+
+/// A setting named as in a plan, checked against what the device takes.
+pub(crate) fn parse_setting(name: &str, value: &toml::Value) -> anyhow::Result<Setting> {
+    let (group, field) = name.split_once('.').unwrap_or((name, ""));
+    let setting = match (group, field) {
+        ("time_scale", "") => Setting::TimeScale(choice(value)?),
+        ("trigger", "source") => Setting::TriggerSource(channel_no(integer(value)?)?),
+        ("trigger", "slope") => Setting::TriggerSlope(choice(value)?),
+        ("trigger", "mode") => Setting::TriggerMode(choice(value)?),
+        ("trigger", "level") => Setting::TriggerLevel(raw(value)?),
+        ("awg", "type") => Setting::AwgType(choice(value)?),
+        ("awg", "frequency") => Setting::AwgFrequency(number(value)?),
+        ("awg", "amplitude") => Setting::AwgAmplitude(number(value)?),
+        ("awg", "offset") => Setting::AwgOffset(number(value)?),
+        (channel, field) if channel.starts_with("channel") => {
+            let channel_no = match channel["channel".len()..].parse() {
+                Ok(channel_no) => channel_no_checked(channel_no)?,
+                Err(_) => bail!("unknown setting: {}", name),
+            };
+            match field {
+                "enabled" => match value.as_bool() {
+                    Some(enabled) => Setting::ChannelEnabled(channel_no, enabled),
+                    None => bail!("expected true or false, got: {}", value),
+                },
+                "scale" => Setting::ChannelScale(channel_no, choice(value)?),
+                "coupling" => Setting::ChannelCoupling(channel_no, choice(value)?),
+                "probe" => Setting::ChannelProbe(channel_no, choice(value)?),
+                "offset" => Setting::ChannelOffset(channel_no, raw(value)?),
+                _ => bail!("unknown setting: {}", name),
+            }
+        }
+        _ => bail!("unknown setting: {}", name),
+    };
+    Ok(setting)
+}
+
+fn choice<T: FromStr>(value: &toml::Value) -> anyhow::Result<T> {
+    value
+        .as_str()
+        .and_then(|it| it.parse().ok())
+        .ok_or_else(|| anyhow!("invalid value: {}", value))
+}
+
+fn number(value: &toml::Value) -> anyhow::Result<f32> {
+    match value {
+        toml::Value::Float(number) => Ok(*number as f32),
+        toml::Value::Integer(number) => Ok(*number as f32),
+        _ => bail!("expected a number, got: {}", value),
+    }
+}
+
+fn integer(value: &toml::Value) -> anyhow::Result<i64> {
+    match value {
+        toml::Value::Integer(number) => Ok(*number),
+        // Values of a numeric range come as floats.
+        toml::Value::Float(number) if number.fract() == 0.0 => Ok(*number as i64),
+        _ => bail!("expected an integer, got: {}", value),
+    }
+}
+
+fn raw(value: &toml::Value) -> anyhow::Result<u8> {
+    match integer(value)? {
+        raw @ 0..=200 => Ok(raw as u8),
+        raw => bail!("raw value must be within 0..=200, got: {}", raw),
+    }
+}
+
+fn channel_no(value: i64) -> anyhow::Result<usize> {
+    channel_no_checked(usize::try_from(value).unwrap_or(0))
+}
+
+fn channel_no_checked(channel_no: usize) -> anyhow::Result<usize> {
+    if (1..=NUM_CHANNELS).contains(&channel_no) {
+        Ok(channel_no)
+    } else {
+        bail!("invalid channel: {}", channel_no)
+    }
+}
+
+fn label(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+/// A swept setting and each of its points as a setting and a label for the results table.
+struct Axis {
+    name: String,
+    points: Vec<(Setting, String)>,
+}
+
+impl Axis {
+    fn parse(axis: AxisFile) -> anyhow::Result<Self> {
+        let values = match (axis.start, axis.stop, axis.step) {
+            (None, None, None) => axis.values,
+            (Some(start), Some(stop), Some(step)) if axis.values.is_empty() => {
+                if step <= 0.0 || stop < start {
+                    bail!("range of {} never reaches its stop", axis.setting);
+                }
+                // Tolerates the rounding of the step, so stop itself is included and the
+                // labels don't end in ...0000001.
+                let count = ((stop - start) / step + 1e-9).floor() as usize + 1;
+                (0..count)
+                    .map(|idx| start + idx as f64 * step)
+                    .map(|it| toml::Value::Float((it * 1e9).round() / 1e9))
+                    .collect()
+            }
+            _ => bail!(
+                "{} needs either values or all of start, stop and step",
+                axis.setting
+            ),
+        };
+        if values.is_empty() {
+            bail!("nothing to sweep for {}", axis.setting);
+        }
+
+        let points = values
+            .iter()
+            .map(|value| {
+                parse_setting(&axis.setting, value)
+                    .map(|setting| (setting, label(value)))
+                    .with_context(|| format!("invalid point of {}", axis.setting))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            name: axis.setting,
+            points,
+        })
+    }
+}
+
+/// A plan checked against what the device takes, so a typo fails before touching the device.
+pub(crate) struct Plan {
+    channel: usize,
+    stats: Vec<Stat>,
+    capture_chunk: usize,
+    captures: usize,
+    settle: Option<Duration>,
+    setup: CommandBatch,
+    axes: Vec<Axis>,
+}
+
+impl Plan {
+    pub(crate) fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read plan: {}", path.display()))?;
+        let file: PlanFile = toml::from_str(&content)
+            .with_context(|| format!("invalid plan: {}", path.display()))?;
+
+        if file.capture_chunk < 64 {
+            bail!(
+                "minimum length of chunks=64, asked for={}",
+                file.capture_chunk
+            );
+        }
+        if file.captures == 0 {
+            bail!("need at least one capture per point");
+        }
+        if file.sweep.is_empty() {
+            bail!("plan has nothing to sweep");
+        }
+
+        let stats = file
+            .stats
+            .iter()
+            .map(|it| it.parse().map_err(|_| anyhow!("unknown stat: {}", it)))
+            .collect::<anyhow::Result<_>>()?;
+        let settle = file
+            .settle
+            .as_deref()
+            .map(humantime::parse_duration)
+            .transpose()
+            .context("invalid settle")?;
+        let setup = file
+            .setup
+            .iter()
+            .map(|(name, value)| parse_setting(name, value))
+            .collect::<anyhow::Result<_>>()
+            .context("invalid setup")?;
+        let axes = file
+            .sweep
+            .into_iter()
+            .map(Axis::parse)
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            channel: channel_no_checked(file.channel)?,
+            stats,
+            capture_chunk: file.capture_chunk,
+            captures: file.captures,
+            settle,
+            setup,
+            axes,
+        })
+    }
+
+    pub(crate) fn num_points(&self) -> usize {
+        self.axes.iter().map(|it| it.points.len()).product()
+    }
+
+    pub(crate) fn header(&self) -> Vec<String> {
+        self.axes
+            .iter()
+            .map(|it| it.name.clone())
+            .chain(self.stats.iter().map(|it| it.to_string()))
+            .collect()
+    }
+
+    /// Runs the sweep, handing each row of the results to `row` as soon as it's measured. Only
+    /// the settings that changed since the previous point are sent to the device.
+    pub(crate) fn run(
+        &self,
+        hantek: &mut Hantek2D42,
+        mut row: impl FnMut(Vec<String>),
+    ) -> anyhow::Result<()> {
+        hantek
+            .apply_batch(&self.setup)
+            .context("could not apply setup")?;
+
+        let mut previous: Option<Vec<usize>> = None;
+        for point in 0..self.num_points() {
+            let indexes = self.indexes(point);
+            let changed: CommandBatch = self
+                .axes
+                .iter()
+                .enumerate()
+                .filter(|(axis_idx, _)| {
+                    previous.as_ref().map(|it| it[*axis_idx]) != Some(indexes[*axis_idx])
+                })
+                .map(|(axis_idx, axis)| axis.points[indexes[axis_idx]].0.clone())
+                .collect();
+            debug!("sweep settings: {:?}", changed.settings());
+            hantek.apply_batch(&changed)?;
+            if let Some(settle) = self.settle {
+                thread::sleep(settle);
+            }
+
+            let mut cells: Vec<String> = self
+                .axes
+                .iter()
+                .zip(&indexes)
+                .map(|(axis, idx)| axis.points[*idx].1.clone())
+                .collect();
+            cells.extend(self.measure(hantek)?.iter().map(|it| show(it.as_ref())));
+            row(cells);
+
+            previous = Some(indexes);
+        }
+
+        Ok(())
+    }
+
+    /// Index into the points of each axis, the last axis changing fastest.
+    fn indexes(&self, mut point: usize) -> Vec<usize> {
+        let mut indexes = vec![0; self.axes.len()];
+        for (idx, axis) in self.axes.iter().enumerate().rev() {
+            indexes[idx] = point % axis.points.len();
+            point /= axis.points.len();
+        }
+        indexes
+    }
+
+    /// Each stat averaged over the captures of a point, `None` if no capture had it.
+    fn measure(&self, hantek: &mut Hantek2D42) -> anyhow::Result<Vec<Option<f32>>> {
+        let mut sums = vec![(0.0, 0); self.stats.len()];
+        for _ in 0..self.captures {
+            let frame = hantek.capture_frame(&[self.channel], self.capture_chunk)?;
+            let samples = match frame.channel_volts(self.channel) {
+                Some(samples) => samples,
+                None => bail!(
+                    "scale of channel {} is unknown, set it in the setup of the plan",
+                    self.channel
+                ),
+            };
+            if let Some(measurements) = measure(&samples, frame.sample_rate()) {
+                for (sum, stat) in sums.iter_mut().zip(&self.stats) {
+                    if let Some(value) = measurements.get(stat) {
+                        sum.0 += value;
+                        sum.1 += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(sums
+            .into_iter()
+            .map(|(sum, count)| (count > 0).then(|| sum / count as f32))
+            .collect())
+    }
+}
+
+fn show(value: Option<&f32>) -> String {
+    value.map_or_else(|| "?".to_string(), |it| format!("{:.4}", it))
+}
+
+/// Prints rows as aligned columns, all at once since the widths depend on every row.
+pub(crate) fn print_table(header: &[String], rows: &[Vec<String>]) {
+    let widths: Vec<usize> = (0..header.len())
+        .map(|column| {
+            rows.iter()
+                .map(|it| it[column].len())
+                .chain([header[column].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    for row in [header.to_vec()].iter().chain(rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:>width$}", cell, width = width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
+
+pub(crate) fn csv_line(row: &[String]) -> String {
+    row.join(",")
+}
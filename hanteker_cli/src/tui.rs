@@ -0,0 +1,418 @@
+use std::time::{Duration, Instant};
+
+use anyhow::bail;
+use hanteker_lib::capture::{CaptureFrame, COUNTS_PER_DIVISION};
+use hanteker_lib::device::cfg::{Coupling, Scale, TimeScale, TriggerMode, TriggerSlope};
+use hanteker_lib::models::hantek2d42::Hantek2D42;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::Marker;
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Axis, Block, Chart, Dataset, GraphType, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+
+/// Raw value of channel offset and trigger level at the center of the screen, the device takes
+/// 0..=200 for the 8 vertical divisions.
+const RAW_CENTER: u8 = 100;
+const RAW_MAX: u8 = 200;
+/// Offset and trigger level move by a fifth of a division per key press.
+const RAW_STEP: u8 = 5;
+
+const CHANNEL_COLORS: [Color; 2] = [Color::Yellow, Color::Cyan];
+
+const KEYS: [(&str, &str); 11] = [
+    ("q", "quit"),
+    ("tab", "select channel"),
+    ("space", "run / stop"),
+    ("+ -", "scale"),
+    ("] [", "time scale"),
+    ("pgup pgdn", "offset"),
+    ("k j", "trigger level"),
+    ("t", "trigger mode"),
+    ("s", "trigger slope"),
+    ("c", "coupling"),
+    ("x", "xy"),
+];
+
+/// Front panel state that isn't kept in the device config.
+pub(crate) struct Dashboard {
+    channels: Vec<usize>,
+    selected: usize,
+    capture_chunk: usize,
+    refresh: Duration,
+    running: bool,
+    /// Channel 2 plotted against channel 1 rather than both against time.
+    xy: bool,
+    frame: Option<CaptureFrame>,
+    status: String,
+}
+
+impl Dashboard {
+    pub(crate) fn new(
+        channels: Vec<usize>,
+        capture_chunk: usize,
+        refresh: Duration,
+        xy: bool,
+    ) -> Self {
+        Self {
+            channels,
+            selected: 0,
+            capture_chunk,
+            refresh,
+            running: true,
+            xy,
+            frame: None,
+            status: String::new(),
+        }
+    }
+
+    /// Takes over the terminal until the user quits, restoring it on the way out even when the
+    /// device fails mid way.
+    pub(crate) fn run(&mut self, hantek: &mut Hantek2D42) -> anyhow::Result<()> {
+        let mut terminal = ratatui::try_init()?;
+        let result = self.event_loop(&mut terminal, hantek);
+        ratatui::restore();
+        result
+    }
+
+    fn event_loop(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        hantek: &mut Hantek2D42,
+    ) -> anyhow::Result<()> {
+        loop {
+            let started = Instant::now();
+            if self.running {
+                self.frame = Some(hantek.capture_frame(&self.channels, self.capture_chunk)?);
+            }
+            terminal.draw(|f| self.draw(f, hantek))?;
+
+            let deadline = started + self.refresh;
+            loop {
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                if !event::poll(timeout)? {
+                    break;
+                }
+                if let Event::Key(key) = event::read()? {
+                    if key.kind != KeyEventKind::Press {
+                        continue;
+                    }
+                    if key.code == KeyCode::Char('q') || key.code == KeyCode::Esc {
+                        return Ok(());
+                    }
+                    // A rejected setting shouldn't tear down the whole dashboard.
+                    self.status = match self.on_key(key.code, hantek) {
+                        Ok(()) => String::new(),
+                        Err(e) => format!("{:#}", e),
+                    };
+                    terminal.draw(|f| self.draw(f, hantek))?;
+                }
+            }
+        }
+    }
+
+    fn channel_no(&self) -> usize {
+        self.channels[self.selected]
+    }
+
+    fn on_key(&mut self, code: KeyCode, hantek: &mut Hantek2D42) -> anyhow::Result<()> {
+        let channel_no = self.channel_no();
+        let config = hantek.get_config();
+
+        match code {
+            KeyCode::Tab | KeyCode::Char('1') | KeyCode::Char('2') => {
+                self.selected = match code {
+                    KeyCode::Char(it) => self
+                        .channels
+                        .iter()
+                        .position(|ch| it.to_digit(10) == Some(*ch as u32))
+                        .unwrap_or(self.selected),
+                    _ => (self.selected + 1) % self.channels.len(),
+                };
+            }
+            KeyCode::Char(' ') => self.running = !self.running,
+            KeyCode::Char('x') => {
+                let both = self.channels.contains(&1) && self.channels.contains(&2);
+                if !(self.xy || both) {
+                    bail!("xy needs both channels captured");
+                }
+                self.xy = !self.xy;
+            }
+            KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Char('-') => {
+                let current = config.channel(channel_no).and_then(|it| it.scale.clone());
+                let scale = step(Scale::my_iter(), current, code != KeyCode::Char('-'));
+                hantek.set_channel_scale(channel_no, scale)?;
+            }
+            KeyCode::Char(']') | KeyCode::Char('[') => {
+                let current = config.time_scale.clone();
+                let time_scale = step(TimeScale::my_iter(), current, code == KeyCode::Char(']'));
+                hantek.set_time_scale(time_scale)?;
+            }
+            KeyCode::PageUp | KeyCode::PageDown => {
+                let current = raw_or_center(config.channel(channel_no).and_then(|it| it.offset));
+                let offset = nudge(current, code == KeyCode::PageUp);
+                hantek.set_channel_offset(channel_no, offset)?;
+            }
+            KeyCode::Char('k') | KeyCode::Char('j') => {
+                let current = raw_or_center(config.trigger_level);
+                let level = nudge(current, code == KeyCode::Char('k'));
+                hantek.set_trigger_level(level)?;
+            }
+            KeyCode::Char('t') => {
+                let current = config.trigger_mode.clone();
+                hantek.set_trigger_mode(cycle(TriggerMode::my_iter(), current))?;
+            }
+            KeyCode::Char('s') => {
+                let current = config.trigger_slope.clone();
+                hantek.set_trigger_slope(cycle(TriggerSlope::my_iter(), current))?;
+            }
+            KeyCode::Char('c') => {
+                let current = config
+                    .channel(channel_no)
+                    .and_then(|it| it.coupling.clone());
+                hantek.set_channel_coupling(channel_no, cycle(Coupling::my_iter(), current))?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn draw(&self, f: &mut Frame, hantek: &Hantek2D42) {
+        let [main, help] =
+            Layout::vertical([Constraint::Min(8), Constraint::Length(3)]).areas(f.area());
+        let [scope, panel] =
+            Layout::horizontal([Constraint::Min(20), Constraint::Length(30)]).areas(main);
+
+        let traces = if self.xy {
+            self.xy_trace()
+        } else {
+            self.traces()
+        };
+        f.render_widget(self.waveform(&traces), scope);
+        f.render_widget(self.settings(hantek), panel);
+        f.render_widget(key_help(), help);
+    }
+
+    /// Samples of each captured channel as chart points, in raw counts from the channel's zero.
+    fn traces(&self) -> Vec<(usize, Vec<(f64, f64)>)> {
+        let frame = match &self.frame {
+            Some(frame) => frame,
+            None => return vec![],
+        };
+
+        frame
+            .channels
+            .iter()
+            .map(|channel_no| {
+                let samples = frame
+                    .channel_raw(*channel_no)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, raw)| (idx as f64, (raw as i8) as f64))
+                    .collect();
+                (*channel_no, samples)
+            })
+            .collect()
+    }
+
+    /// Channel 2 against channel 1 as chart points, in raw counts from each channel's zero, keyed
+    /// by channel 2.
+    fn xy_trace(&self) -> Vec<(usize, Vec<(f64, f64)>)> {
+        let frame = match &self.frame {
+            Some(frame) => frame,
+            None => return vec![],
+        };
+
+        match (frame.channel_raw(1), frame.channel_raw(2)) {
+            (Some(x), Some(y)) => {
+                let points = x
+                    .into_iter()
+                    .zip(y)
+                    .map(|(x, y)| ((x as i8) as f64, (y as i8) as f64))
+                    .collect();
+                vec![(2, points)]
+            }
+            _ => vec![],
+        }
+    }
+
+    fn waveform<'a>(&self, traces: &'a [(usize, Vec<(f64, f64)>)]) -> Chart<'a> {
+        let half = 4.0 * COUNTS_PER_DIVISION as f64;
+        let num_samples = self.frame.as_ref().map_or(0, |it| it.num_samples());
+
+        let datasets = traces
+            .iter()
+            .map(|(channel_no, samples)| {
+                let (name, graph_type) = if self.xy {
+                    ("CH2 vs CH1".to_string(), GraphType::Scatter)
+                } else {
+                    (format!("CH{}", channel_no), GraphType::Line)
+                };
+                Dataset::default()
+                    .name(name)
+                    .marker(Marker::Braille)
+                    .graph_type(graph_type)
+                    .style(Style::default().fg(channel_color(*channel_no)))
+                    .data(samples)
+            })
+            .collect();
+        let x_axis = if self.xy {
+            Axis::default()
+                .bounds([-half, half])
+                .labels(["-4div", "0", "+4div"])
+        } else {
+            Axis::default().bounds([0.0, num_samples.saturating_sub(1).max(1) as f64])
+        };
+
+        let title = match (&self.frame, self.running) {
+            (None, _) => "waiting for capture".to_string(),
+            (Some(frame), true) => crate::plot::annotations(frame),
+            (Some(frame), false) => format!("{}  STOPPED", crate::plot::annotations(frame)),
+        };
+
+        Chart::new(datasets)
+            .block(Block::bordered().title(title))
+            .x_axis(x_axis.style(Style::default().fg(Color::DarkGray)))
+            .y_axis(
+                Axis::default()
+                    .bounds([-half, half])
+                    .labels(["-4div", "0", "+4div"])
+                    .style(Style::default().fg(Color::DarkGray)),
+            )
+    }
+
+    fn settings(&self, hantek: &Hantek2D42) -> Paragraph<'_> {
+        let config = hantek.get_config();
+        let mut lines = vec![];
+
+        for channel_no in &self.channels {
+            let mut title = Style::default()
+                .fg(channel_color(*channel_no))
+                .add_modifier(Modifier::BOLD);
+            if *channel_no == self.channel_no() {
+                title = title.add_modifier(Modifier::REVERSED);
+            }
+            lines.push(Line::from(Span::styled(format!("CH{}", channel_no), title)));
+            let channel = config.channel(*channel_no).cloned().unwrap_or_default();
+            lines.push(field(
+                "scale",
+                channel
+                    .scale()
+                    .map(|it| format!("{}/div", it.engineering())),
+            ));
+            lines.push(field(
+                "offset",
+                channel.offset.map(|it| divisions(it as u8)),
+            ));
+            lines.push(field("coupling", show(&channel.coupling)));
+            lines.push(Line::default());
+        }
+
+        lines.push(Line::from(Span::styled(
+            "Trigger",
+            Style::default().add_modifier(Modifier::BOLD),
+        )));
+        lines.push(field("mode", show(&config.trigger_mode)));
+        lines.push(field("slope", show(&config.trigger_slope)));
+        lines.push(field(
+            "level",
+            config.trigger_level.map(|it| divisions(it as u8)),
+        ));
+        lines.push(field(
+            "source",
+            config.trigger_source_channel.map(|it| format!("CH{}", it)),
+        ));
+        lines.push(Line::default());
+        lines.push(field(
+            "time",
+            config
+                .time_scale
+                .as_ref()
+                .map(|it| format!("{}/div", it.engineering())),
+        ));
+
+        if !self.status.is_empty() {
+            lines.push(Line::default());
+            lines.push(Line::from(Span::styled(
+                self.status.as_str(),
+                Style::default().fg(Color::Red),
+            )));
+        }
+
+        Paragraph::new(lines).block(Block::bordered().title("Settings"))
+    }
+}
+
+fn key_help() -> Paragraph<'static> {
+    let mut spans = vec![];
+    for (key, action) in KEYS {
+        spans.push(Span::styled(
+            key,
+            Style::default().add_modifier(Modifier::BOLD),
+        ));
+        spans.push(Span::raw(format!(" {}  ", action)));
+    }
+    Paragraph::new(Line::from(spans)).block(Block::bordered())
+}
+
+fn field(name: &str, value: Option<String>) -> Line<'static> {
+    Line::from(format!(
+        "  {:<9}{}",
+        name,
+        value.unwrap_or_else(|| "?".to_string())
+    ))
+}
+
+fn show<T: std::fmt::Display>(value: &Option<T>) -> Option<String> {
+    value.as_ref().map(|it| it.to_string())
+}
+
+fn channel_color(channel_no: usize) -> Color {
+    CHANNEL_COLORS[(channel_no - 1) % CHANNEL_COLORS.len()]
+}
+
+/// Raw offset or trigger level as divisions from the center of the screen.
+fn divisions(raw: u8) -> String {
+    format!(
+        "{:+.1}div",
+        (raw as f32 - RAW_CENTER as f32) / COUNTS_PER_DIVISION
+    )
+}
+
+/// Settings are only known once set, until then the dashboard assumes the center of the screen.
+fn raw_or_center(raw: Option<f32>) -> u8 {
+    raw.map(|it| it as u8).unwrap_or(RAW_CENTER)
+}
+
+fn nudge(raw: u8, up: bool) -> u8 {
+    if up {
+        raw.saturating_add(RAW_STEP).min(RAW_MAX)
+    } else {
+        raw.saturating_sub(RAW_STEP)
+    }
+}
+
+/// Neighbour of `current` in the declaration order of the enum, staying put at either end.
+/// Starts from the middle when the current value is unknown.
+fn step<T: PartialEq>(options: impl Iterator<Item = T>, current: Option<T>, up: bool) -> T {
+    let mut options: Vec<T> = options.collect();
+    let idx = match current.and_then(|it| options.iter().position(|o| *o == it)) {
+        Some(idx) if up => (idx + 1).min(options.len() - 1),
+        Some(idx) => idx.saturating_sub(1),
+        None => options.len() / 2,
+    };
+    options.swap_remove(idx)
+}
+
+/// Next value of the enum after `current`, wrapping around.
+fn cycle<T: PartialEq>(options: impl Iterator<Item = T>, current: Option<T>) -> T {
+    let mut options: Vec<T> = options.collect();
+    let idx = match current.and_then(|it| options.iter().position(|o| *o == it)) {
+        Some(idx) => (idx + 1) % options.len(),
+        None => 0,
+    };
+    options.swap_remove(idx)
+}
@@ -0,0 +1,50 @@
+//! udev rule letting users other than root open the device on Linux. Without it libusb fails to
+//! open the device with an access error.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{bail, Context};
+use hanteker_lib::models::hantek2d42::USB_ID;
+use log::info;
+
+pub(crate) const RULE_PATH: &str = "/etc/udev/rules.d/60-hanteker.rules";
+
+/// Access for the user logged in at the seat, and for the plugdev group where it exists.
+pub(crate) fn rule() -> String {
+    let (vid, pid) = USB_ID;
+    format!(
+        "# Hantek 2D42 / 2D72 handheld oscilloscope\n\
+        SUBSYSTEM==\"usb\", ATTR{{idVendor}}==\"{:04x}\", ATTR{{idProduct}}==\"{:04x}\", \
+        MODE=\"0660\", GROUP=\"plugdev\", TAG+=\"uaccess\"\n",
+        vid, pid
+    )
+}
+
+/// Writes the rule and has udev apply it to a device already plugged in, needs root.
+pub(crate) fn install(path: &Path) -> anyhow::Result<()> {
+    fs::write(path, rule()).with_context(|| {
+        format!(
+            "writing {}, installing the rule needs root, e.g. with sudo",
+            path.display()
+        )
+    })?;
+    info!("wrote {}", path.display());
+
+    udevadm(&["control", "--reload-rules"])?;
+    udevadm(&["trigger", "--subsystem-match=usb"])?;
+    info!("udev rules reloaded, replug the device if it still can't be opened");
+    Ok(())
+}
+
+fn udevadm(args: &[&str]) -> anyhow::Result<()> {
+    let status = Command::new("udevadm")
+        .args(args)
+        .status()
+        .context("running udevadm")?;
+    if !status.success() {
+        bail!("udevadm {} failed: {}", args.join(" "), status);
+    }
+    Ok(())
+}
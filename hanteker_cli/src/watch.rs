@@ -0,0 +1,72 @@
+//! Keeps a device set up as a profile says across power cycles and replugging, for bench setups.
+//!
+//! libusb's hotplug events aren't available through the bindings, the device is looked for every
+//! poll instead. Once found it's claimed and the profile applied, then its product string is read
+//! every poll, which fails once the device is gone without sending it anything. The device is
+//! left as the profile set it when the watch is interrupted.
+
+use log::{debug, info, warn};
+
+use hanteker_lib::capture::CaptureHandle;
+use hanteker_lib::device::cfg::HantekConfig;
+use hanteker_lib::device::usb::HantekUsbError;
+use hanteker_lib::models::hantek2d42::Hantek2D42;
+
+use crate::cli::{Cli, WatchCli};
+use crate::exit::ExitStatus;
+use crate::failsafe::{on_signals, EXIT_INTERRUPTED};
+use crate::script::sleep;
+use crate::{open_device, profile};
+
+pub(crate) fn handle_watch(cli: &Cli, sub: &WatchCli) -> anyhow::Result<()> {
+    let profile = profile::read(&sub.profile)?;
+    let interrupted = CaptureHandle::with_flag(on_signals()?);
+    let context = libusb::Context::new()?;
+
+    info!("waiting for the device");
+    while !interrupted.is_cancelled() {
+        let mut hantek = match open_device(cli, &context) {
+            Ok(hantek) => hantek,
+            Err(e) => {
+                debug!("device not available: {:#}", e);
+                sleep(sub.poll, &interrupted);
+                continue;
+            }
+        };
+
+        info!("device connected, applying {}", sub.profile.display());
+        apply(&mut hantek, &profile);
+
+        while !interrupted.is_cancelled() {
+            sleep(sub.poll, &interrupted);
+            // A device without string languages can't be checked, it's taken to stay.
+            if let Err(e @ HantekUsbError::ProductReadUsbError { .. }) = hantek.usb.get_product() {
+                info!("device disconnected, waiting for it");
+                debug!("device check failed: {}", e);
+                break;
+            }
+        }
+        // Expected to fail for a device that is gone.
+        if let Err(e) = hantek.usb.release() {
+            debug!("error releasing device: {}", e);
+        }
+    }
+
+    Err(ExitStatus::new(EXIT_INTERRUPTED, "interrupted").into())
+}
+
+/// Every setting failing is reported, the device is kept either way in case it's a value of the
+/// profile the device doesn't take.
+fn apply(hantek: &mut Hantek2D42, profile: &HantekConfig) {
+    match hantek.apply_config(profile) {
+        Ok(()) => info!("profile applied"),
+        Err(e) => {
+            for item in &e.failed {
+                warn!("failed to apply {}", item);
+            }
+            if !e.not_sent.is_empty() {
+                warn!("not sent: {}", e.not_sent.join(", "));
+            }
+        }
+    }
+}
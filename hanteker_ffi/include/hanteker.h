@@ -0,0 +1,144 @@
+#ifndef HANTEKER_H
+#define HANTEKER_H
+
+/* Generated by cbindgen from hanteker_ffi/src/lib.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum HantekerStatus {
+  HANTEKER_STATUS_OK = 0,
+  // A pointer argument was NULL.
+  HANTEKER_STATUS_NULL_POINTER = -1,
+  // An argument was out of range, or a name was not one of the accepted ones.
+  HANTEKER_STATUS_INVALID_ARGUMENT = -2,
+  // Communication with the device failed.
+  HANTEKER_STATUS_USB = -3,
+  // The value needs a calibration (adjustment) the device hasn't reported yet, e.g. setting
+  // the channel offset in volts before the channel's scale.
+  HANTEKER_STATUS_ADJUSTMENT = -4,
+  // The buffer given for a capture is too small for the samples asked for.
+  HANTEKER_STATUS_BUFFER_TOO_SMALL = -5,
+  // A bug in the library, the device should be closed.
+  HANTEKER_STATUS_PANIC = -6,
+} HantekerStatus;
+
+// Opaque handle of an open device.
+typedef struct HantekerDevice HantekerDevice;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Opens the first Hantek 2D42 found, `timeout_ms` applies to each USB transfer. On success the
+// handle is stored in `device`, to be released with [`hanteker_close`].
+enum HantekerStatus hanteker_open(uint32_t timeout_ms, struct HantekerDevice **device);
+
+// Releases the device, NULL is ignored.
+void hanteker_close(struct HantekerDevice *device);
+
+// Message of the last failure on the calling thread, NULL if nothing failed yet. Valid until
+// the next failing call on the same thread.
+const char *hanteker_last_error(void);
+
+// `function` is one of `Scope`, `AWG` or `DMM`.
+enum HantekerStatus hanteker_set_device_function(struct HantekerDevice *device,
+                                                 const char *function);
+
+enum HantekerStatus hanteker_start(struct HantekerDevice *device);
+
+enum HantekerStatus hanteker_stop(struct HantekerDevice *device);
+
+enum HantekerStatus hanteker_set_channel_enabled(struct HantekerDevice *device,
+                                                 size_t channel_no,
+                                                 bool enabled);
+
+// `coupling` is one of `AC`, `DC` or `GND`.
+enum HantekerStatus hanteker_set_channel_coupling(struct HantekerDevice *device,
+                                                  size_t channel_no,
+                                                  const char *coupling);
+
+// `probe` is one of `X1`, `X10`, `X100` or `X1000`.
+enum HantekerStatus hanteker_set_channel_probe(struct HantekerDevice *device,
+                                               size_t channel_no,
+                                               const char *probe);
+
+// `scale` is the name of the volts per division, e.g. `mv100` or `v1`.
+enum HantekerStatus hanteker_set_channel_scale(struct HantekerDevice *device,
+                                               size_t channel_no,
+                                               const char *scale);
+
+// Offset in volts, needs the channel's scale to be set first.
+enum HantekerStatus hanteker_set_channel_offset(struct HantekerDevice *device,
+                                                size_t channel_no,
+                                                float offset);
+
+enum HantekerStatus hanteker_set_channel_bandwidth_limit(struct HantekerDevice *device,
+                                                         size_t channel_no,
+                                                         bool enabled);
+
+// `time_scale` is the name of the time per division, e.g. `us100` or `ms1`.
+enum HantekerStatus hanteker_set_time_scale(struct HantekerDevice *device, const char *time_scale);
+
+// Needs the time scale to be set first.
+enum HantekerStatus hanteker_set_time_offset(struct HantekerDevice *device, float time_offset);
+
+enum HantekerStatus hanteker_set_trigger_source(struct HantekerDevice *device, size_t channel_no);
+
+// `slope` is one of `Rising`, `Falling` or `Both`.
+enum HantekerStatus hanteker_set_trigger_slope(struct HantekerDevice *device, const char *slope);
+
+// `mode` is one of `Auto`, `Normal` or `Single`.
+enum HantekerStatus hanteker_set_trigger_mode(struct HantekerDevice *device, const char *mode);
+
+// Level in volts, needs the scale of the trigger source channel to be set first.
+enum HantekerStatus hanteker_set_trigger_level(struct HantekerDevice *device, float level);
+
+// Captures `num_samples` samples of each of the `num_channels` channels into `buffer`, at
+// least 64. Samples are signed bytes, interleaved in ascending order of the channel numbers,
+// with duplicate channels captured once. The number of bytes written is stored in `written`,
+// which may be NULL.
+enum HantekerStatus hanteker_capture(struct HantekerDevice *device,
+                                     const size_t *channels,
+                                     size_t num_channels,
+                                     size_t num_samples,
+                                     uint8_t *buffer,
+                                     size_t buffer_len,
+                                     size_t *written);
+
+// `awg_type` is one of `Square`, `Ramp`, `Sin`, `Trap` or `Arb1` to `Arb4`.
+enum HantekerStatus hanteker_set_awg_type(struct HantekerDevice *device, const char *awg_type);
+
+// Frequency in Hz.
+enum HantekerStatus hanteker_set_awg_frequency(struct HantekerDevice *device, float frequency);
+
+// Amplitude in volts.
+enum HantekerStatus hanteker_set_awg_amplitude(struct HantekerDevice *device, float amplitude);
+
+// Offset in volts.
+enum HantekerStatus hanteker_set_awg_offset(struct HantekerDevice *device, float offset);
+
+// Duty cycle of the square waveform, in percent.
+enum HantekerStatus hanteker_set_awg_duty_square(struct HantekerDevice *device, float duty);
+
+// Duty cycle of the ramp waveform, in percent.
+enum HantekerStatus hanteker_set_awg_duty_ramp(struct HantekerDevice *device, float duty);
+
+// Fractions of the period the trapezoid waveform spends high, low and rising.
+enum HantekerStatus hanteker_set_awg_duty_trap(struct HantekerDevice *device,
+                                               float high,
+                                               float low,
+                                               float rise);
+
+enum HantekerStatus hanteker_awg_start(struct HantekerDevice *device);
+
+enum HantekerStatus hanteker_awg_stop(struct HantekerDevice *device);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* HANTEKER_H */
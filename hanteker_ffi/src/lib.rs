@@ -0,0 +1,500 @@
+//! C API over [`hanteker_lib`], for measurement software written in C or C++. The header is
+//! generated into `include/hanteker.h` on build.
+//!
+//! Every function but [`hanteker_close`] and [`hanteker_last_error`] returns a
+//! [`HantekerStatus`], on anything other than `HANTEKER_STATUS_OK` the message of the failure is
+//! available from [`hanteker_last_error`] on the same thread.
+//!
+//! Enum values, e.g. scales and couplings, are passed by their names as printed by the CLI's
+//! `list` subcommand, e.g. `"v1"`, `"us100"` or `"DC"`.
+//!
+//! # Safety
+//!
+//! A device handle must come from [`hanteker_open`], must not be used after being closed and
+//! must not be used from more than one thread at a time. Strings must be nul terminated, buffers
+//! must be valid for the length given along with them.
+
+#![allow(clippy::missing_safety_doc)]
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use hanteker_lib::device::cfg::{
+    AwgType, Coupling, DeviceFunction, Probe, Scale, TimeScale, TriggerMode, TriggerSlope,
+};
+use hanteker_lib::models::hantek2d42::{Hantek2D42, Hantek2D42Error};
+use hanteker_lib::shared::OwnedHantek2D42;
+use libusb::Context;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HantekerStatus {
+    Ok = 0,
+    /// A pointer argument was NULL.
+    NullPointer = -1,
+    /// An argument was out of range, or a name was not one of the accepted ones.
+    InvalidArgument = -2,
+    /// Communication with the device failed.
+    Usb = -3,
+    /// The value needs a calibration (adjustment) the device hasn't reported yet, e.g. setting
+    /// the channel offset in volts before the channel's scale.
+    Adjustment = -4,
+    /// The buffer given for a capture is too small for the samples asked for.
+    BufferTooSmall = -5,
+    /// A bug in the library, the device should be closed.
+    Panic = -6,
+}
+
+/// Opaque handle of an open device.
+pub struct HantekerDevice {
+    hantek: OwnedHantek2D42,
+}
+
+enum FfiError {
+    NullPointer,
+    InvalidArgument(String),
+    BufferTooSmall { needed: usize, given: usize },
+    Libusb(libusb::Error),
+    Device(Hantek2D42Error),
+}
+
+impl From<Hantek2D42Error> for FfiError {
+    fn from(e: Hantek2D42Error) -> Self {
+        FfiError::Device(e)
+    }
+}
+
+impl FfiError {
+    fn status(&self) -> HantekerStatus {
+        match self {
+            FfiError::NullPointer => HantekerStatus::NullPointer,
+            FfiError::InvalidArgument(_) => HantekerStatus::InvalidArgument,
+            FfiError::BufferTooSmall { .. } => HantekerStatus::BufferTooSmall,
+            FfiError::Libusb(_) => HantekerStatus::Usb,
+            FfiError::Device(Hantek2D42Error::HantekUsbError { .. }) => HantekerStatus::Usb,
+            FfiError::Device(Hantek2D42Error::IncompleteCapture { .. }) => HantekerStatus::Usb,
+            FfiError::Device(Hantek2D42Error::InvalidArgument { .. }) => {
+                HantekerStatus::InvalidArgument
+            }
+            FfiError::Device(_) => HantekerStatus::Adjustment,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            FfiError::NullPointer => "null pointer argument".to_string(),
+            FfiError::InvalidArgument(message) => message.clone(),
+            FfiError::BufferTooSmall { needed, given } => {
+                format!("buffer too small, needed={} given={}", needed, given)
+            }
+            FfiError::Libusb(e) => format!("could not initialize libusb: {}", e),
+            FfiError::Device(e) => with_causes(e),
+        }
+    }
+}
+
+/// Message of the error followed by its sources.
+fn with_causes(e: &dyn std::error::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = e.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    // Interior nul bytes can't come from our own messages, but better safe than truncated.
+    let message = CString::new(message.replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|it| *it.borrow_mut() = Some(message));
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    match payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+    {
+        Some(message) => format!("panic: {}", message),
+        None => "panic".to_string(),
+    }
+}
+
+/// Runs the call, turning errors and panics into a status, the message goes to the last error.
+fn guard(call: impl FnOnce() -> Result<(), FfiError>) -> HantekerStatus {
+    match panic::catch_unwind(AssertUnwindSafe(call)) {
+        Ok(Ok(())) => HantekerStatus::Ok,
+        Ok(Err(e)) => {
+            set_last_error(e.message());
+            e.status()
+        }
+        Err(payload) => {
+            set_last_error(panic_message(payload.as_ref()));
+            HantekerStatus::Panic
+        }
+    }
+}
+
+/// Same as [`guard`] on the device of the handle.
+unsafe fn with_device(
+    device: *mut HantekerDevice,
+    call: impl for<'c> FnOnce(&mut Hantek2D42<'c>) -> Result<(), FfiError>,
+) -> HantekerStatus {
+    guard(|| match device.as_mut() {
+        Some(device) => device.hantek.with(call),
+        None => Err(FfiError::NullPointer),
+    })
+}
+
+unsafe fn parse<T: FromStr>(what: &str, name: *const c_char) -> Result<T, FfiError> {
+    if name.is_null() {
+        return Err(FfiError::NullPointer);
+    }
+    let name = CStr::from_ptr(name)
+        .to_str()
+        .map_err(|_| FfiError::InvalidArgument(format!("{} is not valid UTF-8", what)))?;
+    T::from_str(name).map_err(|_| FfiError::InvalidArgument(format!("invalid {}: {}", what, name)))
+}
+
+// ================================================================= DEVICE
+
+/// Opens the first Hantek 2D42 found, `timeout_ms` applies to each USB transfer. On success the
+/// handle is stored in `device`, to be released with [`hanteker_close`].
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_open(
+    timeout_ms: u32,
+    device: *mut *mut HantekerDevice,
+) -> HantekerStatus {
+    guard(|| {
+        if device.is_null() {
+            return Err(FfiError::NullPointer);
+        }
+        let context = Context::new().map_err(FfiError::Libusb)?;
+        let hantek = OwnedHantek2D42::open(context, Duration::from_millis(timeout_ms.into()))?;
+        *device = Box::into_raw(Box::new(HantekerDevice { hantek }));
+        Ok(())
+    })
+}
+
+/// Releases the device, NULL is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_close(device: *mut HantekerDevice) {
+    if !device.is_null() {
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(device))));
+    }
+}
+
+/// Message of the last failure on the calling thread, NULL if nothing failed yet. Valid until
+/// the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn hanteker_last_error() -> *const c_char {
+    LAST_ERROR.with(|it| match it.borrow().as_ref() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// `function` is one of `Scope`, `AWG` or `DMM`.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_device_function(
+    device: *mut HantekerDevice,
+    function: *const c_char,
+) -> HantekerStatus {
+    with_device(device, |hantek| {
+        let function: DeviceFunction = parse("device function", function)?;
+        Ok(hantek.set_device_function(function)?)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_start(device: *mut HantekerDevice) -> HantekerStatus {
+    with_device(device, |hantek| Ok(hantek.start()?))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_stop(device: *mut HantekerDevice) -> HantekerStatus {
+    with_device(device, |hantek| Ok(hantek.stop()?))
+}
+
+// ================================================================ CHANNEL
+
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_channel_enabled(
+    device: *mut HantekerDevice,
+    channel_no: usize,
+    enabled: bool,
+) -> HantekerStatus {
+    with_device(device, |hantek| {
+        if enabled {
+            Ok(hantek.enable_channel(channel_no)?)
+        } else {
+            Ok(hantek.disable_channel(channel_no)?)
+        }
+    })
+}
+
+/// `coupling` is one of `AC`, `DC` or `GND`.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_channel_coupling(
+    device: *mut HantekerDevice,
+    channel_no: usize,
+    coupling: *const c_char,
+) -> HantekerStatus {
+    with_device(device, |hantek| {
+        let coupling: Coupling = parse("coupling", coupling)?;
+        Ok(hantek.set_channel_coupling(channel_no, coupling)?)
+    })
+}
+
+/// `probe` is one of `X1`, `X10`, `X100` or `X1000`.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_channel_probe(
+    device: *mut HantekerDevice,
+    channel_no: usize,
+    probe: *const c_char,
+) -> HantekerStatus {
+    with_device(device, |hantek| {
+        let probe: Probe = parse("probe", probe)?;
+        Ok(hantek.set_channel_probe(channel_no, probe)?)
+    })
+}
+
+/// `scale` is the name of the volts per division, e.g. `mv100` or `v1`.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_channel_scale(
+    device: *mut HantekerDevice,
+    channel_no: usize,
+    scale: *const c_char,
+) -> HantekerStatus {
+    with_device(device, |hantek| {
+        let scale: Scale = parse("scale", scale)?;
+        Ok(hantek.set_channel_scale(channel_no, scale)?)
+    })
+}
+
+/// Offset in volts, needs the channel's scale to be set first.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_channel_offset(
+    device: *mut HantekerDevice,
+    channel_no: usize,
+    offset: f32,
+) -> HantekerStatus {
+    with_device(device, |hantek| {
+        Ok(hantek.set_channel_offset_with_auto_adjustment(channel_no, offset)?)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_channel_bandwidth_limit(
+    device: *mut HantekerDevice,
+    channel_no: usize,
+    enabled: bool,
+) -> HantekerStatus {
+    with_device(device, |hantek| {
+        if enabled {
+            Ok(hantek.channel_enable_bandwidth_limit(channel_no)?)
+        } else {
+            Ok(hantek.channel_disable_bandwidth_limit(channel_no)?)
+        }
+    })
+}
+
+// ================================================================== SCOPE
+
+/// `time_scale` is the name of the time per division, e.g. `us100` or `ms1`.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_time_scale(
+    device: *mut HantekerDevice,
+    time_scale: *const c_char,
+) -> HantekerStatus {
+    with_device(device, |hantek| {
+        let time_scale: TimeScale = parse("time scale", time_scale)?;
+        Ok(hantek.set_time_scale(time_scale)?)
+    })
+}
+
+/// Needs the time scale to be set first.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_time_offset(
+    device: *mut HantekerDevice,
+    time_offset: f32,
+) -> HantekerStatus {
+    with_device(device, |hantek| {
+        Ok(hantek.set_time_offset_with_auto_adjustment(time_offset)?)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_trigger_source(
+    device: *mut HantekerDevice,
+    channel_no: usize,
+) -> HantekerStatus {
+    with_device(device, |hantek| Ok(hantek.set_trigger_source(channel_no)?))
+}
+
+/// `slope` is one of `Rising`, `Falling` or `Both`.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_trigger_slope(
+    device: *mut HantekerDevice,
+    slope: *const c_char,
+) -> HantekerStatus {
+    with_device(device, |hantek| {
+        let slope: TriggerSlope = parse("trigger slope", slope)?;
+        Ok(hantek.set_trigger_slope(slope)?)
+    })
+}
+
+/// `mode` is one of `Auto`, `Normal` or `Single`.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_trigger_mode(
+    device: *mut HantekerDevice,
+    mode: *const c_char,
+) -> HantekerStatus {
+    with_device(device, |hantek| {
+        let mode: TriggerMode = parse("trigger mode", mode)?;
+        Ok(hantek.set_trigger_mode(mode)?)
+    })
+}
+
+/// Level in volts, needs the scale of the trigger source channel to be set first.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_trigger_level(
+    device: *mut HantekerDevice,
+    level: f32,
+) -> HantekerStatus {
+    with_device(device, |hantek| {
+        Ok(hantek.set_trigger_level_with_auto_adjustment(level)?)
+    })
+}
+
+/// Captures `num_samples` samples of each of the `num_channels` channels into `buffer`, at
+/// least 64. Samples are signed bytes, interleaved in ascending order of the channel numbers,
+/// with duplicate channels captured once. The number of bytes written is stored in `written`,
+/// which may be NULL.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_capture(
+    device: *mut HantekerDevice,
+    channels: *const usize,
+    num_channels: usize,
+    num_samples: usize,
+    buffer: *mut u8,
+    buffer_len: usize,
+    written: *mut usize,
+) -> HantekerStatus {
+    with_device(device, |hantek| {
+        if channels.is_null() || buffer.is_null() {
+            return Err(FfiError::NullPointer);
+        }
+        let mut wanted = std::slice::from_raw_parts(channels, num_channels).to_vec();
+        wanted.sort_unstable();
+        wanted.dedup();
+        let needed = num_samples * wanted.len();
+        if buffer_len < needed {
+            return Err(FfiError::BufferTooSmall {
+                needed,
+                given: buffer_len,
+            });
+        }
+
+        let raw = hantek.capture(&wanted, num_samples)?;
+        std::slice::from_raw_parts_mut(buffer, raw.len()).copy_from_slice(&raw);
+        if !written.is_null() {
+            *written = raw.len();
+        }
+        Ok(())
+    })
+}
+
+// ==================================================================== AWG
+
+/// `awg_type` is one of `Square`, `Ramp`, `Sin`, `Trap` or `Arb1` to `Arb4`.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_awg_type(
+    device: *mut HantekerDevice,
+    awg_type: *const c_char,
+) -> HantekerStatus {
+    with_device(device, |hantek| {
+        let awg_type: AwgType = parse("awg type", awg_type)?;
+        Ok(hantek.set_awg_type(awg_type)?)
+    })
+}
+
+/// Frequency in Hz.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_awg_frequency(
+    device: *mut HantekerDevice,
+    frequency: f32,
+) -> HantekerStatus {
+    with_device(device, |hantek| Ok(hantek.set_awg_frequency(frequency)?))
+}
+
+/// Amplitude in volts.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_awg_amplitude(
+    device: *mut HantekerDevice,
+    amplitude: f32,
+) -> HantekerStatus {
+    with_device(device, |hantek| Ok(hantek.set_awg_amplitude(amplitude)?))
+}
+
+/// Offset in volts.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_awg_offset(
+    device: *mut HantekerDevice,
+    offset: f32,
+) -> HantekerStatus {
+    with_device(device, |hantek| Ok(hantek.set_awg_offset(offset)?))
+}
+
+/// Duty cycle of the square waveform, in percent.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_awg_duty_square(
+    device: *mut HantekerDevice,
+    duty: f32,
+) -> HantekerStatus {
+    with_device(device, |hantek| Ok(hantek.set_awg_duty_square(duty)?))
+}
+
+/// Duty cycle of the ramp waveform, in percent.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_awg_duty_ramp(
+    device: *mut HantekerDevice,
+    duty: f32,
+) -> HantekerStatus {
+    with_device(device, |hantek| Ok(hantek.set_awg_duty_ramp(duty)?))
+}
+
+/// Fractions of the period the trapezoid waveform spends high, low and rising.
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_set_awg_duty_trap(
+    device: *mut HantekerDevice,
+    high: f32,
+    low: f32,
+    rise: f32,
+) -> HantekerStatus {
+    with_device(device, |hantek| {
+        Ok(hantek.set_awg_duty_trap(high, low, rise)?)
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_awg_start(device: *mut HantekerDevice) -> HantekerStatus {
+    with_device(device, |hantek| Ok(hantek.awg_start()?))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn hanteker_awg_stop(device: *mut HantekerDevice) -> HantekerStatus {
+    with_device(device, |hantek| Ok(hantek.awg_stop()?))
+}
@@ -0,0 +1,227 @@
+//! The device lives on its own thread, the UI talks to it through [`Request`]s and gets the
+//! resulting config and captures back as commands.
+
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use std::time::Duration;
+
+use druid::{AppDelegate, Command, DelegateCtx, Env, ExtEventSink, Handled, Selector, Target};
+use hanteker_lib::capture::CaptureFrame;
+use hanteker_lib::device::cfg::{
+    AwgType, Coupling, DeviceFunction, HantekConfig, Probe, Scale, TimeScale, TriggerMode,
+    TriggerSlope,
+};
+use hanteker_lib::models::hantek2d42::{Hantek2D42, Hantek2D42Error};
+use log::{debug, error};
+
+use crate::state::AppState;
+
+/// Samples per channel in each capture fed to the waveform view.
+const CAPTURE_CHUNK: usize = 1000;
+
+const CONFIG_CHANGED: Selector<HantekConfig> = Selector::new("hanteker.config-changed");
+const FRAME_CAPTURED: Selector<Arc<CaptureFrame>> = Selector::new("hanteker.frame-captured");
+const CAPTURE_STOPPED: Selector = Selector::new("hanteker.capture-stopped");
+const STATUS: Selector<String> = Selector::new("hanteker.status");
+const CONFIG_CHANGES: Selector<Vec<String>> = Selector::new("hanteker.config-changes");
+
+/// Changes kept in the change log, older ones are dropped.
+const CHANGE_LOG_LEN: usize = 10;
+
+#[derive(Debug, Clone)]
+pub(crate) enum Setting {
+    DeviceFunction(DeviceFunction),
+    Running(bool),
+
+    ChannelEnabled(usize, bool),
+    ChannelScale(usize, Scale),
+    ChannelCoupling(usize, Coupling),
+    ChannelProbe(usize, Probe),
+    ChannelOffset(usize, u8),
+
+    TimeScale(TimeScale),
+    TriggerSource(usize),
+    TriggerSlope(TriggerSlope),
+    TriggerMode(TriggerMode),
+    TriggerLevel(u8),
+
+    AwgType(AwgType),
+    AwgFrequency(f32),
+    AwgAmplitude(f32),
+    AwgOffset(f32),
+    AwgRunning(bool),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Request {
+    Set(Setting),
+    StartCapture(Vec<usize>),
+    StopCapture,
+    Quit,
+}
+
+/// Owns the device until a [`Request::Quit`] comes in or the UI goes away, capturing
+/// continuously while asked to.
+pub(crate) fn run(requests: Receiver<Request>, sink: ExtEventSink, timeout: Duration) {
+    let context = match libusb::Context::new() {
+        Ok(context) => context,
+        Err(e) => {
+            report(&sink, format!("error creating usb context: {}", e));
+            return;
+        }
+    };
+    let mut hantek = match Hantek2D42::open(&context, timeout) {
+        Ok(hantek) => hantek,
+        Err(e) => {
+            report(&sink, format!("{:#}", anyhow::Error::new(e)));
+            return;
+        }
+    };
+    if let Err(e) = hantek.usb.claim() {
+        report(&sink, format!("{:#}", anyhow::Error::new(e)));
+        return;
+    }
+    // Sliders send a request for every step they pass, most of them repeating the last value.
+    hantek.set_diff_mode(true);
+    let mut reported = hantek.get_config().clone();
+    submit(&sink, CONFIG_CHANGED, reported.clone());
+
+    let mut capture: Option<Vec<usize>> = None;
+    loop {
+        let request = if capture.is_some() {
+            match requests.try_recv() {
+                Ok(request) => Some(request),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        } else {
+            match requests.recv() {
+                Ok(request) => Some(request),
+                Err(_) => break,
+            }
+        };
+
+        match request {
+            Some(Request::Set(setting)) => {
+                let result = apply(&mut hantek, setting);
+                // Sent either way, so the panels fall back to the actual value on failure.
+                let config = hantek.get_config().clone();
+                let changes: Vec<String> = reported
+                    .diff(&config)
+                    .iter()
+                    .map(|it| it.to_string())
+                    .collect();
+                if !changes.is_empty() {
+                    submit(&sink, CONFIG_CHANGES, changes);
+                }
+                submit(&sink, CONFIG_CHANGED, config.clone());
+                reported = config;
+                match result {
+                    Ok(()) => submit(&sink, STATUS, String::new()),
+                    Err(e) => report(&sink, format!("{:#}", anyhow::Error::new(e))),
+                }
+            }
+            Some(Request::StartCapture(channels)) => capture = Some(channels),
+            Some(Request::StopCapture) => capture = None,
+            Some(Request::Quit) => break,
+            None => {}
+        }
+
+        if let Some(channels) = &capture {
+            match hantek.capture_frame(channels, CAPTURE_CHUNK) {
+                Ok(frame) => submit(&sink, FRAME_CAPTURED, Arc::new(frame)),
+                Err(e) => {
+                    capture = None;
+                    submit(&sink, CAPTURE_STOPPED, ());
+                    report(&sink, format!("{:#}", anyhow::Error::new(e)));
+                }
+            }
+        }
+    }
+
+    if let Err(e) = hantek.usb.release() {
+        error!("error: {:#}", anyhow::Error::new(e));
+    }
+}
+
+fn apply(hantek: &mut Hantek2D42, setting: Setting) -> Result<(), Hantek2D42Error> {
+    match setting {
+        Setting::DeviceFunction(function) => hantek.set_device_function(function),
+        Setting::Running(true) => hantek.start(),
+        Setting::Running(false) => hantek.stop(),
+
+        Setting::ChannelEnabled(channel_no, true) => hantek.enable_channel(channel_no),
+        Setting::ChannelEnabled(channel_no, false) => hantek.disable_channel(channel_no),
+        Setting::ChannelScale(channel_no, scale) => hantek.set_channel_scale(channel_no, scale),
+        Setting::ChannelCoupling(channel_no, coupling) => {
+            hantek.set_channel_coupling(channel_no, coupling)
+        }
+        Setting::ChannelProbe(channel_no, probe) => hantek.set_channel_probe(channel_no, probe),
+        Setting::ChannelOffset(channel_no, offset) => hantek.set_channel_offset(channel_no, offset),
+
+        Setting::TimeScale(time_scale) => hantek.set_time_scale(time_scale),
+        Setting::TriggerSource(channel_no) => hantek.set_trigger_source(channel_no),
+        Setting::TriggerSlope(slope) => hantek.set_trigger_slope(slope),
+        Setting::TriggerMode(mode) => hantek.set_trigger_mode(mode),
+        Setting::TriggerLevel(level) => hantek.set_trigger_level(level),
+
+        Setting::AwgType(awg_type) => hantek.set_awg_type(awg_type),
+        Setting::AwgFrequency(frequency) => hantek.set_awg_frequency(frequency),
+        Setting::AwgAmplitude(amplitude) => hantek.set_awg_amplitude(amplitude),
+        Setting::AwgOffset(offset) => hantek.set_awg_offset(offset),
+        Setting::AwgRunning(true) => hantek.awg_start(),
+        Setting::AwgRunning(false) => hantek.awg_stop(),
+    }
+}
+
+fn submit<T: std::any::Any + Send>(sink: &ExtEventSink, selector: Selector<T>, payload: T) {
+    if sink
+        .submit_command(selector, payload, Target::Auto)
+        .is_err()
+    {
+        // The window is gone, nothing left to update.
+        debug!("dropped update, ui is closed");
+    }
+}
+
+fn report(sink: &ExtEventSink, status: String) {
+    error!("error: {}", status);
+    submit(sink, STATUS, status);
+}
+
+/// Applies what the device worker reports to the app state.
+pub(crate) struct Delegate;
+
+impl AppDelegate<AppState> for Delegate {
+    fn command(
+        &mut self,
+        _ctx: &mut DelegateCtx,
+        _target: Target,
+        cmd: &Command,
+        data: &mut AppState,
+        _env: &Env,
+    ) -> Handled {
+        if let Some(config) = cmd.get(CONFIG_CHANGED) {
+            data.config = config.clone();
+            Handled::Yes
+        } else if let Some(changes) = cmd.get(CONFIG_CHANGES) {
+            let mut log = data.changes.as_ref().clone();
+            log.extend(changes.iter().cloned());
+            let excess = log.len().saturating_sub(CHANGE_LOG_LEN);
+            log.drain(..excess);
+            data.changes = Arc::new(log);
+            Handled::Yes
+        } else if let Some(frame) = cmd.get(FRAME_CAPTURED) {
+            data.frame = Some(frame.clone());
+            Handled::Yes
+        } else if cmd.is(CAPTURE_STOPPED) {
+            data.capturing = false;
+            Handled::Yes
+        } else if let Some(status) = cmd.get(STATUS) {
+            data.status = status.clone();
+            Handled::Yes
+        } else {
+            Handled::No
+        }
+    }
+}
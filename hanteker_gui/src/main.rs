@@ -0,0 +1,45 @@
+#![cfg_attr(not(debug_assertions), deny(warnings))]
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use druid::{AppLauncher, WindowDesc};
+use hanteker_lib::device::cfg::HantekConfig;
+use log::error;
+
+use crate::device::{Delegate, Request};
+use crate::state::AppState;
+
+mod device;
+mod panels;
+mod state;
+mod waveform;
+
+const NUM_CHANNELS: usize = 2;
+const USB_TIMEOUT: Duration = Duration::from_millis(1000);
+
+fn main() -> anyhow::Result<()> {
+    pretty_env_logger::init();
+
+    let (requests, receiver) = mpsc::channel();
+    let quit = requests.clone();
+
+    let window = WindowDesc::new(move || panels::root(requests))
+        .title("Hanteker")
+        .window_size((1200.0, 720.0));
+    let launcher = AppLauncher::with_window(window).delegate(Delegate);
+
+    let sink = launcher.get_external_handle();
+    let worker = thread::spawn(move || device::run(receiver, sink, USB_TIMEOUT));
+
+    let launched = launcher.launch(AppState::new(HantekConfig::new(NUM_CHANNELS)));
+
+    // Give the device back before exiting, the worker might be in the middle of a capture.
+    let _ = quit.send(Request::Quit);
+    if worker.join().is_err() {
+        error!("device worker panicked");
+    }
+
+    launched.map_err(|e| anyhow::anyhow!("error running gui: {}", e))
+}
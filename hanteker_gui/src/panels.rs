@@ -0,0 +1,425 @@
+use std::fmt::Display;
+use std::sync::mpsc::Sender;
+
+use druid::lens::Map;
+use druid::text::format::ParseFormatter;
+use druid::widget::{
+    Button, Checkbox, Controller, CrossAxisAlignment, Flex, Label, RadioGroup, Slider, TextBox,
+};
+use druid::{Color, Data, Env, Event, EventCtx, Lens, LensExt, Widget, WidgetExt};
+use hanteker_lib::device::cfg::{
+    AwgType, ChannelConfig, Coupling, DeviceFunction, HantekConfig, Probe, RunningStatus, Scale,
+    TimeScale, TriggerMode, TriggerSlope,
+};
+
+use crate::device::{Request, Setting};
+use crate::state::AppState;
+use crate::waveform::Waveform;
+
+const SPACING: f64 = 8.0;
+const PANEL_WIDTH: f64 = 220.0;
+const STATUS_COLOR: Color = Color::rgb8(0xe6, 0x4a, 0x2e);
+
+/// Device offset and trigger level take 0..=200 for the 8 vertical divisions.
+const RAW_MAX: f64 = 200.0;
+const RAW_CENTER: f64 = 100.0;
+
+pub(crate) fn root(requests: Sender<Request>) -> impl Widget<AppState> {
+    let settings = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(device_panel(&requests))
+        .with_spacer(SPACING)
+        .with_child(channel_panel(1, &requests))
+        .with_spacer(SPACING)
+        .with_child(channel_panel(2, &requests))
+        .lens(AppState::config);
+
+    let side = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(trigger_panel(&requests).lens(AppState::config))
+        .with_spacer(SPACING)
+        .with_child(awg_panel(&requests).lens(AppState::config))
+        .with_spacer(SPACING)
+        .with_child(change_log());
+
+    let scope = Flex::column()
+        .with_flex_child(Waveform.lens(AppState::frame).expand(), 1.0)
+        .with_spacer(SPACING)
+        .with_child(capture_bar(&requests));
+
+    Flex::row()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(settings.fix_width(PANEL_WIDTH))
+        .with_spacer(SPACING)
+        .with_flex_child(scope, 1.0)
+        .with_spacer(SPACING)
+        .with_child(side.fix_width(PANEL_WIDTH))
+        .padding(SPACING)
+}
+
+fn device_panel(requests: &Sender<Request>) -> impl Widget<HantekConfig> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(title("Device"))
+        .with_child(
+            radio(DeviceFunction::my_options())
+                .controller(SendOnEdit::new(requests, |it: &Option<DeviceFunction>| {
+                    it.clone().map(Setting::DeviceFunction)
+                }))
+                .lens(HantekConfig::device_function),
+        )
+        .with_child(
+            Checkbox::new("Running")
+                .controller(SendOnEdit::new(requests, |it: &bool| {
+                    Some(Setting::Running(*it))
+                }))
+                .lens(flag(
+                    |it: &HantekConfig| &it.running_status,
+                    |it: &mut HantekConfig| &mut it.running_status,
+                )),
+        )
+}
+
+fn channel_panel(channel_no: usize, requests: &Sender<Request>) -> impl Widget<HantekConfig> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(title(&format!("CH{}", channel_no)))
+        .with_child(
+            Checkbox::new("Enabled")
+                .controller(SendOnEdit::new(requests, move |it: &bool| {
+                    Some(Setting::ChannelEnabled(channel_no, *it))
+                }))
+                .lens(Map::new(
+                    move |it: &HantekConfig| {
+                        it.channel(channel_no).and_then(|it| it.enabled) == Some(true)
+                    },
+                    move |it: &mut HantekConfig, enabled: bool| {
+                        if let Some(channel) = it.channel_mut(channel_no) {
+                            channel.enabled = Some(enabled);
+                        }
+                    },
+                )),
+        )
+        .with_child(
+            stepper(Scale::my_iter().collect(), "/div")
+                .controller(SendOnEdit::new(requests, move |it: &Option<Scale>| {
+                    it.clone()
+                        .map(|scale| Setting::ChannelScale(channel_no, scale))
+                }))
+                .lens(channel_field(
+                    channel_no,
+                    |it| &it.scale,
+                    |it| &mut it.scale,
+                )),
+        )
+        .with_child(
+            radio(Coupling::my_options())
+                .controller(SendOnEdit::new(requests, move |it: &Option<Coupling>| {
+                    it.clone()
+                        .map(|coupling| Setting::ChannelCoupling(channel_no, coupling))
+                }))
+                .lens(channel_field(
+                    channel_no,
+                    |it| &it.coupling,
+                    |it| &mut it.coupling,
+                )),
+        )
+        .with_child(
+            radio(Probe::my_options())
+                .controller(SendOnEdit::new(requests, move |it: &Option<Probe>| {
+                    it.clone()
+                        .map(|probe| Setting::ChannelProbe(channel_no, probe))
+                }))
+                .lens(channel_field(
+                    channel_no,
+                    |it| &it.probe,
+                    |it| &mut it.probe,
+                )),
+        )
+        .with_child(Label::new("Offset"))
+        .with_child(
+            Slider::new()
+                .with_range(0.0, RAW_MAX)
+                .controller(SendOnEdit::new(requests, move |it: &f64| {
+                    Some(Setting::ChannelOffset(channel_no, *it as u8))
+                }))
+                .lens(raw_level(channel_field(
+                    channel_no,
+                    |it| &it.offset,
+                    |it| &mut it.offset,
+                ))),
+        )
+}
+
+fn trigger_panel(requests: &Sender<Request>) -> impl Widget<HantekConfig> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(title("Trigger"))
+        .with_child(
+            stepper(TimeScale::my_iter().collect(), "/div")
+                .controller(SendOnEdit::new(requests, |it: &Option<TimeScale>| {
+                    it.clone().map(Setting::TimeScale)
+                }))
+                .lens(HantekConfig::time_scale),
+        )
+        .with_child(
+            RadioGroup::new(vec![("CH1", Some(1_usize)), ("CH2", Some(2_usize))])
+                .controller(SendOnEdit::new(requests, |it: &Option<usize>| {
+                    it.map(Setting::TriggerSource)
+                }))
+                .lens(HantekConfig::trigger_source_channel),
+        )
+        .with_child(
+            radio(TriggerSlope::my_options())
+                .controller(SendOnEdit::new(requests, |it: &Option<TriggerSlope>| {
+                    it.clone().map(Setting::TriggerSlope)
+                }))
+                .lens(HantekConfig::trigger_slope),
+        )
+        .with_child(
+            radio(TriggerMode::my_options())
+                .controller(SendOnEdit::new(requests, |it: &Option<TriggerMode>| {
+                    it.clone().map(Setting::TriggerMode)
+                }))
+                .lens(HantekConfig::trigger_mode),
+        )
+        .with_child(Label::new("Level"))
+        .with_child(
+            Slider::new()
+                .with_range(0.0, RAW_MAX)
+                .controller(SendOnEdit::new(requests, |it: &f64| {
+                    Some(Setting::TriggerLevel(*it as u8))
+                }))
+                .lens(raw_level(HantekConfig::trigger_level)),
+        )
+}
+
+fn awg_panel(requests: &Sender<Request>) -> impl Widget<HantekConfig> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(title("AWG"))
+        .with_child(
+            radio(AwgType::my_options())
+                .controller(SendOnEdit::new(requests, |it: &Option<AwgType>| {
+                    it.clone().map(Setting::AwgType)
+                }))
+                .lens(HantekConfig::awg_type),
+        )
+        .with_child(number(
+            "Frequency (Hz)",
+            HantekConfig::awg_frequency,
+            requests,
+            Setting::AwgFrequency,
+        ))
+        .with_child(number(
+            "Amplitude (V)",
+            HantekConfig::awg_amplitude,
+            requests,
+            Setting::AwgAmplitude,
+        ))
+        .with_child(number(
+            "Offset (V)",
+            HantekConfig::awg_offset,
+            requests,
+            Setting::AwgOffset,
+        ))
+        .with_child(
+            Checkbox::new("Output")
+                .controller(SendOnEdit::new(requests, |it: &bool| {
+                    Some(Setting::AwgRunning(*it))
+                }))
+                .lens(flag(
+                    |it: &HantekConfig| &it.awg_running_status,
+                    |it: &mut HantekConfig| &mut it.awg_running_status,
+                )),
+        )
+}
+
+fn capture_bar(requests: &Sender<Request>) -> impl Widget<AppState> {
+    let requests = requests.clone();
+    let button = Button::new(|data: &AppState, _env: &Env| {
+        if data.capturing {
+            "Stop capture".to_string()
+        } else {
+            "Start capture".to_string()
+        }
+    })
+    .on_click(move |_ctx, data: &mut AppState, _env| {
+        let request = if data.capturing {
+            Request::StopCapture
+        } else {
+            let channels = data.enabled_channels();
+            if channels.is_empty() {
+                data.status = "enable a channel to capture".to_string();
+                return;
+            }
+            Request::StartCapture(channels)
+        };
+        data.capturing = !data.capturing;
+        let _ = requests.send(request);
+    });
+
+    Flex::row()
+        .with_child(button)
+        .with_spacer(SPACING)
+        .with_flex_child(
+            Label::new(|data: &AppState, _env: &Env| data.status.clone())
+                .with_text_color(STATUS_COLOR),
+            1.0,
+        )
+}
+
+/// Changes to the config the device went through, latest last.
+fn change_log() -> impl Widget<AppState> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(title("Changes"))
+        .with_child(
+            Label::new(|data: &AppState, _env: &Env| data.changes.join("\n")).with_text_size(11.0),
+        )
+}
+
+fn title<T: Data>(text: &str) -> impl Widget<T> {
+    Label::new(text.to_string()).with_text_size(16.0)
+}
+
+fn radio<T: Data + PartialEq>(options: Vec<(String, T)>) -> impl Widget<Option<T>> {
+    RadioGroup::new(
+        options
+            .into_iter()
+            .map(|(name, it)| (name, Some(it)))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// Steps through values too many for a radio group, in declaration order.
+fn stepper<T: Data + PartialEq + Display>(options: Vec<T>, unit: &str) -> impl Widget<Option<T>> {
+    let unit = unit.to_string();
+    let previous = options.clone();
+    let next = options;
+
+    Flex::row()
+        .with_child(
+            Button::new("<").on_click(move |_ctx, data: &mut Option<T>, _env| {
+                *data = step(&previous, data, false);
+            }),
+        )
+        .with_spacer(SPACING)
+        .with_child(
+            Label::new(move |data: &Option<T>, _env: &Env| match data {
+                Some(it) => format!("{}{}", it, unit),
+                None => "?".to_string(),
+            })
+            .fix_width(80.0),
+        )
+        .with_child(
+            Button::new(">").on_click(move |_ctx, data: &mut Option<T>, _env| {
+                *data = step(&next, data, true);
+            }),
+        )
+}
+
+/// Neighbour of `current` in `options`, staying put at either end. Starts from the middle when
+/// the current value is unknown.
+fn step<T: Clone + PartialEq>(options: &[T], current: &Option<T>, up: bool) -> Option<T> {
+    let idx = match current
+        .as_ref()
+        .and_then(|it| options.iter().position(|o| o == it))
+    {
+        Some(idx) if up => (idx + 1).min(options.len() - 1),
+        Some(idx) => idx.saturating_sub(1),
+        None => options.len() / 2,
+    };
+    options.get(idx).cloned()
+}
+
+fn number(
+    name: &str,
+    lens: impl Lens<HantekConfig, Option<f32>> + 'static,
+    requests: &Sender<Request>,
+    setting: fn(f32) -> Setting,
+) -> impl Widget<HantekConfig> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(Label::new(name.to_string()))
+        .with_child(
+            TextBox::new()
+                .with_formatter(ParseFormatter::new())
+                .lens(Map::new(
+                    |it: &Option<f32>| it.unwrap_or_default() as f64,
+                    |it: &mut Option<f32>, value: f64| *it = Some(value as f32),
+                ))
+                .controller(SendOnEdit::new(requests, move |it: &Option<f32>| {
+                    it.map(setting)
+                }))
+                .lens(lens),
+        )
+}
+
+/// Running status as a checkbox, unknown reads as stopped.
+fn flag(
+    get: fn(&HantekConfig) -> &Option<RunningStatus>,
+    get_mut: fn(&mut HantekConfig) -> &mut Option<RunningStatus>,
+) -> impl Lens<HantekConfig, bool> {
+    Map::new(
+        move |it: &HantekConfig| *get(it) == Some(RunningStatus::Start),
+        move |it: &mut HantekConfig, running: bool| {
+            *get_mut(it) = Some(if running {
+                RunningStatus::Start
+            } else {
+                RunningStatus::Stop
+            });
+        },
+    )
+}
+
+fn channel_field<T: Clone + 'static>(
+    channel_no: usize,
+    get: fn(&ChannelConfig) -> &Option<T>,
+    get_mut: fn(&mut ChannelConfig) -> &mut Option<T>,
+) -> impl Lens<HantekConfig, Option<T>> {
+    Map::new(
+        move |it: &HantekConfig| it.channel(channel_no).and_then(|it| get(it).clone()),
+        move |it: &mut HantekConfig, value: Option<T>| {
+            if let Some(channel) = it.channel_mut(channel_no) {
+                *get_mut(channel) = value;
+            }
+        },
+    )
+}
+
+/// Raw device level as a slider value, unknown reads as the center of the screen.
+fn raw_level(lens: impl Lens<HantekConfig, Option<f32>> + 'static) -> impl Lens<HantekConfig, f64> {
+    lens.map(
+        |it: &Option<f32>| it.map_or(RAW_CENTER, |raw| raw as f64),
+        |it: &mut Option<f32>, value: f64| *it = Some(value.round() as f32),
+    )
+}
+
+/// Turns edits made through the wrapped widget into device requests. Updates coming from the
+/// device itself don't go through `event`, so they aren't echoed back.
+struct SendOnEdit<F> {
+    requests: Sender<Request>,
+    to_setting: F,
+}
+
+impl<F> SendOnEdit<F> {
+    fn new(requests: &Sender<Request>, to_setting: F) -> Self {
+        Self {
+            requests: requests.clone(),
+            to_setting,
+        }
+    }
+}
+
+impl<T: Data, W: Widget<T>, F: Fn(&T) -> Option<Setting>> Controller<T, W> for SendOnEdit<F> {
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        let before = data.clone();
+        child.event(ctx, event, data, env);
+        if !before.same(data) {
+            if let Some(setting) = (self.to_setting)(data) {
+                let _ = self.requests.send(Request::Set(setting));
+            }
+        }
+    }
+}
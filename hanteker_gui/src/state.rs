@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use druid::{Data, Lens};
+use hanteker_lib::capture::CaptureFrame;
+use hanteker_lib::device::cfg::HantekConfig;
+
+#[derive(Clone, Data, Lens)]
+pub(crate) struct AppState {
+    /// Settings as last reported by the device worker, edited in place by the panels.
+    pub(crate) config: HantekConfig,
+    /// Latest capture, shared with the worker that produced it.
+    pub(crate) frame: Option<Arc<CaptureFrame>>,
+    pub(crate) capturing: bool,
+    /// Last error from the device, empty if the last request went through.
+    pub(crate) status: String,
+    /// Latest changes to the config as reported by the device worker, oldest first.
+    pub(crate) changes: Arc<Vec<String>>,
+}
+
+impl AppState {
+    pub(crate) fn new(config: HantekConfig) -> Self {
+        Self {
+            config,
+            frame: None,
+            capturing: false,
+            status: String::new(),
+            changes: Arc::new(vec![]),
+        }
+    }
+
+    /// Channels enabled through the channel panels, in capture order.
+    pub(crate) fn enabled_channels(&self) -> Vec<usize> {
+        self.config.enabled_channels()
+    }
+}
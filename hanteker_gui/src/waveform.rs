@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use druid::kurbo::{BezPath, Line};
+use druid::widget::prelude::*;
+use druid::{Color, Point};
+use hanteker_lib::capture::{CaptureFrame, COUNTS_PER_DIVISION};
+
+const HORIZONTAL_DIVISIONS: usize = 10;
+const VERTICAL_DIVISIONS: usize = 8;
+
+const CHANNEL_COLORS: [Color; 2] = [Color::rgb8(0xe6, 0xd2, 0x2e), Color::rgb8(0x2e, 0xc4, 0xe6)];
+const GRID_COLOR: Color = Color::rgb8(0x30, 0x30, 0x30);
+const BACKGROUND_COLOR: Color = Color::BLACK;
+
+/// Scope screen drawing the latest capture, full height being the 8 vertical divisions of the
+/// device screen.
+pub(crate) struct Waveform;
+
+impl Widget<Option<Arc<CaptureFrame>>> for Waveform {
+    fn event(
+        &mut self,
+        _ctx: &mut EventCtx,
+        _event: &Event,
+        _data: &mut Option<Arc<CaptureFrame>>,
+        _env: &Env,
+    ) {
+    }
+
+    fn lifecycle(
+        &mut self,
+        _ctx: &mut LifeCycleCtx,
+        _event: &LifeCycle,
+        _data: &Option<Arc<CaptureFrame>>,
+        _env: &Env,
+    ) {
+    }
+
+    fn update(
+        &mut self,
+        ctx: &mut UpdateCtx,
+        old_data: &Option<Arc<CaptureFrame>>,
+        data: &Option<Arc<CaptureFrame>>,
+        _env: &Env,
+    ) {
+        if !old_data.same(data) {
+            ctx.request_paint();
+        }
+    }
+
+    fn layout(
+        &mut self,
+        _ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        _data: &Option<Arc<CaptureFrame>>,
+        _env: &Env,
+    ) -> Size {
+        bc.max()
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &Option<Arc<CaptureFrame>>, _env: &Env) {
+        let size = ctx.size();
+        ctx.fill(size.to_rect(), &BACKGROUND_COLOR);
+
+        for idx in 1..HORIZONTAL_DIVISIONS {
+            let x = size.width * idx as f64 / HORIZONTAL_DIVISIONS as f64;
+            ctx.stroke(Line::new((x, 0.0), (x, size.height)), &GRID_COLOR, 1.0);
+        }
+        for idx in 1..VERTICAL_DIVISIONS {
+            let y = size.height * idx as f64 / VERTICAL_DIVISIONS as f64;
+            ctx.stroke(Line::new((0.0, y), (size.width, y)), &GRID_COLOR, 1.0);
+        }
+
+        let frame = match data {
+            Some(frame) => frame,
+            None => return,
+        };
+        for channel_no in &frame.channels {
+            let samples = frame.channel_raw(*channel_no).unwrap_or_default();
+            if let Some(path) = trace(&samples, size) {
+                let color = &CHANNEL_COLORS[(channel_no - 1) % CHANNEL_COLORS.len()];
+                ctx.stroke(path, color, 1.5);
+            }
+        }
+    }
+}
+
+fn trace(samples: &[u8], size: Size) -> Option<BezPath> {
+    if samples.len() < 2 {
+        return None;
+    }
+
+    let half = VERTICAL_DIVISIONS as f64 / 2.0 * COUNTS_PER_DIVISION as f64;
+    let mut path = BezPath::new();
+    for (idx, sample) in samples.iter().enumerate() {
+        let counts = ((*sample as i8) as f64).clamp(-half, half);
+        let point = Point::new(
+            size.width * idx as f64 / (samples.len() - 1) as f64,
+            size.height * (half - counts) / (2.0 * half),
+        );
+        if idx == 0 {
+            path.move_to(point);
+        } else {
+            path.line_to(point);
+        }
+    }
+    Some(path)
+}
@@ -0,0 +1,122 @@
+//! Throughput of the data path a capture takes after leaving the device, on buffers of a few
+//! megabytes as continuous acquisition hands out: conversion to volts, de-interleaving of the
+//! channels, the spectrum and the filters. Run with `cargo bench -p hanteker_lib`.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+
+use hanteker_lib::capture::{raw_to_volts, CaptureFrame};
+use hanteker_lib::device::cfg::{Scale, TimeScale};
+use hanteker_lib::dsp::{fft, spectrum, Filter, FilterSpec, Window};
+
+/// Samples of each channel, a power of two so the spectrum takes them all.
+const NUM_SAMPLES: usize = 1 << 21;
+
+/// Sample rate at [`TimeScale::us100`], 25 samples a division.
+const SAMPLE_RATE: f32 = 250_000.0;
+
+/// Two channels of a sine and a ramp, interleaved, 4 MiB of raw samples.
+fn frame() -> CaptureFrame {
+    let raw: Vec<u8> = (0..NUM_SAMPLES * 2)
+        .map(|idx| {
+            let sample = idx / 2;
+            let counts = if idx % 2 == 0 {
+                100.0 * (sample as f32 * 0.01).sin()
+            } else {
+                (sample % 200) as f32 - 100.0
+            };
+            counts as i8 as u8
+        })
+        .collect();
+    CaptureFrame::new(
+        vec![1, 2],
+        vec![Some(Scale::v1), Some(Scale::mv500)],
+        Some(TimeScale::us100),
+        raw,
+    )
+}
+
+fn conversion(c: &mut Criterion) {
+    let frame = frame();
+    let mut group = c.benchmark_group("conversion");
+    group.throughput(Throughput::Bytes(frame.raw.len() as u64));
+    group.bench_function("raw_to_volts", |b| {
+        b.iter(|| {
+            frame
+                .raw
+                .iter()
+                .map(|it| raw_to_volts(*it, &Scale::v1))
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("channel_volts", |b| {
+        b.iter(|| {
+            (
+                frame.channel_volts(black_box(1)),
+                frame.channel_volts(black_box(2)),
+            )
+        })
+    });
+    group.finish();
+}
+
+fn deinterleave(c: &mut Criterion) {
+    let frame = frame();
+    let mut group = c.benchmark_group("deinterleave");
+    group.throughput(Throughput::Bytes(frame.raw.len() as u64));
+    group.bench_function("channel_raw", |b| {
+        b.iter(|| {
+            (
+                frame.channel_raw(black_box(1)),
+                frame.channel_raw(black_box(2)),
+            )
+        })
+    });
+    group.bench_function("channel_counts", |b| {
+        b.iter(|| {
+            (
+                frame.channel_counts(black_box(1)),
+                frame.channel_counts(black_box(2)),
+            )
+        })
+    });
+    group.finish();
+}
+
+fn dsp(c: &mut Criterion) {
+    let volts = frame().channel_volts(1).unwrap();
+    let mut group = c.benchmark_group("dsp");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(
+        (volts.len() * std::mem::size_of::<f32>()) as u64,
+    ));
+
+    group.bench_function("fft", |b| {
+        b.iter_batched_ref(
+            || (volts.clone(), vec![0.0; volts.len()]),
+            |(re, im)| fft(re, im),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("spectrum", |b| {
+        b.iter(|| spectrum(&volts, SAMPLE_RATE, black_box(&Window::Hann)))
+    });
+
+    for (name, spec) in [
+        ("lowpass", FilterSpec::LowPass(10e3)),
+        ("bandpass", FilterSpec::BandPass(1e3, 10e3)),
+        ("average", FilterSpec::MovingAverage(16)),
+    ] {
+        let mut filter = Filter::new(&spec, SAMPLE_RATE).unwrap();
+        group.bench_function(format!("filter_{}", name), |b| {
+            b.iter_batched_ref(
+                || volts.clone(),
+                |samples| filter.process(samples),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, conversion, deinterleave, dsp);
+criterion_main!(benches);
@@ -0,0 +1,93 @@
+//! Reads arbitrary bytes as a stream of framed frames, e.g. a replay file or a stream from a
+//! server, and runs every frame read through de-interleaving, conversion to volts and the
+//! acquisition modes. Frames read must also survive being written and read back.
+
+#![no_main]
+
+use std::io::Cursor;
+
+use libfuzzer_sys::fuzz_target;
+
+use hanteker_lib::capture::{Acquisition, AcquisitionMode, CaptureFrame};
+use hanteker_lib::device::cfg::{Scale, TimeScale};
+use hanteker_lib::export::{read_framed, FramedHeader};
+
+/// Frames read out of one input at most, the rest of it is ignored.
+const MAX_FRAMES: usize = 16;
+
+fuzz_target!(|data: &[u8]| {
+    let (knobs, stream) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    // A small length for the modes and the envelope, from the first byte.
+    let len = (*knobs as usize % 16) + 1;
+    let mut average = Acquisition::new(AcquisitionMode::Average(len));
+    let mut high_res = Acquisition::new(AcquisitionMode::HighRes(len));
+
+    let mut input = Cursor::new(stream);
+    for _ in 0..MAX_FRAMES {
+        let (header, raw) = match read_framed(&mut input) {
+            Ok(Some(frame)) => frame,
+            Ok(None) | Err(_) => break,
+        };
+        assert_eq!(header.channels.len(), header.scales.len());
+        assert_eq!(raw.len(), header.payload_len as usize);
+
+        let mut written = vec![];
+        header.write_to(&mut written).unwrap();
+        let read = FramedHeader::read_from(&mut written.as_slice())
+            .unwrap()
+            .unwrap();
+        assert_eq!(header, read);
+
+        let mut frame = CaptureFrame {
+            started_at: header.timestamp,
+            ..CaptureFrame::new(
+                header.channels.clone(),
+                header.scales.iter().map(|it| it.and_then(scale)).collect(),
+                header.time_scale.and_then(time_scale),
+                raw,
+            )
+        };
+        convert(&frame, len);
+
+        let mut averaged = frame.clone();
+        if average.apply(&mut averaged) {
+            convert(&averaged, len);
+        }
+        assert!(high_res.apply(&mut frame));
+        convert(&frame, len);
+    }
+});
+
+fn convert(frame: &CaptureFrame, samples_per_bucket: usize) {
+    let num_samples = frame.num_samples();
+    for channel_no in frame.channels.iter().copied().chain([0, 9]) {
+        let raw = frame.channel_raw(channel_no);
+        let counts = frame.channel_counts(channel_no);
+        assert_eq!(raw.is_some(), frame.channels.contains(&channel_no));
+        assert_eq!(raw.as_ref().map(Vec::len), counts.as_ref().map(Vec::len));
+        if let Some(raw) = raw {
+            assert!(raw.len() >= num_samples && raw.len() <= num_samples + 1);
+        }
+        if let Some(volts) = frame.channel_volts(channel_no) {
+            assert_eq!(Some(volts.len()), counts.as_ref().map(Vec::len));
+        }
+    }
+    let _ = frame.covered_time();
+    let _ = frame.dead_time();
+    let _ = frame.sample_timestamp(num_samples);
+
+    let envelope = frame.envelope(samples_per_bucket);
+    assert_eq!(envelope.num_samples, num_samples);
+    assert_eq!(envelope.min_max.len() % frame.channels.len().max(1), 0);
+}
+
+fn scale(volts: f32) -> Option<Scale> {
+    Scale::my_iter().find(|it| it.raw_value() == volts)
+}
+
+fn time_scale(seconds: f32) -> Option<TimeScale> {
+    TimeScale::my_iter().find(|it| it.raw_value() == seconds)
+}
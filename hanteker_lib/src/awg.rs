@@ -0,0 +1,138 @@
+//! Stepping the AWG frequency over a range, e.g. for a quick look at the frequency response of
+//! a filter on the scope, and running it in bursts for stimulus-response testing.
+
+use std::hint;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::device::cfg::AwgType;
+use crate::device::usb::Transport;
+use crate::models::hantek2d42::{
+    awg_frequency_max, invalid, Hantek2D42, Hantek2D42Error, AWG_FREQUENCY_MIN,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AwgSweep {
+    /// First frequency, in Hz.
+    pub from: f32,
+    /// Last frequency, in Hz, may be below `from` to sweep down.
+    pub to: f32,
+    /// Number of frequencies, both ends included.
+    pub steps: usize,
+    /// Space the frequencies evenly on a log scale rather than a linear one.
+    pub logarithmic: bool,
+    /// Time spent on each frequency.
+    pub dwell: Duration,
+}
+
+impl AwgSweep {
+    /// Checked against the limit of the given waveform, the sine one if unknown.
+    pub fn check(&self, awg_type: Option<&AwgType>) -> Result<(), Hantek2D42Error> {
+        let max = awg_frequency_max(awg_type.unwrap_or(&AwgType::Sin));
+        for (parameter, frequency) in [("sweep start", self.from), ("sweep end", self.to)] {
+            if !(AWG_FREQUENCY_MIN..=max).contains(&frequency) {
+                return Err(invalid(
+                    parameter,
+                    frequency,
+                    format!("{}..={} Hz", AWG_FREQUENCY_MIN, max),
+                ));
+            }
+        }
+        if self.steps < 2 {
+            return Err(invalid("sweep steps", self.steps, "at least 2"));
+        }
+        Ok(())
+    }
+
+    pub fn frequencies(&self) -> impl Iterator<Item = f32> + '_ {
+        let last = (self.steps - 1) as f64;
+        (0..self.steps).map(move |step| {
+            // Exact at the ends, where rounding could take them past the limits.
+            if step == 0 {
+                return self.from;
+            }
+            if step == self.steps - 1 {
+                return self.to;
+            }
+            let fraction = step as f64 / last;
+            let frequency = if self.logarithmic {
+                let (from, to) = ((self.from as f64).ln(), (self.to as f64).ln());
+                (from + (to - from) * fraction).exp()
+            } else {
+                self.from as f64 + (self.to as f64 - self.from as f64) * fraction
+            };
+            frequency as f32
+        })
+    }
+
+    /// Sets each frequency in turn and holds it for the dwell time, calling back right after
+    /// the frequency is set with its index and value. The AWG is neither started nor stopped.
+    pub fn run(
+        &self,
+        hantek: &mut Hantek2D42,
+        mut on_step: impl FnMut(usize, f32),
+    ) -> Result<(), Hantek2D42Error> {
+        self.check(hantek.get_config().awg_type.as_ref())?;
+        for (step, frequency) in self.frequencies().enumerate() {
+            hantek.set_awg_frequency(frequency)?;
+            on_step(step, frequency);
+            thread::sleep(self.dwell);
+        }
+        Ok(())
+    }
+}
+
+/// The last stretch of a burst is waited out spinning rather than sleeping, the scheduler may
+/// oversleep by about this much.
+const BURST_SPIN: Duration = Duration::from_millis(2);
+
+/// How long the AWG runs once started before it's stopped again. The device has no burst mode,
+/// the timing is the host's, between the start and the stop command being acknowledged, so it's
+/// off by about the latency of a command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AwgBurst {
+    Duration(Duration),
+    /// Periods of the waveform at the current frequency, which must be known.
+    Cycles(u32),
+}
+
+impl AwgBurst {
+    pub fn duration(&self, frequency: Option<f32>) -> Result<Duration, Hantek2D42Error> {
+        match self {
+            AwgBurst::Duration(duration) => Ok(*duration),
+            AwgBurst::Cycles(cycles) => match frequency {
+                Some(frequency) if frequency > 0.0 => {
+                    Ok(Duration::from_secs_f64(*cycles as f64 / frequency as f64))
+                }
+                _ => Err(invalid(
+                    "awg frequency",
+                    "unknown",
+                    "set to run a number of cycles",
+                )),
+            },
+        }
+    }
+
+    /// Starts the AWG and stops it once the burst is over, returning how long it actually ran.
+    pub fn run<T: Transport>(
+        &self,
+        hantek: &mut Hantek2D42<'_, T>,
+    ) -> Result<Duration, Hantek2D42Error> {
+        let duration = self.duration(hantek.get_config().awg_frequency)?;
+        hantek.awg_start()?;
+        let started = Instant::now();
+        wait_until(started + duration);
+        hantek.awg_stop()?;
+        Ok(started.elapsed())
+    }
+}
+
+fn wait_until(deadline: Instant) {
+    let now = Instant::now();
+    if deadline > now + BURST_SPIN {
+        thread::sleep(deadline - now - BURST_SPIN);
+    }
+    while Instant::now() < deadline {
+        hint::spin_loop();
+    }
+}
@@ -0,0 +1,141 @@
+//! Settings queued up and sent to the device in one go.
+//!
+//! The device takes a single command per transfer, no way of packing several into one is known,
+//! so a batch still costs a transfer per setting. What it saves is everything in between: the
+//! whole batch is checked before the first command goes out, then the commands are written back
+//! to back.
+
+use thiserror::Error;
+
+use crate::device::cfg::{AwgType, Coupling, Probe, Scale, TimeScale, TriggerMode, TriggerSlope};
+use crate::device::usb::Transport;
+use crate::models::hantek2d42::{Hantek2D42, Hantek2D42Error};
+
+/// A single setting with its value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Setting {
+    TimeScale(TimeScale),
+    ChannelEnabled(usize, bool),
+    ChannelBandwidthLimit(usize, bool),
+    ChannelScale(usize, Scale),
+    ChannelCoupling(usize, Coupling),
+    ChannelProbe(usize, Probe),
+    /// Raw offset, see [`Hantek2D42::set_channel_offset`].
+    ChannelOffset(usize, u8),
+    TriggerSource(usize),
+    TriggerSlope(TriggerSlope),
+    TriggerMode(TriggerMode),
+    /// Raw level, see [`Hantek2D42::set_trigger_level`].
+    TriggerLevel(u8),
+    AwgType(AwgType),
+    AwgFrequency(f32),
+    AwgAmplitude(f32),
+    AwgOffset(f32),
+}
+
+impl Setting {
+    pub fn apply<T: Transport>(
+        &self,
+        hantek: &mut Hantek2D42<'_, T>,
+    ) -> Result<(), Hantek2D42Error> {
+        match self {
+            Setting::TimeScale(time_scale) => hantek.set_time_scale(time_scale.clone()),
+            Setting::ChannelEnabled(channel_no, true) => hantek.enable_channel(*channel_no),
+            Setting::ChannelEnabled(channel_no, false) => hantek.disable_channel(*channel_no),
+            Setting::ChannelBandwidthLimit(channel_no, true) => {
+                hantek.channel_enable_bandwidth_limit(*channel_no)
+            }
+            Setting::ChannelBandwidthLimit(channel_no, false) => {
+                hantek.channel_disable_bandwidth_limit(*channel_no)
+            }
+            Setting::ChannelScale(channel_no, scale) => {
+                hantek.set_channel_scale(*channel_no, scale.clone())
+            }
+            Setting::ChannelCoupling(channel_no, coupling) => {
+                hantek.set_channel_coupling(*channel_no, coupling.clone())
+            }
+            Setting::ChannelProbe(channel_no, probe) => {
+                hantek.set_channel_probe(*channel_no, probe.clone())
+            }
+            Setting::ChannelOffset(channel_no, offset) => {
+                hantek.set_channel_offset(*channel_no, *offset)
+            }
+            Setting::TriggerSource(channel_no) => hantek.set_trigger_source(*channel_no),
+            Setting::TriggerSlope(slope) => hantek.set_trigger_slope(slope.clone()),
+            Setting::TriggerMode(mode) => hantek.set_trigger_mode(mode.clone()),
+            Setting::TriggerLevel(level) => hantek.set_trigger_level(*level),
+            Setting::AwgType(awg_type) => hantek.set_awg_type(awg_type.clone()),
+            Setting::AwgFrequency(frequency) => hantek.set_awg_frequency(*frequency),
+            Setting::AwgAmplitude(amplitude) => hantek.set_awg_amplitude(*amplitude),
+            Setting::AwgOffset(offset) => hantek.set_awg_offset(*offset),
+        }
+    }
+}
+
+/// Settings in the order they're sent in, see [`Hantek2D42::apply_batch`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CommandBatch {
+    settings: Vec<Setting>,
+}
+
+impl CommandBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, setting: Setting) -> &mut Self {
+        self.settings.push(setting);
+        self
+    }
+
+    pub fn settings(&self) -> &[Setting] {
+        &self.settings
+    }
+
+    pub fn len(&self) -> usize {
+        self.settings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.settings.is_empty()
+    }
+}
+
+impl FromIterator<Setting> for CommandBatch {
+    fn from_iter<I: IntoIterator<Item = Setting>>(iter: I) -> Self {
+        Self {
+            settings: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<Setting> for CommandBatch {
+    fn extend<I: IntoIterator<Item = Setting>>(&mut self, iter: I) {
+        self.settings.extend(iter)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum BatchError {
+    #[error("setting {index} of the batch is invalid, nothing was sent")]
+    Rejected {
+        index: usize,
+        #[source]
+        error: Hantek2D42Error,
+    },
+
+    /// The settings before `index` were applied, the ones after it weren't sent.
+    #[error("setting {index} of the batch failed, the ones before it were applied")]
+    Failed {
+        index: usize,
+        #[source]
+        error: Hantek2D42Error,
+    },
+}
+
+impl BatchError {
+    // Because CLion doesn't like the Display implemented by thiserror.
+    pub fn my_to_string(&self) -> impl std::fmt::Display + '_ {
+        self
+    }
+}
@@ -0,0 +1,163 @@
+//! Frequency response of a circuit driven by the AWG, its input and output captured together on
+//! the two channels. At each frequency of a sweep the gain and phase of the output relative to
+//! the input are taken from the tone at that frequency alone, so noise and harmonics mostly
+//! drop out.
+
+use std::f64::consts::PI;
+use std::thread;
+
+use log::warn;
+
+use crate::awg::AwgSweep;
+use crate::capture::sample_rate;
+use crate::device::cfg::TimeScale;
+use crate::logging::AWG;
+use crate::models::hantek2d42::{invalid, Hantek2D42, Hantek2D42Error};
+
+/// Fewest periods of the tone a capture is set up to span.
+const MIN_PERIODS: f32 = 4.0;
+
+/// Input amplitude in volts below which there's nothing to compare the output against.
+const MIN_INPUT_AMPLITUDE: f32 = 1e-3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BodePoint {
+    /// In Hz.
+    pub frequency: f32,
+    /// Output amplitude over input amplitude.
+    pub gain: f32,
+    pub gain_db: f32,
+    /// Of the output relative to the input, in degrees within -180..=180.
+    pub phase: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bode {
+    pub sweep: AwgSweep,
+    /// Channel on the input of the circuit, the AWG output.
+    pub input: usize,
+    /// Channel on the output of the circuit.
+    pub output: usize,
+    /// Samples per channel captured at each frequency.
+    pub num_samples: usize,
+}
+
+impl Bode {
+    /// Sweeps the AWG, adjusting the time scale to each frequency, and captures both channels
+    /// once the dwell time has passed. The scales of both channels must be known. Frequencies
+    /// that can't be measured, e.g. with no signal on the input, are left out with a warning.
+    pub fn run(
+        &self,
+        hantek: &mut Hantek2D42,
+        mut on_point: impl FnMut(&BodePoint),
+    ) -> Result<Vec<BodePoint>, Hantek2D42Error> {
+        if self.input == self.output {
+            return Err(invalid(
+                "bode channels",
+                self.input,
+                "different input and output channels",
+            ));
+        }
+        self.sweep.check(hantek.get_config().awg_type.as_ref())?;
+        for channel_no in [self.input, self.output] {
+            let config = hantek.get_config();
+            if config
+                .channel(channel_no)
+                .and_then(|it| it.scale())
+                .is_none()
+            {
+                return Err(invalid("channel scale", "unknown", "set before measuring"));
+            }
+        }
+
+        let mut points = vec![];
+        for frequency in self.sweep.frequencies() {
+            hantek.set_awg_frequency(frequency)?;
+            let time_scale = time_scale_for(frequency, self.num_samples);
+            if hantek.get_config().time_scale.as_ref() != Some(&time_scale) {
+                hantek.set_time_scale(time_scale)?;
+            }
+            thread::sleep(self.sweep.dwell);
+
+            let frame = hantek.capture_frame(&[self.input, self.output], self.num_samples)?;
+            let point = match (
+                frame.channel_volts(self.input),
+                frame.channel_volts(self.output),
+                frame.sample_rate(),
+            ) {
+                (Some(input), Some(output), Some(rate)) => {
+                    response(&input, &output, rate, frequency)
+                }
+                _ => None,
+            };
+            match point {
+                Some(point) => {
+                    on_point(&point);
+                    points.push(point);
+                }
+                None => warn!(target: AWG, "could not measure the response at {} Hz", frequency),
+            }
+        }
+        Ok(points)
+    }
+}
+
+/// Fastest time scale at which `num_samples` span at least a few periods of the frequency, the
+/// slowest one if none does.
+pub fn time_scale_for(frequency: f32, num_samples: usize) -> TimeScale {
+    TimeScale::my_iter()
+        .find(|it| num_samples as f32 * frequency / sample_rate(it) >= MIN_PERIODS)
+        .unwrap_or(TimeScale::s500)
+}
+
+/// Amplitude and phase in radians of the tone at `frequency`, correlating a whole number of its
+/// periods against a cosine and a sine. `None` if not even one period was captured.
+pub fn tone(samples: &[f32], sample_rate: f32, frequency: f32) -> Option<(f32, f32)> {
+    let samples_per_period = sample_rate as f64 / frequency as f64;
+    let periods = (samples.len() as f64 / samples_per_period).floor();
+    let len = ((periods * samples_per_period).round() as usize).min(samples.len());
+    if periods < 1.0 || len == 0 {
+        return None;
+    }
+
+    let samples = &samples[..len];
+    let mean = samples.iter().map(|it| *it as f64).sum::<f64>() / len as f64;
+    let (mut re, mut im) = (0.0, 0.0);
+    for (idx, sample) in samples.iter().enumerate() {
+        let angle = 2.0 * PI * idx as f64 / samples_per_period;
+        re += (*sample as f64 - mean) * angle.cos();
+        im += (*sample as f64 - mean) * angle.sin();
+    }
+    let amplitude = 2.0 * (re * re + im * im).sqrt() / len as f64;
+    // A cos(wt + phi) correlates to A/2 cos(phi) with the cosine, -A/2 sin(phi) with the sine.
+    let phase = (-im).atan2(re);
+    Some((amplitude as f32, phase as f32))
+}
+
+/// Gain and phase of the output relative to the input at `frequency`.
+pub fn response(
+    input: &[f32],
+    output: &[f32],
+    sample_rate: f32,
+    frequency: f32,
+) -> Option<BodePoint> {
+    let (input_amplitude, input_phase) = tone(input, sample_rate, frequency)?;
+    let (output_amplitude, output_phase) = tone(output, sample_rate, frequency)?;
+    if input_amplitude < MIN_INPUT_AMPLITUDE {
+        return None;
+    }
+
+    let gain = output_amplitude / input_amplitude;
+    let mut phase = (output_phase - input_phase).to_degrees();
+    if phase > 180.0 {
+        phase -= 360.0;
+    } else if phase <= -180.0 {
+        phase += 360.0;
+    }
+    Some(BodePoint {
+        frequency,
+        gain,
+        gain_db: 20.0 * gain.max(f32::MIN_POSITIVE).log10(),
+        phase,
+    })
+}
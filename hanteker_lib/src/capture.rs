@@ -0,0 +1,699 @@
+//! Interpreting the raw sample buffers read from the device.
+
+use std::fmt::{Display, Formatter};
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use bytes::{Bytes, BytesMut};
+
+use crate::device::cfg::{Scale, TimeScale};
+use crate::dsp::Filter;
+use crate::math::MathExpr;
+use crate::models::hantek2d42::{invalid, Hantek2D42Error};
+
+/// The 8 vertical divisions of the screen span 200 ADC counts, the same range the device uses
+/// for channel offset and trigger level.
+pub const COUNTS_PER_DIVISION: f32 = 25.0;
+
+/// Samples acquired per horizontal division.
+pub const SAMPLES_PER_DIVISION: f32 = 25.0;
+
+/// Convert a single raw sample to volts, samples are signed and centered on the channel's zero
+/// level.
+pub fn raw_to_volts(raw: u8, scale: &Scale) -> f32 {
+    counts_to_volts((raw as i8) as f32, scale)
+}
+
+/// Same as [`raw_to_volts`], for samples in fractions of a count.
+pub fn counts_to_volts(counts: f32, scale: &Scale) -> f32 {
+    counts * scale.volts_per_division() / COUNTS_PER_DIVISION
+}
+
+fn round_to_raw(counts: f32) -> u8 {
+    (counts.round().clamp(i8::MIN as f32, i8::MAX as f32) as i8) as u8
+}
+
+/// Samples per second at the given time scale.
+pub fn sample_rate(time_scale: &TimeScale) -> f32 {
+    SAMPLES_PER_DIVISION / time_scale.raw_value()
+}
+
+/// Slowest time scale at which the device still acquires a whole screen before handing it out.
+/// From 100ms per division on it rolls instead, samples come out as they're acquired.
+pub const ROLL_MODE_TIME_SCALE: f32 = 0.1;
+
+pub fn is_roll_mode(time_scale: &TimeScale) -> bool {
+    time_scale.raw_value() >= ROLL_MODE_TIME_SCALE
+}
+
+/// Condition on one channel enabling the recording of the others, e.g. an enable line marking a
+/// specific phase of a test cycle.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gate {
+    pub channel_no: usize,
+    /// Threshold in volts.
+    pub level: f32,
+    /// Gate is open while the channel is above the level, or below it if `false`.
+    pub above: bool,
+}
+
+impl Gate {
+    pub fn is_open(&self, volts: f32) -> bool {
+        if self.above {
+            volts > self.level
+        } else {
+            volts < self.level
+        }
+    }
+}
+
+/// Lets another thread, or a signal handler, follow a capture and cancel it. Clones share the
+/// same state. Cancelling is checked between the packets of a capture and sticks, every later
+/// capture given the handle fails right away.
+#[derive(Debug, Clone, Default)]
+pub struct CaptureHandle {
+    cancelled: Arc<AtomicBool>,
+    received: Arc<AtomicUsize>,
+    expected: Arc<AtomicUsize>,
+}
+
+impl CaptureHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancelled once the flag is set, e.g. a flag registered with `signal-hook`.
+    pub fn with_flag(cancelled: Arc<AtomicBool>) -> Self {
+        Self {
+            cancelled,
+            ..Self::default()
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Bytes received and expected of the capture in progress, or of the last one.
+    pub fn progress(&self) -> (usize, usize) {
+        (
+            self.received.load(Ordering::SeqCst),
+            self.expected.load(Ordering::SeqCst),
+        )
+    }
+
+    pub(crate) fn start(&self, expected: usize) {
+        self.received.store(0, Ordering::SeqCst);
+        self.expected.store(expected, Ordering::SeqCst);
+    }
+
+    pub(crate) fn advance(&self, received: usize) {
+        self.received.fetch_add(received, Ordering::SeqCst);
+    }
+}
+
+/// Acquisition modes done in software, trading captures or bandwidth for noise below what the
+/// 8 bit ADC resolves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AcquisitionMode {
+    /// Every capture as is.
+    Normal,
+    /// Mean of this many consecutive captures, sample by sample. Only noise not correlated with
+    /// the trigger averages out, so the captures must be triggered.
+    Average(usize),
+    /// Mean of the samples of a capture over a centered boxcar of this many, cutting the
+    /// bandwidth as much but keeping the sample rate.
+    HighRes(usize),
+}
+
+impl AcquisitionMode {
+    pub fn parse(text: &str) -> Result<Self, Hantek2D42Error> {
+        let bad = || {
+            invalid(
+                "acquisition mode",
+                text,
+                "normal, average:<captures> or hires:<samples>, at least 2 of them",
+            )
+        };
+        if text == "normal" {
+            return Ok(Self::Normal);
+        }
+        let (kind, value) = text.split_once(':').ok_or_else(bad)?;
+        let len = match value.parse() {
+            Ok(len) if len > 1 => len,
+            _ => return Err(bad()),
+        };
+        match kind {
+            "average" => Ok(Self::Average(len)),
+            "hires" => Ok(Self::HighRes(len)),
+            _ => Err(bad()),
+        }
+    }
+}
+
+impl Display for AcquisitionMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Normal => write!(f, "normal"),
+            Self::Average(len) => write!(f, "average:{}", len),
+            Self::HighRes(len) => write!(f, "hires:{}", len),
+        }
+    }
+}
+
+/// Applies an [`AcquisitionMode`] to a stream of captures, see [`Self::apply`].
+#[derive(Debug, Clone)]
+pub struct Acquisition {
+    mode: AcquisitionMode,
+    /// Channels of the captures summed so far, and their sum.
+    channels: Vec<usize>,
+    sums: Vec<f32>,
+    averaged: usize,
+    /// Start of the first capture summed, and the gap before it.
+    first: Option<(Instant, SystemTime, Option<Duration>)>,
+    acquisition_time: Duration,
+}
+
+impl Acquisition {
+    pub fn new(mode: AcquisitionMode) -> Self {
+        Self {
+            mode,
+            channels: vec![],
+            sums: vec![],
+            averaged: 0,
+            first: None,
+            acquisition_time: Duration::ZERO,
+        }
+    }
+
+    /// Applies the mode to the capture in place, returning whether it's complete. An average
+    /// completes on its last capture, which gets the mean of them all and the start of the
+    /// first; the captures before it are only summed and may be dropped. A capture of other
+    /// channels or length than the ones summed starts the average over.
+    pub fn apply(&mut self, frame: &mut CaptureFrame) -> bool {
+        match self.mode {
+            AcquisitionMode::Normal => true,
+            AcquisitionMode::HighRes(len) => {
+                let stride = frame.channels.len().max(1);
+                let len = len.max(1);
+                // Samples of a channel, counting the last one of a frame cut short mid-sample.
+                let channel_len = |channel: usize| (frame.raw.len() - channel).div_ceil(stride);
+                let fine = (0..frame.raw.len())
+                    .map(|idx| {
+                        let (sample, channel) = (idx / stride, idx % stride);
+                        let from = sample.saturating_sub(len / 2);
+                        let to = (from + len).min(channel_len(channel));
+                        let sum: f32 = (from..to)
+                            .map(|it| frame.counts(it * stride + channel))
+                            .sum();
+                        sum / (to - from) as f32
+                    })
+                    .collect();
+                frame.set_fine(fine);
+                true
+            }
+            AcquisitionMode::Average(len) => {
+                if self.channels != frame.channels || self.sums.len() != frame.raw.len() {
+                    self.averaged = 0;
+                }
+                if self.averaged == 0 {
+                    self.channels = frame.channels.clone();
+                    self.sums = vec![0.0; frame.raw.len()];
+                    self.first = Some((frame.started, frame.started_at, frame.gap));
+                    self.acquisition_time = Duration::ZERO;
+                }
+                for (idx, sum) in self.sums.iter_mut().enumerate() {
+                    *sum += frame.counts(idx);
+                }
+                self.averaged += 1;
+                self.acquisition_time += frame.acquisition_time;
+                if self.averaged < len {
+                    return false;
+                }
+
+                let fine = self.sums.iter().map(|it| it / len as f32).collect();
+                frame.set_fine(fine);
+                if let Some((started, started_at, gap)) = self.first.take() {
+                    frame.started = started;
+                    frame.started_at = started_at;
+                    frame.gap = gap;
+                }
+                frame.acquisition_time = self.acquisition_time;
+                self.averaged = 0;
+                true
+            }
+        }
+    }
+}
+
+/// Buffers of captures handed back once done with, so continuous acquisition reuses them
+/// instead of allocating one per capture. Keeps at most `max` buffers, dropping the rest, and
+/// any still shared with a clone of its frame.
+#[derive(Debug, Clone, Default)]
+pub struct BufferPool {
+    buffers: Vec<Vec<u8>>,
+    max: usize,
+}
+
+impl BufferPool {
+    pub fn new(max: usize) -> Self {
+        Self {
+            buffers: Vec::with_capacity(max),
+            max,
+        }
+    }
+
+    /// A buffer of `len` bytes, allocated only if none was put back or it's too small. Its
+    /// content is whatever the previous capture left.
+    pub fn take(&mut self, len: usize) -> Vec<u8> {
+        let mut buffer = self.buffers.pop().unwrap_or_default();
+        buffer.resize(len, 0);
+        buffer
+    }
+
+    pub fn put(&mut self, buffer: Bytes) {
+        if self.buffers.len() < self.max {
+            if let Ok(buffer) = buffer.try_into_mut() {
+                self.buffers.push(buffer.into());
+            }
+        }
+    }
+}
+
+/// A single capture of one or more channels, along with the settings needed to interpret it.
+/// The samples are reference counted, a clone of the frame handed to another consumer shares
+/// them until either one modifies its own.
+#[derive(Debug, Clone)]
+pub struct CaptureFrame {
+    /// Captured channels, sorted, in the order their samples are interleaved in `raw`.
+    pub channels: Vec<usize>,
+    /// Scale of each channel in `channels`, if known.
+    pub scales: Vec<Option<Scale>>,
+    pub time_scale: Option<TimeScale>,
+    pub raw: Bytes,
+    /// Wall time spent transferring the samples from the device.
+    pub acquisition_time: Duration,
+    /// Wall time between the end of the previous capture and the start of this one, `None` for
+    /// the first capture of the device.
+    pub gap: Option<Duration>,
+    /// When the capture was asked of the device, on the monotonic clock. Frames of the same run
+    /// are placed relative to each other by it, see [`Self::sample_offset`].
+    pub started: Instant,
+    /// Same instant as `started` on the wall clock, see [`Self::sample_timestamp`].
+    pub started_at: SystemTime,
+    /// Samples in fractions of a count, interleaved as in `raw`, when an [`AcquisitionMode`]
+    /// averaged more resolution out of the captures than whole counts hold. `raw` then has them
+    /// rounded.
+    pub fine: Option<Arc<Vec<f32>>>,
+}
+
+impl CaptureFrame {
+    /// A frame of the samples as captured now, taking no time and with no capture before it.
+    pub fn new(
+        channels: Vec<usize>,
+        scales: Vec<Option<Scale>>,
+        time_scale: Option<TimeScale>,
+        raw: impl Into<Bytes>,
+    ) -> Self {
+        Self {
+            channels,
+            scales,
+            time_scale,
+            raw: raw.into(),
+            acquisition_time: Duration::ZERO,
+            gap: None,
+            started: Instant::now(),
+            started_at: SystemTime::now(),
+            fine: None,
+        }
+    }
+
+    pub fn num_samples(&self) -> usize {
+        if self.channels.is_empty() {
+            0
+        } else {
+            self.raw.len() / self.channels.len()
+        }
+    }
+
+    pub fn sample_rate(&self) -> Option<f32> {
+        self.time_scale.as_ref().map(sample_rate)
+    }
+
+    /// Real time spanned by the samples, if the time scale is known.
+    pub fn covered_time(&self) -> Option<Duration> {
+        self.sample_rate()
+            .map(|rate| Duration::from_secs_f32(self.num_samples() as f32 / rate))
+    }
+
+    /// Time of a sample after the start of the capture, if the time scale is known.
+    pub fn sample_offset(&self, sample: usize) -> Option<Duration> {
+        self.sample_rate()
+            .map(|rate| Duration::from_secs_f64(sample as f64 / rate as f64))
+    }
+
+    /// Time of a sample after the start of another capture of the same run, e.g. the first one,
+    /// negative if it was taken earlier. `None` if the time scale is unknown.
+    pub fn sample_time_since(&self, origin: Instant, sample: usize) -> Option<f64> {
+        let offset = self.sample_offset(sample)?.as_secs_f64();
+        let start = match self.started.checked_duration_since(origin) {
+            Some(after) => after.as_secs_f64(),
+            None => -origin.duration_since(self.started).as_secs_f64(),
+        };
+        Some(start + offset)
+    }
+
+    /// Wall clock time of a sample, if the time scale is known.
+    pub fn sample_timestamp(&self, sample: usize) -> Option<SystemTime> {
+        self.sample_offset(sample).map(|it| self.started_at + it)
+    }
+
+    /// Real time since the end of the previous capture which isn't represented by any sample,
+    /// if the time scale is known.
+    pub fn dead_time(&self) -> Option<Duration> {
+        let covered = self.covered_time()?;
+        let wall = self.gap.unwrap_or_default() + self.acquisition_time;
+        Some(wall.saturating_sub(covered))
+    }
+
+    pub fn scale(&self, channel_no: usize) -> Option<&Scale> {
+        self.channels
+            .iter()
+            .position(|it| *it == channel_no)
+            .and_then(|idx| self.scales[idx].as_ref())
+    }
+
+    /// Raw samples of a single channel, de-interleaved.
+    pub fn channel_raw(&self, channel_no: usize) -> Option<Vec<u8>> {
+        let idx = self.channels.iter().position(|it| *it == channel_no)?;
+        Some(
+            self.raw
+                .iter()
+                .skip(idx)
+                .step_by(self.channels.len())
+                .copied()
+                .collect(),
+        )
+    }
+
+    /// Sample at a position of `raw` in counts, fractional if known.
+    pub fn counts(&self, idx: usize) -> f32 {
+        match &self.fine {
+            Some(fine) => fine[idx],
+            None => (self.raw[idx] as i8) as f32,
+        }
+    }
+
+    /// Samples of a single channel in counts, fractional if known.
+    pub fn channel_counts(&self, channel_no: usize) -> Option<Vec<f32>> {
+        let idx = self.channels.iter().position(|it| *it == channel_no)?;
+        Some(
+            (idx..self.raw.len())
+                .step_by(self.channels.len())
+                .map(|it| self.counts(it))
+                .collect(),
+        )
+    }
+
+    /// Samples of a single channel in volts, `None` if the channel was not captured or its
+    /// scale is unknown.
+    pub fn channel_volts(&self, channel_no: usize) -> Option<Vec<f32>> {
+        let scale = self.scale(channel_no)?.clone();
+        self.channel_counts(channel_no).map(|counts| {
+            counts
+                .into_iter()
+                .map(|it| counts_to_volts(it, &scale))
+                .collect()
+        })
+    }
+
+    /// Sets the fractional samples, and the raw ones to them rounded.
+    fn set_fine(&mut self, fine: Vec<f32>) {
+        self.modify_raw(|raw| {
+            for (raw, sample) in raw.iter_mut().zip(&fine) {
+                *raw = round_to_raw(*sample);
+            }
+        });
+        self.fine = Some(Arc::new(fine));
+    }
+
+    /// Modifies the raw samples in place, copying them first only if they're shared with a
+    /// clone of the frame.
+    fn modify_raw(&mut self, modify: impl FnOnce(&mut [u8])) {
+        let mut raw = match mem::take(&mut self.raw).try_into_mut() {
+            Ok(raw) => raw,
+            Err(shared) => BytesMut::from(shared.as_ref()),
+        };
+        modify(&mut raw);
+        self.raw = raw.freeze();
+    }
+
+    /// Runs the samples of a channel through the filter, in place. The filter works on counts,
+    /// fractional if known, raw samples get the results rounded. Returns `false` if the channel
+    /// wasn't captured.
+    pub fn filter_channel(&mut self, channel_no: usize, filter: &mut Filter) -> bool {
+        let idx = match self.channels.iter().position(|it| *it == channel_no) {
+            Some(idx) => idx,
+            None => return false,
+        };
+        let stride = self.channels.len();
+        let mut samples = self.channel_counts(channel_no).unwrap_or_default();
+        filter.process(&mut samples);
+
+        if let Some(fine) = &mut self.fine {
+            let fine = Arc::make_mut(fine);
+            for (fine, sample) in fine.iter_mut().skip(idx).step_by(stride).zip(&samples) {
+                *fine = *sample;
+            }
+        }
+        self.modify_raw(|raw| {
+            let channel = raw.iter_mut().skip(idx).step_by(stride);
+            for (raw, sample) in channel.zip(samples) {
+                *raw = round_to_raw(sample);
+            }
+        });
+        true
+    }
+
+    /// Derived trace in volts, `None` if a channel it references was not captured or its scale
+    /// is unknown.
+    pub fn math(&self, expr: &MathExpr) -> Option<Vec<f32>> {
+        let channels = expr.channels();
+        let volts = channels
+            .iter()
+            .map(|it| self.channel_volts(*it))
+            .collect::<Option<Vec<_>>>()?;
+        (0..self.num_samples())
+            .map(|idx| {
+                expr.eval(&|channel_no| {
+                    let pos = channels.iter().position(|it| *it == channel_no)?;
+                    Some(volts[pos][idx])
+                })
+            })
+            .collect()
+    }
+
+    /// Min and max of each channel over every run of `samples_per_bucket` samples, the last
+    /// bucket taking what's left.
+    pub fn envelope(&self, samples_per_bucket: usize) -> Envelope {
+        let stride = self.channels.len().max(1);
+        let min_max = self
+            .raw
+            .chunks(stride * samples_per_bucket.max(1))
+            .flat_map(|bucket| {
+                (0..self.channels.len()).map(move |idx| {
+                    bucket
+                        .iter()
+                        .skip(idx)
+                        .step_by(stride)
+                        .fold((i8::MAX, i8::MIN), |(min, max), it| {
+                            (min.min(*it as i8), max.max(*it as i8))
+                        })
+                })
+            })
+            .map(|(min, max)| (min as u8, max as u8))
+            .collect();
+        Envelope {
+            channels: self.channels.clone(),
+            scales: self.scales.clone(),
+            time_scale: self.time_scale.clone(),
+            samples_per_bucket,
+            num_samples: self.num_samples(),
+            min_max,
+        }
+    }
+
+    /// Raw samples of a single channel taken while the gate is open, `None` if either channel
+    /// was not captured or the scale of the gate channel is unknown.
+    pub fn gated_raw(&self, channel_no: usize, gate: &Gate) -> Option<Vec<u8>> {
+        let samples = self.channel_raw(channel_no)?;
+        let gate_volts = self.channel_volts(gate.channel_no)?;
+        Some(
+            samples
+                .into_iter()
+                .zip(gate_volts)
+                .filter(|(_, volts)| gate.is_open(*volts))
+                .map(|(sample, _)| sample)
+                .collect(),
+        )
+    }
+}
+
+/// A capture shrunk to the min and max of each channel over consecutive runs of samples, so long
+/// acquisitions take a fraction of the space while short glitches still show.
+#[derive(Debug, Clone)]
+pub struct Envelope {
+    /// Channels, sorted, in the order their pairs are interleaved in `min_max`.
+    pub channels: Vec<usize>,
+    /// Scale of each channel in `channels`, if known.
+    pub scales: Vec<Option<Scale>>,
+    pub time_scale: Option<TimeScale>,
+    pub samples_per_bucket: usize,
+    /// Samples the envelope was made of, the last bucket has fewer than `samples_per_bucket` if
+    /// it doesn't divide them.
+    pub num_samples: usize,
+    /// Raw min and max of each channel per bucket.
+    pub min_max: Vec<(u8, u8)>,
+}
+
+impl Envelope {
+    pub fn num_buckets(&self) -> usize {
+        if self.channels.is_empty() {
+            0
+        } else {
+            self.min_max.len() / self.channels.len()
+        }
+    }
+
+    pub fn sample_rate(&self) -> Option<f32> {
+        self.time_scale.as_ref().map(sample_rate)
+    }
+
+    pub fn scale(&self, channel_no: usize) -> Option<&Scale> {
+        self.channels
+            .iter()
+            .position(|it| *it == channel_no)
+            .and_then(|idx| self.scales[idx].as_ref())
+    }
+}
+
+/// Running totals over consecutive captures, telling what fraction of real time a "continuous"
+/// capture actually covers.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AcquisitionStats {
+    pub frames: usize,
+    /// Real time spanned by samples, only frames with a known time scale are counted.
+    pub covered: Duration,
+    /// Real time not covered by any sample, only frames with a known time scale are counted.
+    pub dead: Duration,
+    /// Longest latency between two consecutive captures.
+    pub max_gap: Duration,
+    total_gap: Duration,
+    gaps: usize,
+}
+
+impl AcquisitionStats {
+    pub fn record(&mut self, frame: &CaptureFrame) {
+        self.frames += 1;
+        if let (Some(covered), Some(dead)) = (frame.covered_time(), frame.dead_time()) {
+            self.covered += covered;
+            self.dead += dead;
+        }
+        if let Some(gap) = frame.gap {
+            self.max_gap = self.max_gap.max(gap);
+            self.total_gap += gap;
+            self.gaps += 1;
+        }
+    }
+
+    /// Mean latency between consecutive captures.
+    pub fn mean_gap(&self) -> Option<Duration> {
+        if self.gaps == 0 {
+            None
+        } else {
+            Some(self.total_gap / self.gaps as u32)
+        }
+    }
+
+    /// Fraction of real time covered by samples, from 0 to 1.
+    pub fn coverage(&self) -> Option<f32> {
+        let total = self.covered + self.dead;
+        if total.is_zero() {
+            None
+        } else {
+            Some(self.covered.as_secs_f32() / total.as_secs_f32())
+        }
+    }
+}
+
+impl Display for AcquisitionStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "frames={} covered={:.6}s dead={:.6}s",
+            self.frames,
+            self.covered.as_secs_f64(),
+            self.dead.as_secs_f64()
+        )?;
+        match self.coverage() {
+            Some(coverage) => write!(f, " coverage={:.2}%", 100.0 * coverage)?,
+            None => write!(f, " coverage=?")?,
+        }
+        match self.mean_gap() {
+            Some(gap) => write!(
+                f,
+                " gap_mean={:.6}s gap_max={:.6}s",
+                gap.as_secs_f64(),
+                self.max_gap.as_secs_f64()
+            ),
+            None => write!(f, " gap_mean=? gap_max=?"),
+        }
+    }
+}
+
+/// Wall clock timestamps for the samples of consecutive roll mode captures. Samples come out as
+/// they're acquired, so the last one of a capture is taken about when its transfer ends and the
+/// others a sample period apart before it. Timestamps never go back, a capture that would
+/// overlap the previous one is moved to right after it.
+#[derive(Debug, Clone, Default)]
+pub struct RollClock {
+    last: Option<SystemTime>,
+}
+
+impl RollClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Timestamps of the samples of a frame whose transfer ended at `received`, `None` if its
+    /// time scale is unknown.
+    pub fn timestamps(
+        &mut self,
+        frame: &CaptureFrame,
+        received: SystemTime,
+    ) -> Option<Vec<SystemTime>> {
+        let period = Duration::from_secs_f64(1.0 / frame.sample_rate()? as f64);
+        let num_samples = frame.num_samples();
+        let span = period * num_samples.saturating_sub(1) as u32;
+        let mut start = received.checked_sub(span).unwrap_or(received);
+        if let Some(last) = self.last {
+            if start <= last {
+                start = last + period;
+            }
+        }
+
+        let timestamps: Vec<SystemTime> = (0..num_samples)
+            .map(|idx| start + period * idx as u32)
+            .collect();
+        if let Some(last) = timestamps.last() {
+            self.last = Some(*last);
+        }
+        Some(timestamps)
+    }
+}
@@ -0,0 +1,156 @@
+//! Probe compensation check on a captured square wave.
+//!
+//! A x10 probe is a voltage divider whose capacitance has to be trimmed to match the scope
+//! input. Matched, the square wave comes out square. Over-compensated, the leading corners of
+//! each half period spike past the settled level. Under-compensated, they are rounded and the
+//! level creeps up (or down) to where it settles, which also shows as a slow rise time.
+
+use strum_macros::Display;
+
+use crate::measure::{falling_edges, rising_edges};
+
+/// Overshoot or rounding, in percent of the amplitude, tolerated before calling a probe off.
+pub const TOLERANCE: f32 = 3.0;
+
+/// Fewest samples in a half period of the square wave for its shape to tell anything.
+const MIN_HALF_PERIOD: usize = 8;
+
+#[derive(Display, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compensation {
+    Good,
+    Over,
+    Under,
+}
+
+impl Compensation {
+    /// Verdict from overshoot and rounding, both in percent of the amplitude.
+    pub fn judge(overshoot: f32, rounding: f32) -> Self {
+        if overshoot > TOLERANCE && overshoot >= rounding {
+            Compensation::Over
+        } else if rounding > TOLERANCE {
+            Compensation::Under
+        } else {
+            Compensation::Good
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompensationReport {
+    /// Distance between the settled high and low levels, in the unit of the samples.
+    pub amplitude: f32,
+    /// How far the leading corners spike past the settled level, in percent of the amplitude.
+    pub overshoot: f32,
+    /// How far the start of each half period falls short of the settled level on average, in
+    /// percent of the amplitude.
+    pub rounding: f32,
+    /// Mean time from 10% to 90% of the amplitude on the rising edges, in samples.
+    /// Interpolated between samples, `None` if no rising edge is complete in the capture.
+    pub rise_time: Option<f32>,
+    /// Number of complete half periods the figures are averaged over.
+    pub half_periods: usize,
+    pub verdict: Compensation,
+}
+
+/// Analyzes the shape of a square wave. The capture should cover a few periods with at least
+/// [`MIN_HALF_PERIOD`] samples in each half; returns `None` if no complete half period is found.
+pub fn analyze(samples: &[f32]) -> Option<CompensationReport> {
+    let min = samples.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = samples.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    if max <= min {
+        return None;
+    }
+
+    let mut edges: Vec<(usize, bool)> = rising_edges(samples, min, max)
+        .into_iter()
+        .map(|idx| (idx, true))
+        .chain(
+            falling_edges(samples, min, max)
+                .into_iter()
+                .map(|idx| (idx, false)),
+        )
+        .collect();
+    edges.sort_unstable();
+
+    let mut highs = vec![];
+    let mut lows = vec![];
+    let mut overshoot = 0.0;
+    let mut rounding = 0.0;
+    for pair in edges.windows(2) {
+        let ((start, rising), (end, _)) = (pair[0], pair[1]);
+        if end - start < MIN_HALF_PERIOD {
+            continue;
+        }
+
+        // The sample the edge was detected on may still be on its way up, skip it.
+        let half = &samples[start + 1..end];
+        let early = &half[..half.len() / 4];
+        let settled = mean(&half[half.len() / 2..]);
+
+        if rising {
+            let peak = early.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            overshoot += (peak - settled).max(0.0);
+            rounding += (settled - mean(early)).max(0.0);
+            highs.push(settled);
+        } else {
+            let peak = early.iter().copied().fold(f32::INFINITY, f32::min);
+            overshoot += (settled - peak).max(0.0);
+            rounding += (mean(early) - settled).max(0.0);
+            lows.push(settled);
+        }
+    }
+
+    let half_periods = highs.len() + lows.len();
+    if half_periods == 0 {
+        return None;
+    }
+
+    // A capture may hold complete half periods on one side only, fall back to the extremes.
+    let high = if highs.is_empty() { max } else { mean(&highs) };
+    let low = if lows.is_empty() { min } else { mean(&lows) };
+    let amplitude = high - low;
+    if amplitude <= 0.0 {
+        return None;
+    }
+
+    let rise_times: Vec<f32> = edges
+        .windows(3)
+        .filter(|it| it[1].1)
+        .filter_map(|it| {
+            let (from, to) = (it[0].0, it[2].0);
+            let start = crossing(samples, from, to, low + 0.1 * amplitude)?;
+            let end = crossing(samples, start.ceil() as usize, to, low + 0.9 * amplitude)?;
+            Some(end - start)
+        })
+        .collect();
+    let rise_time = if rise_times.is_empty() {
+        None
+    } else {
+        Some(mean(&rise_times))
+    };
+
+    let overshoot = 100.0 * overshoot / half_periods as f32 / amplitude;
+    let rounding = 100.0 * rounding / half_periods as f32 / amplitude;
+    Some(CompensationReport {
+        amplitude,
+        overshoot,
+        rounding,
+        rise_time,
+        half_periods,
+        verdict: Compensation::judge(overshoot, rounding),
+    })
+}
+
+fn mean(samples: &[f32]) -> f32 {
+    samples.iter().sum::<f32>() / samples.len() as f32
+}
+
+/// Fractional index where the samples first rise through the level, searching `from..to`.
+fn crossing(samples: &[f32], from: usize, to: usize, level: f32) -> Option<f32> {
+    (from.max(1)..to.min(samples.len()))
+        .find(|idx| samples[idx - 1] < level && samples[*idx] >= level)
+        .map(|idx| {
+            let (before, after) = (samples[idx - 1], samples[idx]);
+            (idx - 1) as f32 + (level - before) / (after - before)
+        })
+}
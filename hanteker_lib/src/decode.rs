@@ -0,0 +1,18 @@
+//! Protocol decoders working on captured channels, turned into logic levels against a
+//! threshold.
+
+pub mod i2c;
+pub mod spi;
+pub mod uart;
+
+/// High where the sample is above the threshold.
+pub fn digital(samples: &[f32], threshold: f32) -> Vec<bool> {
+    samples.iter().map(|it| *it > threshold).collect()
+}
+
+/// Halfway between the lowest and the highest sample, good enough for a clean logic signal.
+pub fn midpoint(samples: &[f32]) -> Option<f32> {
+    let min = samples.iter().cloned().reduce(f32::min)?;
+    let max = samples.iter().cloned().reduce(f32::max)?;
+    Some((min + max) / 2.0)
+}
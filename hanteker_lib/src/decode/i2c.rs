@@ -0,0 +1,234 @@
+//! I2C from SCL and SDA, with 7 bit addresses.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct I2cByte {
+    pub value: u8,
+    /// SDA pulled low on the ninth clock.
+    pub ack: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct I2cTransaction {
+    /// Seconds from the first sample to the start condition.
+    pub time: f32,
+    /// Address and read bit, `None` if the capture ended before they were complete.
+    pub address: Option<u8>,
+    pub read: bool,
+    pub address_ack: bool,
+    pub data: Vec<I2cByte>,
+    /// Ended with a stop condition, rather than a repeated start or the end of the capture.
+    pub stopped: bool,
+}
+
+/// Transactions from each start condition, SDA falling while SCL is high, to the next start or
+/// stop condition. Bits are sampled on rising SCL edges, nine to a byte with the acknowledge,
+/// and the bits of a byte cut short are dropped.
+pub fn decode(scl: &[bool], sda: &[bool], sample_rate: f32) -> Vec<I2cTransaction> {
+    let len = scl.len().min(sda.len());
+    let mut transactions = vec![];
+    let mut current: Option<(usize, Vec<bool>)> = None;
+
+    for idx in 1..len {
+        let clock_high = scl[idx - 1] && scl[idx];
+        let start = clock_high && sda[idx - 1] && !sda[idx];
+        let stop = clock_high && !sda[idx - 1] && sda[idx];
+
+        if start || stop {
+            if let Some((started, bits)) = current.take() {
+                transactions.push(transaction(started, &bits, sample_rate, stop));
+            }
+            if start {
+                current = Some((idx, vec![]));
+            }
+        } else if !scl[idx - 1] && scl[idx] {
+            if let Some((_, bits)) = current.as_mut() {
+                bits.push(sda[idx]);
+            }
+        }
+    }
+    if let Some((started, bits)) = current {
+        transactions.push(transaction(started, &bits, sample_rate, false));
+    }
+    transactions
+}
+
+fn transaction(started: usize, bits: &[bool], sample_rate: f32, stopped: bool) -> I2cTransaction {
+    let mut bytes = bits.chunks_exact(9).map(|byte| I2cByte {
+        value: byte[..8]
+            .iter()
+            .fold(0u8, |value, bit| value << 1 | *bit as u8),
+        ack: !byte[8],
+    });
+    let (address, read, address_ack) = match bytes.next() {
+        Some(first) => (Some(first.value >> 1), first.value & 1 == 1, first.ack),
+        None => (None, false, false),
+    };
+    I2cTransaction {
+        time: started as f32 / sample_rate,
+        address,
+        read,
+        address_ack,
+        data: bytes.collect(),
+        stopped,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// SCL and SDA as driven by a controller, each level held for two samples.
+    #[derive(Default)]
+    struct Bus {
+        scl: Vec<bool>,
+        sda: Vec<bool>,
+    }
+
+    impl Bus {
+        fn idle() -> Self {
+            let mut bus = Self::default();
+            bus.hold(true, true);
+            bus
+        }
+
+        fn hold(&mut self, scl: bool, sda: bool) {
+            self.scl.extend([scl, scl]);
+            self.sda.extend([sda, sda]);
+        }
+
+        fn start(&mut self) {
+            self.hold(true, true);
+            self.hold(true, false);
+            self.hold(false, false);
+        }
+
+        /// Releases SDA with SCL low first, as before a repeated start.
+        fn repeated_start(&mut self) {
+            self.hold(false, true);
+            self.start();
+        }
+
+        fn bit(&mut self, bit: bool) {
+            self.hold(false, bit);
+            self.hold(true, bit);
+            self.hold(false, bit);
+        }
+
+        fn byte(&mut self, value: u8, ack: bool) {
+            for bit in (0..8).rev() {
+                self.bit(value & (1 << bit) != 0);
+            }
+            self.bit(!ack);
+        }
+
+        fn stop(&mut self) {
+            self.hold(false, false);
+            self.hold(true, false);
+            self.hold(true, true);
+        }
+
+        fn decode(&self) -> Vec<I2cTransaction> {
+            decode(&self.scl, &self.sda, 1.0)
+        }
+    }
+
+    #[test]
+    fn decodes_a_write() {
+        let mut bus = Bus::idle();
+        bus.start();
+        bus.byte(0x50 << 1, true);
+        bus.byte(0xab, true);
+        bus.byte(0xcd, false);
+        bus.stop();
+        bus.hold(true, true);
+
+        assert_eq!(
+            bus.decode(),
+            vec![I2cTransaction {
+                // Idle and the first level of the start condition, SDA falls after them.
+                time: 4.0,
+                address: Some(0x50),
+                read: false,
+                address_ack: true,
+                data: vec![
+                    I2cByte {
+                        value: 0xab,
+                        ack: true,
+                    },
+                    I2cByte {
+                        value: 0xcd,
+                        ack: false,
+                    },
+                ],
+                stopped: true,
+            }]
+        );
+    }
+
+    #[test]
+    fn a_repeated_start_ends_a_transaction() {
+        let mut bus = Bus::idle();
+        bus.start();
+        bus.byte(0x50 << 1, true);
+        bus.byte(0x10, true);
+        bus.repeated_start();
+        bus.byte(0x50 << 1 | 1, true);
+        bus.byte(0x99, false);
+        bus.stop();
+
+        let transactions = bus.decode();
+
+        assert_eq!(transactions.len(), 2);
+        let (write, read) = (&transactions[0], &transactions[1]);
+        assert_eq!(write.address, Some(0x50));
+        assert!(!write.read);
+        assert_eq!(
+            write.data.iter().map(|it| it.value).collect::<Vec<_>>(),
+            vec![0x10]
+        );
+        assert!(!write.stopped);
+        assert_eq!(read.address, Some(0x50));
+        assert!(read.read);
+        assert!(read.address_ack);
+        assert_eq!(
+            read.data,
+            vec![I2cByte {
+                value: 0x99,
+                ack: false,
+            }]
+        );
+        assert!(read.stopped);
+    }
+
+    #[test]
+    fn drops_a_byte_cut_short() {
+        let mut bus = Bus::idle();
+        bus.start();
+        bus.byte(0x21 << 1, false);
+        for _ in 0..4 {
+            bus.bit(true);
+        }
+
+        let transactions = bus.decode();
+
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].address, Some(0x21));
+        assert!(!transactions[0].address_ack);
+        assert!(transactions[0].data.is_empty());
+        assert!(!transactions[0].stopped);
+    }
+
+    #[test]
+    fn no_address_before_the_end() {
+        let mut bus = Bus::idle();
+        bus.start();
+        for _ in 0..3 {
+            bus.bit(false);
+        }
+
+        let transactions = bus.decode();
+
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].address, None);
+    }
+}
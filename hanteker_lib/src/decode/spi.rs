@@ -0,0 +1,154 @@
+//! SPI from the clock and one data line, most significant bit first. Without chip select on a
+//! channel, transactions are told apart by the clock going idle in between.
+
+/// Clock idle for longer than this many of its periods ends a transaction.
+const IDLE_PERIODS: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SpiMode {
+    /// Clock idles high.
+    pub cpol: bool,
+    /// Data is sampled on the second edge of the clock rather than the first.
+    pub cpha: bool,
+}
+
+impl SpiMode {
+    /// Data is sampled on rising edges in modes 0 and 3, falling ones in modes 1 and 2.
+    fn samples_on_rising(&self) -> bool {
+        self.cpol == self.cpha
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpiTransaction {
+    /// Seconds from the first sample to the first sampling edge.
+    pub time: f32,
+    pub bytes: Vec<u8>,
+    /// Bits past the last whole byte, the clock stopped mid byte or the capture cut it off.
+    pub trailing_bits: usize,
+}
+
+pub fn decode(
+    clock: &[bool],
+    data: &[bool],
+    sample_rate: f32,
+    mode: &SpiMode,
+) -> Vec<SpiTransaction> {
+    let len = clock.len().min(data.len());
+    let rising = mode.samples_on_rising();
+    let edges: Vec<usize> = (1..len)
+        .filter(|idx| clock[idx - 1] != clock[*idx] && clock[*idx] == rising)
+        .collect();
+    let mut intervals: Vec<usize> = edges.windows(2).map(|it| it[1] - it[0]).collect();
+    intervals.sort_unstable();
+    let max_gap = intervals
+        .get(intervals.len() / 2)
+        .map_or(usize::MAX, |median| median * IDLE_PERIODS);
+
+    let mut transactions = vec![];
+    let mut bits: Vec<bool> = vec![];
+    let mut started = 0;
+    for (pos, edge) in edges.iter().enumerate() {
+        if pos > 0 && edge - edges[pos - 1] > max_gap {
+            transactions.push(transaction(started, &bits, sample_rate));
+            bits.clear();
+        }
+        if bits.is_empty() {
+            started = *edge;
+        }
+        bits.push(data[*edge]);
+    }
+    if !bits.is_empty() {
+        transactions.push(transaction(started, &bits, sample_rate));
+    }
+    transactions
+}
+
+fn transaction(started: usize, bits: &[bool], sample_rate: f32) -> SpiTransaction {
+    let bytes = bits
+        .chunks_exact(8)
+        .map(|byte| byte.iter().fold(0u8, |value, bit| value << 1 | *bit as u8))
+        .collect();
+    SpiTransaction {
+        time: started as f32 / sample_rate,
+        bytes,
+        trailing_bits: bits.len() % 8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples in half a clock period.
+    const HALF: usize = 2;
+
+    /// Clock and data of the bytes sent in the mode, with the clock idle around them. Data
+    /// changes half a period before the edge it's sampled on.
+    fn send(mode: &SpiMode, bytes: &[u8]) -> (Vec<bool>, Vec<bool>) {
+        let bits: Vec<bool> = bytes
+            .iter()
+            .flat_map(|byte| (0..8).rev().map(move |bit| byte & (1 << bit) != 0))
+            .collect();
+        let mut clock = vec![mode.cpol; 4 * HALF];
+        let mut data = vec![false; 4 * HALF];
+        let mut previous = false;
+        for bit in &bits {
+            // Idle level for the first half of the bit, the other level for the second.
+            clock.resize(clock.len() + HALF, mode.cpol);
+            clock.resize(clock.len() + HALF, !mode.cpol);
+            let first_half = if mode.cpha { previous } else { *bit };
+            data.resize(data.len() + HALF, first_half);
+            data.resize(data.len() + HALF, *bit);
+            previous = *bit;
+        }
+        clock.resize(clock.len() + 4 * HALF, mode.cpol);
+        data.resize(data.len() + HALF, previous);
+        data.resize(data.len() + 3 * HALF, false);
+        (clock, data)
+    }
+
+    #[test]
+    fn decodes_each_mode() {
+        for (cpol, cpha) in [(false, false), (false, true), (true, false), (true, true)] {
+            let mode = SpiMode { cpol, cpha };
+            let (clock, data) = send(&mode, &[0xa5, 0x3c]);
+
+            let transactions = decode(&clock, &data, 1.0, &mode);
+
+            assert_eq!(transactions.len(), 1, "{:?}", mode);
+            assert_eq!(transactions[0].bytes, vec![0xa5, 0x3c], "{:?}", mode);
+            assert_eq!(transactions[0].trailing_bits, 0, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn idle_clock_splits_transactions() {
+        let mode = SpiMode::default();
+        let (mut clock, mut data) = send(&mode, &[0x12]);
+        let (more_clock, more_data) = send(&mode, &[0x34, 0x56]);
+        clock.extend(more_clock);
+        data.extend(more_data);
+
+        let transactions = decode(&clock, &data, 1.0, &mode);
+
+        let bytes: Vec<Vec<u8>> = transactions.into_iter().map(|it| it.bytes).collect();
+        assert_eq!(bytes, vec![vec![0x12], vec![0x34, 0x56]]);
+    }
+
+    #[test]
+    fn counts_the_bits_past_the_last_byte() {
+        let mode = SpiMode::default();
+        let (mut clock, mut data) = send(&mode, &[0xff, 0x0f]);
+        // Cut off in the middle of the fourth bit of the second byte.
+        let cut = 4 * HALF + (8 + 3) * 2 * HALF + HALF;
+        clock.truncate(cut);
+        data.truncate(cut);
+
+        let transactions = decode(&clock, &data, 1.0, &mode);
+
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0].bytes, vec![0xff]);
+        assert_eq!(transactions[0].trailing_bits, 3);
+    }
+}
@@ -0,0 +1,167 @@
+//! 8N1 UART, idle high, least significant bit first.
+
+use crate::models::hantek2d42::{invalid, Hantek2D42Error};
+
+/// Fewest samples per bit at which the middle of a bit can still be found.
+const MIN_SAMPLES_PER_BIT: f32 = 3.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UartByte {
+    /// Seconds from the first sample to the falling edge of the start bit.
+    pub time: f32,
+    pub value: u8,
+    /// Stop bit found low, the value is likely garbage, e.g. from a wrong baud rate.
+    pub framing_error: bool,
+}
+
+/// Bytes of the logic levels, each bit sampled in its middle as timed from the falling edge of
+/// the start bit. A start bit that's gone by its middle is taken for a glitch, a byte cut off
+/// by the end of the capture is dropped.
+pub fn decode(
+    levels: &[bool],
+    sample_rate: f32,
+    baud: f32,
+) -> Result<Vec<UartByte>, Hantek2D42Error> {
+    let samples_per_bit = sample_rate / baud;
+    if samples_per_bit < MIN_SAMPLES_PER_BIT {
+        return Err(invalid(
+            "baud rate",
+            baud,
+            format!(
+                "at most {} at this sample rate, use a faster time scale",
+                sample_rate / MIN_SAMPLES_PER_BIT
+            ),
+        ));
+    }
+    // Sample in the middle of the given bit of the frame, the start bit being 0.
+    let at = |start: usize, bit: f32| start + ((bit + 0.5) * samples_per_bit) as usize;
+
+    let mut bytes = vec![];
+    let mut idx = 1;
+    while idx < levels.len() {
+        let falling = levels[idx - 1] && !levels[idx];
+        if !falling {
+            idx += 1;
+            continue;
+        }
+        let start = idx;
+        let stop = at(start, 9.0);
+        if stop >= levels.len() {
+            break;
+        }
+        if levels[at(start, 0.0)] {
+            idx += 1;
+            continue;
+        }
+
+        let value = (0..8)
+            .filter(|bit| levels[at(start, 1.0 + *bit as f32)])
+            .fold(0u8, |value, bit| value | (1 << bit));
+        bytes.push(UartByte {
+            time: start as f32 / sample_rate,
+            value,
+            framing_error: !levels[stop],
+        });
+        idx = stop;
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES_PER_BIT: usize = 10;
+    const SAMPLE_RATE: f32 = 9600.0 * SAMPLES_PER_BIT as f32;
+
+    fn idle(levels: &mut Vec<bool>, bits: usize) {
+        levels.resize(levels.len() + bits * SAMPLES_PER_BIT, true);
+    }
+
+    /// The start bit, the byte least significant bit first, and a stop bit of the given level.
+    fn frame(levels: &mut Vec<bool>, value: u8, stop: bool) {
+        let bits = std::iter::once(false)
+            .chain((0..8).map(|bit| value & (1 << bit) != 0))
+            .chain(std::iter::once(stop));
+        for bit in bits {
+            levels.resize(levels.len() + SAMPLES_PER_BIT, bit);
+        }
+    }
+
+    #[test]
+    fn decodes_a_clean_byte() {
+        let mut levels = vec![];
+        idle(&mut levels, 2);
+        frame(&mut levels, 0x5a, true);
+        idle(&mut levels, 1);
+        frame(&mut levels, 0x81, true);
+        idle(&mut levels, 2);
+
+        let bytes = decode(&levels, SAMPLE_RATE, 9600.0).unwrap();
+
+        assert_eq!(
+            bytes,
+            vec![
+                UartByte {
+                    time: (2 * SAMPLES_PER_BIT) as f32 / SAMPLE_RATE,
+                    value: 0x5a,
+                    framing_error: false,
+                },
+                UartByte {
+                    time: (13 * SAMPLES_PER_BIT) as f32 / SAMPLE_RATE,
+                    value: 0x81,
+                    framing_error: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn flags_a_low_stop_bit() {
+        let mut levels = vec![];
+        idle(&mut levels, 2);
+        frame(&mut levels, 0x00, false);
+        idle(&mut levels, 2);
+
+        let bytes = decode(&levels, SAMPLE_RATE, 9600.0).unwrap();
+
+        assert_eq!(bytes.len(), 1);
+        assert_eq!(bytes[0].value, 0x00);
+        assert!(bytes[0].framing_error);
+    }
+
+    #[test]
+    fn skips_a_glitch_start_bit() {
+        let mut levels = vec![];
+        idle(&mut levels, 2);
+        levels.extend([false, false]);
+        idle(&mut levels, 2);
+        frame(&mut levels, 0x42, true);
+        idle(&mut levels, 2);
+
+        let bytes = decode(&levels, SAMPLE_RATE, 9600.0).unwrap();
+
+        assert_eq!(bytes.len(), 1);
+        assert_eq!(bytes[0].value, 0x42);
+        assert!(!bytes[0].framing_error);
+    }
+
+    #[test]
+    fn drops_a_byte_cut_off_by_the_end() {
+        let mut levels = vec![];
+        idle(&mut levels, 2);
+        frame(&mut levels, 0x33, true);
+        frame(&mut levels, 0x44, true);
+        levels.truncate(levels.len() - SAMPLES_PER_BIT);
+
+        let bytes = decode(&levels, SAMPLE_RATE, 9600.0).unwrap();
+
+        assert_eq!(bytes.len(), 1);
+        assert_eq!(bytes[0].value, 0x33);
+    }
+
+    #[test]
+    fn rejects_too_few_samples_per_bit() {
+        assert!(decode(&[true; 64], SAMPLE_RATE, SAMPLE_RATE / 2.0).is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! Rare events in a stream of captures: glitches, pulses narrower than a given width, and runts,
+//! pulses crossing one threshold but falling back before the other. Each capture is scanned on
+//! its own, a pulse straddling two captures is missed.
+
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, SystemTime};
+
+use crate::capture::{CaptureFrame, COUNTS_PER_DIVISION};
+use crate::measure::{falling_edges, rising_edges};
+use crate::models::hantek2d42::{invalid, Hantek2D42Error};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Detect {
+    /// Pulses, high or low, narrower than this many seconds. Edges are found around the mid
+    /// level of each capture, so the time scale must be known.
+    Glitch(f32),
+    /// Pulses crossing the lower but not the upper threshold or the other way around, in volts,
+    /// so the scale must be known.
+    Runt(f32, f32),
+}
+
+impl Detect {
+    pub fn parse(text: &str) -> Result<Self, Hantek2D42Error> {
+        let bad = || {
+            invalid(
+                "detect",
+                text,
+                "glitch:<width>, e.g. glitch:100ns, or runt:<low volts>..<high volts>",
+            )
+        };
+        let (kind, value) = text.split_once(':').ok_or_else(bad)?;
+        match kind {
+            "glitch" => match parse_seconds(value) {
+                Some(width) if width > 0.0 => Ok(Self::Glitch(width)),
+                _ => Err(bad()),
+            },
+            "runt" => {
+                let (low, high) = value.split_once("..").ok_or_else(bad)?;
+                match (low.parse::<f32>(), high.parse::<f32>()) {
+                    (Ok(low), Ok(high)) if low < high => Ok(Self::Runt(low, high)),
+                    _ => Err(bad()),
+                }
+            }
+            _ => Err(bad()),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Glitch(_) => "glitch",
+            Self::Runt(..) => "runt",
+        }
+    }
+
+    /// Events on a channel of the frame, `None` if it wasn't captured or what the detection
+    /// needs is unknown.
+    pub fn scan(&self, frame: &CaptureFrame, channel_no: usize) -> Option<Vec<Detection>> {
+        let pulses = match self {
+            Self::Glitch(width) => {
+                let raw: Vec<f32> = frame
+                    .channel_raw(channel_no)?
+                    .into_iter()
+                    .map(|it| (it as i8) as f32)
+                    .collect();
+                let max_len = width * frame.sample_rate()?;
+                glitches(&raw, max_len)
+            }
+            Self::Runt(low, high) => runts(&frame.channel_volts(channel_no)?, *low, *high),
+        };
+        Some(
+            pulses
+                .into_iter()
+                .map(|it| Detection {
+                    channel_no,
+                    sample: it.start,
+                    high: it.high,
+                    width: frame
+                        .sample_rate()
+                        .map(|rate| Duration::from_secs_f64(it.len as f64 / rate as f64)),
+                    timestamp: frame.sample_timestamp(it.start),
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Display for Detect {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Glitch(width) => {
+                let (value, unit) = match width {
+                    it if *it < 1e-6 => (it * 1e9, "ns"),
+                    it if *it < 1e-3 => (it * 1e6, "us"),
+                    it if *it < 1.0 => (it * 1e3, "ms"),
+                    it => (*it, "s"),
+                };
+                // Rounded off the float noise of scaling, e.g. 100.00001ns.
+                write!(f, "glitch:{}{}", (value * 1e3).round() / 1e3, unit)
+            }
+            Self::Runt(low, high) => write!(f, "runt:{}..{}", low, high),
+        }
+    }
+}
+
+/// Seconds, with an optional ns, us, ms or s suffix.
+fn parse_seconds(value: &str) -> Option<f32> {
+    let units = [("ns", 1e-9), ("us", 1e-6), ("ms", 1e-3), ("s", 1.0)];
+    let (number, multiplier) = units
+        .iter()
+        .find_map(|(suffix, multiplier)| Some((value.strip_suffix(suffix)?, *multiplier)))
+        .unwrap_or((value, 1.0));
+    number.parse::<f32>().ok().map(|it| it * multiplier)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Detection {
+    pub channel_no: usize,
+    /// Position in the capture of the first sample of the pulse.
+    pub sample: usize,
+    /// Whether the pulse goes up from the low level, down from the high level otherwise.
+    pub high: bool,
+    pub width: Option<Duration>,
+    /// Wall clock time of the first sample of the pulse.
+    pub timestamp: Option<SystemTime>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Pulse {
+    start: usize,
+    len: usize,
+    high: bool,
+}
+
+/// Pulses between edges closer than `max_len` samples, not counting the partial ones at either
+/// end. A capture swinging less than a division has no edges to speak of, its noise isn't taken
+/// for glitches.
+fn glitches(raw: &[f32], max_len: f32) -> Vec<Pulse> {
+    let min = raw.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = raw.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    if max - min < COUNTS_PER_DIVISION {
+        return vec![];
+    }
+
+    let falling = falling_edges(raw, min, max)
+        .into_iter()
+        .map(|it| (it, false));
+    let mut edges: Vec<(usize, bool)> = rising_edges(raw, min, max)
+        .into_iter()
+        .map(|it| (it, true))
+        .chain(falling)
+        .collect();
+    edges.sort_unstable();
+    edges
+        .windows(2)
+        .filter(|it| it[0].1 != it[1].1 && ((it[1].0 - it[0].0) as f32) < max_len)
+        .map(|it| Pulse {
+            start: it[0].0,
+            len: it[1].0 - it[0].0,
+            high: it[0].1,
+        })
+        .collect()
+}
+
+/// Excursions from below `low` or above `high` that fall back without reaching the other
+/// threshold. Samples before the signal is first out of the band aren't part of any.
+fn runts(volts: &[f32], low: f32, high: f32) -> Vec<Pulse> {
+    let mut pulses = vec![];
+    // Last level the signal was out of the band at, and where it left it since.
+    let mut level: Option<bool> = None;
+    let mut left: Option<usize> = None;
+    for (idx, sample) in volts.iter().enumerate() {
+        let now = if *sample > high {
+            Some(true)
+        } else if *sample < low {
+            Some(false)
+        } else {
+            None
+        };
+        match (level, now) {
+            (Some(_), None) => {
+                left.get_or_insert(idx);
+            }
+            (Some(from), Some(to)) if from == to => {
+                if let Some(start) = left.take() {
+                    pulses.push(Pulse {
+                        start,
+                        len: idx - start,
+                        high: !from,
+                    });
+                }
+            }
+            (_, Some(to)) => {
+                level = Some(to);
+                left = None;
+            }
+            (None, None) => {}
+        }
+    }
+    pulses
+}
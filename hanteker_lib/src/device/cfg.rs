@@ -1,20 +1,33 @@
 //! TODO not all types need to be float, some should actually be u32, e.g. AWG Amplitude.
 
-use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
-#[cfg(feature = "gui")]
-use std::hash::Hash;
 use std::time::Duration;
 
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter, EnumString, EnumVariantNames};
+
+// Frontend integrations, each one pulled in only by its own feature so that a plain
+// `default-features = false` build of the lib depends on neither clap, druid nor serde.
 #[cfg(feature = "cli")]
 use clap::ArgEnum;
 #[cfg(feature = "gui")]
-use druid::Data;
-use strum::IntoEnumIterator;
-use strum_macros::{Display, EnumIter, EnumString, EnumVariantNames};
+use druid::{Data, Lens};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+mod catalog;
+#[cfg(feature = "serde")]
+mod compat;
+mod diff;
+#[cfg(feature = "gui")]
+mod gui;
+
+pub use catalog::{option_catalog, Choice, OptionSpec, ValueSpace};
+pub use diff::{Change, ConfigChange};
 
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "gui", derive(Data))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Adjustment {
     pub upper: f32,
     pub lower: f32,
@@ -69,6 +82,7 @@ impl Adjustment {
 #[derive(Display, Debug, Clone, EnumString, EnumIter, EnumVariantNames, PartialEq, Eq)]
 #[cfg_attr(feature = "cli", derive(ArgEnum))]
 #[cfg_attr(feature = "gui", derive(Data))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum DeviceFunction {
     Scope,
     AWG,
@@ -98,6 +112,7 @@ impl DeviceFunction {
 #[derive(Display, Debug, Clone, EnumString, EnumIter, EnumVariantNames, PartialEq, Eq)]
 #[cfg_attr(feature = "cli", derive(ArgEnum))]
 #[cfg_attr(feature = "gui", derive(Data))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum RunningStatus {
     Start,
     Stop,
@@ -134,6 +149,7 @@ impl RunningStatus {
 #[derive(Display, Debug, Clone, EnumString, EnumIter, EnumVariantNames, PartialEq, Eq)]
 #[cfg_attr(feature = "cli", derive(ArgEnum))]
 #[cfg_attr(feature = "gui", derive(Data))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Coupling {
     AC,
     DC,
@@ -163,6 +179,7 @@ impl Coupling {
 #[derive(Display, Debug, Clone, EnumString, EnumIter, EnumVariantNames, PartialEq, Eq)]
 #[cfg_attr(feature = "cli", derive(ArgEnum))]
 #[cfg_attr(feature = "gui", derive(Data))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Probe {
     X1,
     X10,
@@ -194,6 +211,7 @@ impl Probe {
 #[derive(Display, Debug, Clone, EnumString, EnumIter, EnumVariantNames, PartialEq, Eq)]
 #[cfg_attr(feature = "cli", derive(ArgEnum))]
 #[cfg_attr(feature = "gui", derive(Data))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Scale {
     mv10,
     mv20,
@@ -246,12 +264,32 @@ impl Scale {
             // Self::V100 => ?,
         }
     }
+
+    pub fn volts_per_division(&self) -> f32 {
+        self.raw_value()
+    }
+
+    /// Volts per division in engineering notation, e.g. 200mV.
+    pub fn engineering(&self) -> Engineering {
+        Engineering::new(self.raw_value(), "V")
+    }
+
+    /// The scale closest to `volts` per division, `None` if it isn't a positive number.
+    pub fn from_volts_per_div(volts: f32) -> Option<Self> {
+        nearest_step(Self::my_iter(), Self::raw_value, volts, false)
+    }
+
+    /// Like [Self::from_volts_per_div], but `None` unless a scale is exactly `volts` per division.
+    pub fn from_volts_per_div_exact(volts: f32) -> Option<Self> {
+        nearest_step(Self::my_iter(), Self::raw_value, volts, true)
+    }
 }
 
 #[allow(non_camel_case_types)]
 #[derive(Display, Debug, Clone, EnumString, EnumIter, EnumVariantNames, PartialEq, Eq)]
 #[cfg_attr(feature = "cli", derive(ArgEnum))]
 #[cfg_attr(feature = "gui", derive(Data))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TimeScale {
     ns5,
     ns10,
@@ -307,12 +345,136 @@ impl TimeScale {
     pub fn my_to_string(&self) -> impl std::fmt::Display + '_ {
         self
     }
+
+    /// Seconds per division.
+    pub fn raw_value(&self) -> f32 {
+        match self {
+            Self::ns5 => 5e-9,
+            Self::ns10 => 10e-9,
+            Self::ns20 => 20e-9,
+            Self::ns50 => 50e-9,
+            Self::ns100 => 100e-9,
+            Self::ns200 => 200e-9,
+            Self::ns500 => 500e-9,
+            Self::us1 => 1e-6,
+            Self::us2 => 2e-6,
+            Self::us5 => 5e-6,
+            Self::us10 => 10e-6,
+            Self::us20 => 20e-6,
+            Self::us50 => 50e-6,
+            Self::us100 => 100e-6,
+            Self::us200 => 200e-6,
+            Self::us500 => 500e-6,
+            Self::ms1 => 1e-3,
+            Self::ms2 => 2e-3,
+            Self::ms5 => 5e-3,
+            Self::ms10 => 10e-3,
+            Self::ms20 => 20e-3,
+            Self::ms50 => 50e-3,
+            Self::ms100 => 100e-3,
+            Self::ms200 => 200e-3,
+            Self::ms500 => 500e-3,
+            Self::s1 => 1.0,
+            Self::s2 => 2.0,
+            Self::s5 => 5.0,
+            Self::s10 => 10.0,
+            Self::s20 => 20.0,
+            Self::s50 => 50.0,
+            Self::s100 => 100.0,
+            Self::s200 => 200.0,
+            Self::s500 => 500.0,
+        }
+    }
+
+    /// Time per division.
+    pub fn as_duration(&self) -> Duration {
+        // Through whole nanoseconds, f32 seconds are off by a bit for most steps.
+        Duration::from_nanos((self.raw_value() as f64 * 1e9).round() as u64)
+    }
+
+    /// Sample rate of a capture of `memory_depth` samples spanning the screen.
+    pub fn samples_per_second(&self, memory_depth: usize) -> f32 {
+        memory_depth as f32 / (HORIZONTAL_DIVISIONS as f32 * self.raw_value())
+    }
+
+    /// Time per division in engineering notation, e.g. 100us.
+    pub fn engineering(&self) -> Engineering {
+        Engineering::new(self.raw_value(), "s")
+    }
+
+    /// The time scale closest to `seconds` per division, `None` if it isn't a positive number.
+    pub fn from_seconds_per_div(seconds: f32) -> Option<Self> {
+        nearest_step(Self::my_iter(), Self::raw_value, seconds, false)
+    }
+
+    /// Like [Self::from_seconds_per_div], but `None` unless a time scale is exactly `seconds` per
+    /// division.
+    pub fn from_seconds_per_div_exact(seconds: f32) -> Option<Self> {
+        nearest_step(Self::my_iter(), Self::raw_value, seconds, true)
+    }
+}
+
+/// Horizontal divisions of the screen, a capture of the full memory depth spans all of them.
+pub const HORIZONTAL_DIVISIONS: usize = 12;
+
+/// A value with an SI prefix keeping its mantissa in 1..1000, e.g. 0.2 V as 200mV. Micro is
+/// written as u.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Engineering {
+    pub value: f32,
+    pub unit: &'static str,
+}
+
+impl Engineering {
+    pub fn new(value: f32, unit: &'static str) -> Self {
+        Self { value, unit }
+    }
+}
+
+impl Display for Engineering {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let prefixes = [(1e3, "k"), (1.0, ""), (1e-3, "m"), (1e-6, "u"), (1e-9, "n")];
+        let magnitude = self.value.abs();
+        let (multiplier, prefix) = if magnitude == 0.0 {
+            (1.0, "")
+        } else {
+            *prefixes
+                .iter()
+                .find(|(multiplier, _)| magnitude >= *multiplier * (1.0 - 1e-6))
+                .unwrap_or(&prefixes[prefixes.len() - 1])
+        };
+        // Rounded off the float noise of scaling, e.g. 99.99999mV.
+        let mantissa = (self.value / multiplier * 1e3).round() / 1e3;
+        write!(f, "{}{}{}", mantissa, prefix, self.unit)
+    }
+}
+
+/// The step closest to `value` by ratio, so 0.15 goes to 0.2 rather than 0.1 as the steps
+/// themselves go up by ratio. With `exact`, only a step equal to `value` but for float noise.
+fn nearest_step<T>(
+    steps: impl Iterator<Item = T>,
+    step_value: impl Fn(&T) -> f32,
+    value: f32,
+    exact: bool,
+) -> Option<T> {
+    if !(value.is_finite() && value > 0.0) {
+        return None;
+    }
+    let (distance, step) = steps
+        .map(|it| ((step_value(&it) / value).ln().abs(), it))
+        .min_by(|a, b| a.0.total_cmp(&b.0))?;
+    if exact && distance > 1e-4 {
+        None
+    } else {
+        Some(step)
+    }
 }
 
 #[allow(non_camel_case_types)]
 #[derive(Display, Debug, Clone, EnumString, EnumIter, EnumVariantNames, PartialEq, Eq)]
 #[cfg_attr(feature = "cli", derive(ArgEnum))]
 #[cfg_attr(feature = "gui", derive(Data))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TriggerSlope {
     Rising,
     Falling,
@@ -343,6 +505,7 @@ impl TriggerSlope {
 #[derive(Display, Debug, Clone, EnumString, EnumIter, EnumVariantNames, PartialEq, Eq)]
 #[cfg_attr(feature = "cli", derive(ArgEnum))]
 #[cfg_attr(feature = "gui", derive(Data))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum TriggerMode {
     Auto,
     Normal,
@@ -373,6 +536,7 @@ impl TriggerMode {
 #[derive(Display, Debug, Clone, EnumString, EnumIter, EnumVariantNames, PartialEq, Eq)]
 #[cfg_attr(feature = "cli", derive(ArgEnum))]
 #[cfg_attr(feature = "gui", derive(Data))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum AwgType {
     Square,
     Ramp,
@@ -406,6 +570,7 @@ impl AwgType {
 
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "gui", derive(Data))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct TrapDuty {
     pub high: f32,
     pub low: f32,
@@ -437,19 +602,18 @@ impl Display for TrapDuty {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "gui", derive(Lens))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "compat::ConfigRepr"))]
 pub struct HantekConfig {
     pub timeout: Option<Duration>,
 
     pub device_function: Option<DeviceFunction>,
 
-    pub enabled_channels: HashMap<usize, Option<bool>>,
-    pub channel_coupling: HashMap<usize, Option<Coupling>>,
-    pub channel_probe: HashMap<usize, Option<Probe>>,
-    pub channel_scale: HashMap<usize, Option<Scale>>,
-    pub channel_offset: HashMap<usize, Option<f32>>,
-    pub channel_bandwidth_limit: HashMap<usize, Option<bool>>,
-    pub channel_offset_adjustment: HashMap<usize, Option<Adjustment>>,
+    /// Settings of each channel, channel 1 first. See [HantekConfig::channel].
+    #[cfg_attr(feature = "gui", lens(ignore))]
+    pub channels: Vec<ChannelConfig>,
 
     pub time_scale: Option<TimeScale>,
     pub time_offset: Option<f32>,
@@ -479,13 +643,7 @@ impl HantekConfig {
 
             device_function: None,
 
-            enabled_channels: (1..=num_channels).map(|idx| (idx, None)).collect(),
-            channel_coupling: (1..=num_channels).map(|idx| (idx, None)).collect(),
-            channel_probe: (1..=num_channels).map(|idx| (idx, None)).collect(),
-            channel_scale: (1..=num_channels).map(|idx| (idx, None)).collect(),
-            channel_offset: (1..=num_channels).map(|idx| (idx, None)).collect(),
-            channel_bandwidth_limit: (1..=num_channels).map(|idx| (idx, None)).collect(),
-            channel_offset_adjustment: (1..=num_channels).map(|idx| (idx, None)).collect(),
+            channels: vec![ChannelConfig::default(); num_channels],
 
             time_scale: None,
             time_offset: None,
@@ -508,160 +666,84 @@ impl HantekConfig {
             awg_running_status: None,
         }
     }
-}
-
-#[cfg(feature = "gui")]
-impl Data for HantekConfig {
-    fn same(&self, other: &Self) -> bool {
-        if self.timeout != other.timeout {
-            return false;
-        }
-
-        if self.device_function != other.device_function {
-            return false;
-        }
 
-        if self.enabled_channels != other.enabled_channels {
-            return false;
-        }
-        if self.channel_coupling != other.channel_coupling {
-            return false;
-        }
-        if self.channel_probe != other.channel_probe {
-            return false;
-        }
-        if self.channel_scale != other.channel_scale {
-            return false;
-        }
-        if self.channel_bandwidth_limit != other.channel_bandwidth_limit {
-            return false;
-        }
+    pub fn num_channels(&self) -> usize {
+        self.channels.len()
+    }
 
-        if !compare_map(
-            &self.channel_offset,
-            &other.channel_offset,
-            compare_some_f32,
-        ) {
-            return false;
-        }
+    /// Settings of a channel, numbered from 1, `None` if there's no such channel.
+    pub fn channel(&self, channel_no: usize) -> Option<&ChannelConfig> {
+        self.channels.get(channel_no.checked_sub(1)?)
+    }
 
-        if !compare_map(
-            &self.channel_offset_adjustment,
-            &other.channel_offset_adjustment,
-            compare_some_adjustment,
-        ) {
-            return false;
+    /// Same as [HantekConfig::channel], for updating the settings. Channels up to this one are
+    /// added, unset, if the config has fewer; `None` only for channel 0.
+    pub fn channel_mut(&mut self, channel_no: usize) -> Option<&mut ChannelConfig> {
+        let idx = channel_no.checked_sub(1)?;
+        if self.channels.len() <= idx {
+            self.channels.resize(channel_no, ChannelConfig::default());
         }
+        self.channels.get_mut(idx)
+    }
 
-        if self.time_scale != other.time_scale {
-            return false;
-        }
-        if !compare_some_f32(&self.time_offset, &other.time_offset) {
-            return false;
-        }
-        if !compare_some_adjustment(&self.time_offset_adjustment, &other.time_offset_adjustment) {
-            return false;
-        }
+    /// Every channel along with its number.
+    pub fn channels(&self) -> impl Iterator<Item = (usize, &ChannelConfig)> {
+        self.channels
+            .iter()
+            .enumerate()
+            .map(|(idx, it)| (idx + 1, it))
+    }
 
-        if self.running_status != other.running_status {
-            return false;
-        }
-        if self.trigger_source_channel != other.trigger_source_channel {
-            return false;
-        }
-        if self.trigger_slope != other.trigger_slope {
-            return false;
-        }
-        if self.trigger_mode != other.trigger_mode {
-            return false;
-        }
+    /// Numbers of the channels known to be enabled.
+    pub fn enabled_channels(&self) -> Vec<usize> {
+        self.channels()
+            .filter(|(_, it)| it.enabled == Some(true))
+            .map(|(channel_no, _)| channel_no)
+            .collect()
+    }
+}
 
-        if !compare_some_adjustment(
-            &self.trigger_level_adjustment,
-            &other.trigger_level_adjustment,
-        ) {
-            return false;
-        }
-        if !compare_some_f32(&self.trigger_level, &other.trigger_level) {
-            return false;
-        }
+/// Settings of a single channel, `None` for whatever was never set.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct ChannelConfig {
+    pub enabled: Option<bool>,
+    pub coupling: Option<Coupling>,
+    pub probe: Option<Probe>,
+    pub scale: Option<Scale>,
+    /// Raw offset, see [Adjustment] for the volts it spans.
+    pub offset: Option<f32>,
+    pub bandwidth_limit: Option<bool>,
+    pub offset_adjustment: Option<Adjustment>,
+}
 
-        if self.awg_type != other.awg_type {
-            return false;
-        }
+impl ChannelConfig {
+    pub fn enabled(&self) -> Option<bool> {
+        self.enabled
+    }
 
-        if !compare_some_f32(&self.awg_frequency, &other.awg_frequency) {
-            return false;
-        }
-        if !compare_some_f32(&self.awg_amplitude, &other.awg_amplitude) {
-            return false;
-        }
-        if !compare_some_f32(&self.awg_offset, &other.awg_offset) {
-            return false;
-        }
-        if !compare_some_f32(&self.awg_duty_square, &other.awg_duty_square) {
-            return false;
-        }
-        if !compare_some_f32(&self.awg_duty_ramp, &other.awg_duty_ramp) {
-            return false;
-        }
-        if !compare_some_trap_duty(&self.awg_duty_trap, &other.awg_duty_trap) {
-            return false;
-        }
-        if self.awg_running_status != other.awg_running_status {
-            return false;
-        }
+    pub fn coupling(&self) -> Option<&Coupling> {
+        self.coupling.as_ref()
+    }
 
-        true
+    pub fn probe(&self) -> Option<&Probe> {
+        self.probe.as_ref()
     }
-}
 
-#[cfg(feature = "gui")]
-fn compare_some_trap_duty(t0: &Option<TrapDuty>, t1: &Option<TrapDuty>) -> bool {
-    if t0.is_some() != t1.is_some() {
-        false
-    } else if t0.is_some() {
-        let t0 = t0.as_ref().unwrap();
-        let t1 = t1.as_ref().unwrap();
-        t0.same(t1)
-    } else {
-        true
+    pub fn scale(&self) -> Option<&Scale> {
+        self.scale.as_ref()
     }
-}
 
-#[cfg(feature = "gui")]
-fn compare_some_f32(f0: &Option<f32>, f1: &Option<f32>) -> bool {
-    if f0.is_some() != f1.is_some() {
-        false
-    } else if f0.is_some() {
-        let f0 = f0.unwrap().to_bits();
-        let f1 = f1.unwrap().to_bits();
-        f0 == f1
-    } else {
-        true
+    pub fn offset(&self) -> Option<f32> {
+        self.offset
     }
-}
 
-#[cfg(feature = "gui")]
-fn compare_some_adjustment(a0: &Option<Adjustment>, a1: &Option<Adjustment>) -> bool {
-    if a0.is_some() != a1.is_some() {
-        false
-    } else if a0.is_some() {
-        let a0 = a0.as_ref().unwrap();
-        let a1 = a1.as_ref().unwrap();
-        a0.same(a1)
-    } else {
-        true
+    pub fn bandwidth_limit(&self) -> Option<bool> {
+        self.bandwidth_limit
     }
-}
 
-#[cfg(feature = "gui")]
-fn compare_map<K: std::cmp::Eq + Hash, V>(
-    m0: &HashMap<K, V>,
-    m1: &HashMap<K, V>,
-    comparator: impl Fn(&V, &V) -> bool,
-) -> bool {
-    m0.len() == m1.len()
-        && m0.keys().all(|k| m1.contains_key(k))
-        && m0.iter().all(|(k0, v0)| comparator(v0, &m1[k0]))
+    pub fn offset_adjustment(&self) -> Option<&Adjustment> {
+        self.offset_adjustment.as_ref()
+    }
 }
@@ -0,0 +1,212 @@
+//! Every settable parameter with its value space and unit, for frontends building their
+//! controls generically instead of hard-coding each enum.
+
+use crate::device::cfg::{
+    AwgType, Coupling, DeviceFunction, Probe, Scale, TimeScale, TriggerMode, TriggerSlope,
+};
+use crate::models::hantek2d42::{
+    awg_frequency_max, AWG_AMPLITUDE_MAX, AWG_DUTY_MAX, AWG_DUTY_MIN, AWG_FREQUENCY_MIN,
+    AWG_OFFSET_MAX,
+};
+
+/// Raw value of channel offset and trigger level at the top of the screen, the device takes
+/// 0..=200 for the 8 vertical divisions.
+const RAW_MAX: f32 = 200.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Choice {
+    /// Name as taken by `FromStr` and printed by `Display` of the option's type.
+    pub name: String,
+    /// Physical value of the option in the unit of the parameter, if it has one.
+    pub value: Option<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValueSpace {
+    Bool,
+    /// One of the given options, in ascending order where they have an order.
+    Choice(Vec<Choice>),
+    /// Any number within the bounds, inclusive. A missing bound is one the lib doesn't know.
+    Number {
+        min: Option<f32>,
+        max: Option<f32>,
+    },
+    /// Number of a scope channel.
+    Channel,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionSpec {
+    /// Dotted name, e.g. `trigger.level`.
+    pub name: &'static str,
+    /// Set on each channel separately.
+    pub per_channel: bool,
+    pub values: ValueSpace,
+    pub unit: Option<&'static str>,
+    pub description: &'static str,
+}
+
+impl OptionSpec {
+    fn new(name: &'static str, values: ValueSpace, description: &'static str) -> Self {
+        Self {
+            name,
+            per_channel: name.starts_with("channel."),
+            values,
+            unit: None,
+            description,
+        }
+    }
+
+    fn unit(mut self, unit: &'static str) -> Self {
+        self.unit = Some(unit);
+        self
+    }
+}
+
+fn choices<T>(options: Vec<(String, T)>, value: impl Fn(&T) -> Option<f32>) -> ValueSpace {
+    ValueSpace::Choice(
+        options
+            .into_iter()
+            .map(|(name, it)| Choice {
+                value: value(&it),
+                name,
+            })
+            .collect(),
+    )
+}
+
+fn named<T>(options: Vec<(String, T)>) -> ValueSpace {
+    choices(options, |_| None)
+}
+
+fn number(min: Option<f32>, max: Option<f32>) -> ValueSpace {
+    ValueSpace::Number { min, max }
+}
+
+fn probe_multiplier(probe: &Probe) -> Option<f32> {
+    Some(match probe {
+        Probe::X1 => 1.0,
+        Probe::X10 => 10.0,
+        Probe::X100 => 100.0,
+        Probe::X1000 => 1000.0,
+    })
+}
+
+/// All settable parameters, in the order a front panel would show them.
+pub fn option_catalog() -> Vec<OptionSpec> {
+    vec![
+        OptionSpec::new(
+            "device.function",
+            named(DeviceFunction::my_options()),
+            "Function the device runs as",
+        ),
+        OptionSpec::new(
+            "device.running",
+            ValueSpace::Bool,
+            "Whether the scope is acquiring",
+        ),
+        OptionSpec::new(
+            "channel.enabled",
+            ValueSpace::Bool,
+            "Whether the channel is shown and captured",
+        ),
+        OptionSpec::new(
+            "channel.scale",
+            choices(Scale::my_options(), |it| Some(it.raw_value())),
+            "Vertical scale",
+        )
+        .unit("V/div"),
+        OptionSpec::new(
+            "channel.coupling",
+            named(Coupling::my_options()),
+            "Input coupling",
+        ),
+        OptionSpec::new(
+            "channel.probe",
+            choices(Probe::my_options(), probe_multiplier),
+            "Attenuation of the probe",
+        )
+        .unit("x"),
+        OptionSpec::new(
+            "channel.offset",
+            number(Some(0.0), Some(RAW_MAX)),
+            "Vertical position of the channel's zero level",
+        )
+        .unit("raw"),
+        OptionSpec::new(
+            "channel.bandwidth_limit",
+            ValueSpace::Bool,
+            "Whether the bandwidth limit filter is on",
+        ),
+        OptionSpec::new(
+            "time_scale",
+            choices(TimeScale::my_options(), |it| Some(it.raw_value())),
+            "Horizontal scale",
+        )
+        .unit("s/div"),
+        OptionSpec::new(
+            "trigger.source",
+            ValueSpace::Channel,
+            "Channel the trigger looks at",
+        ),
+        OptionSpec::new(
+            "trigger.slope",
+            named(TriggerSlope::my_options()),
+            "Edge the trigger fires on",
+        ),
+        OptionSpec::new(
+            "trigger.mode",
+            named(TriggerMode::my_options()),
+            "When the scope acquires",
+        ),
+        OptionSpec::new(
+            "trigger.level",
+            number(Some(0.0), Some(RAW_MAX)),
+            "Level the trigger fires at",
+        )
+        .unit("raw"),
+        OptionSpec::new(
+            "awg.type",
+            named(AwgType::my_options()),
+            "Waveform of the generator",
+        ),
+        OptionSpec::new(
+            "awg.frequency",
+            number(
+                Some(AWG_FREQUENCY_MIN),
+                Some(awg_frequency_max(&AwgType::Sin)),
+            ),
+            "Frequency of the generator, the maximum depends on the waveform",
+        )
+        .unit("Hz"),
+        OptionSpec::new(
+            "awg.amplitude",
+            number(Some(-AWG_AMPLITUDE_MAX), Some(AWG_AMPLITUDE_MAX)),
+            "Amplitude of the generator",
+        )
+        .unit("V"),
+        OptionSpec::new(
+            "awg.offset",
+            number(Some(-AWG_OFFSET_MAX), Some(AWG_OFFSET_MAX)),
+            "DC offset of the generator",
+        )
+        .unit("V"),
+        OptionSpec::new(
+            "awg.duty_square",
+            number(Some(AWG_DUTY_MIN), Some(AWG_DUTY_MAX)),
+            "Duty cycle of the square waveform",
+        )
+        .unit("%"),
+        OptionSpec::new(
+            "awg.duty_ramp",
+            number(Some(AWG_DUTY_MIN), Some(AWG_DUTY_MAX)),
+            "Duty cycle of the ramp waveform",
+        )
+        .unit("%"),
+        OptionSpec::new(
+            "awg.running",
+            ValueSpace::Bool,
+            "Whether the generator outputs",
+        ),
+    ]
+}
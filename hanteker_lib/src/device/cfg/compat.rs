@@ -0,0 +1,118 @@
+//! Configs as they were written before the settings of a channel were kept together, one map of
+//! channel number to setting per setting, e.g. `"channel_scale": {"1": "v1"}`. They're still
+//! read, into [HantekConfig::channels]; configs are always written the current way.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::device::cfg::{
+    Adjustment, AwgType, ChannelConfig, Coupling, DeviceFunction, HantekConfig, Probe,
+    RunningStatus, Scale, TimeScale, TrapDuty, TriggerMode, TriggerSlope,
+};
+
+/// A config of either layout, both may even be mixed, a setting of the maps taking precedence.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ConfigRepr {
+    timeout: Option<Duration>,
+
+    device_function: Option<DeviceFunction>,
+
+    #[serde(default)]
+    channels: Vec<ChannelConfig>,
+
+    #[serde(default)]
+    enabled_channels: HashMap<usize, Option<bool>>,
+    #[serde(default)]
+    channel_coupling: HashMap<usize, Option<Coupling>>,
+    #[serde(default)]
+    channel_probe: HashMap<usize, Option<Probe>>,
+    #[serde(default)]
+    channel_scale: HashMap<usize, Option<Scale>>,
+    #[serde(default)]
+    channel_offset: HashMap<usize, Option<f32>>,
+    #[serde(default)]
+    channel_bandwidth_limit: HashMap<usize, Option<bool>>,
+    #[serde(default)]
+    channel_offset_adjustment: HashMap<usize, Option<Adjustment>>,
+
+    time_scale: Option<TimeScale>,
+    time_offset: Option<f32>,
+    time_offset_adjustment: Option<Adjustment>,
+
+    running_status: Option<RunningStatus>,
+    trigger_source_channel: Option<usize>,
+    trigger_slope: Option<TriggerSlope>,
+    trigger_mode: Option<TriggerMode>,
+    trigger_level_adjustment: Option<Adjustment>,
+    trigger_level: Option<f32>,
+
+    awg_type: Option<AwgType>,
+    awg_frequency: Option<f32>,
+    awg_amplitude: Option<f32>,
+    awg_offset: Option<f32>,
+    awg_duty_square: Option<f32>,
+    awg_duty_ramp: Option<f32>,
+    awg_duty_trap: Option<TrapDuty>,
+    awg_running_status: Option<RunningStatus>,
+}
+
+impl From<ConfigRepr> for HantekConfig {
+    fn from(repr: ConfigRepr) -> Self {
+        let mut config = HantekConfig {
+            timeout: repr.timeout,
+
+            device_function: repr.device_function,
+
+            channels: repr.channels,
+
+            time_scale: repr.time_scale,
+            time_offset: repr.time_offset,
+            time_offset_adjustment: repr.time_offset_adjustment,
+
+            running_status: repr.running_status,
+            trigger_source_channel: repr.trigger_source_channel,
+            trigger_slope: repr.trigger_slope,
+            trigger_mode: repr.trigger_mode,
+            trigger_level_adjustment: repr.trigger_level_adjustment,
+            trigger_level: repr.trigger_level,
+
+            awg_type: repr.awg_type,
+            awg_frequency: repr.awg_frequency,
+            awg_amplitude: repr.awg_amplitude,
+            awg_offset: repr.awg_offset,
+            awg_duty_square: repr.awg_duty_square,
+            awg_duty_ramp: repr.awg_duty_ramp,
+            awg_duty_trap: repr.awg_duty_trap,
+            awg_running_status: repr.awg_running_status,
+        };
+
+        merge(&mut config, repr.enabled_channels, |it| &mut it.enabled);
+        merge(&mut config, repr.channel_coupling, |it| &mut it.coupling);
+        merge(&mut config, repr.channel_probe, |it| &mut it.probe);
+        merge(&mut config, repr.channel_scale, |it| &mut it.scale);
+        merge(&mut config, repr.channel_offset, |it| &mut it.offset);
+        merge(&mut config, repr.channel_bandwidth_limit, |it| {
+            &mut it.bandwidth_limit
+        });
+        merge(&mut config, repr.channel_offset_adjustment, |it| {
+            &mut it.offset_adjustment
+        });
+        config
+    }
+}
+
+/// Sets a setting of each channel in the map, there's no channel 0 so it's dropped.
+fn merge<T>(
+    config: &mut HantekConfig,
+    settings: HashMap<usize, Option<T>>,
+    field: fn(&mut ChannelConfig) -> &mut Option<T>,
+) {
+    for (channel_no, value) in settings {
+        if let (Some(channel), Some(value)) = (config.channel_mut(channel_no), value) {
+            *field(channel) = Some(value);
+        }
+    }
+}
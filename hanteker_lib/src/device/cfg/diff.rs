@@ -0,0 +1,353 @@
+//! Field by field comparison of two configs, e.g. the one in use against a saved one, to tell
+//! what applying the latter would change.
+
+use std::fmt::{Debug, Display, Formatter};
+use std::time::Duration;
+
+use crate::batch::Setting;
+use crate::device::cfg::{
+    Adjustment, AwgType, ChannelConfig, Coupling, DeviceFunction, HantekConfig, Probe,
+    RunningStatus, Scale, TimeScale, TrapDuty, TriggerMode, TriggerSlope,
+};
+
+/// A value before and after, `None` where it isn't set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change<T> {
+    pub before: Option<T>,
+    pub after: Option<T>,
+}
+
+impl<T: Debug> Display for Change<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let show = |value: &Option<T>| match value {
+            Some(value) => format!("{:?}", value),
+            None => "unset".to_string(),
+        };
+        write!(f, "{} -> {}", show(&self.before), show(&self.after))
+    }
+}
+
+/// A field differing between two configs, with the channel it belongs to if any.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigChange {
+    Timeout(Change<Duration>),
+    DeviceFunction(Change<DeviceFunction>),
+
+    ChannelEnabled(usize, Change<bool>),
+    ChannelCoupling(usize, Change<Coupling>),
+    ChannelProbe(usize, Change<Probe>),
+    ChannelScale(usize, Change<Scale>),
+    /// Raw offset, see [`crate::models::hantek2d42::Hantek2D42::set_channel_offset`].
+    ChannelOffset(usize, Change<f32>),
+    ChannelBandwidthLimit(usize, Change<bool>),
+    ChannelOffsetAdjustment(usize, Change<Adjustment>),
+
+    TimeScale(Change<TimeScale>),
+    TimeOffset(Change<f32>),
+    TimeOffsetAdjustment(Change<Adjustment>),
+
+    RunningStatus(Change<RunningStatus>),
+    TriggerSource(Change<usize>),
+    TriggerSlope(Change<TriggerSlope>),
+    TriggerMode(Change<TriggerMode>),
+    TriggerLevelAdjustment(Change<Adjustment>),
+    /// Raw level, see [`crate::models::hantek2d42::Hantek2D42::set_trigger_level`].
+    TriggerLevel(Change<f32>),
+
+    AwgType(Change<AwgType>),
+    AwgFrequency(Change<f32>),
+    AwgAmplitude(Change<f32>),
+    AwgOffset(Change<f32>),
+    AwgDutySquare(Change<f32>),
+    AwgDutyRamp(Change<f32>),
+    AwgDutyTrap(Change<TrapDuty>),
+    AwgRunningStatus(Change<RunningStatus>),
+}
+
+impl ConfigChange {
+    /// Name of the field, as in the snapshots of the cli, e.g. `channel1.scale`.
+    pub fn name(&self) -> String {
+        let channel = |channel_no: &usize, name: &str| format!("channel{}.{}", channel_no, name);
+        match self {
+            Self::Timeout(_) => "timeout".to_string(),
+            Self::DeviceFunction(_) => "device_function".to_string(),
+            Self::ChannelEnabled(channel_no, _) => channel(channel_no, "enabled"),
+            Self::ChannelCoupling(channel_no, _) => channel(channel_no, "coupling"),
+            Self::ChannelProbe(channel_no, _) => channel(channel_no, "probe"),
+            Self::ChannelScale(channel_no, _) => channel(channel_no, "scale"),
+            Self::ChannelOffset(channel_no, _) => channel(channel_no, "offset"),
+            Self::ChannelBandwidthLimit(channel_no, _) => channel(channel_no, "bandwidth_limit"),
+            Self::ChannelOffsetAdjustment(channel_no, _) => {
+                channel(channel_no, "offset_adjustment")
+            }
+            Self::TimeScale(_) => "time_scale".to_string(),
+            Self::TimeOffset(_) => "time_offset".to_string(),
+            Self::TimeOffsetAdjustment(_) => "time_offset_adjustment".to_string(),
+            Self::RunningStatus(_) => "running".to_string(),
+            Self::TriggerSource(_) => "trigger.source".to_string(),
+            Self::TriggerSlope(_) => "trigger.slope".to_string(),
+            Self::TriggerMode(_) => "trigger.mode".to_string(),
+            Self::TriggerLevelAdjustment(_) => "trigger.level_adjustment".to_string(),
+            Self::TriggerLevel(_) => "trigger.level".to_string(),
+            Self::AwgType(_) => "awg.type".to_string(),
+            Self::AwgFrequency(_) => "awg.frequency".to_string(),
+            Self::AwgAmplitude(_) => "awg.amplitude".to_string(),
+            Self::AwgOffset(_) => "awg.offset".to_string(),
+            Self::AwgDutySquare(_) => "awg.duty_square".to_string(),
+            Self::AwgDutyRamp(_) => "awg.duty_ramp".to_string(),
+            Self::AwgDutyTrap(_) => "awg.duty_trap".to_string(),
+            Self::AwgRunningStatus(_) => "awg.running".to_string(),
+        }
+    }
+
+    /// The setting bringing the device to the value after, `None` if the field is unset after
+    /// or isn't one a [`Setting`] takes, e.g. the adjustments following from the scales.
+    pub fn setting(&self) -> Option<Setting> {
+        match self {
+            Self::ChannelEnabled(channel_no, change) => {
+                Some(Setting::ChannelEnabled(*channel_no, change.after?))
+            }
+            Self::ChannelCoupling(channel_no, change) => {
+                Some(Setting::ChannelCoupling(*channel_no, change.after.clone()?))
+            }
+            Self::ChannelProbe(channel_no, change) => {
+                Some(Setting::ChannelProbe(*channel_no, change.after.clone()?))
+            }
+            Self::ChannelScale(channel_no, change) => {
+                Some(Setting::ChannelScale(*channel_no, change.after.clone()?))
+            }
+            Self::ChannelOffset(channel_no, change) => {
+                Some(Setting::ChannelOffset(*channel_no, change.after? as u8))
+            }
+            Self::ChannelBandwidthLimit(channel_no, change) => {
+                Some(Setting::ChannelBandwidthLimit(*channel_no, change.after?))
+            }
+            Self::TimeScale(change) => Some(Setting::TimeScale(change.after.clone()?)),
+            Self::TriggerSource(change) => Some(Setting::TriggerSource(change.after?)),
+            Self::TriggerSlope(change) => Some(Setting::TriggerSlope(change.after.clone()?)),
+            Self::TriggerMode(change) => Some(Setting::TriggerMode(change.after.clone()?)),
+            Self::TriggerLevel(change) => Some(Setting::TriggerLevel(change.after? as u8)),
+            Self::AwgType(change) => Some(Setting::AwgType(change.after.clone()?)),
+            Self::AwgFrequency(change) => Some(Setting::AwgFrequency(change.after?)),
+            Self::AwgAmplitude(change) => Some(Setting::AwgAmplitude(change.after?)),
+            Self::AwgOffset(change) => Some(Setting::AwgOffset(change.after?)),
+            Self::Timeout(_)
+            | Self::DeviceFunction(_)
+            | Self::ChannelOffsetAdjustment(..)
+            | Self::TimeOffset(_)
+            | Self::TimeOffsetAdjustment(_)
+            | Self::RunningStatus(_)
+            | Self::TriggerLevelAdjustment(_)
+            | Self::AwgDutySquare(_)
+            | Self::AwgDutyRamp(_)
+            | Self::AwgDutyTrap(_)
+            | Self::AwgRunningStatus(_) => None,
+        }
+    }
+
+    /// Whether the field is set after, i.e. the change is one applying the other config makes
+    /// rather than a field it leaves alone.
+    pub fn is_set_after(&self) -> bool {
+        self.change().is_set_after()
+    }
+
+    fn change(&self) -> &dyn AnyChange {
+        match self {
+            Self::Timeout(change) => change,
+            Self::DeviceFunction(change) => change,
+            Self::ChannelEnabled(_, change) => change,
+            Self::ChannelCoupling(_, change) => change,
+            Self::ChannelProbe(_, change) => change,
+            Self::ChannelScale(_, change) => change,
+            Self::ChannelOffset(_, change) => change,
+            Self::ChannelBandwidthLimit(_, change) => change,
+            Self::ChannelOffsetAdjustment(_, change) => change,
+            Self::TimeScale(change) => change,
+            Self::TimeOffset(change) => change,
+            Self::TimeOffsetAdjustment(change) => change,
+            Self::RunningStatus(change) => change,
+            Self::TriggerSource(change) => change,
+            Self::TriggerSlope(change) => change,
+            Self::TriggerMode(change) => change,
+            Self::TriggerLevelAdjustment(change) => change,
+            Self::TriggerLevel(change) => change,
+            Self::AwgType(change) => change,
+            Self::AwgFrequency(change) => change,
+            Self::AwgAmplitude(change) => change,
+            Self::AwgOffset(change) => change,
+            Self::AwgDutySquare(change) => change,
+            Self::AwgDutyRamp(change) => change,
+            Self::AwgDutyTrap(change) => change,
+            Self::AwgRunningStatus(change) => change,
+        }
+    }
+}
+
+impl Display for ConfigChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.name(), self.change())
+    }
+}
+
+/// A [`Change`] of whatever type, for what doesn't depend on it.
+trait AnyChange: Display {
+    fn is_set_after(&self) -> bool;
+}
+
+impl<T: Debug> AnyChange for Change<T> {
+    fn is_set_after(&self) -> bool {
+        self.after.is_some()
+    }
+}
+
+impl HantekConfig {
+    /// Fields set differently in `other`, in the order of the config's fields. A channel only
+    /// one of the configs has is compared against one with nothing set.
+    pub fn diff(&self, other: &HantekConfig) -> Vec<ConfigChange> {
+        let mut changes = vec![];
+        let mut push = |change: Option<ConfigChange>| changes.extend(change);
+
+        push(change(&self.timeout, &other.timeout, ConfigChange::Timeout));
+        push(change(
+            &self.device_function,
+            &other.device_function,
+            ConfigChange::DeviceFunction,
+        ));
+
+        let unset = ChannelConfig::default();
+        for channel_no in 1..=self.num_channels().max(other.num_channels()) {
+            let before = self.channel(channel_no).unwrap_or(&unset);
+            let after = other.channel(channel_no).unwrap_or(&unset);
+            push(change(&before.enabled, &after.enabled, |it| {
+                ConfigChange::ChannelEnabled(channel_no, it)
+            }));
+            push(change(&before.coupling, &after.coupling, |it| {
+                ConfigChange::ChannelCoupling(channel_no, it)
+            }));
+            push(change(&before.probe, &after.probe, |it| {
+                ConfigChange::ChannelProbe(channel_no, it)
+            }));
+            push(change(&before.scale, &after.scale, |it| {
+                ConfigChange::ChannelScale(channel_no, it)
+            }));
+            push(change(&before.offset, &after.offset, |it| {
+                ConfigChange::ChannelOffset(channel_no, it)
+            }));
+            push(change(
+                &before.bandwidth_limit,
+                &after.bandwidth_limit,
+                |it| ConfigChange::ChannelBandwidthLimit(channel_no, it),
+            ));
+            push(change(
+                &before.offset_adjustment,
+                &after.offset_adjustment,
+                |it| ConfigChange::ChannelOffsetAdjustment(channel_no, it),
+            ));
+        }
+
+        push(change(
+            &self.time_scale,
+            &other.time_scale,
+            ConfigChange::TimeScale,
+        ));
+        push(change(
+            &self.time_offset,
+            &other.time_offset,
+            ConfigChange::TimeOffset,
+        ));
+        push(change(
+            &self.time_offset_adjustment,
+            &other.time_offset_adjustment,
+            ConfigChange::TimeOffsetAdjustment,
+        ));
+
+        push(change(
+            &self.running_status,
+            &other.running_status,
+            ConfigChange::RunningStatus,
+        ));
+        push(change(
+            &self.trigger_source_channel,
+            &other.trigger_source_channel,
+            ConfigChange::TriggerSource,
+        ));
+        push(change(
+            &self.trigger_slope,
+            &other.trigger_slope,
+            ConfigChange::TriggerSlope,
+        ));
+        push(change(
+            &self.trigger_mode,
+            &other.trigger_mode,
+            ConfigChange::TriggerMode,
+        ));
+        push(change(
+            &self.trigger_level_adjustment,
+            &other.trigger_level_adjustment,
+            ConfigChange::TriggerLevelAdjustment,
+        ));
+        push(change(
+            &self.trigger_level,
+            &other.trigger_level,
+            ConfigChange::TriggerLevel,
+        ));
+
+        push(change(
+            &self.awg_type,
+            &other.awg_type,
+            ConfigChange::AwgType,
+        ));
+        push(change(
+            &self.awg_frequency,
+            &other.awg_frequency,
+            ConfigChange::AwgFrequency,
+        ));
+        push(change(
+            &self.awg_amplitude,
+            &other.awg_amplitude,
+            ConfigChange::AwgAmplitude,
+        ));
+        push(change(
+            &self.awg_offset,
+            &other.awg_offset,
+            ConfigChange::AwgOffset,
+        ));
+        push(change(
+            &self.awg_duty_square,
+            &other.awg_duty_square,
+            ConfigChange::AwgDutySquare,
+        ));
+        push(change(
+            &self.awg_duty_ramp,
+            &other.awg_duty_ramp,
+            ConfigChange::AwgDutyRamp,
+        ));
+        push(change(
+            &self.awg_duty_trap,
+            &other.awg_duty_trap,
+            ConfigChange::AwgDutyTrap,
+        ));
+        push(change(
+            &self.awg_running_status,
+            &other.awg_running_status,
+            ConfigChange::AwgRunningStatus,
+        ));
+
+        changes
+    }
+}
+
+fn change<T: Clone + PartialEq>(
+    before: &Option<T>,
+    after: &Option<T>,
+    to_change: impl FnOnce(Change<T>) -> ConfigChange,
+) -> Option<ConfigChange> {
+    if before == after {
+        None
+    } else {
+        Some(to_change(Change {
+            before: before.clone(),
+            after: after.clone(),
+        }))
+    }
+}
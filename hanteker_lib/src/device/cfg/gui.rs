@@ -0,0 +1,136 @@
+//! `druid::Data` for the config types that can't simply derive it.
+
+use druid::Data;
+
+use crate::device::cfg::{Adjustment, ChannelConfig, HantekConfig, TrapDuty};
+
+impl Data for HantekConfig {
+    fn same(&self, other: &Self) -> bool {
+        if self.timeout != other.timeout {
+            return false;
+        }
+
+        if self.device_function != other.device_function {
+            return false;
+        }
+
+        if self.channels.len() != other.channels.len()
+            || !self
+                .channels
+                .iter()
+                .zip(&other.channels)
+                .all(|(c0, c1)| c0.same(c1))
+        {
+            return false;
+        }
+
+        if self.time_scale != other.time_scale {
+            return false;
+        }
+        if !compare_some_f32(&self.time_offset, &other.time_offset) {
+            return false;
+        }
+        if !compare_some_adjustment(&self.time_offset_adjustment, &other.time_offset_adjustment) {
+            return false;
+        }
+
+        if self.running_status != other.running_status {
+            return false;
+        }
+        if self.trigger_source_channel != other.trigger_source_channel {
+            return false;
+        }
+        if self.trigger_slope != other.trigger_slope {
+            return false;
+        }
+        if self.trigger_mode != other.trigger_mode {
+            return false;
+        }
+
+        if !compare_some_adjustment(
+            &self.trigger_level_adjustment,
+            &other.trigger_level_adjustment,
+        ) {
+            return false;
+        }
+        if !compare_some_f32(&self.trigger_level, &other.trigger_level) {
+            return false;
+        }
+
+        if self.awg_type != other.awg_type {
+            return false;
+        }
+
+        if !compare_some_f32(&self.awg_frequency, &other.awg_frequency) {
+            return false;
+        }
+        if !compare_some_f32(&self.awg_amplitude, &other.awg_amplitude) {
+            return false;
+        }
+        if !compare_some_f32(&self.awg_offset, &other.awg_offset) {
+            return false;
+        }
+        if !compare_some_f32(&self.awg_duty_square, &other.awg_duty_square) {
+            return false;
+        }
+        if !compare_some_f32(&self.awg_duty_ramp, &other.awg_duty_ramp) {
+            return false;
+        }
+        if !compare_some_trap_duty(&self.awg_duty_trap, &other.awg_duty_trap) {
+            return false;
+        }
+        if self.awg_running_status != other.awg_running_status {
+            return false;
+        }
+
+        true
+    }
+}
+
+impl Data for ChannelConfig {
+    fn same(&self, other: &Self) -> bool {
+        self.enabled == other.enabled
+            && self.coupling == other.coupling
+            && self.probe == other.probe
+            && self.scale == other.scale
+            && self.bandwidth_limit == other.bandwidth_limit
+            && compare_some_f32(&self.offset, &other.offset)
+            && compare_some_adjustment(&self.offset_adjustment, &other.offset_adjustment)
+    }
+}
+
+fn compare_some_trap_duty(t0: &Option<TrapDuty>, t1: &Option<TrapDuty>) -> bool {
+    if t0.is_some() != t1.is_some() {
+        false
+    } else if t0.is_some() {
+        let t0 = t0.as_ref().unwrap();
+        let t1 = t1.as_ref().unwrap();
+        t0.same(t1)
+    } else {
+        true
+    }
+}
+
+fn compare_some_f32(f0: &Option<f32>, f1: &Option<f32>) -> bool {
+    if f0.is_some() != f1.is_some() {
+        false
+    } else if f0.is_some() {
+        let f0 = f0.unwrap().to_bits();
+        let f1 = f1.unwrap().to_bits();
+        f0 == f1
+    } else {
+        true
+    }
+}
+
+fn compare_some_adjustment(a0: &Option<Adjustment>, a1: &Option<Adjustment>) -> bool {
+    if a0.is_some() != a1.is_some() {
+        false
+    } else if a0.is_some() {
+        let a0 = a0.as_ref().unwrap();
+        let a1 = a1.as_ref().unwrap();
+        a0.same(a1)
+    } else {
+        true
+    }
+}
@@ -1,5 +1,13 @@
+use thiserror::Error;
+
 pub type RawCommand = [u8; 10];
 
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum CommandBuildError {
+    #[error("command field not set: {field}")]
+    MissingField { field: &'static str },
+}
+
 #[derive(Clone, Debug)]
 pub enum Val {
     ValU8([u8; 4]),
@@ -73,6 +81,32 @@ impl HantekCommandBuilder {
         self
     }
 
+    /// Fails if any field but the value is left unset, [`Self::new`] sets none of them.
+    pub fn build(self) -> Result<RawCommand, CommandBuildError> {
+        let missing = |field| CommandBuildError::MissingField { field };
+        let func = self.func.ok_or_else(|| missing("func"))?.to_le_bytes();
+        let val = match self.val.ok_or_else(|| missing("val"))? {
+            Val::ValU8(v) => v,
+            Val::ValU16(v) => {
+                let (v0, v1) = (v[0].to_le_bytes(), v[1].to_le_bytes());
+                [v0[0], v0[1], v1[0], v1[1]]
+            }
+            Val::ValU32(v) => v.to_le_bytes(),
+        };
+        Ok([
+            self.idx.ok_or_else(|| missing("idx"))?,
+            self.boh.ok_or_else(|| missing("boh"))?,
+            func[0],
+            func[1],
+            self.cmd.ok_or_else(|| missing("cmd"))?,
+            val[0],
+            val[1],
+            val[2],
+            val[3],
+            self.last.ok_or_else(|| missing("last"))?,
+        ])
+    }
+
     // =================================================================== DEBUG
 
     pub fn dump(&self) -> String {
@@ -110,8 +144,10 @@ impl HantekCommandBuilder {
     }
 
     pub fn dump_raw(&self) -> String {
-        let cloned = self.clone();
-        let raw: RawCommand = cloned.into();
+        let raw = match self.clone().build() {
+            Ok(raw) => raw,
+            Err(e) => return e.to_string(),
+        };
         format!(
             "idx={}\nboh={}\nfunc={}-{}\ncmd={}\nval={}-{}-{}-{}\nlast={}",
             raw[0], // idx
@@ -144,37 +180,15 @@ impl Default for HantekCommandBuilder {
     }
 }
 
-#[allow(clippy::from_over_into)]
-impl Into<RawCommand> for HantekCommandBuilder {
-    fn into(self) -> RawCommand {
-        let mut as_array = [0u8; 10];
-        as_array[0] = self.idx.expect("idx not set");
-        as_array[1] = self.boh.expect("boh not set");
-        as_array[2] = self.func.expect("func not set").to_be_bytes()[1];
-        as_array[3] = self.func.expect("func not set").to_be_bytes()[0];
-        as_array[4] = self.cmd.expect("cmd not set");
-        match self.val.expect("val not set") {
-            Val::ValU8(v) => {
-                as_array[5] = v[0];
-                as_array[6] = v[1];
-                as_array[7] = v[2];
-                as_array[8] = v[3];
-            }
-            Val::ValU16(v) => {
-                as_array[5] = v[0].to_le_bytes()[0];
-                as_array[6] = v[0].to_le_bytes()[1];
-                as_array[7] = v[1].to_le_bytes()[0];
-                as_array[8] = v[1].to_le_bytes()[1];
-            }
-            Val::ValU32(v) => {
-                as_array[5] = v.to_le_bytes()[0];
-                as_array[6] = v.to_le_bytes()[1];
-                as_array[7] = v.to_le_bytes()[2];
-                as_array[8] = v.to_le_bytes()[3];
-            }
-        }
-        as_array[9] = self.last.expect("last not set");
-
-        as_array
+/// Every field set, the value as bytes.
+impl From<RawCommand> for HantekCommandBuilder {
+    fn from(raw: RawCommand) -> Self {
+        Self::new()
+            .set_idx(raw[0])
+            .set_boh(raw[1])
+            .set_func(u16::from_le_bytes([raw[2], raw[3]]))
+            .set_cmd(raw[4])
+            .set_val_u8(raw[5], raw[6], raw[7], raw[8])
+            .set_last(raw[9])
     }
 }
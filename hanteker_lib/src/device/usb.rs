@@ -1,40 +1,88 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
 use std::time::Duration;
 
-use libusb::{ConfigDescriptor, Context, Device, DeviceDescriptor, DeviceHandle, Language, Speed};
-use log::{debug, trace};
+use libusb::{
+    ConfigDescriptor, Context, Device, DeviceDescriptor, DeviceHandle, Direction, Language, Speed,
+    TransferType,
+};
+use log::{debug, trace, warn};
 use thiserror::Error;
 
+use crate::events::{Event, EventBus};
+use crate::logging::USB;
+
 #[derive(Error, Debug)]
 pub enum HantekUsbError {
     #[error("failed to read from usb")]
-    ReadError { error: libusb::Error },
+    ReadError {
+        #[source]
+        error: libusb::Error,
+    },
 
     #[error("failed to write to usb")]
-    WriteError { error: libusb::Error },
+    WriteError {
+        #[source]
+        error: libusb::Error,
+    },
+
+    #[error("error resetting usb device{}", fmt_reset_hint(.error))]
+    ResetError {
+        #[source]
+        error: libusb::Error,
+    },
 
     #[error("error releasing usb interfaces")]
-    UsbInterfaceReleaseError { error: libusb::Error },
+    UsbInterfaceReleaseError {
+        #[source]
+        error: libusb::Error,
+    },
 
-    #[error("error claiming any of usb interfaces")]
+    #[error("error claiming any of usb interfaces: {}", fmt_claim_errors(.errors))]
     UsbInterfaceClaimError { errors: Vec<(u8, libusb::Error)> },
 
     #[error("error reading usb manufacturer string")]
-    ManufacturerReadUsbError { error: libusb::Error },
+    ManufacturerReadUsbError {
+        #[source]
+        error: libusb::Error,
+    },
 
     #[error("error reading usb product string")]
-    ProductReadUsbError { error: libusb::Error },
+    ProductReadUsbError {
+        #[source]
+        error: libusb::Error,
+    },
+
+    #[error("error reading usb serial number string")]
+    SerialReadUsbError {
+        #[source]
+        error: libusb::Error,
+    },
 
     #[error("error reading usb languages")]
-    ReadLanguagesError { error: libusb::Error },
+    ReadLanguagesError {
+        #[source]
+        error: libusb::Error,
+    },
 
     #[error("failed to get usb devices")]
-    GetUsbDevicesError { error: libusb::Error },
+    GetUsbDevicesError {
+        #[source]
+        error: libusb::Error,
+    },
 
-    #[error("failed to open usb devices")]
-    OpenUsbDeviceError { error: libusb::Error },
+    #[error("failed to open usb devices{}", fmt_access_hint(.error))]
+    OpenUsbDeviceError {
+        #[source]
+        error: libusb::Error,
+    },
 
     #[error("failed to get usb device config")]
-    GetConfigError { error: libusb::Error },
+    GetConfigError {
+        #[source]
+        error: libusb::Error,
+    },
 
     #[error("no usb language available, can not read product string")]
     ProductReadNoLanguageAvailable,
@@ -42,6 +90,9 @@ pub enum HantekUsbError {
     #[error("no usb language available, can not read manufacturer string")]
     ManufacturerReadNoLanguageAvailable,
 
+    #[error("no usb language available, can not read serial number string")]
+    SerialReadNoLanguageAvailable,
+
     #[error("no usb device found with required vid={vid}, pid={pid}")]
     NoDeviceFound { vid: u16, pid: u16 },
 
@@ -57,6 +108,9 @@ pub enum HantekUsbError {
 
     #[error("no interface is claimed yet for the requested operation")]
     NoInterfaceClaimed,
+
+    #[error("interrupted before the transfer")]
+    Interrupted,
 }
 
 impl HantekUsbError {
@@ -66,9 +120,189 @@ impl HantekUsbError {
     }
 }
 
+fn fmt_claim_errors(errors: &[(u8, libusb::Error)]) -> String {
+    if errors.is_empty() {
+        return "device has no interfaces".to_string();
+    }
+
+    errors
+        .iter()
+        .map(|(interface, error)| format!("interface_no={} error={}", interface, error))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn fmt_reset_hint(error: &libusb::Error) -> &'static str {
+    match error {
+        libusb::Error::NotFound => ", it came back as a new device and has to be opened again",
+        _ => "",
+    }
+}
+
+fn fmt_access_hint(error: &libusb::Error) -> &'static str {
+    match error {
+        libusb::Error::Access => {
+            ", permission denied. On Linux install a udev rule, see `hanteker_cli setup-udev`"
+        }
+        _ => "",
+    }
+}
+
+/// CLEAR_FEATURE(ENDPOINT_HALT) of the endpoint, a standard request to an endpoint, for it to
+/// take transfers again after a stall. libusb 0.3 has no `clear_halt` of its own.
+fn clear_halt(handle: &DeviceHandle, endpoint: u8, timeout: Duration) -> libusb::Result<()> {
+    const REQUEST_TYPE_ENDPOINT: u8 = 0x02;
+    const CLEAR_FEATURE: u8 = 0x01;
+    const ENDPOINT_HALT: u16 = 0;
+    handle
+        .write_control(
+            REQUEST_TYPE_ENDPOINT,
+            CLEAR_FEATURE,
+            ENDPOINT_HALT,
+            endpoint as u16,
+            &[],
+            timeout,
+        )
+        .map(|_| ())
+}
+
+/// Retrying of transfers failing with errors known to be transient, a stall (`Pipe`) or a
+/// timeout. Other errors fail right away.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryConfig {
+    /// Retries after the first attempt, 0 to not retry.
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled on every further one.
+    pub backoff: Duration,
+}
+
+impl RetryConfig {
+    pub const NONE: RetryConfig = RetryConfig {
+        max_retries: 0,
+        backoff: Duration::ZERO,
+    };
+
+    fn should_retry(&self, error: &libusb::Error, attempt: u32) -> bool {
+        attempt < self.max_retries && matches!(error, libusb::Error::Pipe | libusb::Error::Timeout)
+    }
+
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.backoff.saturating_mul(1 << attempt.min(16))
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+/// Bulk endpoints found in the config descriptor, and the interface they belong to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Endpoints {
+    pub interface: u8,
+    /// Address of the IN endpoint, direction bit included.
+    pub read: u8,
+    pub write: u8,
+}
+
+/// First interface, or the given one, with both a bulk IN and a bulk OUT endpoint.
+fn discover_endpoints(config: &ConfigDescriptor, interface: Option<u8>) -> Option<Endpoints> {
+    config
+        .interfaces()
+        .filter(|it| interface.is_none() || interface == Some(it.number()))
+        .find_map(|it| {
+            let mut read = None;
+            let mut write = None;
+            for descriptor in it.descriptors() {
+                for endpoint in descriptor.endpoint_descriptors() {
+                    if let TransferType::Bulk = endpoint.transfer_type() {
+                        match endpoint.direction() {
+                            Direction::In => read = read.or(Some(endpoint.address())),
+                            Direction::Out => write = write.or(Some(endpoint.address())),
+                        }
+                    }
+                }
+            }
+            Some(Endpoints {
+                interface: it.number(),
+                read: read?,
+                write: write?,
+            })
+        })
+}
+
+/// A bulk transfer done, as handed to the hook set with [`HantekUsbDevice::set_trace`].
+#[derive(Debug)]
+pub enum Transfer<'b> {
+    Write {
+        endpoint: u8,
+        data: &'b [u8],
+    },
+    Read {
+        endpoint: u8,
+        /// Length of the buffer read into, the device may answer with less.
+        requested: usize,
+        data: &'b [u8],
+    },
+}
+
+pub type TraceHook = Box<dyn FnMut(&Transfer) + Send>;
+
+/// What tells a unit apart from others of its model when opening it anew: its serial number if
+/// it has one, else where it's plugged in, which replugging it may change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UnitId {
+    serial: Option<String>,
+    bus: u8,
+    address: u8,
+}
+
+impl UnitId {
+    fn is(&self, other: &UnitId) -> bool {
+        match (&self.serial, &other.serial) {
+            (Some(serial), Some(other)) => serial == other,
+            _ => self.bus == other.bus && self.address == other.address,
+        }
+    }
+}
+
+/// The candidate that's the unit. Without a serial to tell it by, a unit not found where it
+/// was plugged in is taken to be the only candidate, if there's one.
+fn find_unit<T>(
+    unit: &UnitId,
+    mut candidates: Vec<(UnitId, T)>,
+    (vid, pid): (u16, u16),
+) -> Result<T, HantekUsbError> {
+    let found: Vec<_> = candidates.iter().map(|it| unit.is(&it.0)).collect();
+    let idx = match found.iter().filter(|it| **it).count() {
+        1 => found.iter().position(|it| *it).unwrap(),
+        0 if unit.serial.is_none() && candidates.len() == 1 => 0,
+        0 => return Err(HantekUsbError::NoDeviceFound { vid, pid }),
+        instances => {
+            return Err(HantekUsbError::TooManyDevicesFound {
+                vid,
+                pid,
+                instances,
+            })
+        }
+    };
+    Ok(candidates.swap_remove(idx).1)
+}
+
 pub struct HantekUsbDevice<'a> {
+    /// Of control transfers, e.g. reading descriptor strings.
     timeout: Duration,
+    write_timeout: Duration,
+    read_timeout: Duration,
+    retry: RetryConfig,
+    retries: u64,
     claimed_interface: Option<u8>,
+    endpoints: Option<Endpoints>,
+    interrupt: Option<Arc<AtomicBool>>,
+    trace: Option<TraceHook>,
+    events: Option<EventBus>,
+    unit: UnitId,
     pub device: Device<'a>,
     pub descriptor: DeviceDescriptor,
     pub handle: DeviceHandle<'a>,
@@ -83,7 +317,27 @@ impl<'a> HantekUsbDevice<'a> {
         (vid, pid): (u16, u16),
     ) -> Result<Self, HantekUsbError> {
         let (device, descriptor) = Self::find_single_device(context, (vid, pid))?;
+        Self::open_found(device, descriptor, timeout)
+    }
 
+    /// Same as [`Self::open`], opening every device found rather than failing when there's more
+    /// than one, in the order of the bus. Fails if any can't be opened. None is claimed.
+    pub fn open_all(
+        context: &'a Context,
+        timeout: Duration,
+        (vid, pid): (u16, u16),
+    ) -> Result<Vec<Self>, HantekUsbError> {
+        Self::find_devices(context, (vid, pid))?
+            .into_iter()
+            .map(|(device, descriptor)| Self::open_found(device, descriptor, timeout))
+            .collect()
+    }
+
+    fn open_found(
+        device: Device<'a>,
+        descriptor: DeviceDescriptor,
+        timeout: Duration,
+    ) -> Result<Self, HantekUsbError> {
         let handle = device
             .open()
             .map_err(|error| HantekUsbError::OpenUsbDeviceError { error })?;
@@ -94,15 +348,74 @@ impl<'a> HantekUsbDevice<'a> {
             .config_descriptor(0)
             .map_err(|error| HantekUsbError::GetConfigError { error })?;
 
-        Ok(Self {
+        let endpoints = discover_endpoints(&config, None);
+        debug!(target: USB, "discovered endpoints: {:?}", endpoints);
+
+        let unit = UnitId {
+            serial: None,
+            bus: device.bus_number(),
+            address: device.address(),
+        };
+        let mut usb = Self {
             timeout,
+            write_timeout: timeout,
+            read_timeout: timeout,
+            retry: RetryConfig::NONE,
+            retries: 0,
             claimed_interface: None,
+            endpoints,
+            interrupt: None,
+            trace: None,
+            events: None,
+            unit,
             device,
             descriptor,
             handle,
             language,
             config,
-        })
+        };
+        usb.unit.serial = usb.get_serial().unwrap_or_else(|error| {
+            debug!(target: USB, "could not read serial number: {}", error);
+            None
+        });
+        Ok(usb)
+    }
+
+    /// Opens the device anew, e.g. after it was unplugged or stopped responding, keeping the
+    /// timeouts, retries, interrupt flag, trace hook and events, and claiming it if this one was.
+    /// With several devices attached it's the same unit that's opened, told by its serial number,
+    /// or by where it's plugged in if it has none.
+    pub fn reopen(&mut self, context: &'a Context) -> Result<(), HantekUsbError> {
+        let claimed = self.claimed_interface;
+        if let Err(error) = self.release() {
+            // Expected of a device that is gone.
+            debug!(target: USB, "error releasing device before reopening: {}", error);
+            self.claimed_interface = None;
+        }
+
+        let id = (self.vid(), self.pid());
+        let mut candidates = vec![];
+        for (device, descriptor) in Self::find_devices(context, id)? {
+            match Self::open_found(device, descriptor, self.timeout) {
+                Ok(usb) => candidates.push((usb.unit.clone(), usb)),
+                // Another unit, e.g. one that's busy, needn't stop this one from opening.
+                Err(error) => debug!(target: USB, "skipping device on reopening: {}", error),
+            }
+        }
+        let mut usb = find_unit(&self.unit, candidates, id)?;
+        if let Some(interface) = claimed {
+            usb.claim_interface(interface)?;
+        }
+        usb.write_timeout = self.write_timeout;
+        usb.read_timeout = self.read_timeout;
+        usb.retry = self.retry.clone();
+        usb.retries = self.retries;
+        usb.interrupt = self.interrupt.take();
+        usb.trace = self.trace.take();
+        usb.events = self.events.take();
+
+        *self = usb;
+        Ok(())
     }
 
     // =========================================================================
@@ -119,7 +432,7 @@ impl<'a> HantekUsbDevice<'a> {
             .map(|(device_descriptor, device)| (device, device_descriptor))
             .filter_map(|it| {
                 if it.1.is_err() {
-                    debug!(
+                    debug!(target: USB,
                         "could not open device descriptor, bus={} address={}",
                         it.0.bus_number(),
                         it.0.address()
@@ -131,7 +444,7 @@ impl<'a> HantekUsbDevice<'a> {
             })
             .filter(|it| {
                 if it.1.vendor_id() != vid || it.1.product_id() != pid {
-                    trace!(
+                    trace!(target: USB,
                         "skipping device on mismatch, pid={} vid={}",
                         it.1.product_id(),
                         it.1.vendor_id()
@@ -169,7 +482,7 @@ impl<'a> HantekUsbDevice<'a> {
             .read_languages(timeout)
             .map(|mut languages| {
                 if languages.len() > 1 {
-                    trace!(
+                    trace!(target: USB,
                         "multiple languages available, choosing first. Number of languages={}",
                         languages.len()
                     )
@@ -201,6 +514,23 @@ impl<'a> HantekUsbDevice<'a> {
             .map_err(|error| HantekUsbError::ProductReadUsbError { error })
     }
 
+    /// `None` if the device has no serial number.
+    pub fn get_serial(&self) -> Result<Option<String>, HantekUsbError> {
+        if self.descriptor.serial_number_string_index().is_none() {
+            return Ok(None);
+        }
+        if self.language.is_none() {
+            return Err(HantekUsbError::SerialReadNoLanguageAvailable);
+        }
+
+        self.handle
+            .read_serial_number_string(self.language.unwrap(), &self.descriptor, self.timeout)
+            .map(Some)
+            .map_err(|error| HantekUsbError::SerialReadUsbError { error })
+    }
+
+    /// Claims the interface owning the bulk endpoints, falling back to whichever of the others
+    /// can be claimed.
     pub fn claim(&mut self) -> Result<(), HantekUsbError> {
         if let Some(already_claimed) = self.claimed_interface {
             return Err(HantekUsbError::InterfaceAlreadyClaimed {
@@ -208,48 +538,201 @@ impl<'a> HantekUsbDevice<'a> {
             });
         }
 
+        let mut numbers: Vec<u8> = self.config.interfaces().map(|it| it.number()).collect();
+        if let Some(endpoints) = self.endpoints {
+            numbers.sort_by_key(|it| *it != endpoints.interface);
+        }
+
         let mut errors = vec![];
-        for interface in self.config.interfaces() {
-            let try_claim = self.handle.claim_interface(interface.number());
+        for number in numbers {
+            let try_claim = self.handle.claim_interface(number);
             if try_claim.is_ok() {
-                self.claimed_interface = Some(interface.number());
+                self.claimed(number);
                 return Ok(());
             } else {
-                errors.push((interface.number(), try_claim.err().unwrap()));
+                errors.push((number, try_claim.err().unwrap()));
             }
         }
 
         Err(HantekUsbError::UsbInterfaceClaimError { errors })
     }
 
+    /// Claims the given interface only, for firmware whose endpoints aren't where they're
+    /// looked for.
+    pub fn claim_interface(&mut self, number: u8) -> Result<(), HantekUsbError> {
+        if let Some(already_claimed) = self.claimed_interface {
+            return Err(HantekUsbError::InterfaceAlreadyClaimed {
+                interface: already_claimed,
+            });
+        }
+
+        self.handle.claim_interface(number).map_err(|error| {
+            HantekUsbError::UsbInterfaceClaimError {
+                errors: vec![(number, error)],
+            }
+        })?;
+        self.claimed(number);
+        Ok(())
+    }
+
+    /// Endpoints of the claimed interface are the ones used from then on, if it has any.
+    fn claimed(&mut self, number: u8) {
+        self.claimed_interface = Some(number);
+        if let Some(endpoints) = discover_endpoints(&self.config, Some(number)) {
+            self.endpoints = Some(endpoints);
+        }
+    }
+
+    /// Bulk endpoints to talk to the device through, `None` if none were found and the model's
+    /// defaults apply.
+    pub fn endpoints(&self) -> Option<Endpoints> {
+        self.endpoints
+    }
+
     pub fn release(&mut self) -> Result<(), HantekUsbError> {
         match self.claimed_interface {
             None => Ok(()),
             Some(interface_number) => self
                 .handle
                 .release_interface(interface_number)
-                .map_err(|error| HantekUsbError::UsbInterfaceReleaseError { error }),
+                .map_err(|error| HantekUsbError::UsbInterfaceReleaseError { error })
+                .map(|_| {
+                    self.claimed_interface = None;
+                }),
         }
     }
 
-    pub fn write(&mut self, endpoint: u8, buf: &[u8]) -> Result<usize, HantekUsbError> {
+    /// USB port reset, for a device that stopped answering, then claims the interface again if
+    /// it was claimed. Fails with `NotFound` if the device came back as a new one, it has to be
+    /// opened again then, see [`Self::reopen`].
+    pub fn reset(&mut self) -> Result<(), HantekUsbError> {
+        self.handle
+            .reset()
+            .map_err(|error| HantekUsbError::ResetError { error })?;
+
+        if let Some(interface) = self.claimed_interface.take() {
+            self.claim_interface(interface)?;
+        }
+        Ok(())
+    }
+
+    /// Once the flag is set, every transfer fails with [`HantekUsbError::Interrupted`] instead
+    /// of being started, so a long running operation can be cut short from a signal handler.
+    pub fn set_interrupt(&mut self, interrupt: Option<Arc<AtomicBool>>) {
+        self.interrupt = interrupt;
+    }
+
+    /// Timeouts of the bulk transfers, both default to the one the device was opened with. The
+    /// device only sends capture data, the read timeout is the one of captures.
+    pub fn set_timeouts(&mut self, write: Duration, read: Duration) {
+        self.write_timeout = write;
+        self.read_timeout = read;
+    }
+
+    pub fn set_retry(&mut self, retry: RetryConfig) {
+        self.retry = retry;
+    }
+
+    /// Transfers retried since opening the device, see [`RetryConfig`].
+    pub fn retries(&self) -> u64 {
+        self.retries
+    }
+
+    /// Hook called with every successful bulk transfer, e.g. to log what goes to the device.
+    pub fn set_trace(&mut self, trace: Option<TraceHook>) {
+        self.trace = trace;
+    }
+
+    /// Bus the retries and the disconnection of the device are emitted to.
+    pub fn set_events(&mut self, events: Option<EventBus>) {
+        self.events = events;
+    }
+
+    fn check_transfer(&self) -> Result<(), HantekUsbError> {
         if self.claimed_interface.is_none() {
             return Err(HantekUsbError::NoInterfaceClaimed);
         }
+        match &self.interrupt {
+            Some(interrupt) if interrupt.load(Ordering::SeqCst) => Err(HantekUsbError::Interrupted),
+            _ => Ok(()),
+        }
+    }
 
-        self.handle
-            .write_bulk(endpoint, buf, self.timeout)
-            .map_err(|error| HantekUsbError::WriteError { error })
+    pub fn write(&mut self, endpoint: u8, buf: &[u8]) -> Result<usize, HantekUsbError> {
+        let timeout = self.write_timeout;
+        let written = self
+            .retrying(endpoint, |handle| handle.write_bulk(endpoint, buf, timeout))
+            .map_err(|error| match error {
+                Retried::Transfer(error) => HantekUsbError::WriteError { error },
+                Retried::Check(error) => error,
+            })?;
+        if let Some(trace) = &mut self.trace {
+            trace(&Transfer::Write {
+                endpoint,
+                data: &buf[..written],
+            });
+        }
+        Ok(written)
     }
 
     pub fn read(&mut self, endpoint: u8, buf: &mut [u8]) -> Result<usize, HantekUsbError> {
-        if self.claimed_interface.is_none() {
-            return Err(HantekUsbError::NoInterfaceClaimed);
+        let timeout = self.read_timeout;
+        let read = self
+            .retrying(endpoint, |handle| handle.read_bulk(endpoint, buf, timeout))
+            .map_err(|error| match error {
+                Retried::Transfer(error) => HantekUsbError::ReadError { error },
+                Retried::Check(error) => error,
+            })?;
+        if let Some(trace) = &mut self.trace {
+            trace(&Transfer::Read {
+                endpoint,
+                requested: buf.len(),
+                data: &buf[..read],
+            });
         }
+        Ok(read)
+    }
 
-        self.handle
-            .read_bulk(endpoint, buf, self.timeout)
-            .map_err(|error| HantekUsbError::ReadError { error })
+    fn retrying<T>(
+        &mut self,
+        endpoint: u8,
+        mut transfer: impl FnMut(&DeviceHandle<'a>) -> libusb::Result<T>,
+    ) -> Result<T, Retried> {
+        let mut attempt = 0;
+        loop {
+            self.check_transfer().map_err(Retried::Check)?;
+            let error = match transfer(&self.handle) {
+                Ok(transferred) => return Ok(transferred),
+                Err(error) if self.retry.should_retry(&error, attempt) => error,
+                Err(error) => {
+                    if let (libusb::Error::NoDevice, Some(events)) = (&error, &self.events) {
+                        events.emit(|| Event::DeviceDisconnected);
+                    }
+                    return Err(Retried::Transfer(error));
+                }
+            };
+
+            let backoff = self.retry.backoff(attempt);
+            attempt += 1;
+            self.retries += 1;
+            warn!(target: USB,
+                "usb transfer on endpoint={:#04x} failed, retry {} of {} in {:?}: {}",
+                endpoint, attempt, self.retry.max_retries, backoff, error
+            );
+            if let Some(events) = &self.events {
+                events.emit(|| Event::UsbRetry {
+                    endpoint,
+                    attempt,
+                    error: error.to_string(),
+                });
+            }
+            if let libusb::Error::Pipe = error {
+                if let Err(e) = clear_halt(&self.handle, endpoint, self.timeout) {
+                    debug!(target: USB, "could not clear halt, endpoint={:#04x}: {}", endpoint, e);
+                }
+            }
+            thread::sleep(backoff);
+        }
     }
 
     pub fn pid(&self) -> u16 {
@@ -260,25 +743,128 @@ impl<'a> HantekUsbDevice<'a> {
         self.descriptor.vendor_id()
     }
 
+    /// Release number from the device descriptor, the closest thing to a firmware version that
+    /// can be read without device specific commands.
+    pub fn device_release(&self) -> String {
+        let version = self.descriptor.device_version();
+        format!(
+            "{}.{}.{}",
+            version.major(),
+            version.minor(),
+            version.sub_minor()
+        )
+    }
+
+    pub fn speed(&self) -> &'static str {
+        match self.device.speed() {
+            Speed::Unknown => "Unknown",
+            Speed::Low => "Low (1.5MPps)",
+            Speed::Full => "Full (12MBps)",
+            Speed::High => "High (480MBps)",
+            Speed::Super => "Super (5000MBps)",
+        }
+    }
+
     pub fn pretty_printed_device_info(&self) -> String {
         format!(
             "USB Bus={:03} Device={:03} ID={:04X}:{:04X} Speed={}\n\
             manufacturer={}\n\
-            product={}",
+            product={}\n\
+            serial={}\n\
+            release={}",
             self.device.bus_number(),
             self.device.address(),
             self.pid(),
             self.vid(),
-            match self.device.speed() {
-                Speed::Unknown => "Unknown",
-                Speed::Low => "Low (1.5MPps)",
-                Speed::Full => "Full (12MBps)",
-                Speed::High => "High (480MBps)",
-                Speed::Super => "Super (5000MBps)",
-            },
+            self.speed(),
             self.get_manufacturer()
                 .unwrap_or_else(|_| "ERROR".to_string()),
             self.get_product().unwrap_or_else(|_| "ERROR".to_string()),
+            match self.get_serial() {
+                Ok(Some(serial)) => serial,
+                Ok(None) => "NONE".to_string(),
+                Err(_) => "ERROR".to_string(),
+            },
+            self.device_release(),
         )
     }
 }
+
+enum Retried {
+    /// Failed before the transfer was attempted.
+    Check(HantekUsbError),
+    Transfer(libusb::Error),
+}
+
+/// Bulk transfers to and from the device, all the protocol needs of USB.
+pub trait Transport {
+    fn write(&mut self, endpoint: u8, buf: &[u8]) -> Result<usize, HantekUsbError>;
+
+    fn read(&mut self, endpoint: u8, buf: &mut [u8]) -> Result<usize, HantekUsbError>;
+
+    /// Discovered endpoints, `None` for the model's defaults.
+    fn endpoints(&self) -> Option<Endpoints> {
+        None
+    }
+}
+
+impl Transport for HantekUsbDevice<'_> {
+    fn endpoints(&self) -> Option<Endpoints> {
+        HantekUsbDevice::endpoints(self)
+    }
+
+    fn write(&mut self, endpoint: u8, buf: &[u8]) -> Result<usize, HantekUsbError> {
+        HantekUsbDevice::write(self, endpoint, buf)
+    }
+
+    fn read(&mut self, endpoint: u8, buf: &mut [u8]) -> Result<usize, HantekUsbError> {
+        HantekUsbDevice::read(self, endpoint, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::hantek2d42::USB_ID;
+
+    fn unit(serial: Option<&str>, address: u8) -> UnitId {
+        UnitId {
+            serial: serial.map(str::to_string),
+            bus: 1,
+            address,
+        }
+    }
+
+    #[test]
+    fn reopens_the_unit_of_the_serial() {
+        let units = || vec![(unit(Some("A"), 7), "a"), (unit(Some("B"), 8), "b")];
+        // Replugged, both at another address.
+        let replugged = vec![(unit(Some("B"), 3), "b"), (unit(Some("A"), 4), "a")];
+        assert_eq!(
+            find_unit(&unit(Some("A"), 7), units(), USB_ID).unwrap(),
+            "a"
+        );
+        assert_eq!(
+            find_unit(&unit(Some("B"), 8), units(), USB_ID).unwrap(),
+            "b"
+        );
+        assert_eq!(
+            find_unit(&unit(Some("A"), 7), replugged, USB_ID).unwrap(),
+            "a"
+        );
+        assert!(matches!(
+            find_unit(&unit(Some("C"), 7), units(), USB_ID),
+            Err(HantekUsbError::NoDeviceFound { .. })
+        ));
+    }
+
+    #[test]
+    fn reopens_the_unit_plugged_in_where_it_was() {
+        let units = || vec![(unit(None, 7), "a"), (unit(None, 8), "b")];
+        assert_eq!(find_unit(&unit(None, 8), units(), USB_ID).unwrap(), "b");
+        // Replugged, only a single unit is known to be it.
+        assert!(find_unit(&unit(None, 9), units(), USB_ID).is_err());
+        let single = vec![(unit(None, 9), "a")];
+        assert_eq!(find_unit(&unit(None, 7), single, USB_ID).unwrap(), "a");
+    }
+}
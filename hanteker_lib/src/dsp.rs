@@ -0,0 +1,563 @@
+//! Signal processing on captured samples.
+
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+
+#[cfg(feature = "cli")]
+use clap::ArgEnum;
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter, EnumString, EnumVariantNames};
+
+use crate::models::hantek2d42::{invalid, Hantek2D42Error};
+
+#[derive(Display, Debug, Clone, EnumString, EnumIter, EnumVariantNames, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ArgEnum))]
+pub enum Window {
+    Rectangular,
+    Hann,
+    Hamming,
+    Blackman,
+}
+
+impl Window {
+    pub fn my_iter() -> impl Iterator<Item = Window> {
+        Self::iter()
+    }
+
+    pub fn my_options() -> Vec<(String, Self)> {
+        Self::my_iter()
+            .map(|it| {
+                let as_string = it.my_to_string().to_string();
+                (as_string, it)
+            })
+            .collect()
+    }
+
+    // Because CLion doesn't like the Display implemented by strum.
+    pub fn my_to_string(&self) -> impl std::fmt::Display + '_ {
+        self
+    }
+
+    pub fn coefficients(&self, len: usize) -> Vec<f32> {
+        if len < 2 {
+            return vec![1.0; len];
+        }
+
+        let last = (len - 1) as f32;
+        (0..len)
+            .map(|idx| {
+                let x = 2.0 * PI * idx as f32 / last;
+                match self {
+                    Self::Rectangular => 1.0,
+                    Self::Hann => 0.5 - 0.5 * x.cos(),
+                    Self::Hamming => 0.54 - 0.46 * x.cos(),
+                    Self::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpectrumBin {
+    pub frequency: f32,
+    /// RMS amplitude of the bin in dB relative to 1 V.
+    pub magnitude_dbv: f32,
+}
+
+/// Floor for magnitudes so silent bins don't come out as -inf dBV.
+const MIN_MAGNITUDE: f32 = 1e-9;
+
+/// Single-sided amplitude spectrum of `samples` (in volts), which must have a power of two
+/// length. Returns `len / 2 + 1` bins from DC to Nyquist.
+pub fn spectrum(samples: &[f32], sample_rate: f32, window: &Window) -> Vec<SpectrumBin> {
+    let len = samples.len();
+    if len == 0 {
+        return vec![];
+    }
+
+    let coefficients = window.coefficients(len);
+    let coherent_gain = coefficients.iter().sum::<f32>() / len as f32;
+
+    let mut re: Vec<f32> = samples
+        .iter()
+        .zip(coefficients.iter())
+        .map(|(sample, coefficient)| sample * coefficient)
+        .collect();
+    let mut im = vec![0.0; len];
+    fft(&mut re, &mut im);
+
+    (0..=len / 2)
+        .map(|idx| {
+            let mut peak = (re[idx] * re[idx] + im[idx] * im[idx]).sqrt() / (len as f32);
+            peak /= coherent_gain;
+            // Energy of negative frequencies folded into the positive half, except DC and
+            // Nyquist which have no mirror.
+            let rms = if idx == 0 || idx == len / 2 {
+                peak
+            } else {
+                2.0 * peak / 2f32.sqrt()
+            };
+
+            SpectrumBin {
+                frequency: idx as f32 * sample_rate / len as f32,
+                magnitude_dbv: 20.0 * rms.max(MIN_MAGNITUDE).log10(),
+            }
+        })
+        .collect()
+}
+
+/// In-place iterative radix-2 FFT.
+///
+/// Panics if the lengths differ or aren't a power of two.
+pub fn fft(re: &mut [f32], im: &mut [f32]) {
+    let len = re.len();
+    if len != im.len() || !len.is_power_of_two() {
+        panic!(
+            "fft needs two buffers of the same power of two length, got re={} im={}",
+            len,
+            im.len()
+        );
+    }
+
+    let mut j = 0;
+    for i in 1..len {
+        let mut bit = len >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let mut size = 2;
+    while size <= len {
+        let angle = -2.0 * PI / size as f32;
+        for start in (0..len).step_by(size) {
+            for k in 0..size / 2 {
+                let (w_im, w_re) = (angle * k as f32).sin_cos();
+                let even = start + k;
+                let odd = even + size / 2;
+                let t_re = re[odd] * w_re - im[odd] * w_im;
+                let t_im = re[odd] * w_im + im[odd] * w_re;
+                re[odd] = re[even] - t_re;
+                im[odd] = im[even] - t_im;
+                re[even] += t_re;
+                im[even] += t_im;
+            }
+        }
+        size <<= 1;
+    }
+}
+
+/// Filter on a stream of samples, written `lowpass:10k`, `highpass:50`, `bandpass:1k-5k` or
+/// `average:8`. Frequencies are in Hz, with an optional k or M suffix.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterSpec {
+    LowPass(f32),
+    HighPass(f32),
+    /// Lower and upper corner.
+    BandPass(f32, f32),
+    /// Mean of the given number of most recent samples.
+    MovingAverage(usize),
+}
+
+impl FilterSpec {
+    pub fn parse(text: &str) -> Result<Self, Hantek2D42Error> {
+        let bad = || {
+            invalid(
+                "filter",
+                text,
+                "lowpass:<Hz>, highpass:<Hz>, bandpass:<Hz>-<Hz> or average:<samples>",
+            )
+        };
+        let (kind, value) = text.split_once(':').ok_or_else(bad)?;
+        match kind {
+            "lowpass" => Ok(Self::LowPass(parse_hz(value).ok_or_else(bad)?)),
+            "highpass" => Ok(Self::HighPass(parse_hz(value).ok_or_else(bad)?)),
+            "bandpass" => {
+                let (low, high) = value.split_once('-').ok_or_else(bad)?;
+                Ok(Self::BandPass(
+                    parse_hz(low).ok_or_else(bad)?,
+                    parse_hz(high).ok_or_else(bad)?,
+                ))
+            }
+            "average" => match value.parse() {
+                Ok(len) if len > 0 => Ok(Self::MovingAverage(len)),
+                _ => Err(bad()),
+            },
+            _ => Err(bad()),
+        }
+    }
+}
+
+impl std::fmt::Display for FilterSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::LowPass(corner) => write!(f, "lowpass:{}", corner),
+            Self::HighPass(corner) => write!(f, "highpass:{}", corner),
+            Self::BandPass(low, high) => write!(f, "bandpass:{}-{}", low, high),
+            Self::MovingAverage(len) => write!(f, "average:{}", len),
+        }
+    }
+}
+
+/// Shrinking of captures before they're written out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decimation {
+    /// Min and max of every given number of samples, see [`crate::capture::Envelope`].
+    Envelope(usize),
+}
+
+impl Decimation {
+    pub fn parse(text: &str) -> Result<Self, Hantek2D42Error> {
+        let bad = || invalid("decimation", text, "envelope:<samples>, at least 2 samples");
+        let (kind, value) = text.split_once(':').ok_or_else(bad)?;
+        match kind {
+            "envelope" => match value.parse() {
+                Ok(len) if len > 1 => Ok(Self::Envelope(len)),
+                _ => Err(bad()),
+            },
+            _ => Err(bad()),
+        }
+    }
+}
+
+impl std::fmt::Display for Decimation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Envelope(len) => write!(f, "envelope:{}", len),
+        }
+    }
+}
+
+fn parse_hz(value: &str) -> Option<f32> {
+    let (number, multiplier) = match value.strip_suffix(['k', 'K']) {
+        Some(number) => (number, 1e3),
+        None => match value.strip_suffix('M') {
+            Some(number) => (number, 1e6),
+            None => (value, 1.0),
+        },
+    };
+    number.parse::<f32>().ok().map(|it| it * multiplier)
+}
+
+/// A filter along with its state, carried over from one call of [`Self::process`] to the
+/// next so a stream of captures is filtered as one signal. Low and high-pass are second order
+/// Butterworth sections, band-pass a high-pass at the lower corner followed by a low-pass at
+/// the upper one.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    stages: Vec<Stage>,
+}
+
+impl Filter {
+    pub fn new(spec: &FilterSpec, sample_rate: f32) -> Result<Self, Hantek2D42Error> {
+        let nyquist = sample_rate / 2.0;
+        let corner = |frequency: f32| {
+            if frequency > 0.0 && frequency < nyquist {
+                Ok(frequency / sample_rate)
+            } else {
+                Err(invalid(
+                    "filter corner",
+                    frequency,
+                    format!("0..{} Hz at this sample rate", nyquist),
+                ))
+            }
+        };
+        let stages = match spec {
+            FilterSpec::LowPass(frequency) => vec![Stage::low_pass(corner(*frequency)?)],
+            FilterSpec::HighPass(frequency) => vec![Stage::high_pass(corner(*frequency)?)],
+            FilterSpec::BandPass(low, high) => {
+                if low >= high {
+                    return Err(invalid(
+                        "band-pass corners",
+                        format!("{}-{}", low, high),
+                        "lower corner first",
+                    ));
+                }
+                vec![
+                    Stage::high_pass(corner(*low)?),
+                    Stage::low_pass(corner(*high)?),
+                ]
+            }
+            FilterSpec::MovingAverage(len) => vec![Stage::Average {
+                len: *len,
+                window: VecDeque::with_capacity(*len),
+                sum: 0.0,
+            }],
+        };
+        Ok(Self { stages })
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        for stage in &mut self.stages {
+            for sample in samples.iter_mut() {
+                *sample = stage.next(*sample);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Stage {
+    /// Normalized coefficients, `b` feed forward and `a` feed back, with the last two inputs
+    /// and outputs, `None` until the first sample.
+    Biquad {
+        b: [f64; 3],
+        a: [f64; 2],
+        state: Option<([f64; 2], [f64; 2])>,
+    },
+    Average {
+        len: usize,
+        window: VecDeque<f32>,
+        sum: f64,
+    },
+}
+
+impl Stage {
+    /// Corner as a fraction of the sample rate, coefficients from the RBJ audio EQ cookbook.
+    fn low_pass(corner: f32) -> Self {
+        let (cos, alpha) = Self::prewarp(corner);
+        Self::biquad(
+            [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+            cos,
+            alpha,
+        )
+    }
+
+    fn high_pass(corner: f32) -> Self {
+        let (cos, alpha) = Self::prewarp(corner);
+        Self::biquad(
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            cos,
+            alpha,
+        )
+    }
+
+    fn prewarp(corner: f32) -> (f64, f64) {
+        let omega = 2.0 * std::f64::consts::PI * corner as f64;
+        (omega.cos(), omega.sin() * std::f64::consts::FRAC_1_SQRT_2)
+    }
+
+    fn biquad(b: [f64; 3], cos: f64, alpha: f64) -> Self {
+        let a0 = 1.0 + alpha;
+        Self::Biquad {
+            b: [b[0] / a0, b[1] / a0, b[2] / a0],
+            a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+            state: None,
+        }
+    }
+
+    fn next(&mut self, sample: f32) -> f32 {
+        match self {
+            Self::Biquad { b, a, state } => {
+                let x = sample as f64;
+                // Settled on the first sample as if it had been there forever, rather than
+                // ringing from zero.
+                let (xs, ys) = state.get_or_insert_with(|| {
+                    let dc_gain = (b[0] + b[1] + b[2]) / (1.0 + a[0] + a[1]);
+                    ([x, x], [x * dc_gain, x * dc_gain])
+                });
+                let y = b[0] * x + b[1] * xs[0] + b[2] * xs[1] - a[0] * ys[0] - a[1] * ys[1];
+                *xs = [x, xs[0]];
+                *ys = [y, ys[0]];
+                y as f32
+            }
+            Self::Average { len, window, sum } => {
+                window.push_back(sample);
+                *sum += sample as f64;
+                if window.len() > *len {
+                    *sum -= window.pop_front().unwrap_or_default() as f64;
+                }
+                (*sum / window.len() as f64) as f32
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEN: usize = 64;
+
+    fn assert_close(actual: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{} is not within {} of {}",
+            actual,
+            tolerance,
+            expected
+        );
+    }
+
+    #[test]
+    fn impulse_has_a_flat_spectrum() {
+        let mut re = vec![0.0; LEN];
+        re[0] = 1.0;
+        let mut im = vec![0.0; LEN];
+
+        fft(&mut re, &mut im);
+
+        for idx in 0..LEN {
+            assert_close(re[idx], 1.0, 1e-6);
+            assert_close(im[idx], 0.0, 1e-6);
+        }
+
+        let mut samples = vec![0.0; LEN];
+        samples[0] = 1.0;
+        let bins = spectrum(&samples, LEN as f32, &Window::Rectangular);
+        assert_eq!(bins.len(), LEN / 2 + 1);
+        for bin in &bins[1..LEN / 2] {
+            assert_close(bin.magnitude_dbv, bins[1].magnitude_dbv, 1e-4);
+        }
+    }
+
+    #[test]
+    fn sine_in_its_bin_comes_out_at_its_rms() {
+        let bin = 8;
+        let amplitude = 2.0;
+        let samples: Vec<f32> = (0..LEN)
+            .map(|idx| amplitude * (2.0 * PI * (bin * idx) as f32 / LEN as f32).sin())
+            .collect();
+        let rms_dbv = 20.0 * (amplitude / 2f32.sqrt()).log10();
+
+        for window in Window::my_iter() {
+            let bins = spectrum(&samples, 1000.0, &window);
+
+            assert_close(bins[bin].frequency, bin as f32 * 1000.0 / LEN as f32, 1e-3);
+            assert_close(bins[bin].magnitude_dbv, rms_dbv, 0.2);
+            let loudest = bins
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.magnitude_dbv.total_cmp(&b.1.magnitude_dbv))
+                .map(|it| it.0);
+            assert_eq!(loudest, Some(bin), "{}", window);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "power of two")]
+    fn fft_of_other_lengths_panics() {
+        fft(&mut [0.0; 48], &mut [0.0; 48]);
+    }
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    fn sine(frequency: f32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|idx| (2.0 * PI * frequency * idx as f32 / SAMPLE_RATE).sin())
+            .collect()
+    }
+
+    fn filtered(spec: &FilterSpec, mut samples: Vec<f32>) -> Vec<f32> {
+        Filter::new(spec, SAMPLE_RATE)
+            .unwrap()
+            .process(&mut samples);
+        samples
+    }
+
+    #[test]
+    fn low_pass_passes_dc() {
+        let samples = filtered(&FilterSpec::LowPass(1000.0), vec![1.5; 1000]);
+
+        for sample in samples {
+            assert_close(sample, 1.5, 1e-4);
+        }
+    }
+
+    #[test]
+    fn high_pass_settles_to_zero() {
+        let mut step = vec![0.0; 100];
+        step.resize(10_000, 1.0);
+
+        let samples = filtered(&FilterSpec::HighPass(100.0), step);
+
+        assert!(samples[100] > 0.5);
+        assert_close(samples[samples.len() - 1], 0.0, 1e-3);
+    }
+
+    #[test]
+    fn tone_at_the_corner_is_3_db_down() {
+        for spec in [FilterSpec::LowPass(1000.0), FilterSpec::HighPass(1000.0)] {
+            let samples = filtered(&spec, sine(1000.0, 4800));
+
+            // Past the transient, a whole number of periods.
+            let peak = samples[2400..]
+                .iter()
+                .fold(0f32, |peak, sample| peak.max(sample.abs()));
+            assert_close(20.0 * peak.log10(), -3.0, 0.1);
+        }
+    }
+
+    #[test]
+    fn state_carries_across_calls() {
+        let samples = sine(3000.0, 1000);
+        let spec = FilterSpec::BandPass(1000.0, 5000.0);
+        let whole = filtered(&spec, samples.clone());
+
+        let mut filter = Filter::new(&spec, SAMPLE_RATE).unwrap();
+        let (mut first, mut second) = (samples[..300].to_vec(), samples[300..].to_vec());
+        filter.process(&mut first);
+        filter.process(&mut second);
+        first.extend(second);
+
+        assert_eq!(first, whole);
+    }
+
+    #[test]
+    fn moving_average_of_the_last_samples() {
+        let samples = filtered(&FilterSpec::MovingAverage(2), vec![1.0, 3.0, 5.0, 7.0]);
+
+        assert_eq!(samples, vec![1.0, 2.0, 4.0, 6.0]);
+    }
+
+    #[test]
+    fn parses_filters() {
+        assert_eq!(
+            FilterSpec::parse("lowpass:10k").unwrap(),
+            FilterSpec::LowPass(10_000.0)
+        );
+        assert_eq!(
+            FilterSpec::parse("highpass:50").unwrap(),
+            FilterSpec::HighPass(50.0)
+        );
+        assert_eq!(
+            FilterSpec::parse("bandpass:1k-2.5M").unwrap(),
+            FilterSpec::BandPass(1000.0, 2_500_000.0)
+        );
+        assert_eq!(
+            FilterSpec::parse("average:8").unwrap(),
+            FilterSpec::MovingAverage(8)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_filters() {
+        for text in [
+            "",
+            "lowpass",
+            "lowpass:",
+            "lowpass:fast",
+            "bandpass:1k",
+            "bandpass:1k-",
+            "average:0",
+            "average:-1",
+            "notch:50",
+        ] {
+            assert!(FilterSpec::parse(text).is_err(), "{}", text);
+        }
+        for spec in [
+            FilterSpec::LowPass(SAMPLE_RATE),
+            FilterSpec::HighPass(0.0),
+            FilterSpec::BandPass(5000.0, 1000.0),
+        ] {
+            assert!(Filter::new(&spec, SAMPLE_RATE).is_err(), "{}", spec);
+        }
+    }
+}
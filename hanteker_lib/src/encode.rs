@@ -0,0 +1,139 @@
+//! Byte strings coded as an arbitrary waveform, so the AWG can stand in for a serial line or a
+//! PWM coded source. One period of the waveform holds the whole message with the line idle
+//! around it, the AWG frequency sets the bit rate.
+//!
+//! The USB command loading an arb slot isn't known, the waveform is written out to be loaded
+//! into a slot with the vendor software, then played with `AwgType::Arb1` to `Arb4`.
+
+#[cfg(feature = "cli")]
+use clap::ArgEnum;
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter, EnumString, EnumVariantNames};
+
+use crate::models::hantek2d42::{invalid, Hantek2D42Error};
+
+/// Bits the line is left idle before and after the message.
+const IDLE_BITS: usize = 2;
+
+const LOW: f32 = -1.0;
+const HIGH: f32 = 1.0;
+
+#[allow(non_camel_case_types)]
+#[derive(Display, Debug, Clone, EnumString, EnumIter, EnumVariantNames, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ArgEnum))]
+pub enum Protocol {
+    /// 8N1, least significant bit first, idle high.
+    uart,
+    /// IEEE 802.3 convention, a 0 falls and a 1 rises mid bit, most significant bit first, idle
+    /// low.
+    manchester,
+    /// A 1 is high for three quarters of the bit, a 0 for one quarter, most significant bit
+    /// first, idle low.
+    pwm,
+}
+
+impl Protocol {
+    pub fn my_iter() -> impl Iterator<Item = Protocol> {
+        Self::iter()
+    }
+
+    pub fn my_options() -> Vec<(String, Self)> {
+        Self::my_iter()
+            .map(|it| {
+                let as_string = it.my_to_string().to_string();
+                (as_string, it)
+            })
+            .collect()
+    }
+
+    // Because CLion doesn't like the Display implemented by strum.
+    pub fn my_to_string(&self) -> impl std::fmt::Display + '_ {
+        self
+    }
+
+    fn idle(&self) -> f32 {
+        match self {
+            Self::uart => HIGH,
+            Self::manchester | Self::pwm => LOW,
+        }
+    }
+
+    /// Finer bits can't be told apart.
+    fn min_samples_per_bit(&self) -> usize {
+        match self {
+            Self::uart => 1,
+            Self::manchester => 2,
+            Self::pwm => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtocolWaveform {
+    /// -1 for low and 1 for high, scaled by the AWG amplitude and shifted by its offset.
+    pub samples: Vec<f32>,
+    pub samples_per_bit: usize,
+    /// Bits in one period, idle ones included.
+    pub num_bits: usize,
+}
+
+impl ProtocolWaveform {
+    /// AWG frequency at which bits go out at the given rate.
+    pub fn frequency(&self, baud: f32) -> f32 {
+        baud / self.num_bits as f32
+    }
+}
+
+pub fn encode(
+    protocol: &Protocol,
+    data: &[u8],
+    samples_per_bit: usize,
+) -> Result<ProtocolWaveform, Hantek2D42Error> {
+    if data.is_empty() {
+        return Err(invalid("encoded data", "empty", "at least one byte"));
+    }
+    let min = protocol.min_samples_per_bit();
+    if samples_per_bit < min {
+        return Err(invalid(
+            "samples per bit",
+            samples_per_bit,
+            format!("at least {} for {}", min, protocol),
+        ));
+    }
+
+    let mut bits = vec![];
+    for byte in data {
+        match protocol {
+            Protocol::uart => {
+                bits.push(false);
+                bits.extend((0..8).map(|idx| byte & (1 << idx) != 0));
+                bits.push(true);
+            }
+            Protocol::manchester | Protocol::pwm => {
+                bits.extend((0..8).rev().map(|idx| byte & (1 << idx) != 0));
+            }
+        }
+    }
+
+    let idle = vec![protocol.idle(); IDLE_BITS * samples_per_bit];
+    let mut samples = idle.clone();
+    for bit in &bits {
+        // Samples of the bit before the switch, and the levels on either side of it.
+        let (switch, first, second) = match (protocol, bit) {
+            (Protocol::uart, true) => (samples_per_bit, HIGH, HIGH),
+            (Protocol::uart, false) => (samples_per_bit, LOW, LOW),
+            (Protocol::manchester, true) => (samples_per_bit / 2, LOW, HIGH),
+            (Protocol::manchester, false) => (samples_per_bit / 2, HIGH, LOW),
+            (Protocol::pwm, true) => (samples_per_bit * 3 / 4, HIGH, LOW),
+            (Protocol::pwm, false) => (samples_per_bit / 4, HIGH, LOW),
+        };
+        samples.extend((0..samples_per_bit).map(|idx| if idx < switch { first } else { second }));
+    }
+    samples.extend(idle);
+
+    Ok(ProtocolWaveform {
+        samples,
+        samples_per_bit,
+        num_bits: bits.len() + 2 * IDLE_BITS,
+    })
+}
@@ -0,0 +1,75 @@
+//! What happens on a device, handed to whoever subscribed, so frontends needn't poll for it.
+//!
+//! Every [`Hantek2D42`](crate::models::hantek2d42::Hantek2D42) has an [`EventBus`], see its
+//! `events`. Events are only built while someone is subscribed, a device without subscribers
+//! pays for nothing but a lock.
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::capture::CaptureFrame;
+use crate::device::cfg::HantekConfig;
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// A capture was asked of the device, samples per channel.
+    CaptureStarted {
+        channels: Vec<usize>,
+        num_samples: usize,
+    },
+    /// A capture read through `capture_frame` and its siblings. A clone sharing the samples of
+    /// the frame returned to the caller, which then can't go back into a pool while held.
+    FrameReady(Arc<CaptureFrame>),
+    /// A transfer failed because the device is gone, e.g. unplugged.
+    DeviceDisconnected,
+    /// A setting was sent, the config being the cached one after it.
+    ConfigChanged(Arc<HantekConfig>),
+    /// A transfer failed with a transient error and is retried, see `RetryConfig`.
+    UsbRetry {
+        endpoint: u8,
+        /// Starts at 1 for the first retry.
+        attempt: u32,
+        error: String,
+    },
+}
+
+/// Clones share their subscribers, e.g. the device and its USB transport emit to the same bus.
+#[derive(Clone, Default)]
+pub struct EventBus {
+    subscribers: Arc<Mutex<Vec<Sender<Event>>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every event from now on, until the receiver is dropped. The channel is unbounded, a
+    /// subscriber is expected to keep draining it.
+    pub fn subscribe(&self) -> Receiver<Event> {
+        let (sender, receiver) = mpsc::channel();
+        self.lock().push(sender);
+        receiver
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        !self.lock().is_empty()
+    }
+
+    /// Builds the event only if anyone is subscribed, dropping the subscribers that are gone.
+    pub(crate) fn emit(&self, event: impl FnOnce() -> Event) {
+        let mut subscribers = self.lock();
+        if subscribers.is_empty() {
+            return;
+        }
+        let event = event();
+        subscribers.retain(|it| it.send(event.clone()).is_ok());
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Sender<Event>>> {
+        // Nothing a panic while holding the lock could leave half done.
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
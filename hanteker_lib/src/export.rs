@@ -0,0 +1,881 @@
+//! Sinks writing capture frames out as they come, one row per sample. Nothing is kept between
+//! frames but the running sample index, so indefinite captures run in bounded memory.
+
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "cli")]
+use clap::ArgEnum;
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter, EnumString, EnumVariantNames};
+
+use crate::capture::{counts_to_volts, raw_to_volts, CaptureFrame, Envelope, RollClock};
+use crate::device::cfg::Scale;
+use crate::math::MathExpr;
+
+#[derive(Display, Debug, Clone, EnumString, EnumIter, EnumVariantNames, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ArgEnum))]
+pub enum ExportFormat {
+    /// Samples as captured, interleaved, one signed byte each.
+    Raw,
+    /// A header naming the columns, then a row per sample.
+    Csv,
+    /// A JSON object per sample.
+    Jsonl,
+    /// Each frame's raw samples after a header describing them, see [`FramedSink`].
+    Framed,
+    /// Samples as interleaved little-endian float32 volts, see [`F32Sink`].
+    F32le,
+}
+
+impl ExportFormat {
+    pub fn my_iter() -> impl Iterator<Item = ExportFormat> {
+        Self::iter()
+    }
+
+    pub fn my_options() -> Vec<(String, Self)> {
+        Self::my_iter()
+            .map(|it| {
+                let as_string = it.my_to_string().to_string();
+                (as_string, it)
+            })
+            .collect()
+    }
+
+    // Because CLion doesn't like the Display implemented by strum.
+    pub fn my_to_string(&self) -> impl std::fmt::Display + '_ {
+        self
+    }
+
+    pub fn sink<'a, W: Write + 'a>(&self, out: W) -> Box<dyn FrameSink + 'a> {
+        self.sink_with_math(out, vec![])
+    }
+
+    /// Same as [`Self::sink`], the row formats getting a column in volts for each expression,
+    /// left empty where a channel it references has no known scale. Raw and framed have no room
+    /// for them.
+    pub fn sink_with_math<'a, W: Write + 'a>(
+        &self,
+        out: W,
+        math: Vec<MathExpr>,
+    ) -> Box<dyn FrameSink + 'a> {
+        match self {
+            Self::Raw => Box::new(RawSink { out }),
+            Self::Csv => Box::new(RowSink::new(out, RowFormat::Csv, math)),
+            Self::Jsonl => Box::new(RowSink::new(out, RowFormat::Jsonl, math)),
+            Self::Framed => Box::new(FramedSink { out }),
+            Self::F32le => Box::new(F32Sink {
+                out,
+                stream: None,
+                buf: vec![],
+            }),
+        }
+    }
+
+    /// Same as [`Self::sink_with_math`], each frame being a segment of a segmented capture. The
+    /// row formats get a leading column numbering the segments from 0, the sample and the time
+    /// starting over at each one. Framed tells the time of each segment in its header.
+    pub fn segment_sink<'a, W: Write + 'a>(
+        &self,
+        out: W,
+        math: Vec<MathExpr>,
+    ) -> Box<dyn FrameSink + 'a> {
+        match self {
+            Self::Csv => Box::new(RowSink::new(out, RowFormat::Csv, math).segmented()),
+            Self::Jsonl => Box::new(RowSink::new(out, RowFormat::Jsonl, math).segmented()),
+            _ => self.sink_with_math(out, math),
+        }
+    }
+
+    /// Writes envelopes rather than frames, raw as the min then the max byte of each channel
+    /// per bucket, the row formats as a row per bucket with a min and a max column per channel.
+    pub fn envelope_sink<W: Write>(&self, out: W) -> EnvelopeSink<W> {
+        EnvelopeSink {
+            out,
+            format: self.clone(),
+            layout: None,
+            bucket: 0,
+            samples: 0,
+        }
+    }
+}
+
+pub trait FrameSink {
+    fn write_frame(&mut self, frame: &CaptureFrame) -> io::Result<()>;
+
+    fn flush(&mut self) -> io::Result<()>;
+}
+
+pub struct RawSink<W: Write> {
+    out: W,
+}
+
+impl<W: Write> FrameSink for RawSink<W> {
+    fn write_frame(&mut self, frame: &CaptureFrame) -> io::Result<()> {
+        self.out.write_all(&frame.raw)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// First bytes of every framed frame.
+pub const FRAMED_MAGIC: [u8; 4] = *b"HNTK";
+/// Version of the framed header, bumped on any change a reader must know of.
+pub const FRAMED_VERSION: u8 = 1;
+/// Length of the framed header before the scales of the channels.
+const FRAMED_FIXED_LEN: usize = 24;
+
+/// Header of a frame written by [`FramedSink`], all numbers little endian:
+///
+/// | offset | type    | field                                                            |
+/// |--------|---------|------------------------------------------------------------------|
+/// | 0      | [u8; 4] | magic, `HNTK`                                                    |
+/// | 4      | u8      | version, 1                                                       |
+/// | 5      | u8      | channel mask, bit 0 for channel 1                                |
+/// | 6      | u16     | length of the header in bytes, the payload starts right after it |
+/// | 8      | f32     | time scale in seconds per division, NaN if unknown               |
+/// | 12     | u64     | microseconds since the Unix epoch the capture started at         |
+/// | 20     | u32     | length of the payload in bytes                                   |
+/// | 24     | f32 × n | scale of each channel in the mask, volts per division, NaN if unknown |
+///
+/// The payload is the raw samples as with [`ExportFormat::Raw`], interleaved in the order of
+/// the channels. Readers skip any header bytes past the scales, later versions may add fields
+/// there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FramedHeader {
+    pub version: u8,
+    /// Channels in the mask, sorted.
+    pub channels: Vec<usize>,
+    /// Seconds per division.
+    pub time_scale: Option<f32>,
+    pub timestamp: SystemTime,
+    pub payload_len: u32,
+    /// Volts per division of each channel in `channels`.
+    pub scales: Vec<Option<f32>>,
+}
+
+impl FramedHeader {
+    fn of(frame: &CaptureFrame) -> io::Result<Self> {
+        if frame.channels.iter().any(|it| !(1..=8).contains(it)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("channels {:?} don't fit the mask", frame.channels),
+            ));
+        }
+        let payload_len = u32::try_from(frame.raw.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame is too long"))?;
+        Ok(Self {
+            version: FRAMED_VERSION,
+            channels: frame.channels.clone(),
+            time_scale: frame.time_scale.as_ref().map(|it| it.raw_value()),
+            timestamp: frame.started_at,
+            payload_len,
+            scales: frame
+                .scales
+                .iter()
+                .map(|it| it.as_ref().map(|it| it.raw_value()))
+                .collect(),
+        })
+    }
+
+    /// Bytes of the header as written.
+    pub fn header_len(&self) -> usize {
+        FRAMED_FIXED_LEN + 4 * self.channels.len()
+    }
+
+    pub fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mask = self
+            .channels
+            .iter()
+            .fold(0u8, |mask, it| mask | 1 << (it - 1));
+        let micros = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        out.write_all(&FRAMED_MAGIC)?;
+        out.write_all(&[self.version, mask])?;
+        out.write_all(&(self.header_len() as u16).to_le_bytes())?;
+        out.write_all(&self.time_scale.unwrap_or(f32::NAN).to_le_bytes())?;
+        out.write_all(&micros.to_le_bytes())?;
+        out.write_all(&self.payload_len.to_le_bytes())?;
+        for scale in &self.scales {
+            out.write_all(&scale.unwrap_or(f32::NAN).to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads a header, `None` at the end of the input right before one.
+    pub fn read_from<R: Read>(input: &mut R) -> io::Result<Option<Self>> {
+        let mut fixed = [0; FRAMED_FIXED_LEN];
+        let read = read_full(input, &mut fixed)?;
+        if read == 0 {
+            return Ok(None);
+        }
+        if read < fixed.len() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        if fixed[0..4] != FRAMED_MAGIC {
+            return Err(invalid_data("not a framed frame, magic doesn't match"));
+        }
+
+        let version = fixed[4];
+        if version == 0 || version > FRAMED_VERSION {
+            return Err(invalid_data(format!("unknown framed version {}", version)));
+        }
+        let mask = fixed[5];
+        let channels: Vec<usize> = (0..8)
+            .filter(|bit| mask & (1 << bit) != 0)
+            .map(|bit| bit + 1)
+            .collect();
+        let len = u16::from_le_bytes([fixed[6], fixed[7]]) as usize;
+        if len < FRAMED_FIXED_LEN + 4 * channels.len() {
+            return Err(invalid_data(format!(
+                "header of {} bytes is too short",
+                len
+            )));
+        }
+        let f32_at = |at: usize| f32::from_le_bytes(fixed[at..at + 4].try_into().unwrap());
+        let time_scale = Some(f32_at(8)).filter(|it| !it.is_nan());
+        let micros = u64::from_le_bytes(fixed[12..20].try_into().unwrap());
+        let payload_len = u32::from_le_bytes(fixed[20..24].try_into().unwrap());
+
+        let mut rest = vec![0; len - FRAMED_FIXED_LEN];
+        input.read_exact(&mut rest)?;
+        let scales = rest
+            .chunks_exact(4)
+            .take(channels.len())
+            .map(|it| f32::from_le_bytes(it.try_into().unwrap()))
+            .map(|it| Some(it).filter(|it| !it.is_nan()))
+            .collect();
+
+        Ok(Some(Self {
+            version,
+            channels,
+            time_scale,
+            timestamp: UNIX_EPOCH + Duration::from_micros(micros),
+            payload_len,
+            scales,
+        }))
+    }
+}
+
+/// Reads a frame written by [`FramedSink`], `None` at the end of the input.
+pub fn read_framed<R: Read>(input: &mut R) -> io::Result<Option<(FramedHeader, Vec<u8>)>> {
+    let header = match FramedHeader::read_from(input)? {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    // Grown as the payload arrives rather than to the length the header claims, a truncated or
+    // corrupt input mustn't allocate gigabytes before failing.
+    let mut payload = vec![];
+    input
+        .take(header.payload_len as u64)
+        .read_to_end(&mut payload)?;
+    if payload.len() < header.payload_len as usize {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(Some((header, payload)))
+}
+
+/// Bytes read before the end of the input, less than the buffer only at the end.
+fn read_full<R: Read>(input: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match input.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
+
+fn invalid_data(message: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.into())
+}
+
+/// Writes each frame as a [`FramedHeader`] followed by its raw samples. Unlike the other
+/// formats, frames may differ in channels and scales, every frame tells its own.
+pub struct FramedSink<W: Write> {
+    out: W,
+}
+
+impl<W: Write> FrameSink for FramedSink<W> {
+    fn write_frame(&mut self, frame: &CaptureFrame) -> io::Result<()> {
+        FramedHeader::of(frame)?.write_to(&mut self.out)?;
+        self.out.write_all(&frame.raw)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Writes the samples as interleaved little-endian float32 volts, as the float streams of GNU
+/// Radio and the like take them. There is no header, nothing tells the sample rate or where a
+/// frame ends: every frame must have the channels and sample rate of the first one, and the scale
+/// of every channel must be known.
+pub struct F32Sink<W: Write> {
+    out: W,
+    /// Channels and sample rate of the first frame.
+    stream: Option<(Vec<usize>, Option<f32>)>,
+    /// Bytes of a frame, kept between frames.
+    buf: Vec<u8>,
+}
+
+impl<W: Write> FrameSink for F32Sink<W> {
+    fn write_frame(&mut self, frame: &CaptureFrame) -> io::Result<()> {
+        if let Some(idx) = frame.scales.iter().position(Option::is_none) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "scale of channel {} is unknown, f32le has volts only",
+                    frame.channels[idx]
+                ),
+            ));
+        }
+        let stream = (frame.channels.clone(), frame.sample_rate());
+        match &self.stream {
+            None => self.stream = Some(stream),
+            Some(first) if *first != stream => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "frame has channels {:?} at {:?} samples/s, the stream was started with {:?} at {:?}",
+                        stream.0, stream.1, first.0, first.1
+                    ),
+                ))
+            }
+            Some(_) => {}
+        }
+
+        let scales = &frame.scales;
+        self.buf.clear();
+        for idx in 0..frame.num_samples() * scales.len() {
+            // Checked above.
+            let scale = scales[idx % scales.len()].as_ref().unwrap();
+            let sample = counts_to_volts(frame.counts(idx), scale);
+            self.buf.extend_from_slice(&sample.to_le_bytes());
+        }
+        self.out.write_all(&self.buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+enum RowFormat {
+    Csv,
+    Jsonl,
+}
+
+/// Columns of the rows, fixed by the first frame.
+struct Layout {
+    channels: Vec<usize>,
+    /// Whether each channel is written in volts, raw counts otherwise.
+    volts: Vec<bool>,
+    columns: Vec<String>,
+    /// Whether a time column is written, in seconds since the start of the first frame. Time
+    /// between captures is counted, as told by the monotonic clock.
+    time: bool,
+}
+
+impl Layout {
+    fn of(frame: &CaptureFrame) -> Self {
+        let volts = frame
+            .channels
+            .iter()
+            .map(|it| frame.scale(*it).is_some())
+            .collect();
+        Self::new(&frame.channels, volts, frame.sample_rate().is_some())
+    }
+
+    fn of_envelope(envelope: &Envelope) -> Self {
+        let volts = envelope
+            .channels
+            .iter()
+            .map(|it| envelope.scale(*it).is_some())
+            .collect();
+        Self::new(&envelope.channels, volts, envelope.sample_rate().is_some())
+    }
+
+    fn new(channels: &[usize], volts: Vec<bool>, time: bool) -> Self {
+        let columns = channels
+            .iter()
+            .zip(&volts)
+            .map(|(channel_no, volts)| {
+                let unit = if *volts { "volts" } else { "raw" };
+                format!("ch{}_{}", channel_no, unit)
+            })
+            .collect();
+        Self {
+            channels: channels.to_vec(),
+            volts,
+            columns,
+            time,
+        }
+    }
+}
+
+/// Writes a row per sample. Every frame must have the channels of the first one, the columns
+/// are decided on it; a channel whose scale was unknown then stays in raw counts.
+pub struct RowSink<W: Write> {
+    out: W,
+    format: RowFormat,
+    layout: Option<Layout>,
+    math: Vec<MathExpr>,
+    index: u64,
+    /// Start of the first frame, the origin of the time column.
+    origin: Option<Instant>,
+    /// Segment the next frame is, when each frame is a segment of its own.
+    segment: Option<u64>,
+}
+
+impl<W: Write> RowSink<W> {
+    fn new(out: W, format: RowFormat, math: Vec<MathExpr>) -> Self {
+        Self {
+            out,
+            format,
+            layout: None,
+            math,
+            index: 0,
+            origin: None,
+            segment: None,
+        }
+    }
+
+    fn segmented(mut self) -> Self {
+        self.segment = Some(0);
+        self
+    }
+
+    fn write_header(&mut self, layout: &Layout) -> io::Result<()> {
+        if let RowFormat::Jsonl = self.format {
+            return Ok(());
+        }
+        if self.segment.is_some() {
+            write!(self.out, "segment,")?;
+        }
+        write!(self.out, "sample")?;
+        if layout.time {
+            write!(self.out, ",time")?;
+        }
+        for column in &layout.columns {
+            write!(self.out, ",{}", column)?;
+        }
+        for expr in &self.math {
+            write!(self.out, ",{}", expr)?;
+        }
+        writeln!(self.out)
+    }
+
+    fn write_row(
+        &mut self,
+        layout: &Layout,
+        samples: &[f32],
+        scales: &[Option<&Scale>],
+        time: Option<f64>,
+        math: &[Option<f32>],
+    ) -> io::Result<()> {
+        let json = matches!(self.format, RowFormat::Jsonl);
+        match (json, self.segment) {
+            (true, Some(segment)) => write!(self.out, "{{\"segment\":{},", segment)?,
+            (true, None) => write!(self.out, "{{")?,
+            (false, Some(segment)) => write!(self.out, "{},", segment)?,
+            (false, None) => {}
+        }
+        if json {
+            write!(self.out, "\"sample\":{}", self.index)?;
+        } else {
+            write!(self.out, "{}", self.index)?;
+        }
+        if let Some(time) = time {
+            if json {
+                write!(self.out, ",\"time\":{}", time)?;
+            } else {
+                write!(self.out, ",{}", time)?;
+            }
+        }
+        for ((sample, scale), column) in samples.iter().zip(scales).zip(&layout.columns) {
+            if json {
+                write!(self.out, ",\"{}\":", column)?;
+            } else {
+                write!(self.out, ",")?;
+            }
+            match scale {
+                Some(scale) => write!(self.out, "{}", counts_to_volts(*sample, scale))?,
+                None => write!(self.out, "{}", sample)?,
+            }
+        }
+        for (expr, value) in self.math.iter().zip(math) {
+            match (json, value) {
+                (true, Some(value)) => write!(self.out, ",\"{}\":{}", expr, value)?,
+                (true, None) => write!(self.out, ",\"{}\":null", expr)?,
+                (false, Some(value)) => write!(self.out, ",{}", value)?,
+                (false, None) => write!(self.out, ",")?,
+            }
+        }
+        self.index += 1;
+        writeln!(self.out, "{}", if json { "}" } else { "" })
+    }
+}
+
+impl<W: Write> FrameSink for RowSink<W> {
+    fn write_frame(&mut self, frame: &CaptureFrame) -> io::Result<()> {
+        let layout = match self.layout.take() {
+            Some(layout) => layout,
+            None => {
+                let layout = Layout::of(frame);
+                self.write_header(&layout)?;
+                layout
+            }
+        };
+        if frame.channels != layout.channels {
+            let error = io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "frame has channels {:?}, the rows were started with {:?}",
+                    frame.channels, layout.channels
+                ),
+            );
+            self.layout = Some(layout);
+            return Err(error);
+        }
+
+        let scales: Vec<_> = layout
+            .channels
+            .iter()
+            .zip(&layout.volts)
+            .map(|(channel_no, volts)| frame.scale(*channel_no).filter(|_| *volts))
+            .collect();
+        if self.segment.is_some() {
+            self.origin = None;
+            self.index = 0;
+        }
+        let origin = *self.origin.get_or_insert(frame.started);
+        let math: Vec<Option<Vec<f32>>> = self.math.iter().map(|it| frame.math(it)).collect();
+
+        let stride = layout.channels.len().max(1);
+        let mut samples = Vec::with_capacity(stride);
+        let result = (0..frame.raw.len() / stride).try_for_each(|idx| {
+            samples.clear();
+            samples.extend((idx * stride..(idx + 1) * stride).map(|it| frame.counts(it)));
+            let values: Vec<Option<f32>> = math
+                .iter()
+                .map(|it| it.as_ref().map(|trace| trace[idx]))
+                .collect();
+            let time = frame.sample_time_since(origin, idx).filter(|_| layout.time);
+            self.write_row(&layout, &samples, &scales, time, &values)
+        });
+        if let Some(segment) = &mut self.segment {
+            *segment += 1;
+        }
+
+        self.layout = Some(layout);
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Csv rows of roll mode captures, appended as they come. The first column is the wall clock
+/// time of the sample in seconds since the Unix epoch, as told by a [`RollClock`]. Every frame
+/// must have the channels of the first one and a known time scale.
+pub struct RollCsvSink<W: Write> {
+    out: W,
+    clock: RollClock,
+    layout: Option<Layout>,
+    math: Vec<MathExpr>,
+}
+
+impl<W: Write> RollCsvSink<W> {
+    pub fn new(out: W, math: Vec<MathExpr>) -> Self {
+        Self {
+            out,
+            clock: RollClock::new(),
+            layout: None,
+            math,
+        }
+    }
+
+    /// Writes the samples of a frame whose transfer ended at `received`.
+    pub fn write_frame(&mut self, frame: &CaptureFrame, received: SystemTime) -> io::Result<()> {
+        if let Some(layout) = &self.layout {
+            if frame.channels != layout.channels {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "frame has channels {:?}, the rows were started with {:?}",
+                        frame.channels, layout.channels
+                    ),
+                ));
+            }
+        }
+        let timestamps = self.clock.timestamps(frame, received).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "time scale of the frame is unknown",
+            )
+        })?;
+        let layout = match self.layout.take() {
+            Some(layout) => layout,
+            None => {
+                let layout = Layout::of(frame);
+                write!(self.out, "time")?;
+                for column in &layout.columns {
+                    write!(self.out, ",{}", column)?;
+                }
+                for expr in &self.math {
+                    write!(self.out, ",{}", expr)?;
+                }
+                writeln!(self.out)?;
+                layout
+            }
+        };
+
+        let scales: Vec<_> = layout
+            .channels
+            .iter()
+            .zip(&layout.volts)
+            .map(|(channel_no, volts)| frame.scale(*channel_no).filter(|_| *volts))
+            .collect();
+        let math: Vec<Option<Vec<f32>>> = self.math.iter().map(|it| frame.math(it)).collect();
+
+        let result = frame
+            .raw
+            .chunks_exact(layout.channels.len().max(1))
+            .zip(timestamps)
+            .enumerate()
+            .try_for_each(|(idx, (samples, timestamp))| {
+                let time = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default();
+                write!(self.out, "{:.6}", time.as_secs_f64())?;
+                for (sample, scale) in samples.iter().zip(&scales) {
+                    match scale {
+                        Some(scale) => write!(self.out, ",{}", raw_to_volts(*sample, scale))?,
+                        None => write!(self.out, ",{}", *sample as i8)?,
+                    }
+                }
+                for trace in &math {
+                    match trace {
+                        Some(trace) => write!(self.out, ",{}", trace[idx])?,
+                        None => write!(self.out, ",")?,
+                    }
+                }
+                writeln!(self.out)
+            });
+
+        self.layout = Some(layout);
+        result
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Csv rows pairing the samples of two channels, one channel against the other rather than
+/// against time, e.g. for Lissajous figures and curve tracing. The columns are in volts where
+/// the channel's scale is known on the first frame, raw counts otherwise. Every frame must have
+/// both channels.
+pub struct XyCsvSink<W: Write> {
+    out: W,
+    x: usize,
+    y: usize,
+    layout: Option<Layout>,
+}
+
+impl<W: Write> XyCsvSink<W> {
+    pub fn new(out: W, x: usize, y: usize) -> Self {
+        Self {
+            out,
+            x,
+            y,
+            layout: None,
+        }
+    }
+}
+
+impl<W: Write> FrameSink for XyCsvSink<W> {
+    fn write_frame(&mut self, frame: &CaptureFrame) -> io::Result<()> {
+        let (x, y) = match (frame.channel_counts(self.x), frame.channel_counts(self.y)) {
+            (Some(x), Some(y)) => (x, y),
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "frame has channels {:?}, x and y are channels {} and {}",
+                        frame.channels, self.x, self.y
+                    ),
+                ))
+            }
+        };
+        let layout = match self.layout.take() {
+            Some(layout) => layout,
+            None => {
+                let channels = [self.x, self.y];
+                let volts = channels
+                    .iter()
+                    .map(|it| frame.scale(*it).is_some())
+                    .collect();
+                let layout = Layout::new(&channels, volts, false);
+                writeln!(self.out, "{}", layout.columns.join(","))?;
+                layout
+            }
+        };
+
+        let scales: Vec<_> = layout
+            .channels
+            .iter()
+            .zip(&layout.volts)
+            .map(|(channel_no, volts)| frame.scale(*channel_no).filter(|_| *volts))
+            .collect();
+        let value = |sample: f32, scale: Option<&Scale>| match scale {
+            Some(scale) => counts_to_volts(sample, scale).to_string(),
+            None => sample.to_string(),
+        };
+
+        let result = x.into_iter().zip(y).try_for_each(|(x, y)| {
+            writeln!(self.out, "{},{}", value(x, scales[0]), value(y, scales[1]))
+        });
+
+        self.layout = Some(layout);
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// See [`ExportFormat::envelope_sink`]. Every envelope must have the channels of the first one,
+/// as with [`RowSink`] the columns are decided on it.
+pub struct EnvelopeSink<W: Write> {
+    out: W,
+    format: ExportFormat,
+    layout: Option<Layout>,
+    bucket: u64,
+    /// Samples of the envelopes so far, for the time column.
+    samples: u64,
+}
+
+impl<W: Write> EnvelopeSink<W> {
+    fn write_header(&mut self, layout: &Layout) -> io::Result<()> {
+        if let ExportFormat::Jsonl = self.format {
+            return Ok(());
+        }
+        write!(self.out, "bucket")?;
+        if layout.time {
+            write!(self.out, ",time")?;
+        }
+        for column in &layout.columns {
+            write!(self.out, ",{0}_min,{0}_max", column)?;
+        }
+        writeln!(self.out)
+    }
+
+    /// `time` is that of the first sample of the bucket.
+    fn write_bucket(
+        &mut self,
+        layout: &Layout,
+        pairs: &[(u8, u8)],
+        scales: &[Option<&Scale>],
+        time: Option<f64>,
+    ) -> io::Result<()> {
+        let json = matches!(self.format, ExportFormat::Jsonl);
+        if json {
+            write!(self.out, "{{\"bucket\":{}", self.bucket)?;
+        } else {
+            write!(self.out, "{}", self.bucket)?;
+        }
+        if let Some(time) = time {
+            if json {
+                write!(self.out, ",\"time\":{}", time)?;
+            } else {
+                write!(self.out, ",{}", time)?;
+            }
+        }
+        for (((min, max), scale), column) in pairs.iter().zip(scales).zip(&layout.columns) {
+            let (min, max) = match scale {
+                Some(scale) => (
+                    raw_to_volts(*min, scale).to_string(),
+                    raw_to_volts(*max, scale).to_string(),
+                ),
+                None => ((*min as i8).to_string(), (*max as i8).to_string()),
+            };
+            if json {
+                write!(
+                    self.out,
+                    ",\"{0}_min\":{1},\"{0}_max\":{2}",
+                    column, min, max
+                )?;
+            } else {
+                write!(self.out, ",{},{}", min, max)?;
+            }
+        }
+        self.bucket += 1;
+        writeln!(self.out, "{}", if json { "}" } else { "" })
+    }
+
+    pub fn write_envelope(&mut self, envelope: &Envelope) -> io::Result<()> {
+        if let ExportFormat::Raw = self.format {
+            for (min, max) in &envelope.min_max {
+                self.out.write_all(&[*min, *max])?;
+            }
+            return Ok(());
+        }
+
+        let layout = match self.layout.take() {
+            Some(layout) => layout,
+            None => {
+                let layout = Layout::of_envelope(envelope);
+                self.write_header(&layout)?;
+                layout
+            }
+        };
+        if envelope.channels != layout.channels {
+            let error = io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "envelope has channels {:?}, the rows were started with {:?}",
+                    envelope.channels, layout.channels
+                ),
+            );
+            self.layout = Some(layout);
+            return Err(error);
+        }
+
+        let scales: Vec<_> = layout
+            .channels
+            .iter()
+            .zip(&layout.volts)
+            .map(|(channel_no, volts)| envelope.scale(*channel_no).filter(|_| *volts))
+            .collect();
+        let rate = envelope.sample_rate().filter(|_| layout.time);
+        let first = self.samples;
+
+        let result = envelope
+            .min_max
+            .chunks_exact(layout.channels.len().max(1))
+            .enumerate()
+            .try_for_each(|(idx, pairs)| {
+                let sample = first + (idx * envelope.samples_per_bucket) as u64;
+                let time = rate.map(|rate| sample as f64 / rate as f64);
+                self.write_bucket(&layout, pairs, &scales, time)
+            });
+        self.samples += envelope.num_samples as u64;
+
+        self.layout = Some(layout);
+        result
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
@@ -0,0 +1,24 @@
+//! Compile-time view of the optional frontend integrations built into this copy of the lib.
+//!
+//! The lib itself only needs libusb; `cli` adds `clap::ArgEnum` derives on the config enums,
+//! `gui` adds `druid::Data` implementations and `serde` the (de)serialization of the config.
+//! Embedders (e.g. a headless test service) should depend on the lib with
+//! `default-features = false` and enable only what they use.
+
+pub const CLI: bool = cfg!(feature = "cli");
+pub const GUI: bool = cfg!(feature = "gui");
+pub const SERDE: bool = cfg!(feature = "serde");
+
+pub fn enabled() -> Vec<&'static str> {
+    let mut enabled = vec![];
+    if CLI {
+        enabled.push("cli");
+    }
+    if GUI {
+        enabled.push("gui");
+    }
+    if SERDE {
+        enabled.push("serde");
+    }
+    enabled
+}
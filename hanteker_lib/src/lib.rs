@@ -1,4 +1,25 @@
 #![cfg_attr(not(debug_assertions), deny(warnings))]
 
+pub mod awg;
+pub mod batch;
+pub mod bode;
+pub mod capture;
+pub mod compensation;
+pub mod decode;
+pub mod detect;
 pub mod device;
+pub mod dsp;
+pub mod encode;
+pub mod events;
+pub mod export;
+pub mod features;
+pub mod logging;
+pub mod manager;
+pub mod mask;
+pub mod math;
+pub mod measure;
+pub mod metrics;
 pub mod models;
+pub mod quirks;
+pub mod shared;
+pub mod verify;
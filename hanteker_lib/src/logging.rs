@@ -0,0 +1,7 @@
+//! Log targets of the lib, one per subsystem, so each can be filtered on by itself, e.g.
+//! `hanteker::usb=trace`.
+
+pub const USB: &str = "hanteker::usb";
+pub const CAPTURE: &str = "hanteker::capture";
+pub const AWG: &str = "hanteker::awg";
+pub const DEVICE: &str = "hanteker::device";
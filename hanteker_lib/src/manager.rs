@@ -0,0 +1,303 @@
+//! The device owned by a thread of its own, driven through a [`ManagerHandle`] any number of
+//! threads hold a clone of.
+//!
+//! Requests are served in the order they come in, one at a time. While anyone is subscribed to a
+//! stream of captures, the device captures whenever no request is waiting, so a stream never
+//! holds up a request for longer than a single capture.
+//!
+//! A watchdog, off by default, checks a device that was idle, or whose capture failed, still
+//! takes commands, reconnecting it when it doesn't. See [`ManagerHandle::set_watchdog`].
+
+use std::sync::mpsc::{
+    self, Receiver, RecvTimeoutError, Sender, SyncSender, TryRecvError, TrySendError,
+};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use thiserror::Error;
+
+use crate::batch::{BatchError, CommandBatch, Setting};
+use crate::capture::CaptureFrame;
+use crate::device::cfg::HantekConfig;
+use crate::logging::DEVICE;
+use crate::models::hantek2d42::{Hantek2D42, Hantek2D42Error};
+use crate::shared::OwnedHantek2D42;
+
+/// Frames a subscriber may fall behind by before further ones are dropped for it.
+const STREAM_BUFFER: usize = 4;
+
+#[derive(Error, Debug)]
+pub enum ManagerError {
+    #[error("device manager is stopped")]
+    Stopped,
+
+    #[error("device failed")]
+    DeviceError {
+        #[from]
+        error: Hantek2D42Error,
+    },
+
+    #[error("batch failed")]
+    BatchError {
+        #[from]
+        error: BatchError,
+    },
+}
+
+impl ManagerError {
+    // Because CLion doesn't like the Display implemented by thiserror.
+    pub fn my_to_string(&self) -> impl std::fmt::Display + '_ {
+        self
+    }
+}
+
+/// A capture of a stream, a failed capture ends the stream after being handed to it, waiting for
+/// the subscriber to make room for it if its buffer is full.
+pub type StreamFrame = Result<Arc<CaptureFrame>, Arc<Hantek2D42Error>>;
+
+type Call = Box<dyn for<'c> FnOnce(&mut Hantek2D42<'c>) + Send>;
+
+enum Request {
+    Call(Call),
+    Subscribe(Stream),
+    Watchdog(Option<Duration>),
+    Stop,
+}
+
+struct Stream {
+    channels: Vec<usize>,
+    num_samples: usize,
+    frames: SyncSender<StreamFrame>,
+}
+
+/// Owns the thread the device lives on, stopping it when dropped.
+pub struct DeviceManager {
+    handle: ManagerHandle,
+    thread: Option<JoinHandle<OwnedHantek2D42>>,
+}
+
+impl DeviceManager {
+    /// Moves the device to a new thread. It's expected to be claimed and set up already, e.g.
+    /// its timeouts.
+    pub fn spawn(hantek: OwnedHantek2D42) -> Self {
+        let (requests, receiver) = mpsc::channel();
+        let thread = thread::spawn(move || run(hantek, receiver));
+        Self {
+            handle: ManagerHandle { requests },
+            thread: Some(thread),
+        }
+    }
+
+    pub fn handle(&self) -> ManagerHandle {
+        self.handle.clone()
+    }
+
+    /// Stops the thread once the request being served is done, handing back the device, e.g.
+    /// to release it. `None` if the thread panicked, taking the device with it.
+    pub fn stop(mut self) -> Option<OwnedHantek2D42> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Option<OwnedHantek2D42> {
+        let thread = self.thread.take()?;
+        // Fails if the thread is gone already, joining tells why.
+        let _ = self.handle.requests.send(Request::Stop);
+        thread.join().ok()
+    }
+}
+
+impl Drop for DeviceManager {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+/// Sends requests to the device thread and waits for their results. Every call fails with
+/// [`ManagerError::Stopped`] once the manager is stopped.
+#[derive(Clone)]
+pub struct ManagerHandle {
+    requests: Sender<Request>,
+}
+
+impl ManagerHandle {
+    /// Runs `call` on the device thread, for anything the other methods don't cover.
+    pub fn with<R: Send + 'static>(
+        &self,
+        call: impl for<'c> FnOnce(&mut Hantek2D42<'c>) -> R + Send + 'static,
+    ) -> Result<R, ManagerError> {
+        let (result, receiver) = mpsc::sync_channel(1);
+        let call: Call = Box::new(move |hantek| {
+            // The caller may have given up waiting, nothing to do about it.
+            let _ = result.send(call(hantek));
+        });
+        self.requests
+            .send(Request::Call(call))
+            .map_err(|_| ManagerError::Stopped)?;
+        receiver.recv().map_err(|_| ManagerError::Stopped)
+    }
+
+    pub fn config(&self) -> Result<HantekConfig, ManagerError> {
+        self.with(|hantek| hantek.get_config().clone())
+    }
+
+    pub fn apply(&self, setting: Setting) -> Result<(), ManagerError> {
+        Ok(self.with(move |hantek| setting.apply(hantek))??)
+    }
+
+    pub fn apply_batch(&self, batch: CommandBatch) -> Result<(), ManagerError> {
+        Ok(self.with(move |hantek| hantek.apply_batch(&batch))??)
+    }
+
+    pub fn capture(
+        &self,
+        channels: &[usize],
+        num_samples: usize,
+    ) -> Result<CaptureFrame, ManagerError> {
+        let channels = channels.to_vec();
+        Ok(self.with(move |hantek| hantek.capture_frame(&channels, num_samples))??)
+    }
+
+    /// Captures continuously until the receiver is dropped. Subscribers of the same channels and
+    /// number of samples share each capture. A subscriber not keeping up misses frames.
+    pub fn subscribe(
+        &self,
+        channels: &[usize],
+        num_samples: usize,
+    ) -> Result<Receiver<StreamFrame>, ManagerError> {
+        let mut channels = channels.to_vec();
+        channels.sort_unstable();
+        channels.dedup();
+        let (frames, receiver) = mpsc::sync_channel(STREAM_BUFFER);
+        self.requests
+            .send(Request::Subscribe(Stream {
+                channels,
+                num_samples,
+                frames,
+            }))
+            .map_err(|_| ManagerError::Stopped)?;
+        Ok(receiver)
+    }
+
+    /// Checks the device with [`Hantek2D42::keep_alive`] once it was idle for the interval, no
+    /// capture succeeding in it, and right after a capture failed. A device failing the check
+    /// is reconnected, or tried to be again after another interval. `None` turns it off.
+    pub fn set_watchdog(&self, interval: Option<Duration>) -> Result<(), ManagerError> {
+        self.requests
+            .send(Request::Watchdog(interval))
+            .map_err(|_| ManagerError::Stopped)
+    }
+}
+
+fn run(mut hantek: OwnedHantek2D42, requests: Receiver<Request>) -> OwnedHantek2D42 {
+    let mut streams: Vec<Stream> = Vec::new();
+    let mut watchdog: Option<Duration> = None;
+    let mut alive = Instant::now();
+    loop {
+        let request = if streams.is_empty() {
+            let waited = match watchdog {
+                Some(interval) => requests.recv_timeout(interval.saturating_sub(alive.elapsed())),
+                None => requests.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match waited {
+                Ok(request) => Some(request),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        } else {
+            match requests.try_recv() {
+                Ok(request) => Some(request),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => break,
+            }
+        };
+
+        let mut failed = false;
+        match request {
+            Some(Request::Call(call)) => hantek.with(call),
+            Some(Request::Subscribe(stream)) => streams.push(stream),
+            Some(Request::Watchdog(interval)) => {
+                watchdog = interval;
+                alive = Instant::now();
+            }
+            Some(Request::Stop) => break,
+            // The watchdog's interval is up.
+            None if streams.is_empty() => {}
+            // Captures only once no request is waiting.
+            None => {
+                if hantek.with(|hantek| capture(hantek, &mut streams)) {
+                    alive = Instant::now();
+                } else {
+                    failed = true;
+                }
+            }
+        }
+
+        if let Some(interval) = watchdog {
+            if failed || alive.elapsed() >= interval {
+                check(&mut hantek);
+                alive = Instant::now();
+            }
+        }
+    }
+
+    debug!(target: DEVICE, "device manager stopped");
+    hantek
+}
+
+/// Checks the device takes commands, reconnecting it if it doesn't.
+fn check(hantek: &mut OwnedHantek2D42) {
+    match hantek.with(|hantek| hantek.keep_alive()) {
+        Ok(true) => debug!(target: DEVICE, "watchdog: device is alive"),
+        Ok(false) => {
+            debug!(target: DEVICE, "watchdog: nothing known of the device that is safe to send")
+        }
+        Err(e) => {
+            warn!(target: DEVICE, "watchdog: device not responding, reconnecting: {}", e);
+            match hantek.reconnect() {
+                Ok(()) => debug!(target: DEVICE, "watchdog: device reconnected"),
+                Err(e) => warn!(target: DEVICE, "watchdog: error reconnecting device: {}", e),
+            }
+        }
+    }
+}
+
+/// Captures once for each distinct stream, dropping the streams whose subscriber is gone or
+/// whose capture failed. `false` if any capture failed.
+fn capture(hantek: &mut Hantek2D42, streams: &mut Vec<Stream>) -> bool {
+    let mut frames: Vec<(&[usize], usize, StreamFrame)> = Vec::new();
+    let mut keep = Vec::with_capacity(streams.len());
+    for stream in streams.iter() {
+        let frame = match frames
+            .iter()
+            .find(|it| it.0 == stream.channels.as_slice() && it.1 == stream.num_samples)
+        {
+            Some((_, _, frame)) => frame.clone(),
+            None => {
+                let frame = hantek
+                    .capture_frame(&stream.channels, stream.num_samples)
+                    .map(Arc::new)
+                    .map_err(Arc::new);
+                frames.push((&stream.channels, stream.num_samples, frame.clone()));
+                frame
+            }
+        };
+        let failed = frame.is_err();
+        keep.push(match stream.frames.try_send(frame) {
+            Ok(()) => !failed,
+            // A capture dropped is made up for by the next, the error ending the stream isn't.
+            Err(TrySendError::Full(frame)) if failed => {
+                stream.frames.send(frame).ok();
+                false
+            }
+            Err(TrySendError::Full(_)) => true,
+            Err(TrySendError::Disconnected(_)) => false,
+        });
+    }
+
+    let captured = frames.iter().all(|it| it.2.is_ok());
+    let mut keep = keep.into_iter();
+    streams.retain(|_| keep.next().unwrap_or(false));
+    captured
+}
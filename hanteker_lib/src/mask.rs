@@ -0,0 +1,140 @@
+//! Pass/fail testing of captures against a mask, a lower and an upper limit for every sample of
+//! a channel. Samples are compared by their position in the capture, so captures must be
+//! triggered the same way as the one the mask was made from.
+
+use std::fmt::{Display, Formatter};
+
+use crate::capture::CaptureFrame;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mask {
+    pub channel_no: usize,
+    /// Limits in volts, by position of the sample in the capture. Both are of the same length.
+    pub lower: Vec<f32>,
+    pub upper: Vec<f32>,
+}
+
+impl Mask {
+    /// Mask around a golden capture of the channel, in volts. Each limit is `margin` volts past
+    /// the extreme of the golden samples up to `spread` positions either side, so that a bit of
+    /// jitter still passes.
+    pub fn around(channel_no: usize, golden: &[f32], margin: f32, spread: usize) -> Self {
+        let (lower, upper) = (0..golden.len())
+            .map(|idx| {
+                let end = (idx + spread + 1).min(golden.len());
+                let window = &golden[idx.saturating_sub(spread)..end];
+                let min = window.iter().copied().fold(f32::INFINITY, f32::min);
+                let max = window.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+                (min - margin, max + margin)
+            })
+            .unzip();
+        Self {
+            channel_no,
+            lower,
+            upper,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lower.len().min(self.upper.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Samples past the end of the mask aren't tested.
+    pub fn test(&self, samples: &[f32]) -> MaskResult {
+        let mut result = MaskResult::default();
+        for (idx, sample) in samples.iter().take(self.len()).enumerate() {
+            result.tested += 1;
+            if *sample > self.upper[idx] {
+                result.above += 1;
+            } else if *sample < self.lower[idx] {
+                result.below += 1;
+            } else {
+                continue;
+            }
+            result.first_violation.get_or_insert(idx);
+        }
+        result
+    }
+
+    /// Tests the mask's channel of the frame, `None` if it wasn't captured or its scale is
+    /// unknown.
+    pub fn test_frame(&self, frame: &CaptureFrame) -> Option<MaskResult> {
+        frame
+            .channel_volts(self.channel_no)
+            .map(|samples| self.test(&samples))
+    }
+}
+
+/// Tests every mask against the frame, as a single result. `None` if a masked channel wasn't
+/// captured or its scale is unknown.
+pub fn test_frame(masks: &[Mask], frame: &CaptureFrame) -> Option<MaskResult> {
+    let mut result = MaskResult::default();
+    for mask in masks {
+        let channel = mask.test_frame(frame)?;
+        result.tested += channel.tested;
+        result.above += channel.above;
+        result.below += channel.below;
+        result.first_violation = match (result.first_violation, channel.first_violation) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+    Some(result)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MaskResult {
+    pub tested: usize,
+    /// Samples above the upper limit.
+    pub above: usize,
+    /// Samples below the lower limit.
+    pub below: usize,
+    /// Position of the first sample out of limits.
+    pub first_violation: Option<usize>,
+}
+
+impl MaskResult {
+    pub fn violations(&self) -> usize {
+        self.above + self.below
+    }
+
+    pub fn passed(&self) -> bool {
+        self.violations() == 0
+    }
+}
+
+/// Results over a run of captures, a capture failing on any violating sample.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MaskStats {
+    pub captures: usize,
+    pub failed: usize,
+    pub violations: usize,
+}
+
+impl MaskStats {
+    pub fn record(&mut self, result: &MaskResult) {
+        self.captures += 1;
+        self.violations += result.violations();
+        if !result.passed() {
+            self.failed += 1;
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+impl Display for MaskStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "captures={} failed={} violations={}",
+            self.captures, self.failed, self.violations
+        )
+    }
+}
@@ -0,0 +1,417 @@
+//! Traces derived from captured channels, e.g. the difference of the two for a differential
+//! measurement with two ground referenced probes.
+//!
+//! Expressions combine the channels `ch1` and `ch2` and numbers with `+ - * /` and parentheses,
+//! e.g. `ch1 - 2*ch2`, and are evaluated sample by sample on volts. They're parsed from text
+//! or built with the operators, e.g. `MathExpr::channel(1) - MathExpr::channel(2) * 2.0`.
+
+use std::fmt::{Display, Formatter};
+use std::iter::Peekable;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::str::CharIndices;
+
+use crate::models::hantek2d42::{invalid, Hantek2D42Error};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MathExpr {
+    Channel(usize),
+    Constant(f32),
+    Neg(Box<MathExpr>),
+    Add(Box<MathExpr>, Box<MathExpr>),
+    Sub(Box<MathExpr>, Box<MathExpr>),
+    Mul(Box<MathExpr>, Box<MathExpr>),
+    Div(Box<MathExpr>, Box<MathExpr>),
+}
+
+impl MathExpr {
+    pub fn channel(channel_no: usize) -> Self {
+        Self::Channel(channel_no)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Hantek2D42Error> {
+        let mut parser = Parser {
+            text,
+            chars: text.char_indices().peekable(),
+        };
+        let expr = parser.sum()?;
+        parser.skip_whitespace();
+        match parser.chars.next() {
+            None => Ok(expr),
+            Some((at, found)) => Err(parser.error(at, format!("unexpected '{}'", found))),
+        }
+    }
+
+    /// Channels referenced, sorted, without duplicates.
+    pub fn channels(&self) -> Vec<usize> {
+        let mut channels = vec![];
+        self.collect_channels(&mut channels);
+        channels.sort_unstable();
+        channels.dedup();
+        channels
+    }
+
+    fn collect_channels(&self, into: &mut Vec<usize>) {
+        match self {
+            Self::Channel(channel_no) => into.push(*channel_no),
+            Self::Constant(_) => {}
+            Self::Neg(expr) => expr.collect_channels(into),
+            Self::Add(a, b) | Self::Sub(a, b) | Self::Mul(a, b) | Self::Div(a, b) => {
+                a.collect_channels(into);
+                b.collect_channels(into);
+            }
+        }
+    }
+
+    /// Value for a single sample given the value of each channel, `None` if a channel's isn't.
+    pub fn eval(&self, channel: &impl Fn(usize) -> Option<f32>) -> Option<f32> {
+        Some(match self {
+            Self::Channel(channel_no) => channel(*channel_no)?,
+            Self::Constant(value) => *value,
+            Self::Neg(expr) => -expr.eval(channel)?,
+            Self::Add(a, b) => a.eval(channel)? + b.eval(channel)?,
+            Self::Sub(a, b) => a.eval(channel)? - b.eval(channel)?,
+            Self::Mul(a, b) => a.eval(channel)? * b.eval(channel)?,
+            Self::Div(a, b) => a.eval(channel)? / b.eval(channel)?,
+        })
+    }
+}
+
+/// Parenthesized only where needed, so it parses back the same.
+impl Display for MathExpr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.fmt_within(f, 0)
+    }
+}
+
+impl MathExpr {
+    /// Higher binds tighter.
+    fn precedence(&self) -> u8 {
+        match self {
+            Self::Add(..) | Self::Sub(..) => 1,
+            Self::Mul(..) | Self::Div(..) => 2,
+            Self::Neg(_) => 3,
+            Self::Channel(_) | Self::Constant(_) => 4,
+        }
+    }
+
+    /// Operators are left associative, the right operand needs parentheses at equal precedence.
+    fn fmt_within(&self, f: &mut Formatter<'_>, min_precedence: u8) -> std::fmt::Result {
+        let precedence = self.precedence();
+        if precedence < min_precedence {
+            write!(f, "(")?;
+        }
+        match self {
+            Self::Channel(channel_no) => write!(f, "ch{}", channel_no)?,
+            Self::Constant(value) => write!(f, "{}", value)?,
+            Self::Neg(expr) => {
+                write!(f, "-")?;
+                // Parenthesized, a minus right before a number is parsed as its sign.
+                let min_precedence = match **expr {
+                    Self::Constant(_) => u8::MAX,
+                    _ => precedence,
+                };
+                expr.fmt_within(f, min_precedence)?;
+            }
+            Self::Add(a, b) | Self::Sub(a, b) | Self::Mul(a, b) | Self::Div(a, b) => {
+                let operator = match self {
+                    Self::Add(..) => "+",
+                    Self::Sub(..) => "-",
+                    Self::Mul(..) => "*",
+                    _ => "/",
+                };
+                a.fmt_within(f, precedence)?;
+                write!(f, " {} ", operator)?;
+                b.fmt_within(f, precedence + 1)?;
+            }
+        }
+        if precedence < min_precedence {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl Neg for MathExpr {
+    type Output = MathExpr;
+
+    fn neg(self) -> Self::Output {
+        MathExpr::Neg(Box::new(self))
+    }
+}
+
+impl Add for MathExpr {
+    type Output = MathExpr;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        MathExpr::Add(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl Sub for MathExpr {
+    type Output = MathExpr;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        MathExpr::Sub(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl Mul for MathExpr {
+    type Output = MathExpr;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        MathExpr::Mul(Box::new(self), Box::new(rhs))
+    }
+}
+
+impl Div for MathExpr {
+    type Output = MathExpr;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        MathExpr::Div(Box::new(self), Box::new(rhs))
+    }
+}
+
+/// Scaling by a constant.
+impl Mul<f32> for MathExpr {
+    type Output = MathExpr;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        self * MathExpr::Constant(rhs)
+    }
+}
+
+/// Recursive descent, the usual precedence with `*` and `/` binding tighter than `+` and `-`.
+struct Parser<'a> {
+    text: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    fn sum(&mut self) -> Result<MathExpr, Hantek2D42Error> {
+        let mut expr = self.product()?;
+        loop {
+            self.skip_whitespace();
+            expr = match self.chars.peek() {
+                Some((_, '+')) => {
+                    self.chars.next();
+                    expr + self.product()?
+                }
+                Some((_, '-')) => {
+                    self.chars.next();
+                    expr - self.product()?
+                }
+                _ => return Ok(expr),
+            };
+        }
+    }
+
+    fn product(&mut self) -> Result<MathExpr, Hantek2D42Error> {
+        let mut expr = self.unary()?;
+        loop {
+            self.skip_whitespace();
+            expr = match self.chars.peek() {
+                Some((_, '*')) => {
+                    self.chars.next();
+                    expr * self.unary()?
+                }
+                Some((_, '/')) => {
+                    self.chars.next();
+                    expr / self.unary()?
+                }
+                _ => return Ok(expr),
+            };
+        }
+    }
+
+    fn unary(&mut self) -> Result<MathExpr, Hantek2D42Error> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some((_, '-')) => {
+                self.chars.next();
+                self.skip_whitespace();
+                match self.chars.peek() {
+                    // The sign of a number, for a negative constant to parse back as one.
+                    Some((_, it)) if it.is_ascii_digit() || *it == '.' => match self.atom()? {
+                        MathExpr::Constant(value) => Ok(MathExpr::Constant(-value)),
+                        expr => Ok(-expr),
+                    },
+                    _ => Ok(-self.unary()?),
+                }
+            }
+            _ => self.atom(),
+        }
+    }
+
+    fn atom(&mut self) -> Result<MathExpr, Hantek2D42Error> {
+        let (at, first) = match self.chars.next() {
+            Some(next) => next,
+            None => return Err(self.error(self.text.len(), "unexpected end")),
+        };
+        match first {
+            '(' => {
+                let expr = self.sum()?;
+                self.skip_whitespace();
+                match self.chars.next() {
+                    Some((_, ')')) => Ok(expr),
+                    Some((at, _)) => Err(self.error(at, "expected ')'")),
+                    None => Err(self.error(self.text.len(), "expected ')'")),
+                }
+            }
+            'c' | 'C' => {
+                match self.chars.next() {
+                    Some((_, 'h')) | Some((_, 'H')) => {}
+                    _ => return Err(self.error(at, "expected a channel, e.g. ch1")),
+                }
+                let digits = self.take_while(|it| it.is_ascii_digit());
+                match digits.parse() {
+                    Ok(channel_no) => Ok(MathExpr::Channel(channel_no)),
+                    Err(_) => Err(self.error(at, "expected a channel, e.g. ch1")),
+                }
+            }
+            it if it.is_ascii_digit() || it == '.' => {
+                let rest = self.take_while(|it| it.is_ascii_digit() || it == '.');
+                match format!("{}{}", first, rest).parse() {
+                    Ok(value) => Ok(MathExpr::Constant(value)),
+                    Err(_) => Err(self.error(at, "bad number")),
+                }
+            }
+            it => Err(self.error(at, format!("unexpected '{}'", it))),
+        }
+    }
+
+    fn take_while(&mut self, accept: impl Fn(char) -> bool) -> String {
+        let mut taken = String::new();
+        while let Some((_, it)) = self.chars.peek() {
+            if !accept(*it) {
+                break;
+            }
+            taken.push(*it);
+            self.chars.next();
+        }
+        taken
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn error(&self, at: usize, what: impl Display) -> Hantek2D42Error {
+        invalid(
+            "math expression",
+            self.text,
+            format!("e.g. ch1 - 2*ch2, {} at {}", what, at),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ch(channel_no: usize) -> MathExpr {
+        MathExpr::channel(channel_no)
+    }
+
+    fn constant(value: f32) -> MathExpr {
+        MathExpr::Constant(value)
+    }
+
+    fn error(text: &str) -> String {
+        MathExpr::parse(text).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn products_bind_tighter_than_sums() {
+        assert_eq!(
+            MathExpr::parse("ch1 - 2*ch2 + 3 / ch1").unwrap(),
+            ch(1) - constant(2.0) * ch(2) + constant(3.0) / ch(1)
+        );
+        assert_eq!(
+            MathExpr::parse("ch1 / ch2 * 2").unwrap(),
+            (ch(1) / ch(2)) * constant(2.0)
+        );
+        assert_eq!(
+            MathExpr::parse("ch1 - ch2 - ch1").unwrap(),
+            (ch(1) - ch(2)) - ch(1)
+        );
+    }
+
+    #[test]
+    fn parentheses_group() {
+        assert_eq!(
+            MathExpr::parse("(ch1 - ch2) * 2").unwrap(),
+            (ch(1) - ch(2)) * constant(2.0)
+        );
+        assert_eq!(
+            MathExpr::parse("ch1 - (ch2 - ch1)").unwrap(),
+            ch(1) - (ch(2) - ch(1))
+        );
+        assert_eq!(MathExpr::parse("((ch2))").unwrap(), ch(2));
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(MathExpr::parse("-ch1").unwrap(), -ch(1));
+        assert_eq!(MathExpr::parse("--ch1").unwrap(), -(-ch(1)));
+        assert_eq!(
+            MathExpr::parse("-2.5 * ch1").unwrap(),
+            constant(-2.5) * ch(1)
+        );
+        assert_eq!(
+            MathExpr::parse("ch1 * - 2").unwrap(),
+            ch(1) * constant(-2.0)
+        );
+        assert_eq!(MathExpr::parse("ch1--1").unwrap(), ch(1) - constant(-1.0));
+        assert_eq!(MathExpr::parse("-(1)").unwrap(), -constant(1.0));
+        assert_eq!(MathExpr::parse("-(ch1 + ch2)").unwrap(), -(ch(1) + ch(2)));
+    }
+
+    #[test]
+    fn errors_tell_where() {
+        assert!(error("ch1 +").contains("unexpected end at 5"));
+        assert!(error("ch1 * (ch2").contains("expected ')' at 10"));
+        assert!(error("ch1 ch2").contains("unexpected 'c' at 4"));
+        assert!(error("ch1 + cx").contains("expected a channel, e.g. ch1 at 6"));
+        assert!(error("1.2.3").contains("bad number at 0"));
+        assert!(error("ch1 % 2").contains("unexpected '%' at 4"));
+    }
+
+    #[test]
+    fn displays_what_parses_back_the_same() {
+        let exprs = [
+            ch(1) - ch(2) * 2.0,
+            (ch(1) - ch(2)) * 2.0,
+            ch(1) - (ch(2) - ch(1)),
+            ch(1) / (ch(2) / ch(1)),
+            -(ch(1) + ch(2)),
+            -(-ch(1)),
+            constant(-1.0),
+            -constant(1.0),
+            -constant(-1.0),
+            ch(1) - constant(-0.5),
+            ch(2) * constant(-3.0) / -constant(4.0),
+        ];
+        for expr in exprs {
+            let text = expr.to_string();
+            assert_eq!(MathExpr::parse(&text).unwrap(), expr, "{}", text);
+        }
+        assert_eq!((ch(1) - ch(2) * 2.0).to_string(), "ch1 - ch2 * 2");
+        assert_eq!(constant(-1.0).to_string(), "-1");
+        assert_eq!((-constant(1.0)).to_string(), "-(1)");
+    }
+
+    #[test]
+    fn evaluates_per_sample() {
+        let expr = MathExpr::parse("(ch1 - ch2) / 2").unwrap();
+
+        assert_eq!(expr.channels(), vec![1, 2]);
+        assert_eq!(
+            expr.eval(&|channel_no| Some(channel_no as f32 * 3.0)),
+            Some(-1.5)
+        );
+        assert_eq!(
+            expr.eval(&|channel_no| (channel_no == 1).then_some(1.0)),
+            None
+        );
+    }
+}
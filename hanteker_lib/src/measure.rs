@@ -0,0 +1,320 @@
+//! Standard scope measurements computed from captured samples.
+
+#[cfg(feature = "cli")]
+use clap::ArgEnum;
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter, EnumString, EnumVariantNames};
+
+#[allow(non_camel_case_types)]
+#[derive(Display, Debug, Clone, EnumString, EnumIter, EnumVariantNames, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(ArgEnum))]
+pub enum Stat {
+    min,
+    max,
+    vpp,
+    vrms,
+    mean,
+    freq,
+    period,
+    duty,
+}
+
+impl Stat {
+    pub fn my_iter() -> impl Iterator<Item = Stat> {
+        Self::iter()
+    }
+
+    pub fn my_options() -> Vec<(String, Self)> {
+        Self::my_iter()
+            .map(|it| {
+                let as_string = it.my_to_string().to_string();
+                (as_string, it)
+            })
+            .collect()
+    }
+
+    // Because CLion doesn't like the Display implemented by strum.
+    pub fn my_to_string(&self) -> impl std::fmt::Display + '_ {
+        self
+    }
+
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Self::min | Self::max | Self::vpp | Self::vrms | Self::mean => "V",
+            Self::freq => "Hz",
+            Self::period => "s",
+            Self::duty => "%",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Measurements {
+    pub min: f32,
+    pub max: f32,
+    pub vpp: f32,
+    pub vrms: f32,
+    pub mean: f32,
+    /// Time based measurements need a sample rate and at least two full periods in the capture.
+    pub frequency: Option<f32>,
+    pub period: Option<f32>,
+    /// Percent of a period the signal spends above the mid level.
+    pub duty: Option<f32>,
+}
+
+impl Measurements {
+    pub fn get(&self, stat: &Stat) -> Option<f32> {
+        match stat {
+            Stat::min => Some(self.min),
+            Stat::max => Some(self.max),
+            Stat::vpp => Some(self.vpp),
+            Stat::vrms => Some(self.vrms),
+            Stat::mean => Some(self.mean),
+            Stat::freq => self.frequency,
+            Stat::period => self.period,
+            Stat::duty => self.duty,
+        }
+    }
+}
+
+/// Fraction of peak-to-peak used as hysteresis around the mid level when looking for edges, so
+/// noise riding on a slow edge isn't counted as several crossings.
+const HYSTERESIS: f32 = 0.1;
+
+/// Returns `None` for an empty capture. `sample_rate` is in samples per second; without it only
+/// the amplitude measurements are available.
+pub fn measure(samples: &[f32], sample_rate: Option<f32>) -> Option<Measurements> {
+    if samples.is_empty() {
+        return None;
+    }
+
+    let min = samples.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = samples.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+    let vrms = (samples.iter().map(|it| it * it).sum::<f32>() / samples.len() as f32).sqrt();
+
+    let (period, duty) = match sample_rate {
+        Some(rate) if rate > 0.0 => match period_and_duty(samples, min, max) {
+            Some((period_samples, duty)) => (Some(period_samples / rate), Some(duty)),
+            None => (None, None),
+        },
+        _ => (None, None),
+    };
+
+    Some(Measurements {
+        min,
+        max,
+        vpp: max - min,
+        vrms,
+        mean,
+        frequency: period.map(|it| 1.0 / it),
+        period,
+        duty,
+    })
+}
+
+/// Rising edges of the signal as sample indexes, detected around the mid level with hysteresis.
+pub fn rising_edges(samples: &[f32], min: f32, max: f32) -> Vec<usize> {
+    rising_edges_at(samples, (max + min) / 2.0, (max - min) * HYSTERESIS)
+}
+
+/// Rising edges crossing `level`, the signal having to go `hysteresis` below it to re-arm and
+/// `hysteresis` above it to count.
+pub fn rising_edges_at(samples: &[f32], level: f32, hysteresis: f32) -> Vec<usize> {
+    let (low, high) = (level - hysteresis, level + hysteresis);
+
+    let mut edges = vec![];
+    let mut armed = false;
+    for (idx, sample) in samples.iter().enumerate() {
+        if *sample < low {
+            armed = true;
+        } else if armed && *sample > high {
+            armed = false;
+            edges.push(idx);
+        }
+    }
+    edges
+}
+
+/// Falling edges of the signal as sample indexes, the mirror image of [`rising_edges`].
+pub fn falling_edges(samples: &[f32], min: f32, max: f32) -> Vec<usize> {
+    let inverted: Vec<f32> = samples.iter().map(|it| -it).collect();
+    rising_edges(&inverted, -max, -min)
+}
+
+/// Average period in samples and duty cycle in percent, measured between the first and last
+/// rising edge.
+fn period_and_duty(samples: &[f32], min: f32, max: f32) -> Option<(f32, f32)> {
+    if max <= min {
+        return None;
+    }
+
+    let edges = rising_edges(samples, min, max);
+    if edges.len() < 2 {
+        return None;
+    }
+
+    let first = edges[0];
+    let last = edges[edges.len() - 1];
+    let period = (last - first) as f32 / (edges.len() - 1) as f32;
+
+    let mid = (max + min) / 2.0;
+    let high = samples[first..last].iter().filter(|it| **it > mid).count();
+    let duty = 100.0 * high as f32 / (last - first) as f32;
+
+    Some((period, duty))
+}
+
+/// Pulses and time spent high accumulated over captures, making a frequency counter out of a
+/// scope that only sees a short window at a time. The time between captures isn't seen, all
+/// counts are over the captured time only.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EdgeCounter {
+    /// Rising edges seen.
+    pub pulses: usize,
+    pub samples: usize,
+    pub high_samples: usize,
+    /// Captured time, in seconds.
+    pub seconds: f64,
+}
+
+impl EdgeCounter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts crossings of the threshold, the mid level of each capture if not given. A pulse
+    /// straddling two captures is missed.
+    pub fn record(&mut self, samples: &[f32], sample_rate: f32, threshold: Option<f32>) {
+        if samples.is_empty() || sample_rate <= 0.0 {
+            return;
+        }
+        let min = samples.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = samples.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let level = threshold.unwrap_or((max + min) / 2.0);
+
+        if max > min {
+            self.pulses += rising_edges_at(samples, level, (max - min) * HYSTERESIS).len();
+        }
+        self.high_samples += samples.iter().filter(|it| **it > level).count();
+        self.samples += samples.len();
+        self.seconds += samples.len() as f64 / sample_rate as f64;
+    }
+
+    /// Pulses per captured second.
+    pub fn frequency(&self) -> Option<f32> {
+        if self.seconds > 0.0 {
+            Some((self.pulses as f64 / self.seconds) as f32)
+        } else {
+            None
+        }
+    }
+
+    /// Percent of the captured time spent above the threshold.
+    pub fn duty(&self) -> Option<f32> {
+        if self.samples > 0 {
+            Some(100.0 * self.high_samples as f32 / self.samples as f32)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 10_000.0;
+
+    /// 100 Hz between -1 and 3 V, high for the last quarter of each period.
+    fn square(len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|idx| if idx % 100 >= 75 { 3.0 } else { -1.0 })
+            .collect()
+    }
+
+    fn assert_close(actual: Option<f32>, expected: f32) {
+        let actual = actual.unwrap();
+        assert!(
+            (actual - expected).abs() <= expected.abs() * 1e-4,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn measures_a_square_wave() {
+        let measurements = measure(&square(1000), Some(SAMPLE_RATE)).unwrap();
+
+        assert_eq!(measurements.min, -1.0);
+        assert_eq!(measurements.max, 3.0);
+        assert_eq!(measurements.vpp, 4.0);
+        assert_eq!(measurements.mean, 0.0);
+        assert_close(Some(measurements.vrms), 3f32.sqrt());
+        assert_close(measurements.frequency, 100.0);
+        assert_close(measurements.period, 0.01);
+        assert_close(measurements.duty, 25.0);
+        assert_eq!(measurements.get(&Stat::vpp), Some(4.0));
+        assert_eq!(measurements.get(&Stat::freq), measurements.frequency);
+    }
+
+    #[test]
+    fn times_need_a_sample_rate_and_two_edges() {
+        let without_rate = measure(&square(1000), None).unwrap();
+        assert_eq!(without_rate.vpp, 4.0);
+        assert_eq!(without_rate.frequency, None);
+
+        let one_edge = measure(&square(150), Some(SAMPLE_RATE)).unwrap();
+        assert_eq!(one_edge.frequency, None);
+        assert_eq!(one_edge.period, None);
+        assert_eq!(one_edge.duty, None);
+    }
+
+    #[test]
+    fn flat_signal_has_no_period() {
+        let measurements = measure(&[1.5; 1000], Some(SAMPLE_RATE)).unwrap();
+
+        assert_eq!(measurements.vpp, 0.0);
+        assert_eq!(measurements.mean, 1.5);
+        assert_eq!(measurements.frequency, None);
+        assert_eq!(measurements.duty, None);
+        assert_eq!(measure(&[], Some(SAMPLE_RATE)), None);
+    }
+
+    #[test]
+    fn hysteresis_ignores_noise_around_the_level() {
+        let samples = [0.0, 0.45, 0.55, 0.45, 0.55, 1.0, 0.0, 0.65];
+
+        assert_eq!(rising_edges_at(&samples, 0.5, 0.1), vec![5, 7]);
+        assert_eq!(rising_edges_at(&samples, 0.5, 0.0), vec![2, 4, 7]);
+        assert_eq!(rising_edges(&square(300), -1.0, 3.0), vec![75, 175, 275]);
+        assert_eq!(falling_edges(&square(300), -1.0, 3.0), vec![100, 200]);
+    }
+
+    #[test]
+    fn counts_edges_across_captures() {
+        let mut counter = EdgeCounter::new();
+        assert_eq!(counter.frequency(), None);
+        assert_eq!(counter.duty(), None);
+
+        counter.record(&square(1000), SAMPLE_RATE, None);
+        counter.record(&square(1000), SAMPLE_RATE, None);
+
+        assert_eq!(counter.pulses, 20);
+        assert_close(counter.frequency(), 100.0);
+        assert_close(counter.duty(), 25.0);
+    }
+
+    #[test]
+    fn flat_capture_counts_time_but_no_edges() {
+        let mut counter = EdgeCounter::new();
+
+        counter.record(&[2.0; 1000], SAMPLE_RATE, Some(1.0));
+
+        assert_eq!(counter.pulses, 0);
+        assert_eq!(counter.frequency(), Some(0.0));
+        assert_eq!(counter.duty(), Some(100.0));
+        assert_eq!(counter.seconds, 0.1);
+    }
+}
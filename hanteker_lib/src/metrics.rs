@@ -0,0 +1,105 @@
+//! Latency of the transfers to and from the device, aggregated per command so the totals stay
+//! bounded however long a capture runs.
+
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyStats {
+    /// Number of transfers.
+    pub count: u64,
+    pub bytes: u64,
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+impl LatencyStats {
+    fn new(bytes: usize, latency: Duration) -> Self {
+        Self {
+            count: 1,
+            bytes: bytes as u64,
+            total: latency,
+            min: latency,
+            max: latency,
+        }
+    }
+
+    fn record(&mut self, bytes: usize, latency: Duration) {
+        self.count += 1;
+        self.bytes += bytes as u64;
+        self.total += latency;
+        self.min = self.min.min(latency);
+        self.max = self.max.max(latency);
+    }
+
+    pub fn mean(&self) -> Duration {
+        self.total.div_f64(self.count as f64)
+    }
+}
+
+/// Latency of each command, i.e. the wall time from submitting a bulk transfer to libusb until
+/// it reports the transfer done. Time the host spends between transfers isn't included, so
+/// comparing the total against the wall time of the whole operation tells the device and USB
+/// stack apart from the host side.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// Keyed by what the command does, as in the errors, and the channel it applies to.
+    pub commands: BTreeMap<(&'static str, Option<usize>), LatencyStats>,
+    /// Reads of a capture the device answered with less than asked for, nothing included.
+    pub short_reads: u64,
+}
+
+impl Metrics {
+    pub(crate) fn record(
+        &mut self,
+        action: &'static str,
+        channel_no: Option<usize>,
+        bytes: usize,
+        latency: Duration,
+    ) {
+        self.commands
+            .entry((action, channel_no))
+            .and_modify(|it| it.record(bytes, latency))
+            .or_insert_with(|| LatencyStats::new(bytes, latency));
+    }
+
+    /// Time spent in transfers, over all commands.
+    pub fn total(&self) -> Duration {
+        self.commands.values().map(|it| it.total).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+impl Display for Metrics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for ((action, channel_no), stats) in &self.commands {
+            let channel = match channel_no {
+                Some(channel_no) => format!(" (channel {})", channel_no),
+                None => "".to_string(),
+            };
+            writeln!(
+                f,
+                "{}{}: count={} bytes={} mean={:?} min={:?} max={:?} total={:?}",
+                action,
+                channel,
+                stats.count,
+                stats.bytes,
+                stats.mean(),
+                stats.min,
+                stats.max,
+                stats.total,
+            )?;
+        }
+        write!(
+            f,
+            "total in transfers={:?} short reads={}",
+            self.total(),
+            self.short_reads
+        )
+    }
+}
@@ -1,29 +1,68 @@
-use std::time::Duration;
+use std::fmt::{Display, Formatter};
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
 use libusb::Context;
+use log::debug;
 use thiserror::Error;
 
+use crate::batch::{BatchError, CommandBatch, Setting};
+use crate::capture::{BufferPool, CaptureFrame, CaptureHandle};
 use crate::device::cfg::{
-    Adjustment, AwgType, Coupling, DeviceFunction, HantekConfig, Probe, RunningStatus, Scale,
-    TimeScale, TrapDuty, TriggerMode, TriggerSlope,
+    Adjustment, AwgType, ChannelConfig, Coupling, DeviceFunction, HantekConfig, Probe,
+    RunningStatus, Scale, TimeScale, TrapDuty, TriggerMode, TriggerSlope,
 };
-use crate::device::cmd::{HantekCommandBuilder, RawCommand};
-use crate::device::usb::{HantekUsbDevice, HantekUsbError};
+use crate::device::cmd::{CommandBuildError, HantekCommandBuilder, RawCommand};
+use crate::device::usb::{HantekUsbDevice, HantekUsbError, Transport};
+use crate::events::{Event, EventBus};
+use crate::logging::{AWG, CAPTURE};
+use crate::metrics::Metrics;
 use crate::models::hantek2d42_codes::*;
+use crate::quirks::{quirks_of, Quirk};
 
 const IDX: u8 = 0x00;
 const BOH: u8 = 0x0A;
-const NUM_CHANNELS: usize = 2;
+pub(crate) const NUM_CHANNELS: usize = 2;
+pub(crate) const RAW_LEVEL_MAX: u8 = 200;
 
-const WRITE_ENDPOINT: u8 = 2;
-const READ_ENDPOINT: u8 = 0x80 | 1;
+/// Endpoint commands are written to, unless others are found in the config descriptor.
+pub const WRITE_ENDPOINT: u8 = 2;
+/// Endpoint the device answers on, e.g. with capture data, unless others are found in the
+/// config descriptor.
+pub const READ_ENDPOINT: u8 = 0x80 | 1;
+
+fn write_endpoint<T: Transport>(usb: &T) -> u8 {
+    usb.endpoints().map_or(WRITE_ENDPOINT, |it| it.write)
+}
+
+fn read_endpoint<T: Transport>(usb: &T) -> u8 {
+    usb.endpoints().map_or(READ_ENDPOINT, |it| it.read)
+}
+
+/// Most bytes of a capture asked for at once by default, a single full speed bulk packet. The
+/// only size the device is known to answer a capture command with in full.
+pub const CAPTURE_PACKET: usize = 64;
+/// Largest read of a capture [`Hantek2D42::set_capture_packet`] takes.
+pub const CAPTURE_PACKET_MAX: usize = 4096;
+/// Reads in a row returning nothing before a capture is given up on.
+const CAPTURE_MAX_EMPTY_READS: usize = 8;
 
 #[derive(Error, Debug)]
 pub enum Hantek2D42Error {
-    #[error("error with usb device: {error}")]
+    #[error("error with usb device while {failed_action}{}", fmt_channel_no(.channel_no))]
     HantekUsbError {
+        #[source]
         error: HantekUsbError,
         failed_action: &'static str,
+        channel_no: Option<usize>,
+    },
+
+    #[error("failed to build command")]
+    CommandBuildError {
+        #[from]
+        error: CommandBuildError,
     },
 
     #[error("missing or bad channel adjustment")]
@@ -34,6 +73,25 @@ pub enum Hantek2D42Error {
 
     #[error("missing or bad trigger level adjustment")]
     TriggerLevelAdjustmentError,
+
+    #[error("scale of channel {channel_no} is unknown, set it first")]
+    ChannelScaleUnknown { channel_no: usize },
+
+    #[error("trigger source is unknown, set it first or name the channel the level is for")]
+    TriggerSourceUnknown,
+
+    #[error("invalid value for {parameter}: {value}, allowed: {allowed}")]
+    InvalidArgument {
+        parameter: &'static str,
+        value: String,
+        allowed: String,
+    },
+
+    #[error("device stopped sending capture data, expected={expected} received={received}")]
+    IncompleteCapture { expected: usize, received: usize },
+
+    #[error("capture cancelled, expected={expected} received={received}")]
+    CaptureCancelled { expected: usize, received: usize },
 }
 
 impl Hantek2D42Error {
@@ -43,90 +101,566 @@ impl Hantek2D42Error {
     }
 }
 
-pub struct Hantek2D42<'a> {
-    pub usb: HantekUsbDevice<'a>,
+/// A setting of a config the device didn't take, named as in
+/// [`crate::device::cfg::ConfigChange::name`], e.g. `channel1.scale`.
+#[derive(Error, Debug)]
+#[error("{name}: {error}")]
+pub struct ConfigItemError {
+    pub name: String,
+    #[source]
+    pub error: Hantek2D42Error,
+}
+
+/// What went wrong applying a config, see [`Hantek2D42::apply_config`]. The settings not in
+/// either list were applied.
+#[derive(Error, Debug)]
+#[error(
+    "{} settings of the config failed and {} weren't sent{}",
+    .failed.len(),
+    .not_sent.len(),
+    first_failure(.failed)
+)]
+pub struct ApplyConfigError {
+    pub failed: Vec<ConfigItemError>,
+    /// Settings left after writing to the device failed.
+    pub not_sent: Vec<String>,
+}
+
+impl ApplyConfigError {
+    // Because CLion doesn't like the Display implemented by thiserror.
+    pub fn my_to_string(&self) -> impl std::fmt::Display + '_ {
+        self
+    }
+}
+
+fn first_failure(failed: &[ConfigItemError]) -> String {
+    match failed.first() {
+        Some(first) => format!(", first: {}", first),
+        None => "".to_string(),
+    }
+}
+
+pub(crate) fn invalid(
+    parameter: &'static str,
+    value: impl std::fmt::Display,
+    allowed: impl Into<String>,
+) -> Hantek2D42Error {
+    Hantek2D42Error::InvalidArgument {
+        parameter,
+        value: value.to_string(),
+        allowed: allowed.into(),
+    }
+}
+
+/// Rejects NaN and infinities, which turn into garbage once cast to the raw values.
+fn check_finite(parameter: &'static str, value: f32) -> Result<(), Hantek2D42Error> {
+    if value.is_finite() {
+        Ok(())
+    } else {
+        Err(invalid(parameter, value, "a finite number"))
+    }
+}
+
+/// Raw value of channel offset and trigger level, the 8 vertical divisions span 0..=200.
+fn check_raw_level(parameter: &'static str, value: u8) -> Result<(), Hantek2D42Error> {
+    if value <= RAW_LEVEL_MAX {
+        Ok(())
+    } else {
+        Err(invalid(parameter, value, format!("0..={}", RAW_LEVEL_MAX)))
+    }
+}
+
+/// Converts a level in volts to its raw value, rejecting levels off the screen.
+fn raw_level(
+    parameter: &'static str,
+    volts: f32,
+    adjustment: &Adjustment,
+) -> Result<u8, Hantek2D42Error> {
+    let raw =
+        (volts - adjustment.lower) * RAW_LEVEL_MAX as f32 / (adjustment.upper - adjustment.lower);
+    // Rounding errors at the very edges of the screen shouldn't fail.
+    if !(-0.5..=RAW_LEVEL_MAX as f32 + 0.5).contains(&raw) {
+        return Err(invalid(
+            parameter,
+            volts,
+            format!("{}..={} V", adjustment.lower, adjustment.upper),
+        ));
+    }
+    Ok(raw.round().clamp(0.0, RAW_LEVEL_MAX as f32) as u8)
+}
+
+/// Raw time offset spanning the screen, 25 samples per division over 12 divisions, the center
+/// of the screen being where the time offset in seconds is zero.
+pub(crate) const TIME_OFFSET_SCREEN: u32 = 300;
+
+/// USB vendor and product id the device is found by.
+pub const USB_ID: (u16, u16) = (VENDOR_ID__2D42, PRODUCT_ID__2D42);
+
+/// Limit of the AWG amplitude either way, in volts.
+pub const AWG_AMPLITUDE_MAX: f32 = 3.5;
+
+/// Limit of the AWG offset either way, in volts.
+pub const AWG_OFFSET_MAX: f32 = 3.5;
+
+/// Lowest AWG frequency, the device takes whole hertz.
+pub const AWG_FREQUENCY_MIN: f32 = 1.0;
+
+/// Range of the square and ramp duty cycles, in percent.
+pub const AWG_DUTY_MIN: f32 = 1.0;
+pub const AWG_DUTY_MAX: f32 = 99.0;
+
+/// Highest frequency the generator outputs the waveform at, in Hz.
+pub fn awg_frequency_max(awg_type: &AwgType) -> f32 {
+    match awg_type {
+        AwgType::Sin => 25_000_000.0,
+        AwgType::Square => 10_000_000.0,
+        AwgType::Ramp | AwgType::Trap => 1_000_000.0,
+        AwgType::Arb1 | AwgType::Arb2 | AwgType::Arb3 | AwgType::Arb4 => 10_000_000.0,
+    }
+}
+
+fn check_awg_frequency(
+    parameter: &'static str,
+    frequency: f32,
+    awg_type: &AwgType,
+) -> Result<(), Hantek2D42Error> {
+    let max = awg_frequency_max(awg_type);
+    if (AWG_FREQUENCY_MIN..=max).contains(&frequency) {
+        Ok(())
+    } else {
+        Err(invalid(
+            parameter,
+            frequency,
+            format!("{}..={} Hz for {}", AWG_FREQUENCY_MIN, max, awg_type),
+        ))
+    }
+}
+
+fn check_awg_volts(parameter: &'static str, volts: f32, max: f32) -> Result<(), Hantek2D42Error> {
+    check_finite(parameter, volts)?;
+    if volts.abs() <= max {
+        Ok(())
+    } else {
+        Err(invalid(parameter, volts, format!("-{}..={} V", max, max)))
+    }
+}
+
+/// Duty cycle in percent.
+fn check_awg_duty(parameter: &'static str, duty: f32) -> Result<(), Hantek2D42Error> {
+    check_finite(parameter, duty)?;
+    if (AWG_DUTY_MIN..=AWG_DUTY_MAX).contains(&duty) {
+        Ok(())
+    } else {
+        Err(invalid(
+            parameter,
+            duty,
+            format!("{}..={} %", AWG_DUTY_MIN, AWG_DUTY_MAX),
+        ))
+    }
+}
+
+/// Fraction of the period.
+fn check_awg_fraction(parameter: &'static str, fraction: f32) -> Result<(), Hantek2D42Error> {
+    check_finite(parameter, fraction)?;
+    if (0.0..=1.0).contains(&fraction) {
+        Ok(())
+    } else {
+        Err(invalid(parameter, fraction, "0..=1"))
+    }
+}
+
+fn send<T: Transport>(
+    usb: &mut T,
+    metrics: &mut Metrics,
+    cmd: &RawCommand,
+    failed_action: &'static str,
+    channel_no: Option<usize>,
+) -> Result<usize, Hantek2D42Error> {
+    let started = Instant::now();
+    let written =
+        usb.write(write_endpoint(usb), cmd)
+            .map_err(|error| Hantek2D42Error::HantekUsbError {
+                error,
+                failed_action,
+                channel_no,
+            })?;
+    metrics.record(failed_action, channel_no, written, started.elapsed());
+    Ok(written)
+}
+
+/// Reads a capture into the buffer, the samples of all channels interleaved, asking for a packet
+/// at a time. The device may answer with less than asked for, the rest is asked for again, but
+/// if it keeps answering with nothing the capture fails. Every read waits for the one before,
+/// queuing them needs asynchronous transfers, which the libusb bindings don't have.
+fn read_capture<T: Transport>(
+    usb: &mut T,
+    metrics: &mut Metrics,
+    cmd: &RawCommand,
+    buffer: &mut [u8],
+    packet: usize,
+    handle: &CaptureHandle,
+) -> Result<(), Hantek2D42Error> {
+    let total = buffer.len();
+    handle.start(total);
+    let mut count = 0;
+    let mut empty_reads = 0;
+    while count < total {
+        if handle.is_cancelled() {
+            return Err(Hantek2D42Error::CaptureCancelled {
+                expected: total,
+                received: count,
+            });
+        }
+        let length = (total - count).min(packet);
+        send(usb, metrics, cmd, "sending capture command", None)?;
+        let started = Instant::now();
+        let actual_len = usb
+            .read(read_endpoint(usb), &mut buffer[count..(count + length)])
+            .map_err(|error| Hantek2D42Error::HantekUsbError {
+                error,
+                failed_action: "reading capture",
+                channel_no: None,
+            })?;
+        metrics.record("reading capture", None, actual_len, started.elapsed());
+        if actual_len < length {
+            metrics.short_reads += 1;
+        }
+
+        if actual_len == 0 {
+            empty_reads += 1;
+            if empty_reads == CAPTURE_MAX_EMPTY_READS {
+                return Err(Hantek2D42Error::IncompleteCapture {
+                    expected: total,
+                    received: count,
+                });
+            }
+        } else {
+            empty_reads = 0;
+        }
+        count += actual_len;
+        handle.advance(actual_len);
+    }
+    Ok(())
+}
+
+/// A command parsed back from its bytes, the inverse of [`HantekCommandBuilder`], with the
+/// names of its codes where known. Displays as e.g.
+/// `func=SCOPE_SETTING cmd=SCALE_CH1 val=1V [00 0a 00 00 04 06 00 00 00 00]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedCommand {
+    pub raw: RawCommand,
+    pub func: u16,
+    pub cmd: u8,
+    pub val: [u8; 4],
+    pub func_name: Option<&'static str>,
+    pub cmd_name: Option<&'static str>,
+    /// Meaning of the value, for commands taking one of a set of values.
+    pub val_name: Option<&'static str>,
+}
+
+impl From<RawCommand> for DecodedCommand {
+    fn from(raw: RawCommand) -> Self {
+        let func = u16::from_le_bytes([raw[2], raw[3]]);
+        let cmd = raw[4];
+        let val = [raw[5], raw[6], raw[7], raw[8]];
+        let val_name = if val[1..].iter().all(|it| *it == 0) {
+            val_name(func, cmd, val[0])
+        } else {
+            None
+        };
+        Self {
+            raw,
+            func,
+            cmd,
+            val,
+            func_name: func_name(func),
+            cmd_name: cmd_name(func, cmd),
+            val_name,
+        }
+    }
+}
+
+/// E.g. a command read from a USB trace.
+impl TryFrom<&[u8]> for DecodedCommand {
+    type Error = Hantek2D42Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        match RawCommand::try_from(bytes) {
+            Ok(raw) => Ok(raw.into()),
+            Err(_) => Err(invalid("command length", bytes.len(), "10 bytes")),
+        }
+    }
+}
+
+impl Display for DecodedCommand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let hex = |bytes: &[u8], separator: &str| {
+            let hex: Vec<String> = bytes.iter().map(|it| format!("{:02x}", it)).collect();
+            hex.join(separator)
+        };
+        match self.func_name {
+            Some(name) => write!(f, "func={}", name)?,
+            None => write!(f, "func={:#06x}", self.func)?,
+        }
+        match self.cmd_name {
+            Some(name) => write!(f, " cmd={}", name)?,
+            None => write!(f, " cmd={:#04x}", self.cmd)?,
+        }
+        match self.val_name {
+            Some(name) => write!(f, " val={}", name)?,
+            None => write!(f, " val={}", hex(&self.val, ":"))?,
+        }
+        write!(f, " [{}]", hex(&self.raw, " "))
+    }
+}
+
+fn fmt_channel_no(channel_no: &Option<usize>) -> String {
+    match channel_no {
+        Some(channel_no) => format!(" on channel {}", channel_no),
+        None => "".to_string(),
+    }
+}
+
+/// The device, driven over USB unless given another [`Transport`], e.g. a
+/// [`crate::models::simulated::SimulatedHantek2D42`].
+pub struct Hantek2D42<'a, T: Transport = HantekUsbDevice<'a>> {
+    pub usb: T,
     config: HantekConfig,
+    last_capture_end: Option<Instant>,
+    metrics: Metrics,
+    diff_mode: bool,
+    force_next: bool,
+    capture_packet: usize,
+    events: EventBus,
+    context: PhantomData<&'a Context>,
 }
 
 impl<'a> Hantek2D42<'a> {
-    pub fn new(usb: HantekUsbDevice<'a>, config: HantekConfig) -> Self {
-        Self { usb, config }
+    pub fn new(mut usb: HantekUsbDevice<'a>, config: HantekConfig) -> Self {
+        let events = EventBus::new();
+        usb.set_events(Some(events.clone()));
+        Self::with_events(usb, config, events)
     }
 
     pub fn open(context: &'a Context, timeout: Duration) -> Result<Self, Hantek2D42Error> {
-        let usb = HantekUsbDevice::open(context, timeout, (VENDOR_ID__2D42, PRODUCT_ID__2D42))
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
+        let usb = HantekUsbDevice::open(context, timeout, USB_ID).map_err(|error| {
+            Hantek2D42Error::HantekUsbError {
                 error,
-                failed_action: "device open",
-            })?;
+                failed_action: "opening device",
+                channel_no: None,
+            }
+        })?;
         let mut config = HantekConfig::new(NUM_CHANNELS);
         config.timeout = Some(timeout);
         Ok(Self::new(usb, config))
     }
 
+    /// Every device connected, see [`HantekUsbDevice::open_all`]. Tell them apart by their
+    /// serial number, or where they are on the bus if they have none.
+    pub fn open_all(context: &'a Context, timeout: Duration) -> Result<Vec<Self>, Hantek2D42Error> {
+        let devices = HantekUsbDevice::open_all(context, timeout, USB_ID).map_err(|error| {
+            Hantek2D42Error::HantekUsbError {
+                error,
+                failed_action: "opening devices",
+                channel_no: None,
+            }
+        })?;
+        Ok(devices
+            .into_iter()
+            .map(|usb| {
+                let mut config = HantekConfig::new(NUM_CHANNELS);
+                config.timeout = Some(timeout);
+                Self::new(usb, config)
+            })
+            .collect())
+    }
+
+    /// Opens the device anew after it stopped responding, see [`HantekUsbDevice::reopen`]. The
+    /// cached config is kept, it's not sent to the device again.
+    pub fn reconnect(&mut self, context: &'a Context) -> Result<(), Hantek2D42Error> {
+        self.last_capture_end = None;
+        self.usb
+            .reopen(context)
+            .map_err(|error| Hantek2D42Error::HantekUsbError {
+                error,
+                failed_action: "reconnecting device",
+                channel_no: None,
+            })
+    }
+
+    /// USB reset of the device, see [`HantekUsbDevice::reset`]. If it comes back as a new
+    /// device, [`Self::reconnect`] opens it again. The cached config is kept.
+    pub fn reset(&mut self) -> Result<(), Hantek2D42Error> {
+        self.last_capture_end = None;
+        self.usb
+            .reset()
+            .map_err(|error| Hantek2D42Error::HantekUsbError {
+                error,
+                failed_action: "resetting device",
+                channel_no: None,
+            })
+    }
+
+    /// Firmware bugs of the device as far as known for its release, see [`crate::quirks`].
+    pub fn quirks(&self) -> Vec<Quirk> {
+        quirks_of((self.usb.vid(), self.usb.pid()), &self.usb.device_release())
+    }
+
+    pub fn has_quirk(&self, quirk: Quirk) -> bool {
+        self.quirks().contains(&quirk)
+    }
+
+    /// Builder with the framing every command shares, only cmd and val left to set. For
+    /// exploring codes not known yet, along with [`Self::send_raw`].
+    pub fn command_builder(func: u16) -> HantekCommandBuilder {
+        Self::cmd(func)
+    }
+}
+
+impl<'a, T: Transport> Hantek2D42<'a, T> {
+    /// Same as [`Hantek2D42::new`], over any transport. The config is what's known of the
+    /// device's settings, usually nothing, see [`HantekConfig::new`].
+    pub fn with_transport(usb: T, config: HantekConfig) -> Self {
+        Self::with_events(usb, config, EventBus::new())
+    }
+
+    fn with_events(usb: T, config: HantekConfig, events: EventBus) -> Self {
+        Self {
+            usb,
+            config,
+            last_capture_end: None,
+            metrics: Metrics::default(),
+            diff_mode: false,
+            force_next: false,
+            capture_packet: CAPTURE_PACKET,
+            events,
+            context: PhantomData,
+        }
+    }
+
     /// ================================================================= DEVICE
 
+    /// Settings as last set through this handle. No read-back of the device's settings is
+    /// known, a change made with its buttons or by another program goes unnoticed, drift can't
+    /// be detected, only undone by setting everything again.
     pub fn get_config(&self) -> &HantekConfig {
         &self.config
     }
 
+    /// Bus to subscribe to for what happens on the device, see [`crate::events`].
+    pub fn events(&self) -> &EventBus {
+        &self.events
+    }
+
+    /// Latency of the commands sent since the previous call, or since opening the device.
+    pub fn take_metrics(&mut self) -> Metrics {
+        std::mem::take(&mut self.metrics)
+    }
+
+    /// Same as [`Self::take_metrics`], leaving them to be taken.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// In diff mode the setters skip writing a value the cached config already has. Only worth it
+    /// when nothing else changes the device, e.g. its buttons, or the cache goes stale.
+    pub fn set_diff_mode(&mut self, diff_mode: bool) {
+        self.diff_mode = diff_mode;
+    }
+
+    pub fn diff_mode(&self) -> bool {
+        self.diff_mode
+    }
+
+    /// Makes the next setter write its value even if diff mode would skip it, e.g.
+    /// `hantek.force().set_channel_scale(1, Scale::v1)`.
+    pub fn force(&mut self) -> &mut Self {
+        self.force_next = true;
+        self
+    }
+
+    /// Bytes of a capture asked for with each capture command, a multiple of the 64 byte bulk
+    /// packet. Larger reads only pay off if the device answers a command with more than a
+    /// packet, otherwise each read waits for the full timeout before returning what was sent.
+    pub fn set_capture_packet(&mut self, bytes: usize) -> Result<(), Hantek2D42Error> {
+        let packets = bytes / CAPTURE_PACKET;
+        if packets == 0 || packets * CAPTURE_PACKET != bytes || bytes > CAPTURE_PACKET_MAX {
+            return Err(invalid(
+                "capture packet",
+                bytes,
+                format!(
+                    "a multiple of {} up to {}",
+                    CAPTURE_PACKET, CAPTURE_PACKET_MAX
+                ),
+            ));
+        }
+        self.capture_packet = bytes;
+        Ok(())
+    }
+
+    pub fn capture_packet(&self) -> usize {
+        self.capture_packet
+    }
+
     pub fn start(&mut self) -> Result<(), Hantek2D42Error> {
-        let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
-            .set_cmd(SCOPE_START_STOP)
-            .set_val0(1)
-            .into();
+        let cmd = Self::start_stop_cmd(true)?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "sending Start command to device",
-            })
+        self.send(&cmd, "sending Start command to device", None)
             .map(|_| {
                 self.config.running_status = Some(RunningStatus::Start);
+                self.config_changed();
             })
     }
 
     pub fn stop(&mut self) -> Result<(), Hantek2D42Error> {
-        let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
-            .set_cmd(SCOPE_START_STOP)
-            .set_val0(0)
-            .into();
+        let cmd = Self::start_stop_cmd(false)?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "sending Stop command to device",
-            })
+        self.send(&cmd, "sending Stop command to device", None)
             .map(|_| {
                 self.config.running_status = Some(RunningStatus::Stop);
+                self.config_changed();
             })
     }
 
+    /// Checks the device still takes commands by sending it the running status, or else the
+    /// device function, it already has. Nothing can be read back, a device that's gone fails the
+    /// write. The config is left as is, nothing changed for subscribers to hear of.
+    ///
+    /// Start re-arms a capture in single trigger mode, a started device in it is sent its
+    /// function instead. `false` if nothing that's safe to send is known.
+    pub fn keep_alive(&mut self) -> Result<bool, Hantek2D42Error> {
+        let single = self.config.trigger_mode == Some(TriggerMode::Single);
+        let cmd = match (&self.config.running_status, &self.config.device_function) {
+            (Some(RunningStatus::Start), _) if !single => Self::start_stop_cmd(true)?,
+            (Some(RunningStatus::Stop), _) => Self::start_stop_cmd(false)?,
+            (_, Some(function)) => Self::device_function_cmd(function)?,
+            _ => return Ok(false),
+        };
+        self.send(&cmd, "checking device is alive", None)?;
+        Ok(true)
+    }
+
     pub fn set_device_function(&mut self, function: DeviceFunction) -> Result<(), Hantek2D42Error> {
-        let cmd: RawCommand = Self::cmd(FUNC_SCREEN_SETTING)
-            .set_cmd(0)
-            .set_val0(match function {
-                DeviceFunction::Scope => SCREEN_VAL_SCOPE,
-                DeviceFunction::AWG => SCREEN_VAL_AWG,
-                DeviceFunction::DMM => SCREEN_VAL_DMM,
-            })
-            .into();
+        if self.unchanged(self.config.device_function.as_ref() == Some(&function)) {
+            return Ok(());
+        }
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting device function",
-            })
-            .map(|_| self.config.device_function = Some(function))
+        let cmd = Self::device_function_cmd(&function)?;
+
+        self.send(&cmd, "setting device function", None).map(|_| {
+            self.config.device_function = Some(function);
+            self.config_changed();
+        })
     }
 
     /// ================================================================ CHANNEL
 
     pub fn enable_channel(&mut self, channel_no: usize) -> Result<(), Hantek2D42Error> {
-        self.assert_channel_no(channel_no);
+        self.check_channel_no(channel_no)?;
+
+        if self.unchanged(self.config.channel(channel_no).and_then(|it| it.enabled) == Some(true)) {
+            return Ok(());
+        }
 
         let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
             .set_cmd(match channel_no {
@@ -135,21 +669,22 @@ impl<'a> Hantek2D42<'a> {
                 _ => unreachable!(),
             })
             .set_val0(1)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "enabling channel",
-            })
+        self.send(&cmd, "enabling channel", Some(channel_no))
             .map(|_| {
-                self.config.enabled_channels.insert(channel_no, Some(true));
+                self.channel_mut(channel_no).enabled = Some(true);
+                self.config_changed();
             })
     }
 
     pub fn disable_channel(&mut self, channel_no: usize) -> Result<(), Hantek2D42Error> {
-        self.assert_channel_no(channel_no);
+        self.check_channel_no(channel_no)?;
+
+        if self.unchanged(self.config.channel(channel_no).and_then(|it| it.enabled) == Some(false))
+        {
+            return Ok(());
+        }
 
         let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
             .set_cmd(match channel_no {
@@ -158,16 +693,12 @@ impl<'a> Hantek2D42<'a> {
                 _ => unreachable!(),
             })
             .set_val0(0)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "disabling channel",
-            })
+        self.send(&cmd, "disabling channel", Some(channel_no))
             .map(|_| {
-                self.config.enabled_channels.insert(channel_no, Some(false));
+                self.channel_mut(channel_no).enabled = Some(false);
+                self.config_changed();
             })
     }
 
@@ -176,7 +707,13 @@ impl<'a> Hantek2D42<'a> {
         channel_no: usize,
         coupling: Coupling,
     ) -> Result<(), Hantek2D42Error> {
-        self.assert_channel_no(channel_no);
+        self.check_channel_no(channel_no)?;
+
+        if self.unchanged(
+            self.config.channel(channel_no).and_then(|it| it.coupling()) == Some(&coupling),
+        ) {
+            return Ok(());
+        }
 
         let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
             .set_cmd(match channel_no {
@@ -189,18 +726,12 @@ impl<'a> Hantek2D42<'a> {
                 Coupling::DC => SCOPE_VAL_COUPLING_DC,
                 Coupling::GND => SCOPE_VAL_COUPLING_GND,
             })
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting channel coupling",
-            })
+        self.send(&cmd, "setting channel coupling", Some(channel_no))
             .map(|_| {
-                self.config
-                    .channel_coupling
-                    .insert(channel_no, Some(coupling));
+                self.channel_mut(channel_no).coupling = Some(coupling);
+                self.config_changed();
             })
     }
 
@@ -209,7 +740,12 @@ impl<'a> Hantek2D42<'a> {
         channel_no: usize,
         probe: Probe,
     ) -> Result<(), Hantek2D42Error> {
-        self.assert_channel_no(channel_no);
+        self.check_channel_no(channel_no)?;
+
+        if self.unchanged(self.config.channel(channel_no).and_then(|it| it.probe()) == Some(&probe))
+        {
+            return Ok(());
+        }
 
         let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
             .set_cmd(match channel_no {
@@ -223,16 +759,12 @@ impl<'a> Hantek2D42<'a> {
                 Probe::X100 => SCOPE_VAL_PROBE_X100,
                 Probe::X1000 => SCOPE_VAL_PROBE_X1000,
             })
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting chanel probe",
-            })
+        self.send(&cmd, "setting channel probe", Some(channel_no))
             .map(|_| {
-                self.config.channel_probe.insert(channel_no, Some(probe));
+                self.channel_mut(channel_no).probe = Some(probe);
+                self.config_changed();
             })
     }
 
@@ -241,7 +773,12 @@ impl<'a> Hantek2D42<'a> {
         channel_no: usize,
         scale: Scale,
     ) -> Result<(), Hantek2D42Error> {
-        self.assert_channel_no(channel_no);
+        self.check_channel_no(channel_no)?;
+
+        if self.unchanged(self.config.channel(channel_no).and_then(|it| it.scale()) == Some(&scale))
+        {
+            return Ok(());
+        }
 
         let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
             .set_cmd(match channel_no {
@@ -261,23 +798,17 @@ impl<'a> Hantek2D42<'a> {
                 Scale::v5 => SCOPE_VAL_SCALE_5V,
                 Scale::v10 => SCOPE_VAL_SCALE_10V,
             })
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting channel scale",
-            })
+        self.send(&cmd, "setting channel scale", Some(channel_no))
             .map(|_| {
-                self.config.channel_offset_adjustment.insert(
-                    channel_no,
-                    Some(Adjustment::new(
-                        4.0 * scale.raw_value(),
-                        -4.0 * scale.raw_value(),
-                    )),
-                );
-                self.config.channel_scale.insert(channel_no, Some(scale));
+                let channel = self.channel_mut(channel_no);
+                channel.offset_adjustment = Some(Adjustment::new(
+                    4.0 * scale.raw_value(),
+                    -4.0 * scale.raw_value(),
+                ));
+                channel.scale = Some(scale);
+                self.config_changed();
             })
     }
 
@@ -286,15 +817,13 @@ impl<'a> Hantek2D42<'a> {
         channel_no: usize,
         offset: f32,
     ) -> Result<(), Hantek2D42Error> {
-        if offset.is_nan() || offset.is_infinite() {
-            panic!(
-                "invalid value for channel offset, channel_no={}, offset={}",
-                channel_no, offset
-            );
-        }
-        // TODO sanitize offset value range.
+        self.check_channel_no(channel_no)?;
+        check_finite("channel offset", offset)?;
 
-        let adjustment = self.config.channel_offset_adjustment[&channel_no].as_ref();
+        let adjustment = self
+            .config
+            .channel(channel_no)
+            .and_then(|it| it.offset_adjustment());
         if adjustment.is_none() {
             return Err(Hantek2D42Error::ChannelAdjustmentError);
         }
@@ -303,14 +832,9 @@ impl<'a> Hantek2D42<'a> {
             return Err(Hantek2D42Error::ChannelAdjustmentError);
         }
 
-        let dev_offset = {
-            let mut dev_offset = offset - adjustment.lower;
-            dev_offset *= 200.0;
-            dev_offset /= adjustment.upper - adjustment.lower;
-            dev_offset
-        };
+        let dev_offset = raw_level("channel offset", offset, adjustment)?;
 
-        self.set_channel_offset(channel_no, dev_offset as u8)
+        self.set_channel_offset(channel_no, dev_offset)
     }
 
     pub fn set_channel_offset(
@@ -318,8 +842,14 @@ impl<'a> Hantek2D42<'a> {
         channel_no: usize,
         offset: u8,
     ) -> Result<(), Hantek2D42Error> {
-        self.assert_channel_no(channel_no);
-        // TODO sanitize offset value range.
+        self.check_channel_no(channel_no)?;
+        check_raw_level("channel offset", offset)?;
+
+        if self.unchanged(
+            self.config.channel(channel_no).and_then(|it| it.offset) == Some(offset as f32),
+        ) {
+            return Ok(());
+        }
 
         let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
             .set_cmd(match channel_no {
@@ -328,18 +858,12 @@ impl<'a> Hantek2D42<'a> {
                 _ => unreachable!(),
             })
             .set_val0(offset)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting channel offset",
-            })
+        self.send(&cmd, "setting channel offset", Some(channel_no))
             .map(|_| {
-                self.config
-                    .channel_offset
-                    .insert(channel_no, Some(offset as f32));
+                self.channel_mut(channel_no).offset = Some(offset as f32);
+                self.config_changed();
             })
     }
 
@@ -347,7 +871,16 @@ impl<'a> Hantek2D42<'a> {
         &mut self,
         channel_no: usize,
     ) -> Result<(), Hantek2D42Error> {
-        self.assert_channel_no(channel_no);
+        self.check_channel_no(channel_no)?;
+
+        if self.unchanged(
+            self.config
+                .channel(channel_no)
+                .and_then(|it| it.bandwidth_limit)
+                == Some(true),
+        ) {
+            return Ok(());
+        }
 
         let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
             .set_cmd(match channel_no {
@@ -356,18 +889,12 @@ impl<'a> Hantek2D42<'a> {
                 _ => unreachable!(),
             })
             .set_val0(1)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "enabling channel bandwidth limit",
-            })
+        self.send(&cmd, "enabling channel bandwidth limit", Some(channel_no))
             .map(|_| {
-                self.config
-                    .channel_bandwidth_limit
-                    .insert(channel_no, Some(true));
+                self.channel_mut(channel_no).bandwidth_limit = Some(true);
+                self.config_changed();
             })
     }
 
@@ -375,7 +902,16 @@ impl<'a> Hantek2D42<'a> {
         &mut self,
         channel_no: usize,
     ) -> Result<(), Hantek2D42Error> {
-        self.assert_channel_no(channel_no);
+        self.check_channel_no(channel_no)?;
+
+        if self.unchanged(
+            self.config
+                .channel(channel_no)
+                .and_then(|it| it.bandwidth_limit)
+                == Some(false),
+        ) {
+            return Ok(());
+        }
 
         let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
             .set_cmd(match channel_no {
@@ -384,18 +920,12 @@ impl<'a> Hantek2D42<'a> {
                 _ => unreachable!(),
             })
             .set_val0(0)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "disabling channel bandwidth limit",
-            })
+        self.send(&cmd, "disabling channel bandwidth limit", Some(channel_no))
             .map(|_| {
-                self.config
-                    .channel_bandwidth_limit
-                    .insert(channel_no, Some(false));
+                self.channel_mut(channel_no).bandwidth_limit = Some(false);
+                self.config_changed();
             })
     }
 
@@ -404,57 +934,162 @@ impl<'a> Hantek2D42<'a> {
         channels: &[usize],
         num_samples: usize,
     ) -> Result<Vec<u8>, Hantek2D42Error> {
-        if num_samples < 64 {
-            panic!("minimum number of samples is 64, asked for={}", num_samples);
-        }
+        self.capture_with(channels, num_samples, &CaptureHandle::new())
+    }
 
-        for channel_no in channels {
-            self.assert_channel_no(*channel_no);
-        }
+    /// Same as [`Self::capture`], reporting progress to and cancelled through the handle.
+    pub fn capture_with(
+        &mut self,
+        channels: &[usize],
+        num_samples: usize,
+        handle: &CaptureHandle,
+    ) -> Result<Vec<u8>, Hantek2D42Error> {
+        let mut buffer = vec![0; num_samples * self.num_capture_channels(channels)?];
+        self.capture_into_with(channels, &mut buffer, handle)?;
+        Ok(buffer)
+    }
 
-        let num_channels = {
-            let ch1 = if channels.contains(&1) { 1 } else { 0 };
-            let ch2 = if channels.contains(&2) { 1 } else { 0 };
-            ch1 + ch2
-        };
+    /// Same as [`Self::capture`], into a buffer the caller keeps reusing. The number of samples
+    /// is the length of the buffer divided by the number of channels.
+    pub fn capture_into(
+        &mut self,
+        channels: &[usize],
+        buffer: &mut [u8],
+    ) -> Result<(), Hantek2D42Error> {
+        self.capture_into_with(channels, buffer, &CaptureHandle::new())
+    }
 
-        if num_channels == 0 {
-            panic!("no channel selected for capture");
+    /// Same as [`Self::capture_into`], reporting progress to and cancelled through the handle.
+    pub fn capture_into_with(
+        &mut self,
+        channels: &[usize],
+        buffer: &mut [u8],
+        handle: &CaptureHandle,
+    ) -> Result<(), Hantek2D42Error> {
+        let num_channels = self.num_capture_channels(channels)?;
+        let num_samples = buffer.len() / num_channels;
+        if num_samples < 64 {
+            return Err(invalid("num_samples", num_samples, "at least 64"));
+        }
+        if num_samples * num_channels != buffer.len() {
+            return Err(invalid(
+                "buffer length",
+                buffer.len(),
+                format!("a multiple of the {} channels", num_channels),
+            ));
         }
 
+        self.events.emit(|| Event::CaptureStarted {
+            channels: channels.to_vec(),
+            num_samples,
+        });
         let cmd: RawCommand = Self::cmd(FUNC_SCOPE_CAPTURE)
             .set_cmd(SCOPE_START_RECV)
             .set_val_u16(
                 ((num_samples * num_channels) / 2) as u16,
                 ((num_samples * num_channels) / 2) as u16,
             )
-            .into();
-
-        let mut buffer = vec![0; (num_samples * num_channels) as usize];
-        let mut count = 0;
-        while count < (num_samples as usize) {
-            let length = if (num_samples * num_channels) - count < 64 {
-                num_samples - count
-            } else {
-                64
-            };
-            self.usb.write(WRITE_ENDPOINT, &cmd).map_err(|error| {
-                Hantek2D42Error::HantekUsbError {
-                    error,
-                    failed_action: "capture write command",
-                }
-            })?;
-            let buf = &mut buffer[count..(count + length)];
-            let actual_len = self.usb.read(READ_ENDPOINT, buf).map_err(|error| {
-                Hantek2D42Error::HantekUsbError {
-                    error,
-                    failed_action: "capture read",
-                }
-            })?;
-            count += actual_len;
+            .build()?;
+
+        read_capture(
+            &mut self.usb,
+            &mut self.metrics,
+            &cmd,
+            buffer,
+            self.capture_packet,
+            handle,
+        )?;
+        debug!(
+            target: CAPTURE,
+            "captured channels={:?} num_samples={}", channels, num_samples
+        );
+
+        self.last_capture_end = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Same as [`Self::capture`], along with the cached channel and time scales needed to
+    /// interpret the samples.
+    pub fn capture_frame(
+        &mut self,
+        channels: &[usize],
+        num_samples: usize,
+    ) -> Result<CaptureFrame, Hantek2D42Error> {
+        self.capture_frame_with(channels, num_samples, &CaptureHandle::new())
+    }
+
+    /// Same as [`Self::capture_frame`], reporting progress to and cancelled through the handle.
+    pub fn capture_frame_with(
+        &mut self,
+        channels: &[usize],
+        num_samples: usize,
+        handle: &CaptureHandle,
+    ) -> Result<CaptureFrame, Hantek2D42Error> {
+        self.capture_frame_pooled(channels, num_samples, handle, &mut BufferPool::default())
+    }
+
+    /// Same as [`Self::capture_frame_with`], the samples going into a buffer of the pool. The
+    /// frame's `raw` is to be put back into the pool once done with.
+    pub fn capture_frame_pooled(
+        &mut self,
+        channels: &[usize],
+        num_samples: usize,
+        handle: &CaptureHandle,
+        pool: &mut BufferPool,
+    ) -> Result<CaptureFrame, Hantek2D42Error> {
+        let mut channels = channels.to_vec();
+        channels.sort_unstable();
+        channels.dedup();
+
+        let previous_end = self.last_capture_end;
+        let mut raw = pool.take(num_samples * channels.len());
+        let started = Instant::now();
+        let started_at = SystemTime::now();
+        if let Err(e) = self.capture_into_with(&channels, &mut raw, handle) {
+            pool.put(raw.into());
+            return Err(e);
         }
 
-        Ok(buffer)
+        let frame = CaptureFrame {
+            acquisition_time: started.elapsed(),
+            gap: previous_end.map(|it| started.duration_since(it)),
+            scales: channels
+                .iter()
+                .map(|it| self.config.channel(*it).and_then(|it| it.scale.clone()))
+                .collect(),
+            channels,
+            time_scale: self.config.time_scale.clone(),
+            raw: raw.into(),
+            started,
+            started_at,
+            fine: None,
+        };
+        self.events
+            .emit(|| Event::FrameReady(Arc::new(frame.clone())));
+        Ok(frame)
+    }
+
+    /// Arms the trigger and waits for it, returning the capture it took; meant for single
+    /// trigger mode, where the device holds each triggered capture until armed again. The device
+    /// can't be asked whether it triggered, a new capture is told from the one held before arming
+    /// by its samples differing. The trigger is then within `poll` before the capture started.
+    /// Waits until cancelled through the handle.
+    pub fn capture_segment(
+        &mut self,
+        channels: &[usize],
+        num_samples: usize,
+        poll: Duration,
+        handle: &CaptureHandle,
+    ) -> Result<CaptureFrame, Hantek2D42Error> {
+        let held = self.capture_frame_with(channels, num_samples, handle)?.raw;
+        self.start()?;
+        loop {
+            let frame = self.capture_frame_with(channels, num_samples, handle)?;
+            if frame.raw != held {
+                return Ok(frame);
+            }
+            thread::sleep(poll);
+        }
     }
 
     /// ================================================================== SCOPE
@@ -497,31 +1132,28 @@ impl<'a> Hantek2D42<'a> {
             TimeScale::s500 => SCOPE_VAL_SCALE_TIME_500s,
         };
 
+        if self.unchanged(self.config.time_scale.as_ref() == Some(&time_scale)) {
+            return Ok(());
+        }
+
         let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
             .set_cmd(SCOPE_SCALE_TIME)
             .set_val0(raw)
-            .into();
-
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting time scale",
-            })
-            .map(|_| {
-                self.config.time_offset_adjustment =
-                    Some(Adjustment::new(15.0 * (raw as f32), -15.0 * (raw as f32)));
-                self.config.time_scale = Some(time_scale);
-            })
+            .build()?;
+
+        self.send(&cmd, "setting time scale", None).map(|_| {
+            self.config.time_offset_adjustment =
+                Some(Adjustment::new(15.0 * (raw as f32), -15.0 * (raw as f32)));
+            self.config.time_scale = Some(time_scale);
+            self.config_changed();
+        })
     }
 
     pub fn set_time_offset_with_auto_adjustment(
         &mut self,
         time_offset: f32,
     ) -> Result<(), Hantek2D42Error> {
-        if time_offset.is_nan() || time_offset.is_infinite() {
-            panic!("bad value for time_offset={}", time_offset);
-        }
+        check_finite("time offset", time_offset)?;
 
         let adjustment = self.config.time_offset_adjustment.as_ref();
         if adjustment.is_none() {
@@ -540,53 +1172,66 @@ impl<'a> Hantek2D42<'a> {
             dev_time_offset = dev_time_offset.round();
             dev_time_offset
         };
+        if !(0.0..=u32::MAX as f32).contains(&dev_time_offset) {
+            return Err(invalid(
+                "time offset",
+                time_offset,
+                format!("at least {}", adjustment.lower / 15.0 * 6.0),
+            ));
+        }
 
         self.set_time_offset(dev_time_offset as u32)
     }
 
+    /// Horizontal position of the trigger point in percent of the screen width from its left
+    /// edge, 50 being the center. Anything left of it is signal from before the trigger. Needs
+    /// nothing else known, the raw time offset being in samples across the screen.
+    pub fn set_trigger_position(&mut self, percent: f32) -> Result<(), Hantek2D42Error> {
+        check_finite("trigger position", percent)?;
+        if !(0.0..=100.0).contains(&percent) {
+            return Err(invalid("trigger position", percent, "0..=100"));
+        }
+
+        let raw = (percent * TIME_OFFSET_SCREEN as f32 / 100.0).round() as u32;
+        self.set_time_offset(raw)
+    }
+
     pub fn set_time_offset(&mut self, time_offset: u32) -> Result<(), Hantek2D42Error> {
+        if self.unchanged(self.config.time_offset == Some(time_offset as f32)) {
+            return Ok(());
+        }
+
         let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
             .set_cmd(SCOPE_OFFSET_TIME)
             .set_val_u32(time_offset)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting time offset",
-            })
-            .map(|_| {
-                self.config.time_offset = Some(time_offset as f32);
-            })
+        self.send(&cmd, "setting time offset", None).map(|_| {
+            self.config.time_offset = Some(time_offset as f32);
+            self.config_changed();
+        })
     }
 
+    /// Needs the scale of the channel, the trigger level adjustment is derived from it.
     pub fn set_trigger_source(&mut self, channel_no: usize) -> Result<(), Hantek2D42Error> {
-        self.assert_channel_no(channel_no);
+        let adjustment = self.trigger_level_adjustment_of(channel_no)?;
 
-        let scale = self.config.channel_scale[&channel_no]
-            .as_ref()
-            .map(|it| it.raw_value());
-        if scale.is_none() {
-            return Err(Hantek2D42Error::TriggerLevelAdjustmentError);
+        let unchanged = self.config.trigger_source_channel == Some(channel_no)
+            && self.config.trigger_level_adjustment.as_ref() == Some(&adjustment);
+        if self.unchanged(unchanged) {
+            return Ok(());
         }
-        let scale = scale.unwrap();
 
         let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
             .set_cmd(SCOPE_TRIGGER_SOURCE)
             .set_val0((channel_no - 1) as u8)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting trigger source",
-            })
+        self.send(&cmd, "setting trigger source", Some(channel_no))
             .map(|_| {
                 self.config.trigger_source_channel = Some(channel_no);
-                self.config.trigger_level_adjustment =
-                    Some(Adjustment::new(4.0 * scale, -4.0 * scale));
+                self.config.trigger_level_adjustment = Some(adjustment);
+                self.config_changed();
             })
     }
 
@@ -594,6 +1239,10 @@ impl<'a> Hantek2D42<'a> {
         &mut self,
         trigger_slope: TriggerSlope,
     ) -> Result<(), Hantek2D42Error> {
+        if self.unchanged(self.config.trigger_slope.as_ref() == Some(&trigger_slope)) {
+            return Ok(());
+        }
+
         let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
             .set_cmd(SCOPE_TRIGGER_SLOPE)
             .set_val0(match trigger_slope {
@@ -601,20 +1250,19 @@ impl<'a> Hantek2D42<'a> {
                 TriggerSlope::Falling => SCOPE_VAL_TRIGGER_SLOPE_FALLING,
                 TriggerSlope::Both => SCOPE_VAL_TRIGGER_SLOPE_BOTH,
             })
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting trigger slope",
-            })
-            .map(|_| {
-                self.config.trigger_slope = Some(trigger_slope);
-            })
+        self.send(&cmd, "setting trigger slope", None).map(|_| {
+            self.config.trigger_slope = Some(trigger_slope);
+            self.config_changed();
+        })
     }
 
     pub fn set_trigger_mode(&mut self, trigger_mode: TriggerMode) -> Result<(), Hantek2D42Error> {
+        if self.unchanged(self.config.trigger_mode.as_ref() == Some(&trigger_mode)) {
+            return Ok(());
+        }
+
         let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
             .set_cmd(SCOPE_TRIGGER_MODE)
             .set_val0(match trigger_mode {
@@ -622,29 +1270,19 @@ impl<'a> Hantek2D42<'a> {
                 TriggerMode::Normal => SCOPE_VAL_TRIGGER_MODE_NORMAL,
                 TriggerMode::Single => SCOPE_VAL_TRIGGER_MODE_SINGLE,
             })
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting trigger mode",
-            })
-            .map(|_| {
-                self.config.trigger_mode = Some(trigger_mode);
-            })
+        self.send(&cmd, "setting trigger mode", None).map(|_| {
+            self.config.trigger_mode = Some(trigger_mode);
+            self.config_changed();
+        })
     }
 
     pub fn set_trigger_level_with_auto_adjustment(
         &mut self,
         trigger_level: f32,
     ) -> Result<(), Hantek2D42Error> {
-        if trigger_level.is_nan() || trigger_level.is_infinite() {
-            panic!(
-                "invalid value for trigger level, trigger_level={}",
-                trigger_level
-            );
-        }
+        check_finite("trigger level", trigger_level)?;
 
         let adjustment = self.config.trigger_level_adjustment.as_ref();
         if adjustment.is_none() {
@@ -655,34 +1293,72 @@ impl<'a> Hantek2D42<'a> {
             return Err(Hantek2D42Error::TriggerLevelAdjustmentError);
         }
 
-        let dev_trigger_level = {
-            let mut dev_trigger_level = trigger_level - adjustment.lower;
-            dev_trigger_level *= 200.0;
-            dev_trigger_level /= adjustment.upper - adjustment.lower;
-            dev_trigger_level
+        let dev_trigger_level = raw_level("trigger level", trigger_level, adjustment)?;
+
+        self.set_trigger_level(dev_trigger_level)
+    }
+
+    /// Level in volts on the given channel, or the trigger source if `None`. Unlike
+    /// [`Self::set_trigger_level_with_auto_adjustment`] the adjustment is derived from the
+    /// channel's scale, which alone must be known.
+    pub fn set_trigger_level_volts(
+        &mut self,
+        trigger_level: f32,
+        channel_no: Option<usize>,
+    ) -> Result<(), Hantek2D42Error> {
+        check_finite("trigger level", trigger_level)?;
+
+        let channel_no = match channel_no.or(self.config.trigger_source_channel) {
+            Some(channel_no) => channel_no,
+            None => return Err(Hantek2D42Error::TriggerSourceUnknown),
         };
+        let adjustment = self.trigger_level_adjustment_of(channel_no)?;
+        let dev_trigger_level = raw_level("trigger level", trigger_level, &adjustment)?;
 
-        self.set_trigger_level(dev_trigger_level as u8)
+        self.set_trigger_level(dev_trigger_level)
+    }
+
+    /// Level in percent of the screen height from the bottom, needs nothing else known.
+    pub fn set_trigger_level_percent(&mut self, percent: f32) -> Result<(), Hantek2D42Error> {
+        check_finite("trigger level percent", percent)?;
+        if !(0.0..=100.0).contains(&percent) {
+            return Err(invalid("trigger level percent", percent, "0..=100"));
+        }
+
+        let raw = (percent * RAW_LEVEL_MAX as f32 / 100.0).round() as u8;
+        self.set_trigger_level(raw)
     }
 
     pub fn set_trigger_level(&mut self, trigger_level: u8) -> Result<(), Hantek2D42Error> {
+        check_raw_level("trigger level", trigger_level)?;
+
+        if self.unchanged(self.config.trigger_level == Some(trigger_level as f32)) {
+            return Ok(());
+        }
+
         let cmd: RawCommand = Self::cmd(FUNC_SCOPE_SETTING)
             .set_cmd(SCOPE_TRIGGER_LEVEL)
             .set_val0(trigger_level)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting trigger level",
-            })
-            .map(|_| self.config.trigger_level = Some(trigger_level as f32))
+        self.send(&cmd, "setting trigger level", None).map(|_| {
+            self.config.trigger_level = Some(trigger_level as f32);
+            self.config_changed();
+        })
     }
 
     ///=================================================================== AWG
 
+    /// Fails if the frequency set before is beyond what the waveform is output at.
     pub fn set_awg_type(&mut self, awg_type: AwgType) -> Result<(), Hantek2D42Error> {
+        if let Some(frequency) = self.config.awg_frequency {
+            check_awg_frequency("awg frequency", frequency, &awg_type)?;
+        }
+
+        if self.unchanged(self.config.awg_type.as_ref() == Some(&awg_type)) {
+            return Ok(());
+        }
+
         let cmd: RawCommand = Self::cmd(FUNC_AWG_SETTING)
             .set_cmd(AWG_TYPE)
             .set_val0(match awg_type {
@@ -695,40 +1371,38 @@ impl<'a> Hantek2D42<'a> {
                 AwgType::Arb3 => AWG_VAL_TYPE_ARB3,
                 AwgType::Arb4 => AWG_VAL_TYPE_ARB4,
             })
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting awg mode",
-            })
-            .map(|_| {
-                self.config.awg_type = Some(awg_type);
-            })
+        self.send(&cmd, "setting awg mode", None).map(|_| {
+            self.config.awg_type = Some(awg_type);
+            self.config_changed();
+        })
     }
 
+    /// Frequency in Hz, within [`awg_frequency_max`] of the waveform type if known.
     pub fn set_awg_frequency(&mut self, frequency: f32) -> Result<(), Hantek2D42Error> {
-        // TODO sanitize frequency?
+        check_finite("awg frequency", frequency)?;
+        // Unknown waveform is checked against the fastest one, the type is checked once set.
+        let awg_type = self.config.awg_type.clone().unwrap_or(AwgType::Sin);
+        check_awg_frequency("awg frequency", frequency, &awg_type)?;
+
+        if self.unchanged(self.config.awg_frequency == Some(frequency)) {
+            return Ok(());
+        }
 
         let cmd: RawCommand = Self::cmd(FUNC_AWG_SETTING)
             .set_cmd(AWG_FREQ)
             .set_val_u32(frequency as u32)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting awg frequency",
-            })
-            .map(|_| {
-                self.config.awg_frequency = Some(frequency);
-            })
+        self.send(&cmd, "setting awg frequency", None).map(|_| {
+            self.config.awg_frequency = Some(frequency);
+            self.config_changed();
+        })
     }
 
     pub fn set_awg_amplitude(&mut self, amplitude: f32) -> Result<(), Hantek2D42Error> {
-        // TODO sanitize amplitude?
+        check_awg_volts("awg amplitude", amplitude, AWG_AMPLITUDE_MAX)?;
 
         let raw = (amplitude.abs() * 1000.0) as u16;
         let sign = if amplitude.is_sign_negative() {
@@ -736,24 +1410,23 @@ impl<'a> Hantek2D42<'a> {
         } else {
             0u16
         };
+        if self.unchanged(self.config.awg_amplitude == Some(amplitude)) {
+            return Ok(());
+        }
+
         let cmd: RawCommand = Self::cmd(FUNC_AWG_SETTING)
             .set_cmd(AWG_AMPLITUDE)
             .set_val_u16(raw, sign)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting awg amplitude",
-            })
-            .map(|_| {
-                self.config.awg_amplitude = Some(amplitude);
-            })
+        self.send(&cmd, "setting awg amplitude", None).map(|_| {
+            self.config.awg_amplitude = Some(amplitude);
+            self.config_changed();
+        })
     }
 
     pub fn set_awg_offset(&mut self, offset: f32) -> Result<(), Hantek2D42Error> {
-        // TODO sanitize offset?
+        check_awg_volts("awg offset", offset, AWG_OFFSET_MAX)?;
 
         let raw = (offset.abs() * 1000.0) as u16;
         let sign = if offset.is_sign_negative() {
@@ -761,61 +1434,58 @@ impl<'a> Hantek2D42<'a> {
         } else {
             0u16
         };
+        if self.unchanged(self.config.awg_offset == Some(offset)) {
+            return Ok(());
+        }
+
         let cmd: RawCommand = Self::cmd(FUNC_AWG_SETTING)
             .set_cmd(AWG_OFFSET)
             .set_val_u16(raw, sign)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting awg offset",
-            })
-            .map(|_| {
-                self.config.awg_offset = Some(offset);
-            })
+        self.send(&cmd, "setting awg offset", None).map(|_| {
+            self.config.awg_offset = Some(offset);
+            self.config_changed();
+        })
     }
 
     pub fn set_awg_duty_square(&mut self, duty: f32) -> Result<(), Hantek2D42Error> {
-        // TODO sanitize duty?
+        check_awg_duty("awg square duty", duty)?;
 
         let raw = (duty * 100.0) as u16;
+        if self.unchanged(self.config.awg_duty_square == Some(duty)) {
+            return Ok(());
+        }
+
         let cmd: RawCommand = Self::cmd(FUNC_AWG_SETTING)
             .set_cmd(AWG_SQUARE_DUTY)
             .set_val_u16(raw, 0)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting awg square duty",
-            })
-            .map(|_| {
-                self.config.awg_duty_square = Some(duty);
-            })
+        self.send(&cmd, "setting awg square duty", None).map(|_| {
+            self.config.awg_duty_square = Some(duty);
+            self.config_changed();
+        })
     }
 
     pub fn set_awg_duty_ramp(&mut self, duty: f32) -> Result<(), Hantek2D42Error> {
-        // TODO sanitize duty?
+        check_awg_duty("awg ramp duty", duty)?;
 
         let raw = (duty * 100.0) as u16;
 
+        if self.unchanged(self.config.awg_duty_ramp == Some(duty)) {
+            return Ok(());
+        }
+
         let cmd: RawCommand = Self::cmd(FUNC_AWG_SETTING)
             .set_cmd(AWG_RAMP_DUTY)
             .set_val_u16(raw, 0)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting awg ramp duty",
-            })
-            .map(|_| {
-                self.config.awg_duty_ramp = Some(duty);
-            })
+        self.send(&cmd, "setting awg ramp duty", None).map(|_| {
+            self.config.awg_duty_ramp = Some(duty);
+            self.config_changed();
+        })
     }
 
     pub fn set_awg_duty_trap(
@@ -824,64 +1494,335 @@ impl<'a> Hantek2D42<'a> {
         low: f32,
         rise: f32,
     ) -> Result<(), Hantek2D42Error> {
-        // TODO sanitize high, low, rise?
+        check_awg_fraction("awg trap high duty", high)?;
+        check_awg_fraction("awg trap low duty", low)?;
+        check_awg_fraction("awg trap rise duty", rise)?;
 
         let raw_high = (high * 100.0) as u8;
         let raw_low = (low * 100.0) as u8;
         let raw_rise = (rise * 100.0) as u8;
 
+        if self.unchanged(self.config.awg_duty_trap == Some(TrapDuty { high, low, rise })) {
+            return Ok(());
+        }
+
         let cmd: RawCommand = Self::cmd(FUNC_AWG_SETTING)
             .set_cmd(AWG_TRAP_DUTY)
             .set_val_u8(raw_rise, raw_high, raw_low, 0)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "setting awg ramp duty",
-            })
-            .map(|_| {
-                self.config.awg_duty_trap = Some(TrapDuty { high, low, rise });
-            })
+        self.send(&cmd, "setting awg trap duty", None).map(|_| {
+            self.config.awg_duty_trap = Some(TrapDuty { high, low, rise });
+            self.config_changed();
+        })
     }
 
     pub fn awg_start(&mut self) -> Result<(), Hantek2D42Error> {
         let cmd: RawCommand = Self::cmd(FUNC_AWG_SETTING)
             .set_cmd(AWG_START_STOP)
             .set_val0(1)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "awg start",
-            })
-            .map(|_| {
-                self.config.awg_running_status = Some(RunningStatus::Start);
-            })
+        self.send(&cmd, "starting awg", None).map(|_| {
+            self.config.awg_running_status = Some(RunningStatus::Start);
+            self.config_changed();
+            debug!(target: AWG, "awg started");
+        })
     }
 
     pub fn awg_stop(&mut self) -> Result<(), Hantek2D42Error> {
         let cmd: RawCommand = Self::cmd(FUNC_AWG_SETTING)
             .set_cmd(AWG_START_STOP)
             .set_val0(0)
-            .into();
+            .build()?;
 
-        self.usb
-            .write(WRITE_ENDPOINT, &cmd)
-            .map_err(|error| Hantek2D42Error::HantekUsbError {
-                error,
-                failed_action: "awg stop",
-            })
-            .map(|_| {
-                self.config.awg_running_status = Some(RunningStatus::Stop);
-            })
+        self.send(&cmd, "stopping awg", None).map(|_| {
+            self.config.awg_running_status = Some(RunningStatus::Stop);
+            self.config_changed();
+            debug!(target: AWG, "awg stopped");
+        })
+    }
+
+    ///================================================================== BATCH
+
+    /// Checks every setting of the batch, then sends them in order, stopping at the first one
+    /// the device fails on. The checks are the ones not depending on the state of the device,
+    /// e.g. a scale set earlier in the same batch, so those can still fail midway.
+    pub fn apply_batch(&mut self, batch: &CommandBatch) -> Result<(), BatchError> {
+        for (index, setting) in batch.settings().iter().enumerate() {
+            self.check_setting(setting)
+                .map_err(|error| BatchError::Rejected { index, error })?;
+        }
+        for (index, setting) in batch.settings().iter().enumerate() {
+            setting
+                .apply(self)
+                .map_err(|error| BatchError::Failed { index, error })?;
+        }
+        Ok(())
+    }
+
+    ///================================================================= CONFIG
+
+    /// Sends every setting the config has, leaving alone what it doesn't set, e.g. to load a
+    /// profile or restore a snapshot. The order is the one the settings depend on each other in:
+    /// the device function first, each channel's scale before its offset, the channel scales
+    /// before the trigger source and the source before the level. The awg frequency is lowered
+    /// before and raised after switching the waveform, so it stays in range of either. The
+    /// running statuses go last, once everything else is set up.
+    ///
+    /// A setting failing doesn't stop the ones after it, unless writing to the device failed,
+    /// which leaves the rest unsent. The adjustments follow from the scales and the timeout is
+    /// the connection's, none of them are sent.
+    pub fn apply_config(&mut self, config: &HantekConfig) -> Result<(), ApplyConfigError> {
+        let mut result = ApplyConfigError {
+            failed: vec![],
+            not_sent: vec![],
+        };
+
+        if let Some(function) = &config.device_function {
+            self.apply_config_item(&mut result, "device_function".to_string(), |it| {
+                it.set_device_function(function.clone())
+            });
+        }
+
+        for (channel_no, channel) in config.channels() {
+            let name = |field: &str| format!("channel{}.{}", channel_no, field);
+            if let Some(enabled) = channel.enabled {
+                self.apply_config_item(&mut result, name("enabled"), |it| {
+                    if enabled {
+                        it.enable_channel(channel_no)
+                    } else {
+                        it.disable_channel(channel_no)
+                    }
+                });
+            }
+            if let Some(coupling) = &channel.coupling {
+                self.apply_config_item(&mut result, name("coupling"), |it| {
+                    it.set_channel_coupling(channel_no, coupling.clone())
+                });
+            }
+            if let Some(probe) = &channel.probe {
+                self.apply_config_item(&mut result, name("probe"), |it| {
+                    it.set_channel_probe(channel_no, probe.clone())
+                });
+            }
+            if let Some(bandwidth_limit) = channel.bandwidth_limit {
+                self.apply_config_item(&mut result, name("bandwidth_limit"), |it| {
+                    if bandwidth_limit {
+                        it.channel_enable_bandwidth_limit(channel_no)
+                    } else {
+                        it.channel_disable_bandwidth_limit(channel_no)
+                    }
+                });
+            }
+            if let Some(scale) = &channel.scale {
+                self.apply_config_item(&mut result, name("scale"), |it| {
+                    it.set_channel_scale(channel_no, scale.clone())
+                });
+            }
+            if let Some(offset) = channel.offset {
+                self.apply_config_item(&mut result, name("offset"), |it| {
+                    it.set_channel_offset(channel_no, offset as u8)
+                });
+            }
+        }
+
+        if let Some(time_scale) = &config.time_scale {
+            self.apply_config_item(&mut result, "time_scale".to_string(), |it| {
+                it.set_time_scale(time_scale.clone())
+            });
+        }
+        if let Some(time_offset) = config.time_offset {
+            self.apply_config_item(&mut result, "time_offset".to_string(), |it| {
+                it.set_time_offset(time_offset as u32)
+            });
+        }
+
+        if let Some(channel_no) = config.trigger_source_channel {
+            self.apply_config_item(&mut result, "trigger.source".to_string(), |it| {
+                it.set_trigger_source(channel_no)
+            });
+        }
+        if let Some(slope) = &config.trigger_slope {
+            self.apply_config_item(&mut result, "trigger.slope".to_string(), |it| {
+                it.set_trigger_slope(slope.clone())
+            });
+        }
+        if let Some(mode) = &config.trigger_mode {
+            self.apply_config_item(&mut result, "trigger.mode".to_string(), |it| {
+                it.set_trigger_mode(mode.clone())
+            });
+        }
+        if let Some(level) = config.trigger_level {
+            self.apply_config_item(&mut result, "trigger.level".to_string(), |it| {
+                it.set_trigger_level(level as u8)
+            });
+        }
+
+        // Whichever of the two comes first is checked against the other's value before.
+        let lowering = match (config.awg_frequency, self.config.awg_frequency) {
+            (Some(after), Some(before)) => after < before,
+            _ => false,
+        };
+        if lowering {
+            self.apply_awg_frequency(&mut result, config);
+        }
+        if let Some(awg_type) = &config.awg_type {
+            self.apply_config_item(&mut result, "awg.type".to_string(), |it| {
+                it.set_awg_type(awg_type.clone())
+            });
+        }
+        if !lowering {
+            self.apply_awg_frequency(&mut result, config);
+        }
+        if let Some(amplitude) = config.awg_amplitude {
+            self.apply_config_item(&mut result, "awg.amplitude".to_string(), |it| {
+                it.set_awg_amplitude(amplitude)
+            });
+        }
+        if let Some(offset) = config.awg_offset {
+            self.apply_config_item(&mut result, "awg.offset".to_string(), |it| {
+                it.set_awg_offset(offset)
+            });
+        }
+        if let Some(duty) = config.awg_duty_square {
+            self.apply_config_item(&mut result, "awg.duty_square".to_string(), |it| {
+                it.set_awg_duty_square(duty)
+            });
+        }
+        if let Some(duty) = config.awg_duty_ramp {
+            self.apply_config_item(&mut result, "awg.duty_ramp".to_string(), |it| {
+                it.set_awg_duty_ramp(duty)
+            });
+        }
+        if let Some(duty) = &config.awg_duty_trap {
+            self.apply_config_item(&mut result, "awg.duty_trap".to_string(), |it| {
+                it.set_awg_duty_trap(duty.high, duty.low, duty.rise)
+            });
+        }
+
+        if let Some(status) = &config.awg_running_status {
+            self.apply_config_item(&mut result, "awg.running".to_string(), |it| match status {
+                RunningStatus::Start => it.awg_start(),
+                RunningStatus::Stop => it.awg_stop(),
+            });
+        }
+        if let Some(status) = &config.running_status {
+            self.apply_config_item(&mut result, "running".to_string(), |it| match status {
+                RunningStatus::Start => it.start(),
+                RunningStatus::Stop => it.stop(),
+            });
+        }
+
+        if result.failed.is_empty() {
+            Ok(())
+        } else {
+            Err(result)
+        }
+    }
+
+    fn apply_awg_frequency(&mut self, result: &mut ApplyConfigError, config: &HantekConfig) {
+        if let Some(frequency) = config.awg_frequency {
+            self.apply_config_item(result, "awg.frequency".to_string(), |it| {
+                it.set_awg_frequency(frequency)
+            });
+        }
+    }
+
+    /// Applies a single setting of [`Self::apply_config`], unless the device was already found
+    /// gone.
+    fn apply_config_item(
+        &mut self,
+        result: &mut ApplyConfigError,
+        name: String,
+        apply: impl FnOnce(&mut Self) -> Result<(), Hantek2D42Error>,
+    ) {
+        let gone = result
+            .failed
+            .iter()
+            .any(|it| matches!(it.error, Hantek2D42Error::HantekUsbError { .. }));
+        if gone {
+            result.not_sent.push(name);
+            return;
+        }
+
+        if let Err(error) = apply(self) {
+            result.failed.push(ConfigItemError { name, error });
+        }
+    }
+
+    ///==================================================================== RAW
+
+    /// Sends a command as is. The config isn't updated, it may stop reflecting the device.
+    pub fn send_raw(&mut self, cmd: &RawCommand) -> Result<usize, Hantek2D42Error> {
+        self.send(cmd, "sending raw command", None)
+    }
+
+    /// Reads what the device answers with, up to the length of the buffer.
+    pub fn read_raw(&mut self, buf: &mut [u8]) -> Result<usize, Hantek2D42Error> {
+        let started = Instant::now();
+        let endpoint = read_endpoint(&self.usb);
+        let read =
+            self.usb
+                .read(endpoint, buf)
+                .map_err(|error| Hantek2D42Error::HantekUsbError {
+                    error,
+                    failed_action: "reading raw response",
+                    channel_no: None,
+                })?;
+        self.metrics
+            .record("reading raw response", None, read, started.elapsed());
+        Ok(read)
     }
 
     ///=============================================================== INTERNAL
 
+    fn config_changed(&self) {
+        self.events
+            .emit(|| Event::ConfigChanged(Arc::new(self.config.clone())));
+    }
+
+    fn send(
+        &mut self,
+        cmd: &RawCommand,
+        failed_action: &'static str,
+        channel_no: Option<usize>,
+    ) -> Result<usize, Hantek2D42Error> {
+        self.force_next = false;
+        send(
+            &mut self.usb,
+            &mut self.metrics,
+            cmd,
+            failed_action,
+            channel_no,
+        )
+    }
+
+    /// Whether the setter can skip its write, the cached value being the same in diff mode.
+    /// Takes up a pending [`Self::force`].
+    fn unchanged(&mut self, same: bool) -> bool {
+        let forced = std::mem::take(&mut self.force_next);
+        self.diff_mode && same && !forced
+    }
+
+    fn start_stop_cmd(start: bool) -> Result<RawCommand, Hantek2D42Error> {
+        Ok(Self::cmd(FUNC_SCOPE_SETTING)
+            .set_cmd(SCOPE_START_STOP)
+            .set_val0(if start { 1 } else { 0 })
+            .build()?)
+    }
+
+    fn device_function_cmd(function: &DeviceFunction) -> Result<RawCommand, Hantek2D42Error> {
+        Ok(Self::cmd(FUNC_SCREEN_SETTING)
+            .set_cmd(0)
+            .set_val0(match function {
+                DeviceFunction::Scope => SCREEN_VAL_SCOPE,
+                DeviceFunction::AWG => SCREEN_VAL_AWG,
+                DeviceFunction::DMM => SCREEN_VAL_DMM,
+            })
+            .build()?)
+    }
+
     fn cmd(func: u16) -> HantekCommandBuilder {
         HantekCommandBuilder::new()
             .set_idx(IDX)
@@ -890,12 +1831,695 @@ impl<'a> Hantek2D42<'a> {
             .set_last(0)
     }
 
-    fn assert_channel_no(&self, channel_no: usize) {
-        if channel_no != 1 && channel_no != 2 {
-            panic!(
-                "channel_no out of bound, expected 1 or 2, got: {}",
-                channel_no
-            );
+    /// The screen spans 4 divisions either way of the channel's zero level.
+    fn trigger_level_adjustment_of(
+        &self,
+        channel_no: usize,
+    ) -> Result<Adjustment, Hantek2D42Error> {
+        self.check_channel_no(channel_no)?;
+        match self.config.channel(channel_no).and_then(|it| it.scale()) {
+            Some(scale) => {
+                let scale = scale.raw_value();
+                Ok(Adjustment::new(4.0 * scale, -4.0 * scale))
+            }
+            None => Err(Hantek2D42Error::ChannelScaleUnknown { channel_no }),
+        }
+    }
+
+    fn check_setting(&self, setting: &Setting) -> Result<(), Hantek2D42Error> {
+        match setting {
+            Setting::TimeScale(_)
+            | Setting::TriggerSlope(_)
+            | Setting::TriggerMode(_)
+            | Setting::AwgType(_) => Ok(()),
+            Setting::ChannelEnabled(channel_no, _)
+            | Setting::ChannelBandwidthLimit(channel_no, _)
+            | Setting::ChannelScale(channel_no, _)
+            | Setting::ChannelCoupling(channel_no, _)
+            | Setting::ChannelProbe(channel_no, _)
+            | Setting::TriggerSource(channel_no) => self.check_channel_no(*channel_no),
+            Setting::ChannelOffset(channel_no, offset) => {
+                self.check_channel_no(*channel_no)?;
+                check_raw_level("channel offset", *offset)
+            }
+            Setting::TriggerLevel(level) => check_raw_level("trigger level", *level),
+            // The upper limit depends on the waveform, maybe set in the same batch.
+            Setting::AwgFrequency(frequency) => {
+                check_finite("awg frequency", *frequency)?;
+                if *frequency < AWG_FREQUENCY_MIN {
+                    return Err(invalid(
+                        "awg frequency",
+                        frequency,
+                        format!("{}.. Hz", AWG_FREQUENCY_MIN),
+                    ));
+                }
+                Ok(())
+            }
+            Setting::AwgAmplitude(amplitude) => {
+                check_awg_volts("awg amplitude", *amplitude, AWG_AMPLITUDE_MAX)
+            }
+            Setting::AwgOffset(offset) => check_awg_volts("awg offset", *offset, AWG_OFFSET_MAX),
+        }
+    }
+
+    /// Number of distinct channels of a capture, each checked.
+    fn num_capture_channels(&self, channels: &[usize]) -> Result<usize, Hantek2D42Error> {
+        for channel_no in channels {
+            self.check_channel_no(*channel_no)?;
+        }
+
+        let num_channels = {
+            let ch1 = if channels.contains(&1) { 1 } else { 0 };
+            let ch2 = if channels.contains(&2) { 1 } else { 0 };
+            ch1 + ch2
+        };
+
+        if num_channels == 0 {
+            return Err(invalid("channels", "none", "at least one channel"));
+        }
+        Ok(num_channels)
+    }
+
+    /// Settings of a channel whose number was checked, for updating them.
+    fn channel_mut(&mut self, channel_no: usize) -> &mut ChannelConfig {
+        self.config
+            .channel_mut(channel_no)
+            .expect("channel numbers are checked first")
+    }
+
+    fn check_channel_no(&self, channel_no: usize) -> Result<(), Hantek2D42Error> {
+        if (1..=NUM_CHANNELS).contains(&channel_no) {
+            Ok(())
+        } else {
+            Err(invalid(
+                "channel_no",
+                channel_no,
+                format!("1..={}", NUM_CHANNELS),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    /// Answers reads from a stream of samples, capping each read at the next scripted length
+    /// once there is one, and keeps every command written. Cancels the handle after the given
+    /// number of reads, if any.
+    struct MockTransport {
+        samples: Vec<u8>,
+        position: usize,
+        read_lengths: VecDeque<usize>,
+        written: Vec<RawCommand>,
+        reads: usize,
+        handle: CaptureHandle,
+        cancel_after: Option<usize>,
+    }
+
+    impl MockTransport {
+        fn new(total: usize, read_lengths: &[usize]) -> Self {
+            Self {
+                samples: (0..total).map(|it| (it % 251) as u8).collect(),
+                position: 0,
+                read_lengths: read_lengths.iter().copied().collect(),
+                written: vec![],
+                reads: 0,
+                handle: CaptureHandle::new(),
+                cancel_after: None,
+            }
+        }
+    }
+
+    impl Transport for MockTransport {
+        fn write(&mut self, endpoint: u8, buf: &[u8]) -> Result<usize, HantekUsbError> {
+            assert_eq!(endpoint, WRITE_ENDPOINT);
+            self.written.push(RawCommand::try_from(buf).unwrap());
+            Ok(buf.len())
+        }
+
+        fn read(&mut self, endpoint: u8, buf: &mut [u8]) -> Result<usize, HantekUsbError> {
+            assert_eq!(endpoint, READ_ENDPOINT);
+            assert!(buf.len() <= CAPTURE_PACKET);
+            self.reads += 1;
+            let available = self.samples.len() - self.position;
+            let length = match self.read_lengths.pop_front() {
+                Some(limit) => buf.len().min(limit),
+                None => buf.len(),
+            }
+            .min(available);
+            buf[..length].copy_from_slice(&self.samples[self.position..self.position + length]);
+            self.position += length;
+            if self.cancel_after == Some(self.reads) {
+                self.handle.cancel();
+            }
+            Ok(length)
+        }
+    }
+
+    fn capture(
+        usb: &mut MockTransport,
+        num_channels: usize,
+        num_samples: usize,
+    ) -> Result<Vec<u8>, Hantek2D42Error> {
+        let cmd = [0; 10];
+        let handle = usb.handle.clone();
+        let mut buffer = vec![0; num_samples * num_channels];
+        read_capture(
+            usb,
+            &mut Metrics::default(),
+            &cmd,
+            &mut buffer,
+            CAPTURE_PACKET,
+            &handle,
+        )?;
+        Ok(buffer)
+    }
+
+    #[test]
+    fn one_channel() {
+        let mut usb = MockTransport::new(1000, &[]);
+        let buffer = capture(&mut usb, 1, 1000).unwrap();
+        assert_eq!(buffer, usb.samples);
+        assert_eq!(usb.reads, 16);
+        assert_eq!(usb.written.len(), usb.reads);
+    }
+
+    #[test]
+    fn two_channels() {
+        let mut usb = MockTransport::new(2000, &[]);
+        let buffer = capture(&mut usb, 2, 1000).unwrap();
+        assert_eq!(buffer.len(), 2000);
+        assert_eq!(buffer, usb.samples);
+        assert_eq!(usb.reads, 32);
+        assert_eq!(usb.handle.progress(), (2000, 2000));
+    }
+
+    #[test]
+    fn short_reads() {
+        let mut usb = MockTransport::new(2 * 100, &[10, 64, 1, 33, 7, 64, 5]);
+        let buffer = capture(&mut usb, 2, 100).unwrap();
+        assert_eq!(buffer, usb.samples);
+    }
+
+    #[test]
+    fn short_reads_are_counted() {
+        let mut usb = MockTransport::new(2 * 100, &[10, 64, 1, 33, 7, 64, 5]);
+        let handle = usb.handle.clone();
+        let mut metrics = Metrics::default();
+        let mut buffer = vec![0; 2 * 100];
+        read_capture(
+            &mut usb,
+            &mut metrics,
+            &[0; 10],
+            &mut buffer,
+            CAPTURE_PACKET,
+            &handle,
+        )
+        .unwrap();
+        // The last one is asked for the 21 bytes left.
+        assert_eq!(metrics.short_reads, 5);
+    }
+
+    #[test]
+    fn empty_reads_are_retried() {
+        let empty = vec![0; CAPTURE_MAX_EMPTY_READS - 1];
+        let lengths = [&[64, 20][..], &empty, &[64], &empty].concat();
+        let mut usb = MockTransport::new(200, &lengths);
+        let buffer = capture(&mut usb, 1, 200).unwrap();
+        assert_eq!(buffer, usb.samples);
+    }
+
+    #[test]
+    fn device_going_silent_fails() {
+        let mut usb = MockTransport::new(100, &[]);
+        match capture(&mut usb, 2, 100) {
+            Err(Hantek2D42Error::IncompleteCapture { expected, received }) => {
+                assert_eq!(expected, 200);
+                assert_eq!(received, 100);
+            }
+            other => panic!("expected an incomplete capture, got {:?}", other),
+        }
+        assert_eq!(usb.reads, 2 + CAPTURE_MAX_EMPTY_READS);
+    }
+
+    #[test]
+    fn cancelled_between_packets() {
+        let mut usb = MockTransport::new(2000, &[]);
+        usb.cancel_after = Some(3);
+        match capture(&mut usb, 2, 1000) {
+            Err(Hantek2D42Error::CaptureCancelled { expected, received }) => {
+                assert_eq!(expected, 2000);
+                assert_eq!(received, 3 * CAPTURE_PACKET);
+            }
+            other => panic!("expected a cancelled capture, got {:?}", other),
+        }
+        assert_eq!(usb.reads, 3);
+        assert_eq!(usb.handle.progress(), (3 * CAPTURE_PACKET, 2000));
+
+        // Stays cancelled.
+        assert!(capture(&mut usb, 2, 1000).is_err());
+        assert_eq!(usb.reads, 3);
+    }
+
+    type Setter = fn(&mut Hantek2D42<'static, MockTransport>) -> Result<(), Hantek2D42Error>;
+
+    /// Bytes each setter writes for representative values, in the format of [`DecodedCommand`],
+    /// fixed so a change to the framing or to a code doesn't go unnoticed. The codes are those
+    /// of the C implementation this is a port of. Run in order on a single device, a setter may
+    /// need a setting made further up, e.g. the scale of a channel for a level in volts.
+    const GOLDEN: &[(&str, Setter, &str)] = &[
+        (
+            "function scope",
+            |it| it.set_device_function(DeviceFunction::Scope),
+            "00 0a 03 00 00 00 00 00 00 00",
+        ),
+        (
+            "function dmm",
+            |it| it.set_device_function(DeviceFunction::DMM),
+            "00 0a 03 00 00 01 00 00 00 00",
+        ),
+        (
+            "function awg",
+            |it| it.set_device_function(DeviceFunction::AWG),
+            "00 0a 03 00 00 02 00 00 00 00",
+        ),
+        ("start", |it| it.start(), "00 0a 00 00 0c 01 00 00 00 00"),
+        ("stop", |it| it.stop(), "00 0a 00 00 0c 00 00 00 00 00"),
+        (
+            "enable 1",
+            |it| it.enable_channel(1),
+            "00 0a 00 00 00 01 00 00 00 00",
+        ),
+        (
+            "enable 2",
+            |it| it.enable_channel(2),
+            "00 0a 00 00 06 01 00 00 00 00",
+        ),
+        (
+            "disable 2",
+            |it| it.disable_channel(2),
+            "00 0a 00 00 06 00 00 00 00 00",
+        ),
+        (
+            "coupling 1 ac",
+            |it| it.set_channel_coupling(1, Coupling::AC),
+            "00 0a 00 00 01 00 00 00 00 00",
+        ),
+        (
+            "coupling 1 dc",
+            |it| it.set_channel_coupling(1, Coupling::DC),
+            "00 0a 00 00 01 01 00 00 00 00",
+        ),
+        (
+            "coupling 2 gnd",
+            |it| it.set_channel_coupling(2, Coupling::GND),
+            "00 0a 00 00 07 02 00 00 00 00",
+        ),
+        (
+            "probe 1 x1",
+            |it| it.set_channel_probe(1, Probe::X1),
+            "00 0a 00 00 02 00 00 00 00 00",
+        ),
+        (
+            "probe 1 x10",
+            |it| it.set_channel_probe(1, Probe::X10),
+            "00 0a 00 00 02 01 00 00 00 00",
+        ),
+        (
+            "probe 2 x100",
+            |it| it.set_channel_probe(2, Probe::X100),
+            "00 0a 00 00 08 02 00 00 00 00",
+        ),
+        (
+            "probe 2 x1000",
+            |it| it.set_channel_probe(2, Probe::X1000),
+            "00 0a 00 00 08 03 00 00 00 00",
+        ),
+        (
+            "bw limit 1 on",
+            |it| it.channel_enable_bandwidth_limit(1),
+            "00 0a 00 00 03 01 00 00 00 00",
+        ),
+        (
+            "bw limit 2 off",
+            |it| it.channel_disable_bandwidth_limit(2),
+            "00 0a 00 00 09 00 00 00 00 00",
+        ),
+        (
+            "scale 1 10mV",
+            |it| it.set_channel_scale(1, Scale::mv10),
+            "00 0a 00 00 04 00 00 00 00 00",
+        ),
+        (
+            "scale 2 10V",
+            |it| it.set_channel_scale(2, Scale::v10),
+            "00 0a 00 00 0a 09 00 00 00 00",
+        ),
+        (
+            "scale 1 1V",
+            |it| it.set_channel_scale(1, Scale::v1),
+            "00 0a 00 00 04 06 00 00 00 00",
+        ),
+        (
+            "offset 1 raw",
+            |it| it.set_channel_offset(1, 100),
+            "00 0a 00 00 05 64 00 00 00 00",
+        ),
+        (
+            "offset 2 raw",
+            |it| it.set_channel_offset(2, 0),
+            "00 0a 00 00 0b 00 00 00 00 00",
+        ),
+        (
+            "offset 1 -2V",
+            |it| it.set_channel_offset_with_auto_adjustment(1, -2.0),
+            "00 0a 00 00 05 32 00 00 00 00",
+        ),
+        (
+            "offset 1 4V",
+            |it| it.set_channel_offset_with_auto_adjustment(1, 4.0),
+            "00 0a 00 00 05 c8 00 00 00 00",
+        ),
+        (
+            "time scale 5ns",
+            |it| it.set_time_scale(TimeScale::ns5),
+            "00 0a 00 00 0e 00 00 00 00 00",
+        ),
+        (
+            "time scale 500s",
+            |it| it.set_time_scale(TimeScale::s500),
+            "00 0a 00 00 0e 21 00 00 00 00",
+        ),
+        (
+            "time scale 100us",
+            |it| it.set_time_scale(TimeScale::us100),
+            "00 0a 00 00 0e 0d 00 00 00 00",
+        ),
+        (
+            "time offset raw",
+            |it| it.set_time_offset(300),
+            "00 0a 00 00 0f 2c 01 00 00 00",
+        ),
+        (
+            "trigger position",
+            |it| it.set_trigger_position(50.0),
+            "00 0a 00 00 0f 96 00 00 00 00",
+        ),
+        (
+            "time offset 0s",
+            |it| it.set_time_offset_with_auto_adjustment(0.0),
+            "00 0a 00 00 0f 96 00 00 00 00",
+        ),
+        (
+            "trigger source 2",
+            |it| it.set_trigger_source(2),
+            "00 0a 00 00 10 01 00 00 00 00",
+        ),
+        (
+            "trigger source 1",
+            |it| it.set_trigger_source(1),
+            "00 0a 00 00 10 00 00 00 00 00",
+        ),
+        (
+            "slope rising",
+            |it| it.set_trigger_slope(TriggerSlope::Rising),
+            "00 0a 00 00 11 00 00 00 00 00",
+        ),
+        (
+            "slope falling",
+            |it| it.set_trigger_slope(TriggerSlope::Falling),
+            "00 0a 00 00 11 01 00 00 00 00",
+        ),
+        (
+            "slope both",
+            |it| it.set_trigger_slope(TriggerSlope::Both),
+            "00 0a 00 00 11 02 00 00 00 00",
+        ),
+        (
+            "mode auto",
+            |it| it.set_trigger_mode(TriggerMode::Auto),
+            "00 0a 00 00 12 00 00 00 00 00",
+        ),
+        (
+            "mode normal",
+            |it| it.set_trigger_mode(TriggerMode::Normal),
+            "00 0a 00 00 12 01 00 00 00 00",
+        ),
+        (
+            "mode single",
+            |it| it.set_trigger_mode(TriggerMode::Single),
+            "00 0a 00 00 12 02 00 00 00 00",
+        ),
+        (
+            "trigger level raw",
+            |it| it.set_trigger_level(200),
+            "00 0a 00 00 14 c8 00 00 00 00",
+        ),
+        (
+            "trigger level %",
+            |it| it.set_trigger_level_percent(50.0),
+            "00 0a 00 00 14 64 00 00 00 00",
+        ),
+        (
+            "trigger level 1V",
+            |it| it.set_trigger_level_volts(1.0, Some(1)),
+            "00 0a 00 00 14 7d 00 00 00 00",
+        ),
+        (
+            "trigger level 0V",
+            |it| it.set_trigger_level_with_auto_adjustment(0.0),
+            "00 0a 00 00 14 64 00 00 00 00",
+        ),
+        (
+            "awg square",
+            |it| it.set_awg_type(AwgType::Square),
+            "00 0a 02 00 00 00 00 00 00 00",
+        ),
+        (
+            "awg ramp",
+            |it| it.set_awg_type(AwgType::Ramp),
+            "00 0a 02 00 00 01 00 00 00 00",
+        ),
+        (
+            "awg trap",
+            |it| it.set_awg_type(AwgType::Trap),
+            "00 0a 02 00 00 03 00 00 00 00",
+        ),
+        (
+            "awg arb1",
+            |it| it.set_awg_type(AwgType::Arb1),
+            "00 0a 02 00 00 04 00 00 00 00",
+        ),
+        (
+            "awg arb4",
+            |it| it.set_awg_type(AwgType::Arb4),
+            "00 0a 02 00 00 07 00 00 00 00",
+        ),
+        (
+            "awg sin",
+            |it| it.set_awg_type(AwgType::Sin),
+            "00 0a 02 00 00 02 00 00 00 00",
+        ),
+        (
+            "awg 1kHz",
+            |it| it.set_awg_frequency(1000.0),
+            "00 0a 02 00 01 e8 03 00 00 00",
+        ),
+        (
+            "awg 25MHz",
+            |it| it.set_awg_frequency(25_000_000.0),
+            "00 0a 02 00 01 40 78 7d 01 00",
+        ),
+        (
+            "amplitude 2.5V",
+            |it| it.set_awg_amplitude(2.5),
+            "00 0a 02 00 02 c4 09 00 00 00",
+        ),
+        (
+            "amplitude -1.5V",
+            |it| it.set_awg_amplitude(-1.5),
+            "00 0a 02 00 02 dc 05 01 00 00",
+        ),
+        (
+            "awg offset -0.25V",
+            |it| it.set_awg_offset(-0.25),
+            "00 0a 02 00 03 fa 00 01 00 00",
+        ),
+        (
+            "awg offset 0V",
+            |it| it.set_awg_offset(0.0),
+            "00 0a 02 00 03 00 00 00 00 00",
+        ),
+        (
+            "square duty",
+            |it| it.set_awg_duty_square(25.0),
+            "00 0a 02 00 04 c4 09 00 00 00",
+        ),
+        (
+            "ramp duty",
+            |it| it.set_awg_duty_ramp(75.0),
+            "00 0a 02 00 05 4c 1d 00 00 00",
+        ),
+        (
+            "trap duty",
+            |it| it.set_awg_duty_trap(0.3, 0.2, 0.1),
+            "00 0a 02 00 06 0a 1e 14 00 00",
+        ),
+        (
+            "awg start",
+            |it| it.awg_start(),
+            "00 0a 02 00 08 01 00 00 00 00",
+        ),
+        (
+            "awg stop",
+            |it| it.awg_stop(),
+            "00 0a 02 00 08 00 00 00 00 00",
+        ),
+        (
+            "capture 1 channel",
+            |it| it.capture(&[1], 64).map(drop),
+            "00 0a 00 01 16 20 00 20 00 00",
+        ),
+        (
+            "capture 2 channels",
+            |it| it.capture(&[1, 2], 100).map(drop),
+            "00 0a 00 01 16 64 00 64 00 00",
+        ),
+    ];
+
+    /// Names of the commands written, in order.
+    fn sent(usb: &MockTransport) -> Vec<&'static str> {
+        usb.written
+            .iter()
+            .map(|it| DecodedCommand::from(*it).cmd_name.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn keep_alive_changes_nothing() {
+        let usb = MockTransport::new(0, &[]);
+        let mut hantek = Hantek2D42::with_transport(usb, HantekConfig::new(NUM_CHANNELS));
+        assert!(!hantek.keep_alive().unwrap());
+
+        hantek.set_device_function(DeviceFunction::Scope).unwrap();
+        hantek.start().unwrap();
+        hantek.usb.written.clear();
+        let events = hantek.events().subscribe();
+        assert!(hantek.keep_alive().unwrap());
+        assert_eq!(sent(&hantek.usb), ["START_STOP"]);
+        assert!(events.try_recv().is_err());
+
+        // Start would re-arm a single capture.
+        hantek.config.trigger_mode = Some(TriggerMode::Single);
+        hantek.usb.written.clear();
+        assert!(hantek.keep_alive().unwrap());
+        assert_eq!(sent(&hantek.usb), ["FUNCTION"]);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn apply_config_order() {
+        let usb = MockTransport::new(0, &[]);
+        let mut hantek = Hantek2D42::with_transport(usb, HantekConfig::new(NUM_CHANNELS));
+        hantek.set_awg_frequency(1000.0).unwrap();
+        hantek.usb.written.clear();
+
+        let mut config = HantekConfig::new(NUM_CHANNELS);
+        config.device_function = Some(DeviceFunction::Scope);
+        config.channels[0].offset = Some(10.0);
+        config.channels[0].scale = Some(Scale::v1);
+        config.trigger_level = Some(20.0);
+        config.trigger_source_channel = Some(1);
+        config.awg_type = Some(AwgType::Square);
+        config.awg_frequency = Some(100.0);
+        hantek.apply_config(&config).unwrap();
+        assert_eq!(
+            sent(&hantek.usb),
+            [
+                "FUNCTION",
+                "SCALE_CH1",
+                "OFFSET_CH1",
+                "TRIGGER_SOURCE",
+                "TRIGGER_LEVEL",
+                "FREQ",
+                "TYPE"
+            ]
+        );
+
+        // Raised after the type instead.
+        hantek.usb.written.clear();
+        let mut config = HantekConfig::new(NUM_CHANNELS);
+        config.awg_type = Some(AwgType::Sin);
+        config.awg_frequency = Some(10000.0);
+        hantek.apply_config(&config).unwrap();
+        assert_eq!(sent(&hantek.usb), ["TYPE", "FREQ"]);
+    }
+
+    #[test]
+    fn apply_config_error_without_failures() {
+        let error = ApplyConfigError {
+            failed: vec![],
+            not_sent: vec!["awg.type".to_string()],
+        };
+        assert_eq!(
+            error.to_string(),
+            "0 settings of the config failed and 1 weren't sent"
+        );
+    }
+
+    #[test]
+    fn config_channel_numbers() {
+        let mut config = HantekConfig::new(NUM_CHANNELS);
+        assert!(config.channel(0).is_none());
+        assert!(config.channel(NUM_CHANNELS + 1).is_none());
+        assert!(config.channel_mut(0).is_none());
+
+        config.channel_mut(NUM_CHANNELS + 1).unwrap().enabled = Some(true);
+        assert_eq!(config.num_channels(), NUM_CHANNELS + 1);
+        assert_eq!(
+            config.channel(NUM_CHANNELS),
+            Some(&ChannelConfig::default())
+        );
+
+        let mut hantek =
+            Hantek2D42::with_transport(MockTransport::new(0, &[]), HantekConfig::new(NUM_CHANNELS));
+        assert!(hantek.enable_channel(0).is_err());
+        assert!(hantek.enable_channel(NUM_CHANNELS + 1).is_err());
+    }
+
+    fn hex(command: &str) -> RawCommand {
+        let bytes: Vec<u8> = command
+            .split(' ')
+            .map(|it| u8::from_str_radix(it, 16).unwrap())
+            .collect();
+        RawCommand::try_from(bytes.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn golden_bytes() {
+        let usb = MockTransport::new(64 + 2 * 100, &[]);
+        let mut hantek = Hantek2D42::with_transport(usb, HantekConfig::new(NUM_CHANNELS));
+        for (name, setter, expected) in GOLDEN {
+            hantek.usb.written.clear();
+            if let Err(e) = setter(&mut hantek) {
+                panic!("{} failed: {}", name, e);
+            }
+            let expected = hex(expected);
+            // A capture sends its command again for every packet.
+            assert!(!hantek.usb.written.is_empty(), "{} wrote nothing", name);
+            for written in &hantek.usb.written {
+                assert_eq!(
+                    *written,
+                    expected,
+                    "{}: sent {}, expected {}",
+                    name,
+                    DecodedCommand::from(*written),
+                    DecodedCommand::from(expected)
+                );
+            }
         }
     }
 }
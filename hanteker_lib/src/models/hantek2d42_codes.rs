@@ -8,6 +8,14 @@ pub(crate) const FUNC_SCOPE_CAPTURE: u16 = 0x0100;
 pub(crate) const FUNC_AWG_SETTING: u16 = 0x0002;
 pub(crate) const FUNC_SCREEN_SETTING: u16 = 0x0003;
 
+// No function reading back the LCD framebuffer is known, the vendor software never asks for one
+// and FUNC_SCREEN_SETTING only switches between scope, AWG and DMM. A screenshot has to be
+// rendered from a capture instead.
+
+// Nor is any function reporting the firmware or hardware version, or a serial number other than
+// the one of the USB descriptor. The descriptor's release number is all there is to tell
+// firmwares apart, see HantekUsbDevice::device_release.
+
 pub(crate) const SCOPE_ENABLE_CH1: u8 = 0x00;
 pub(crate) const SCOPE_COUPLING_CH1: u8 = 0x01;
 pub(crate) const SCOPE_PROBE_X_CH1: u8 = 0x02;
@@ -32,8 +40,14 @@ pub(crate) const SCOPE_TRIGGER_SLOPE: u8 = 0x11;
 pub(crate) const SCOPE_TRIGGER_MODE: u8 = 0x12;
 pub(crate) const SCOPE_TRIGGER_LEVEL: u8 = 0x14;
 
+// No trigger holdoff or trigger coupling (HF / noise reject) command is known. The front panel
+// of the device offers neither, and the unassigned codes around the trigger ones, 0x0D and
+// 0x15, haven't been seen in any capture of the vendor software. They're left out rather than
+// guessed, an unknown code may well change some other setting. Blocked until a USB capture of
+// the vendor software setting them turns up; `set_trigger_holdoff` and `set_trigger_coupling`
+// would go next to `set_trigger_mode` then.
+
 // TODO how to send this to device?
-#[allow(dead_code)]
 pub(crate) const SCOPE_AUTO_SETTING: u8 = 0x13;
 
 pub(crate) const SCOPE_START_RECV: u8 = 0x16;
@@ -122,3 +136,79 @@ pub(crate) const AWG_VAL_TYPE_ARB4: u8 = 0x07;
 pub(crate) const SCREEN_VAL_SCOPE: u8 = 0x00;
 pub(crate) const SCREEN_VAL_DMM: u8 = 0x01;
 pub(crate) const SCREEN_VAL_AWG: u8 = 0x02;
+
+/// Name of a function code, for tracing what's sent.
+pub(crate) fn func_name(func: u16) -> Option<&'static str> {
+    Some(match func {
+        FUNC_SCOPE_SETTING => "SCOPE_SETTING",
+        FUNC_SCOPE_CAPTURE => "SCOPE_CAPTURE",
+        FUNC_AWG_SETTING => "AWG_SETTING",
+        FUNC_SCREEN_SETTING => "SCREEN_SETTING",
+        _ => return None,
+    })
+}
+
+/// Name of a command code within its function, for tracing what's sent.
+pub(crate) fn cmd_name(func: u16, cmd: u8) -> Option<&'static str> {
+    Some(match (func, cmd) {
+        (FUNC_SCOPE_SETTING, SCOPE_ENABLE_CH1) => "ENABLE_CH1",
+        (FUNC_SCOPE_SETTING, SCOPE_COUPLING_CH1) => "COUPLING_CH1",
+        (FUNC_SCOPE_SETTING, SCOPE_PROBE_X_CH1) => "PROBE_X_CH1",
+        (FUNC_SCOPE_SETTING, SCOPE_BW_LIMIT_CH1) => "BW_LIMIT_CH1",
+        (FUNC_SCOPE_SETTING, SCOPE_SCALE_CH1) => "SCALE_CH1",
+        (FUNC_SCOPE_SETTING, SCOPE_OFFSET_CH1) => "OFFSET_CH1",
+        (FUNC_SCOPE_SETTING, SCOPE_ENABLE_CH2) => "ENABLE_CH2",
+        (FUNC_SCOPE_SETTING, SCOPE_COUPLING_CH2) => "COUPLING_CH2",
+        (FUNC_SCOPE_SETTING, SCOPE_PROBE_X_CH2) => "PROBE_X_CH2",
+        (FUNC_SCOPE_SETTING, SCOPE_BW_LIMIT_CH2) => "BW_LIMIT_CH2",
+        (FUNC_SCOPE_SETTING, SCOPE_SCALE_CH2) => "SCALE_CH2",
+        (FUNC_SCOPE_SETTING, SCOPE_OFFSET_CH2) => "OFFSET_CH2",
+        (FUNC_SCOPE_SETTING, SCOPE_START_STOP) => "START_STOP",
+        (FUNC_SCOPE_SETTING, SCOPE_SCALE_TIME) => "SCALE_TIME",
+        (FUNC_SCOPE_SETTING, SCOPE_OFFSET_TIME) => "OFFSET_TIME",
+        (FUNC_SCOPE_SETTING, SCOPE_TRIGGER_SOURCE) => "TRIGGER_SOURCE",
+        (FUNC_SCOPE_SETTING, SCOPE_TRIGGER_SLOPE) => "TRIGGER_SLOPE",
+        (FUNC_SCOPE_SETTING, SCOPE_TRIGGER_MODE) => "TRIGGER_MODE",
+        (FUNC_SCOPE_SETTING, SCOPE_AUTO_SETTING) => "AUTO_SETTING",
+        (FUNC_SCOPE_SETTING, SCOPE_TRIGGER_LEVEL) => "TRIGGER_LEVEL",
+        (FUNC_SCOPE_CAPTURE, SCOPE_START_RECV) => "START_RECV",
+        (FUNC_AWG_SETTING, AWG_TYPE) => "TYPE",
+        (FUNC_AWG_SETTING, AWG_FREQ) => "FREQ",
+        (FUNC_AWG_SETTING, AWG_AMPLITUDE) => "AMPLITUDE",
+        (FUNC_AWG_SETTING, AWG_OFFSET) => "OFFSET",
+        (FUNC_AWG_SETTING, AWG_SQUARE_DUTY) => "SQUARE_DUTY",
+        (FUNC_AWG_SETTING, AWG_RAMP_DUTY) => "RAMP_DUTY",
+        (FUNC_AWG_SETTING, AWG_TRAP_DUTY) => "TRAP_DUTY",
+        (FUNC_AWG_SETTING, AWG_START_STOP) => "START_STOP",
+        (FUNC_SCREEN_SETTING, 0) => "FUNCTION",
+        _ => return None,
+    })
+}
+
+/// Name of the first value byte of a command taking one of a set of values, for tracing what's
+/// sent. The values of each set are numbered from 0 up, in the order listed.
+pub(crate) fn val_name(func: u16, cmd: u8, val: u8) -> Option<&'static str> {
+    let names: &[&'static str] = match (func, cmd) {
+        (FUNC_SCOPE_SETTING, SCOPE_COUPLING_CH1 | SCOPE_COUPLING_CH2) => &["AC", "DC", "GND"],
+        (FUNC_SCOPE_SETTING, SCOPE_PROBE_X_CH1 | SCOPE_PROBE_X_CH2) => {
+            &["X1", "X10", "X100", "X1000"]
+        }
+        (FUNC_SCOPE_SETTING, SCOPE_SCALE_CH1 | SCOPE_SCALE_CH2) => &[
+            "10mV", "20mV", "50mV", "100mV", "200mV", "500mV", "1V", "2V", "5V", "10V",
+        ],
+        (FUNC_SCOPE_SETTING, SCOPE_SCALE_TIME) => &[
+            "5ns", "10ns", "20ns", "50ns", "100ns", "200ns", "500ns", "1us", "2us", "5us", "10us",
+            "20us", "50us", "100us", "200us", "500us", "1ms", "2ms", "5ms", "10ms", "20ms", "50ms",
+            "100ms", "200ms", "500ms", "1s", "2s", "5s", "10s", "20s", "50s", "100s", "200s",
+            "500s",
+        ],
+        (FUNC_SCOPE_SETTING, SCOPE_TRIGGER_SLOPE) => &["RISING", "FALLING", "BOTH"],
+        (FUNC_SCOPE_SETTING, SCOPE_TRIGGER_MODE) => &["AUTO", "NORMAL", "SINGLE"],
+        (FUNC_AWG_SETTING, AWG_TYPE) => &[
+            "SQUARE", "RAMP", "SIN", "TRAP", "ARB1", "ARB2", "ARB3", "ARB4",
+        ],
+        (FUNC_SCREEN_SETTING, 0) => &["SCOPE", "DMM", "AWG"],
+        _ => return None,
+    };
+    names.get(val as usize).copied()
+}
@@ -1,2 +1,3 @@
 pub mod hantek2d42;
 pub(crate) mod hantek2d42_codes;
+pub mod simulated;
@@ -0,0 +1,604 @@
+//! A 2D42 living in memory only, for running the GUI, a server or integration tests with no
+//! hardware around.
+//!
+//! [`SimulatedHantek2D42`] is a [`Transport`] taking the same commands as the device. It keeps
+//! the settings they make, and answers capture commands with samples of a synthetic signal on
+//! each channel: scaled by the channel's scale, clipped where its offset moves the signal off
+//! the screen, and triggered as the trigger settings say. Every channel sees the AWG's output by
+//! default, as if wired to it, see [`SimulatedSignal`].
+//!
+//! Probe and bandwidth limit are taken but don't change the samples. The real device is asked
+//! for a number of samples only, which channels it sends is up to the channels enabled, the
+//! simulation sends the enabled ones as well, channel 1 first.
+
+use std::collections::VecDeque;
+use std::f64::consts::TAU;
+
+use log::debug;
+
+use crate::capture::{COUNTS_PER_DIVISION, SAMPLES_PER_DIVISION};
+use crate::device::cfg::{
+    AwgType, Coupling, DeviceFunction, HantekConfig, Probe, RunningStatus, Scale, TimeScale,
+    TrapDuty, TriggerMode, TriggerSlope,
+};
+use crate::device::cmd::RawCommand;
+use crate::device::usb::{HantekUsbError, Transport};
+use crate::models::hantek2d42::{
+    DecodedCommand, Hantek2D42, NUM_CHANNELS, RAW_LEVEL_MAX, READ_ENDPOINT, TIME_OFFSET_SCREEN,
+    WRITE_ENDPOINT,
+};
+use crate::models::hantek2d42_codes::*;
+
+/// Steps a period of the trigger source is searched for the trigger in.
+const TRIGGER_SEARCH_STEPS: usize = 512;
+
+/// Peak noise added to each sample by default, in ADC counts.
+const DEFAULT_NOISE: f32 = 1.0;
+
+/// What a channel's probe is connected to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimulatedSignal {
+    /// The AWG's output, as its settings say. Nothing while it's stopped. The amplitude is taken
+    /// as the peak, half the peak to peak. Arbitrary waveforms are whatever was loaded into the
+    /// device, they come out as a sine.
+    Awg,
+    /// A constant level, in volts.
+    Dc(f32),
+    /// In Hz and volts, the amplitude being the peak.
+    Sine {
+        frequency: f32,
+        amplitude: f32,
+        offset: f32,
+    },
+}
+
+pub struct SimulatedHantek2D42 {
+    state: HantekConfig,
+    inputs: Vec<SimulatedSignal>,
+    noise: f32,
+    rng: u64,
+    /// Seconds since the simulation started at which the next capture may begin.
+    time: f64,
+    /// Last capture taken, sent again while the scope is stopped or waiting for a trigger.
+    held: Vec<u8>,
+    /// Rest of the capture being read.
+    pending: VecDeque<u8>,
+    commands: Vec<DecodedCommand>,
+}
+
+impl SimulatedHantek2D42 {
+    /// Scope showing channel 1 at 1V and 200us a division, triggering on its rising edges
+    /// through the center of the screen. The AWG is set to a 1kHz sine of 1V but stopped.
+    pub fn new() -> Self {
+        let mut state = HantekConfig::new(NUM_CHANNELS);
+        state.device_function = Some(DeviceFunction::Scope);
+        for channel_no in 1..=NUM_CHANNELS {
+            let channel = state
+                .channel_mut(channel_no)
+                .expect("channels are numbered from 1");
+            channel.enabled = Some(channel_no == 1);
+            channel.coupling = Some(Coupling::DC);
+            channel.probe = Some(Probe::X1);
+            channel.scale = Some(Scale::v1);
+            channel.offset = Some((RAW_LEVEL_MAX / 2) as f32);
+            channel.bandwidth_limit = Some(false);
+        }
+        state.time_scale = Some(TimeScale::us200);
+        state.time_offset = Some((TIME_OFFSET_SCREEN / 2) as f32);
+        state.running_status = Some(RunningStatus::Start);
+        state.trigger_source_channel = Some(1);
+        state.trigger_slope = Some(TriggerSlope::Rising);
+        state.trigger_mode = Some(TriggerMode::Auto);
+        state.trigger_level = Some((RAW_LEVEL_MAX / 2) as f32);
+        state.awg_type = Some(AwgType::Sin);
+        state.awg_frequency = Some(1000.0);
+        state.awg_amplitude = Some(1.0);
+        state.awg_offset = Some(0.0);
+        state.awg_duty_square = Some(50.0);
+        state.awg_duty_ramp = Some(50.0);
+        state.awg_duty_trap = Some(TrapDuty {
+            high: 0.25,
+            low: 0.25,
+            rise: 0.25,
+        });
+        state.awg_running_status = Some(RunningStatus::Stop);
+
+        Self {
+            state,
+            inputs: vec![SimulatedSignal::Awg; NUM_CHANNELS],
+            noise: DEFAULT_NOISE,
+            rng: 0x2d42_2d42_2d42_2d42,
+            time: 0.0,
+            held: vec![],
+            pending: VecDeque::new(),
+            commands: vec![],
+        }
+    }
+
+    /// Settings as the device has them, from the commands it took. Unlike
+    /// [`Hantek2D42::get_config`] everything is known, offsets and levels are raw.
+    pub fn state(&self) -> &HantekConfig {
+        &self.state
+    }
+
+    /// Every command taken but the capture commands, oldest first.
+    pub fn commands(&self) -> &[DecodedCommand] {
+        &self.commands
+    }
+
+    /// Connects a channel, numbered from 1, to the signal. Panics if there's no such channel.
+    pub fn set_input(&mut self, channel_no: usize, signal: SimulatedSignal) {
+        self.inputs[channel_no - 1] = signal;
+    }
+
+    /// Peak noise added to each sample, in ADC counts, 25 to a division. Zero makes captures
+    /// exact, but a stopped or untriggered scope can then no longer be told from one capturing
+    /// the same signal again, see [`Hantek2D42::capture_segment`].
+    pub fn set_noise(&mut self, counts: f32) {
+        self.noise = counts.max(0.0);
+    }
+
+    fn take(&mut self, cmd: &RawCommand) {
+        let decoded = DecodedCommand::from(*cmd);
+        let val = decoded.val;
+        let val0 = val[0];
+        let val_u16 = u16::from_le_bytes([val[0], val[1]]);
+        let signed_millis = |it: u16| {
+            let sign = if val[2] == 0 { 1.0 } else { -1.0 };
+            sign * it as f32 / 1000.0
+        };
+
+        let state = &mut self.state;
+        match (decoded.func, decoded.cmd) {
+            (FUNC_SCOPE_CAPTURE, SCOPE_START_RECV) => {
+                let total = 2 * val_u16 as usize;
+                if self.pending.is_empty() {
+                    self.capture(total);
+                }
+                return;
+            }
+            (FUNC_SCREEN_SETTING, 0) => {
+                if let Some(it) = device_function_of(val0) {
+                    state.device_function = Some(it);
+                }
+            }
+            (FUNC_SCOPE_SETTING, SCOPE_START_STOP) => {
+                state.running_status = Some(if val0 == 0 {
+                    RunningStatus::Stop
+                } else {
+                    RunningStatus::Start
+                });
+            }
+            (FUNC_SCOPE_SETTING, SCOPE_SCALE_TIME) => {
+                if let Some(it) = TimeScale::my_iter().nth(val0 as usize) {
+                    state.time_scale = Some(it);
+                }
+            }
+            (FUNC_SCOPE_SETTING, SCOPE_OFFSET_TIME) => {
+                state.time_offset = Some(u32::from_le_bytes(val) as f32);
+            }
+            (FUNC_SCOPE_SETTING, SCOPE_TRIGGER_SOURCE) => {
+                if (val0 as usize) < NUM_CHANNELS {
+                    state.trigger_source_channel = Some(val0 as usize + 1);
+                }
+            }
+            (FUNC_SCOPE_SETTING, SCOPE_TRIGGER_SLOPE) => {
+                if let Some(it) = trigger_slope_of(val0) {
+                    state.trigger_slope = Some(it);
+                }
+            }
+            (FUNC_SCOPE_SETTING, SCOPE_TRIGGER_MODE) => {
+                if let Some(it) = trigger_mode_of(val0) {
+                    state.trigger_mode = Some(it);
+                }
+            }
+            (FUNC_SCOPE_SETTING, SCOPE_TRIGGER_LEVEL) if val0 <= RAW_LEVEL_MAX => {
+                state.trigger_level = Some(val0 as f32);
+            }
+            (FUNC_SCOPE_SETTING, cmd) if cmd <= SCOPE_OFFSET_CH2 => {
+                let channel_no = 1 + (cmd / (SCOPE_ENABLE_CH2 - SCOPE_ENABLE_CH1)) as usize;
+                let channel = state
+                    .channel_mut(channel_no)
+                    .expect("channels are numbered from 1");
+                match cmd % (SCOPE_ENABLE_CH2 - SCOPE_ENABLE_CH1) {
+                    SCOPE_ENABLE_CH1 => channel.enabled = Some(val0 != 0),
+                    SCOPE_COUPLING_CH1 => {
+                        if let Some(it) = coupling_of(val0) {
+                            channel.coupling = Some(it);
+                        }
+                    }
+                    SCOPE_PROBE_X_CH1 => {
+                        if let Some(it) = probe_of(val0) {
+                            channel.probe = Some(it);
+                        }
+                    }
+                    SCOPE_BW_LIMIT_CH1 => channel.bandwidth_limit = Some(val0 != 0),
+                    SCOPE_SCALE_CH1 => {
+                        if let Some(it) = Scale::my_iter().nth(val0 as usize) {
+                            channel.scale = Some(it);
+                        }
+                    }
+                    SCOPE_OFFSET_CH1 if val0 <= RAW_LEVEL_MAX => {
+                        channel.offset = Some(val0 as f32);
+                    }
+                    _ => {}
+                }
+            }
+            (FUNC_AWG_SETTING, AWG_TYPE) => {
+                if let Some(it) = awg_type_of(val0) {
+                    state.awg_type = Some(it);
+                }
+            }
+            (FUNC_AWG_SETTING, AWG_FREQ) => {
+                state.awg_frequency = Some(u32::from_le_bytes(val) as f32);
+            }
+            (FUNC_AWG_SETTING, AWG_AMPLITUDE) => {
+                state.awg_amplitude = Some(signed_millis(val_u16));
+            }
+            (FUNC_AWG_SETTING, AWG_OFFSET) => {
+                state.awg_offset = Some(signed_millis(val_u16));
+            }
+            (FUNC_AWG_SETTING, AWG_SQUARE_DUTY) => {
+                state.awg_duty_square = Some(val_u16 as f32 / 100.0);
+            }
+            (FUNC_AWG_SETTING, AWG_RAMP_DUTY) => {
+                state.awg_duty_ramp = Some(val_u16 as f32 / 100.0);
+            }
+            (FUNC_AWG_SETTING, AWG_TRAP_DUTY) => {
+                state.awg_duty_trap = Some(TrapDuty {
+                    rise: val[0] as f32 / 100.0,
+                    high: val[1] as f32 / 100.0,
+                    low: val[2] as f32 / 100.0,
+                });
+            }
+            (FUNC_AWG_SETTING, AWG_START_STOP) => {
+                state.awg_running_status = Some(if val0 == 0 {
+                    RunningStatus::Stop
+                } else {
+                    RunningStatus::Start
+                });
+            }
+            _ => debug!("simulated device ignoring {}", decoded),
+        }
+        self.commands.push(decoded);
+    }
+
+    /// Queues `total` bytes of samples to be read, a new capture unless the scope holds the last
+    /// one.
+    fn capture(&mut self, total: usize) {
+        let mut channels = self.state.enabled_channels();
+        if channels.is_empty() {
+            channels.push(1);
+        }
+        let num_samples = total.div_ceil(channels.len());
+        let dt = self.sample_interval();
+        let trigger_index = self
+            .state
+            .time_offset
+            .unwrap_or_default()
+            .min(num_samples.saturating_sub(1) as f32) as f64;
+
+        let running = self.state.running_status != Some(RunningStatus::Stop);
+        let trigger = if running { self.find_trigger() } else { None };
+        let start = match (running, &self.state.trigger_mode, trigger) {
+            (false, _, _) => None,
+            (true, Some(TriggerMode::Single), Some(at)) => {
+                self.state.running_status = Some(RunningStatus::Stop);
+                Some(at - trigger_index * dt)
+            }
+            (true, _, Some(at)) => Some(at - trigger_index * dt),
+            (true, Some(TriggerMode::Auto) | None, None) => Some(self.time),
+            (true, _, None) => None,
+        };
+
+        let start = match start {
+            Some(start) => start,
+            // Nothing held yet, or not as long, is taken as the scope free running.
+            None if self.held.len() == total => {
+                self.pending.extend(&self.held);
+                return;
+            }
+            None => self.time,
+        };
+
+        let mut frame = Vec::with_capacity(num_samples * channels.len());
+        for idx in 0..num_samples {
+            let at = start + idx as f64 * dt;
+            for channel_no in &channels {
+                let counts = self.counts(*channel_no, at) + self.noise * self.next_noise();
+                frame.push(self.clip(*channel_no, counts));
+            }
+        }
+        frame.truncate(total);
+
+        let duration = num_samples as f64 * dt;
+        self.time = self.time.max(start + duration) + duration * self.next_noise().abs() as f64;
+        self.pending.extend(&frame);
+        self.held = frame;
+    }
+
+    fn sample_interval(&self) -> f64 {
+        let time_scale = self.state.time_scale.as_ref().unwrap_or(&TimeScale::us200);
+        time_scale.raw_value() as f64 / SAMPLES_PER_DIVISION as f64
+    }
+
+    /// Time of the first edge of the trigger source through the trigger level from
+    /// [`Self::time`] on, `None` if it never gets there.
+    fn find_trigger(&self) -> Option<f64> {
+        let channel_no = self.state.trigger_source_channel.unwrap_or(1);
+        let period = self.period(&self.inputs[channel_no - 1])?;
+        let level = self.state.trigger_level.unwrap_or_default() - self.offset(channel_no);
+        let slope = self
+            .state
+            .trigger_slope
+            .as_ref()
+            .unwrap_or(&TriggerSlope::Rising);
+
+        let step = period / TRIGGER_SEARCH_STEPS as f64;
+        let mut before = self.counts(channel_no, self.time);
+        for idx in 1..=TRIGGER_SEARCH_STEPS {
+            let at = self.time + idx as f64 * step;
+            let after = self.counts(channel_no, at);
+            let rising = before < level && after >= level;
+            let falling = before > level && after <= level;
+            let crossed = match slope {
+                TriggerSlope::Rising => rising,
+                TriggerSlope::Falling => falling,
+                TriggerSlope::Both => rising || falling,
+            };
+            if crossed {
+                let fraction = ((level - before) / (after - before)) as f64;
+                return Some(at - step + fraction * step);
+            }
+            before = after;
+        }
+        None
+    }
+
+    /// Sample of the channel at the time, in ADC counts from the channel's zero level, before
+    /// noise and clipping.
+    fn counts(&self, channel_no: usize, at: f64) -> f32 {
+        let channel = self.state.channel(channel_no);
+        let signal = &self.inputs[channel_no - 1];
+        let coupling = channel.and_then(|it| it.coupling());
+        let volts = match coupling.unwrap_or(&Coupling::DC) {
+            Coupling::DC => self.volts(signal, at),
+            Coupling::AC => self.volts(signal, at) - self.mean(signal),
+            Coupling::GND => 0.0,
+        };
+        let scale = channel.and_then(|it| it.scale()).unwrap_or(&Scale::v1);
+        volts * COUNTS_PER_DIVISION / scale.volts_per_division()
+    }
+
+    /// Zero level of the channel in counts from the bottom of the screen.
+    fn offset(&self, channel_no: usize) -> f32 {
+        self.state
+            .channel(channel_no)
+            .and_then(|it| it.offset)
+            .unwrap_or_default()
+    }
+
+    /// The screen spans 0..=200 counts, the channel's zero level being at its offset. Whatever
+    /// is off the screen is clipped to its edge.
+    fn clip(&self, channel_no: usize, counts: f32) -> u8 {
+        let offset = self.offset(channel_no);
+        let counts = counts.clamp(-offset, RAW_LEVEL_MAX as f32 - offset);
+        (counts.round().clamp(i8::MIN as f32, i8::MAX as f32) as i8) as u8
+    }
+
+    fn volts(&self, signal: &SimulatedSignal, at: f64) -> f32 {
+        match signal {
+            SimulatedSignal::Dc(volts) => *volts,
+            SimulatedSignal::Sine {
+                frequency,
+                amplitude,
+                offset,
+            } => offset + amplitude * (TAU * *frequency as f64 * at).sin() as f32,
+            SimulatedSignal::Awg if !self.awg_running() => 0.0,
+            SimulatedSignal::Awg => {
+                let phase = (at * self.state.awg_frequency.unwrap_or_default() as f64).fract();
+                self.state.awg_offset.unwrap_or_default()
+                    + self.state.awg_amplitude.unwrap_or_default() * self.awg_shape(phase as f32)
+            }
+        }
+    }
+
+    /// Average over a period, what AC coupling takes away.
+    fn mean(&self, signal: &SimulatedSignal) -> f32 {
+        match signal {
+            SimulatedSignal::Dc(volts) => *volts,
+            SimulatedSignal::Sine { offset, .. } => *offset,
+            SimulatedSignal::Awg if !self.awg_running() => 0.0,
+            SimulatedSignal::Awg => {
+                let shape_mean = match self.state.awg_type.as_ref().unwrap_or(&AwgType::Sin) {
+                    AwgType::Square => {
+                        2.0 * self.state.awg_duty_square.unwrap_or(50.0) / 100.0 - 1.0
+                    }
+                    AwgType::Trap => {
+                        let duty = self.state.awg_duty_trap.as_ref().unwrap_or(&TrapDuty::ZERO);
+                        duty.high - duty.low
+                    }
+                    _ => 0.0,
+                };
+                self.state.awg_offset.unwrap_or_default()
+                    + self.state.awg_amplitude.unwrap_or_default() * shape_mean
+            }
+        }
+    }
+
+    /// Seconds the signal repeats after, `None` if it's flat.
+    fn period(&self, signal: &SimulatedSignal) -> Option<f64> {
+        let frequency = match signal {
+            SimulatedSignal::Dc(_) => return None,
+            SimulatedSignal::Sine { frequency, .. } => *frequency,
+            SimulatedSignal::Awg if !self.awg_running() => return None,
+            SimulatedSignal::Awg => self.state.awg_frequency.unwrap_or_default(),
+        };
+        if frequency > 0.0 {
+            Some(1.0 / frequency as f64)
+        } else {
+            None
+        }
+    }
+
+    fn awg_running(&self) -> bool {
+        self.state.awg_running_status == Some(RunningStatus::Start)
+    }
+
+    /// The AWG's waveform at the phase, 0..1 of the period, between -1 and 1.
+    fn awg_shape(&self, phase: f32) -> f32 {
+        match self.state.awg_type.as_ref().unwrap_or(&AwgType::Sin) {
+            AwgType::Square => {
+                let duty = self.state.awg_duty_square.unwrap_or(50.0) / 100.0;
+                if phase < duty {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            AwgType::Ramp => {
+                let duty = (self.state.awg_duty_ramp.unwrap_or(50.0) / 100.0).clamp(0.01, 0.99);
+                if phase < duty {
+                    -1.0 + 2.0 * phase / duty
+                } else {
+                    1.0 - 2.0 * (phase - duty) / (1.0 - duty)
+                }
+            }
+            // Rises, stays high, falls as long as it rose, and stays low for the rest.
+            AwgType::Trap => {
+                let duty = self.state.awg_duty_trap.as_ref().unwrap_or(&TrapDuty::ZERO);
+                let rise = duty.rise.max(f32::EPSILON);
+                let high = rise + duty.high;
+                let fall = high + duty.rise;
+                if phase < rise {
+                    -1.0 + 2.0 * phase / rise
+                } else if phase < high {
+                    1.0
+                } else if phase < fall {
+                    1.0 - 2.0 * (phase - high) / rise
+                } else {
+                    -1.0
+                }
+            }
+            AwgType::Sin | AwgType::Arb1 | AwgType::Arb2 | AwgType::Arb3 | AwgType::Arb4 => {
+                (std::f32::consts::TAU * phase).sin()
+            }
+        }
+    }
+
+    /// Uniform between -1 and 1, xorshift so a simulation is the same every run.
+    fn next_noise(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    }
+}
+
+impl Default for SimulatedHantek2D42 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for SimulatedHantek2D42 {
+    fn write(&mut self, endpoint: u8, buf: &[u8]) -> Result<usize, HantekUsbError> {
+        let cmd = match RawCommand::try_from(buf) {
+            Ok(cmd) if endpoint == WRITE_ENDPOINT => cmd,
+            _ => {
+                return Err(HantekUsbError::WriteError {
+                    error: libusb::Error::InvalidParam,
+                })
+            }
+        };
+        self.take(&cmd);
+        Ok(buf.len())
+    }
+
+    /// Times out unless a capture command asked for samples, as the device does.
+    fn read(&mut self, endpoint: u8, buf: &mut [u8]) -> Result<usize, HantekUsbError> {
+        if endpoint != READ_ENDPOINT {
+            return Err(HantekUsbError::ReadError {
+                error: libusb::Error::InvalidParam,
+            });
+        }
+        if self.pending.is_empty() {
+            return Err(HantekUsbError::ReadError {
+                error: libusb::Error::Timeout,
+            });
+        }
+        let length = buf.len().min(self.pending.len());
+        for (to, from) in buf.iter_mut().zip(self.pending.drain(..length)) {
+            *to = from;
+        }
+        Ok(length)
+    }
+}
+
+impl Hantek2D42<'static, SimulatedHantek2D42> {
+    /// A device of its own, see [`SimulatedHantek2D42::new`] for how it starts out. Nothing is
+    /// known of its settings until set, as with a real one.
+    pub fn simulated() -> Self {
+        Self::with_transport(SimulatedHantek2D42::new(), HantekConfig::new(NUM_CHANNELS))
+    }
+}
+
+fn device_function_of(val: u8) -> Option<DeviceFunction> {
+    match val {
+        SCREEN_VAL_SCOPE => Some(DeviceFunction::Scope),
+        SCREEN_VAL_AWG => Some(DeviceFunction::AWG),
+        SCREEN_VAL_DMM => Some(DeviceFunction::DMM),
+        _ => None,
+    }
+}
+
+fn coupling_of(val: u8) -> Option<Coupling> {
+    match val {
+        SCOPE_VAL_COUPLING_AC => Some(Coupling::AC),
+        SCOPE_VAL_COUPLING_DC => Some(Coupling::DC),
+        SCOPE_VAL_COUPLING_GND => Some(Coupling::GND),
+        _ => None,
+    }
+}
+
+fn probe_of(val: u8) -> Option<Probe> {
+    match val {
+        SCOPE_VAL_PROBE_X1 => Some(Probe::X1),
+        SCOPE_VAL_PROBE_X10 => Some(Probe::X10),
+        SCOPE_VAL_PROBE_X100 => Some(Probe::X100),
+        SCOPE_VAL_PROBE_X1000 => Some(Probe::X1000),
+        _ => None,
+    }
+}
+
+fn trigger_slope_of(val: u8) -> Option<TriggerSlope> {
+    match val {
+        SCOPE_VAL_TRIGGER_SLOPE_RISING => Some(TriggerSlope::Rising),
+        SCOPE_VAL_TRIGGER_SLOPE_FALLING => Some(TriggerSlope::Falling),
+        SCOPE_VAL_TRIGGER_SLOPE_BOTH => Some(TriggerSlope::Both),
+        _ => None,
+    }
+}
+
+fn trigger_mode_of(val: u8) -> Option<TriggerMode> {
+    match val {
+        SCOPE_VAL_TRIGGER_MODE_AUTO => Some(TriggerMode::Auto),
+        SCOPE_VAL_TRIGGER_MODE_NORMAL => Some(TriggerMode::Normal),
+        SCOPE_VAL_TRIGGER_MODE_SINGLE => Some(TriggerMode::Single),
+        _ => None,
+    }
+}
+
+fn awg_type_of(val: u8) -> Option<AwgType> {
+    match val {
+        AWG_VAL_TYPE_SQUARE => Some(AwgType::Square),
+        AWG_VAL_TYPE_RAMP => Some(AwgType::Ramp),
+        AWG_VAL_TYPE_SIN => Some(AwgType::Sin),
+        AWG_VAL_TYPE_TRAP => Some(AwgType::Trap),
+        AWG_VAL_TYPE_ARB1 => Some(AwgType::Arb1),
+        AWG_VAL_TYPE_ARB2 => Some(AwgType::Arb2),
+        AWG_VAL_TYPE_ARB3 => Some(AwgType::Arb3),
+        AWG_VAL_TYPE_ARB4 => Some(AwgType::Arb4),
+        _ => None,
+    }
+}
@@ -0,0 +1,71 @@
+//! Firmware bugs known of a model, so frontends warn about them only where they apply.
+//!
+//! Entries are keyed by the USB id of the model and the release number of its device
+//! descriptor, the only firmware version there is to read. No release fixing any of them is
+//! known yet, an entry without releases applies to every release. None of the known ones has a
+//! workaround either, the value is set, only the screen of the device doesn't show it.
+
+use strum::IntoEnumIterator;
+use strum_macros::{Display, EnumIter};
+
+use crate::models::hantek2d42::USB_ID as USB_ID_2D42;
+
+#[derive(Display, Debug, Clone, Copy, EnumIter, PartialEq, Eq)]
+pub enum Quirk {
+    AwgOffsetNotShown,
+    AwgRunningNotShown,
+}
+
+impl Quirk {
+    pub fn my_iter() -> impl Iterator<Item = Quirk> {
+        Self::iter()
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Quirk::AwgOffsetNotShown => {
+                "The offset in the UI will not be updated properly, but it is set. \
+                 This is a bug in the device firmware."
+            }
+            Quirk::AwgRunningNotShown => {
+                "The running status in the UI will not be updated properly, but it is set. \
+                 This is a bug in the device firmware."
+            }
+        }
+    }
+
+    // Because CLion doesn't like the Display implemented by strum.
+    pub fn my_to_string(&self) -> impl std::fmt::Display + '_ {
+        self
+    }
+}
+
+struct Entry {
+    quirk: Quirk,
+    usb_id: (u16, u16),
+    /// Device releases as in `HantekUsbDevice::device_release`, empty for all of them.
+    releases: &'static [&'static str],
+}
+
+const REGISTRY: &[Entry] = &[
+    Entry {
+        quirk: Quirk::AwgOffsetNotShown,
+        usb_id: USB_ID_2D42,
+        releases: &[],
+    },
+    Entry {
+        quirk: Quirk::AwgRunningNotShown,
+        usb_id: USB_ID_2D42,
+        releases: &[],
+    },
+];
+
+/// Quirks of the model with the given USB id running the given device release.
+pub fn quirks_of(usb_id: (u16, u16), release: &str) -> Vec<Quirk> {
+    REGISTRY
+        .iter()
+        .filter(|it| it.usb_id == usb_id)
+        .filter(|it| it.releases.is_empty() || it.releases.contains(&release))
+        .map(|it| it.quirk)
+        .collect()
+}
@@ -0,0 +1,117 @@
+//! A device owning its libusb context, so it isn't tied to the scope the context was created in
+//! and can be moved to, or shared between, threads.
+//!
+//! [`Hantek2D42`] borrows the context it was opened with. [`OwnedHantek2D42`] keeps the context
+//! on the heap for as long as the device lives, and [`SharedHantek2D42`] puts one behind a mutex
+//! so every clone of it drives the same device, one call at a time.
+//!
+//! The device is only ever lent out for a lifetime of the borrow, never as `'static`: anything
+//! taken out of it, e.g. a libusb handle, or a device swapped with another one, would otherwise
+//! outlive the context freed on drop.
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use libusb::Context;
+
+use crate::models::hantek2d42::{Hantek2D42, Hantek2D42Error};
+
+/// The context a device borrows from, freed by [`OwnedHantek2D42`] once the device is gone.
+struct ContextPtr(*mut Context);
+
+// SAFETY: libusb contexts are thread safe, the pointer is only ever turned back into the box.
+unsafe impl Send for ContextPtr {}
+unsafe impl Sync for ContextPtr {}
+
+pub struct OwnedHantek2D42 {
+    // Dropped before the context it borrows from, see Drop. Never handed out as 'static.
+    hantek: Option<Hantek2D42<'static>>,
+    context: ContextPtr,
+}
+
+impl OwnedHantek2D42 {
+    /// Same as [`Hantek2D42::open`], taking over the context.
+    pub fn open(context: Context, timeout: Duration) -> Result<Self, Hantek2D42Error> {
+        let context = Box::into_raw(Box::new(context));
+        // SAFETY: the context outlives the device, it's freed in Drop after the device is.
+        match Hantek2D42::open(unsafe { &*context }, timeout) {
+            Ok(hantek) => Ok(Self {
+                hantek: Some(hantek),
+                context: ContextPtr(context),
+            }),
+            Err(e) => {
+                // SAFETY: nothing borrows it, opening failed.
+                unsafe { drop(Box::from_raw(context)) };
+                Err(e)
+            }
+        }
+    }
+
+    /// Same as [`Hantek2D42::reconnect`], with the context the device was opened with.
+    pub fn reconnect(&mut self) -> Result<(), Hantek2D42Error> {
+        // SAFETY: freed only in Drop, after the device is gone.
+        let context = unsafe { &*self.context.0 };
+        self.device_mut().reconnect(context)
+    }
+
+    /// The device, borrowing the context no longer than `self`.
+    pub fn get(&self) -> &Hantek2D42<'_> {
+        self.hantek.as_ref().expect("device is only taken on drop")
+    }
+
+    /// Runs `call` with the device. It works for any lifetime of the context, so nothing
+    /// borrowing it can be kept past the call.
+    pub fn with<R>(&mut self, call: impl for<'c> FnOnce(&mut Hantek2D42<'c>) -> R) -> R {
+        call(self.device_mut())
+    }
+
+    fn device_mut(&mut self) -> &mut Hantek2D42<'static> {
+        self.hantek.as_mut().expect("device is only taken on drop")
+    }
+}
+
+impl Drop for OwnedHantek2D42 {
+    fn drop(&mut self) {
+        self.hantek.take();
+        // SAFETY: leaked in open, and nothing borrows it anymore.
+        unsafe { drop(Box::from_raw(self.context.0)) };
+    }
+}
+
+/// A device any number of threads hold a clone of, each call locking it for its duration.
+/// Settings of a capture that must not be interleaved with another thread's are best done
+/// under a single [`Self::lock`].
+#[derive(Clone)]
+pub struct SharedHantek2D42 {
+    inner: Arc<Mutex<OwnedHantek2D42>>,
+}
+
+impl SharedHantek2D42 {
+    pub fn new(hantek: OwnedHantek2D42) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(hantek)),
+        }
+    }
+
+    pub fn open(context: Context, timeout: Duration) -> Result<Self, Hantek2D42Error> {
+        OwnedHantek2D42::open(context, timeout).map(Self::new)
+    }
+
+    /// Blocks until no other thread uses the device. A thread panicking while holding the lock
+    /// doesn't make the device unusable, its config may be off though.
+    pub fn lock(&self) -> MutexGuard<'_, OwnedHantek2D42> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Runs `call` with the device locked, see [`OwnedHantek2D42::with`].
+    pub fn with<R>(&self, call: impl for<'c> FnOnce(&mut Hantek2D42<'c>) -> R) -> R {
+        self.lock().with(call)
+    }
+}
+
+// Fails to build once anything the device holds stops being Send. libusb 0.3 marks Context,
+// Device and DeviceHandle Send and Sync despite their raw pointers, libusb being thread safe.
+const _: fn() = || {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<SharedHantek2D42>();
+};
@@ -0,0 +1,101 @@
+//! Compatibility check of the time scales and channel scales against the connected device.
+//!
+//! Every value is set in turn and the outcome recorded. The device has no read-back, so a
+//! value the firmware silently ignores or remaps can't be told from one it applied; such
+//! values show up as [`Outcome::Accepted`] and only outright rejections are caught.
+
+use std::fmt::{Display, Formatter};
+
+use crate::device::cfg::{Scale, TimeScale};
+use crate::models::hantek2d42::{Hantek2D42, Hantek2D42Error};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The write went through, whether the firmware applied the value as is is unknown.
+    Accepted,
+    /// The write failed, with the error it failed with.
+    Rejected(String),
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Accepted => write!(f, "accepted"),
+            Outcome::Rejected(_) => write!(f, "rejected"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// Dotted name as in [`crate::device::cfg::option_catalog`].
+    pub setting: &'static str,
+    pub channel_no: Option<usize>,
+    pub value: String,
+    pub outcome: Outcome,
+}
+
+/// Outcome of each value on a device, identified by its USB release number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompatibilityMatrix {
+    pub device_release: String,
+    pub entries: Vec<Entry>,
+}
+
+impl CompatibilityMatrix {
+    pub fn rejected(&self) -> impl Iterator<Item = &Entry> {
+        self.entries
+            .iter()
+            .filter(|it| matches!(it.outcome, Outcome::Rejected(_)))
+    }
+}
+
+fn outcome(result: Result<(), Hantek2D42Error>) -> Outcome {
+    match result {
+        Ok(()) => Outcome::Accepted,
+        Err(e) => Outcome::Rejected(e.to_string()),
+    }
+}
+
+/// Sets every time scale, then every scale on each of the given channels. The settings known
+/// beforehand are restored afterwards, the others are left at the last value tried.
+pub fn verify_scales(
+    hantek: &mut Hantek2D42,
+    channels: &[usize],
+) -> Result<CompatibilityMatrix, Hantek2D42Error> {
+    let config = hantek.get_config().clone();
+    let mut entries = vec![];
+
+    for time_scale in TimeScale::my_iter() {
+        entries.push(Entry {
+            setting: "time_scale",
+            channel_no: None,
+            value: time_scale.to_string(),
+            outcome: outcome(hantek.set_time_scale(time_scale)),
+        });
+    }
+    for channel_no in channels {
+        for scale in Scale::my_iter() {
+            entries.push(Entry {
+                setting: "channel.scale",
+                channel_no: Some(*channel_no),
+                value: scale.to_string(),
+                outcome: outcome(hantek.set_channel_scale(*channel_no, scale)),
+            });
+        }
+    }
+
+    if let Some(time_scale) = config.time_scale.clone() {
+        hantek.set_time_scale(time_scale)?;
+    }
+    for channel_no in channels {
+        if let Some(scale) = config.channel(*channel_no).and_then(|it| it.scale.clone()) {
+            hantek.set_channel_scale(*channel_no, scale)?;
+        }
+    }
+
+    Ok(CompatibilityMatrix {
+        device_release: hantek.usb.device_release(),
+        entries,
+    })
+}
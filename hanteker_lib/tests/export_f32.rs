@@ -0,0 +1,65 @@
+//! The f32le stream is the samples in volts, interleaved, and nothing else.
+
+use hanteker_lib::capture::{raw_to_volts, CaptureFrame};
+use hanteker_lib::device::cfg::{Scale, TimeScale};
+use hanteker_lib::export::ExportFormat;
+
+fn frame(channels: Vec<usize>, time_scale: TimeScale) -> CaptureFrame {
+    let raw: Vec<u8> = (0..100).map(|it| (it as i8 - 50) as u8).collect();
+    let scales = vec![Some(Scale::v1), Some(Scale::mv200)][..channels.len()].to_vec();
+    CaptureFrame::new(channels, scales, Some(time_scale), raw)
+}
+
+fn floats(bytes: &[u8]) -> Vec<f32> {
+    bytes
+        .chunks_exact(4)
+        .map(|it| f32::from_le_bytes(it.try_into().unwrap()))
+        .collect()
+}
+
+#[test]
+fn samples_are_interleaved_volts() {
+    let frame = frame(vec![1, 2], TimeScale::us100);
+    let mut out = vec![];
+    {
+        let mut sink = ExportFormat::F32le.sink(&mut out);
+        sink.write_frame(&frame).unwrap();
+        sink.write_frame(&frame).unwrap();
+        sink.flush().unwrap();
+    }
+
+    let samples = floats(&out);
+    assert_eq!(samples.len(), 2 * frame.raw.len());
+    for (idx, sample) in samples[..frame.raw.len()].iter().enumerate() {
+        let expected = match idx % 2 {
+            0 => raw_to_volts(frame.raw[idx], &Scale::v1),
+            _ => raw_to_volts(frame.raw[idx], &Scale::mv200),
+        };
+        assert_eq!(*sample, expected);
+    }
+    assert_eq!(samples[..frame.raw.len()], samples[frame.raw.len()..]);
+}
+
+#[test]
+fn stream_keeps_its_channels_and_rate() {
+    let mut out = vec![];
+    let mut sink = ExportFormat::F32le.sink(&mut out);
+    sink.write_frame(&frame(vec![1, 2], TimeScale::us100))
+        .unwrap();
+    assert!(sink.write_frame(&frame(vec![1], TimeScale::us100)).is_err());
+    assert!(sink
+        .write_frame(&frame(vec![1, 2], TimeScale::ms1))
+        .is_err());
+}
+
+#[test]
+fn unknown_scale_is_refused() {
+    let mut frame = frame(vec![1, 2], TimeScale::us100);
+    frame.scales[1] = None;
+    let mut out = vec![];
+    let mut sink = ExportFormat::F32le.sink(&mut out);
+    let e = sink.write_frame(&frame).unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+    drop(sink);
+    assert!(out.is_empty());
+}
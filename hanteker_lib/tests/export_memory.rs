@@ -0,0 +1,121 @@
+//! The row sinks must write indefinite captures in bounded memory. There's no device in the
+//! loop, frames are synthesized on the fly as a capture would hand them out, and every byte
+//! written is counted then dropped.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::io::{self, BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use hanteker_lib::capture::CaptureFrame;
+use hanteker_lib::device::cfg::{Scale, TimeScale};
+use hanteker_lib::export::ExportFormat;
+
+struct Counting;
+
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+/// Held while measuring, the counters are global and tests run in parallel.
+static MEASURING: Mutex<()> = Mutex::new(());
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            let live = LIVE.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(live, Ordering::SeqCst);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        LIVE.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Samples per channel in each frame, as with the default capture chunk.
+const CHUNK: usize = 1000;
+
+/// Headroom allowed over the memory in use once the first frame was written.
+const MAX_GROWTH: usize = 1024 * 1024;
+
+struct Discard<'a> {
+    written: &'a Cell<u64>,
+}
+
+impl Write for Discard<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.written.set(self.written.get() + buf.len() as u64);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Two channels of a sawtooth wave, one with its scale unknown so both the volts and the raw
+/// column paths are taken.
+fn frame(seq: usize) -> CaptureFrame {
+    let raw: Vec<u8> = (0..CHUNK * 2)
+        .map(|idx| ((seq * CHUNK + idx / 2) % 200) as i32 - 100)
+        .map(|it| it as i8 as u8)
+        .collect();
+    CaptureFrame::new(
+        vec![1, 2],
+        vec![Some(Scale::v1), None],
+        Some(TimeScale::us100),
+        raw,
+    )
+}
+
+/// Writes frames until `bytes` are out, returns the peak growth of the heap past the first
+/// frame.
+fn stream(format: ExportFormat, bytes: u64) -> usize {
+    let written = Cell::new(0);
+    let mut sink = format.sink(BufWriter::new(Discard { written: &written }));
+    sink.write_frame(&frame(0)).unwrap();
+    sink.flush().unwrap();
+    let baseline = LIVE.load(Ordering::SeqCst);
+    PEAK.store(baseline, Ordering::SeqCst);
+
+    let mut seq = 1;
+    while written.get() < bytes {
+        sink.write_frame(&frame(seq)).unwrap();
+        sink.flush().unwrap();
+        seq += 1;
+    }
+    PEAK.load(Ordering::SeqCst).saturating_sub(baseline)
+}
+
+fn assert_flat(format: ExportFormat, bytes: u64) {
+    // A test that failed measuring leaves the counters as good as any other.
+    let _measuring = MEASURING.lock().unwrap_or_else(|e| e.into_inner());
+    let growth = stream(format.clone(), bytes);
+    assert!(
+        growth < MAX_GROWTH,
+        "{} sink grew the heap by {} bytes",
+        format,
+        growth
+    );
+}
+
+#[test]
+fn memory_stays_flat() {
+    assert_flat(ExportFormat::Csv, 64 * 1024 * 1024);
+    assert_flat(ExportFormat::Jsonl, 64 * 1024 * 1024);
+}
+
+/// Run with `cargo test --release -- --ignored`, takes a few minutes.
+#[test]
+#[ignore]
+fn multi_gigabyte_memory_stays_flat() {
+    assert_flat(ExportFormat::Csv, 4 * 1024 * 1024 * 1024);
+    assert_flat(ExportFormat::Jsonl, 4 * 1024 * 1024 * 1024);
+}
@@ -0,0 +1,125 @@
+//! The config and its enums must survive a round trip through serde, enums being written by the
+//! same names the cli takes and displays. Run with `cargo test --features serde`.
+
+use std::fmt::Debug;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use hanteker_lib::device::cfg::{
+    Adjustment, AwgType, Coupling, DeviceFunction, HantekConfig, Probe, RunningStatus, Scale,
+    TimeScale, TrapDuty, TriggerMode, TriggerSlope,
+};
+
+fn round_trip<T>(value: &T) -> T
+where
+    T: Serialize + DeserializeOwned,
+{
+    let json = serde_json::to_string(value).unwrap();
+    serde_json::from_str(&json).unwrap()
+}
+
+fn assert_round_trips<T>(options: Vec<(String, T)>)
+where
+    T: Serialize + DeserializeOwned + PartialEq + Debug,
+{
+    for (name, value) in options {
+        assert_eq!(serde_json::to_value(&value).unwrap(), name);
+        assert_eq!(round_trip(&value), value);
+    }
+}
+
+#[test]
+fn enums_round_trip_by_name() {
+    assert_round_trips(DeviceFunction::my_options());
+    assert_round_trips(RunningStatus::my_options());
+    assert_round_trips(Coupling::my_options());
+    assert_round_trips(Probe::my_options());
+    assert_round_trips(Scale::my_options());
+    assert_round_trips(TimeScale::my_options());
+    assert_round_trips(TriggerSlope::my_options());
+    assert_round_trips(TriggerMode::my_options());
+    assert_round_trips(AwgType::my_options());
+}
+
+#[test]
+fn unknown_names_are_rejected() {
+    assert!(serde_json::from_str::<Scale>("\"v3\"").is_err());
+    assert!(serde_json::from_str::<Coupling>("\"ac\"").is_err());
+}
+
+#[test]
+fn empty_config_round_trips() {
+    let config = HantekConfig::new(2);
+    assert_eq!(round_trip(&config), config);
+}
+
+#[test]
+fn config_round_trips() {
+    let mut config = HantekConfig::new(2);
+    config.timeout = Some(Duration::from_millis(1500));
+    config.device_function = Some(DeviceFunction::Scope);
+    config.channel_mut(1).unwrap().enabled = Some(true);
+    config.channel_mut(2).unwrap().enabled = Some(false);
+    config.channel_mut(1).unwrap().coupling = Some(Coupling::AC);
+    config.channel_mut(2).unwrap().probe = Some(Probe::X10);
+    config.channel_mut(1).unwrap().scale = Some(Scale::mv200);
+    config.channel_mut(1).unwrap().offset = Some(-0.25);
+    config.channel_mut(2).unwrap().bandwidth_limit = Some(true);
+    config.channel_mut(1).unwrap().offset_adjustment = Some(Adjustment::new(0.8, -0.8));
+    config.time_scale = Some(TimeScale::us100);
+    config.time_offset = Some(0.5);
+    config.running_status = Some(RunningStatus::Start);
+    config.trigger_source_channel = Some(2);
+    config.trigger_slope = Some(TriggerSlope::Falling);
+    config.trigger_mode = Some(TriggerMode::Single);
+    config.trigger_level = Some(1.5);
+    config.awg_type = Some(AwgType::Trap);
+    config.awg_frequency = Some(1000.0);
+    config.awg_amplitude = Some(2.5);
+    config.awg_duty_trap = Some(TrapDuty {
+        high: 0.4,
+        low: 0.4,
+        rise: 0.1,
+    });
+    config.awg_running_status = Some(RunningStatus::Stop);
+
+    assert_eq!(round_trip(&config), config);
+}
+
+#[test]
+fn config_of_channel_maps_is_read() {
+    let json = r#"{
+        "timeout": null,
+        "device_function": "Scope",
+        "enabled_channels": {"1": true, "2": null},
+        "channel_coupling": {"1": "AC", "2": null},
+        "channel_probe": {"1": null, "2": "X10"},
+        "channel_scale": {"1": "mv200", "2": null},
+        "channel_offset": {"1": -0.25, "2": null},
+        "channel_bandwidth_limit": {"1": null, "2": true},
+        "channel_offset_adjustment": {"1": null, "2": null},
+        "time_scale": "us100",
+        "trigger_source_channel": 2
+    }"#;
+    let mut expected = HantekConfig::new(2);
+    expected.device_function = Some(DeviceFunction::Scope);
+    expected.channel_mut(1).unwrap().enabled = Some(true);
+    expected.channel_mut(1).unwrap().coupling = Some(Coupling::AC);
+    expected.channel_mut(2).unwrap().probe = Some(Probe::X10);
+    expected.channel_mut(1).unwrap().scale = Some(Scale::mv200);
+    expected.channel_mut(1).unwrap().offset = Some(-0.25);
+    expected.channel_mut(2).unwrap().bandwidth_limit = Some(true);
+    expected.time_scale = Some(TimeScale::us100);
+    expected.trigger_source_channel = Some(2);
+
+    let config: HantekConfig = serde_json::from_str(json).unwrap();
+    assert_eq!(config, expected);
+    assert_eq!(round_trip(&config), expected);
+}
+
+#[test]
+fn unknown_config_fields_are_rejected() {
+    assert!(serde_json::from_str::<HantekConfig>(r#"{"channel_gain": {}}"#).is_err());
+}
@@ -0,0 +1,59 @@
+//! Frames handed to several consumers share their samples, and each modifying its own doesn't
+//! show in the others.
+
+use hanteker_lib::capture::{Acquisition, AcquisitionMode, BufferPool, CaptureFrame};
+use hanteker_lib::device::cfg::{Scale, TimeScale};
+use hanteker_lib::dsp::{Filter, FilterSpec};
+
+const NUM_SAMPLES: usize = 1000;
+
+fn frame(pool: &mut BufferPool) -> CaptureFrame {
+    let mut raw = pool.take(NUM_SAMPLES * 2);
+    for (idx, it) in raw.iter_mut().enumerate() {
+        *it = ((idx % 50) as i8 - 25) as u8;
+    }
+    CaptureFrame::new(
+        vec![1, 2],
+        vec![Some(Scale::v1), Some(Scale::v1)],
+        Some(TimeScale::us100),
+        raw,
+    )
+}
+
+#[test]
+fn clones_share_samples() {
+    let frame = frame(&mut BufferPool::default());
+    let clone = frame.clone();
+    assert_eq!(frame.raw.as_ptr(), clone.raw.as_ptr());
+}
+
+#[test]
+fn modifying_a_clone_leaves_the_others() {
+    let original = frame(&mut BufferPool::default());
+    let mut filtered = original.clone();
+    let mut filter = Filter::new(&FilterSpec::MovingAverage(10), 250_000.0).unwrap();
+    assert!(filtered.filter_channel(1, &mut filter));
+    assert_ne!(original.raw, filtered.raw);
+    assert_eq!(original.channel_raw(2), filtered.channel_raw(2));
+
+    let mut high_res = original.clone();
+    assert!(Acquisition::new(AcquisitionMode::HighRes(10)).apply(&mut high_res));
+    assert!(original.fine.is_none());
+    assert_ne!(original.raw, high_res.raw);
+    assert_eq!(frame(&mut BufferPool::default()).raw, original.raw);
+}
+
+#[test]
+fn pool_takes_back_only_unshared_buffers() {
+    let mut pool = BufferPool::new(1);
+    let first = frame(&mut pool);
+    let ptr = first.raw.as_ptr();
+    pool.put(first.raw);
+    assert_eq!(frame(&mut pool).raw.as_ptr(), ptr);
+
+    let mut pool = BufferPool::new(1);
+    let first = frame(&mut pool);
+    let held = first.clone();
+    pool.put(first.raw);
+    assert_ne!(frame(&mut pool).raw.as_ptr(), held.raw.as_ptr());
+}
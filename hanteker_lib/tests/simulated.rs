@@ -0,0 +1,120 @@
+//! The simulated device driven through the same API as a real one, its captures following the
+//! settings sent to it.
+
+use hanteker_lib::device::cfg::{AwgType, Coupling, RunningStatus, Scale, TriggerMode};
+use hanteker_lib::models::hantek2d42::Hantek2D42;
+use hanteker_lib::models::simulated::{SimulatedHantek2D42, SimulatedSignal};
+
+const NUM_SAMPLES: usize = 1000;
+
+/// Channel 1 at 1V a division, with no noise so samples are exact.
+fn device() -> Hantek2D42<'static, SimulatedHantek2D42> {
+    let mut hantek = Hantek2D42::simulated();
+    hantek.usb.set_noise(0.0);
+    hantek.enable_channel(1).unwrap();
+    hantek.set_channel_scale(1, Scale::v1).unwrap();
+    hantek
+}
+
+fn samples(hantek: &mut Hantek2D42<'static, SimulatedHantek2D42>) -> Vec<i8> {
+    let raw = hantek.capture(&[1], NUM_SAMPLES).unwrap();
+    raw.into_iter().map(|it| it as i8).collect()
+}
+
+#[test]
+fn settings_reach_the_device() {
+    let mut hantek = device();
+    hantek.set_channel_coupling(1, Coupling::AC).unwrap();
+    hantek.set_awg_type(AwgType::Square).unwrap();
+    hantek.set_awg_frequency(2000.0).unwrap();
+    hantek.set_awg_amplitude(-1.5).unwrap();
+    hantek.set_channel_offset(1, 40).unwrap();
+
+    let state = hantek.usb.state();
+    assert_eq!(state.channel(1).unwrap().coupling, Some(Coupling::AC));
+    assert_eq!(state.channel(1).unwrap().offset, Some(40.0));
+    assert_eq!(state.awg_type, Some(AwgType::Square));
+    assert_eq!(state.awg_frequency, Some(2000.0));
+    assert_eq!(state.awg_amplitude, Some(-1.5));
+    assert_eq!(hantek.usb.commands().len(), 7);
+}
+
+#[test]
+fn scale_and_offset() {
+    let mut hantek = device();
+    hantek.usb.set_input(1, SimulatedSignal::Dc(2.0));
+    assert!(samples(&mut hantek).iter().all(|it| *it == 50));
+
+    hantek.set_channel_scale(1, Scale::v2).unwrap();
+    assert!(samples(&mut hantek).iter().all(|it| *it == 25));
+
+    // 5 divisions up from the center, clipped at the top of the screen 4 divisions up.
+    hantek.set_channel_scale(1, Scale::v1).unwrap();
+    hantek.usb.set_input(1, SimulatedSignal::Dc(5.0));
+    assert!(samples(&mut hantek).iter().all(|it| *it == 100));
+
+    // Lowering the zero level puts it back on the screen.
+    hantek.set_channel_offset(1, 40).unwrap();
+    assert!(samples(&mut hantek).iter().all(|it| *it == 125));
+}
+
+#[test]
+fn coupling() {
+    let mut hantek = device();
+    hantek.set_awg_offset(1.0).unwrap();
+    hantek.awg_start().unwrap();
+
+    let mean = |samples: &[i8]| samples.iter().map(|it| *it as f32).sum::<f32>() / 1000.0;
+    let dc = samples(&mut hantek);
+    hantek.set_channel_coupling(1, Coupling::AC).unwrap();
+    let ac = samples(&mut hantek);
+    assert!((mean(&dc) - 25.0).abs() < 1.0, "{}", mean(&dc));
+    assert!(mean(&ac).abs() < 1.0, "{}", mean(&ac));
+
+    hantek.set_channel_coupling(1, Coupling::GND).unwrap();
+    assert!(samples(&mut hantek).iter().all(|it| *it == 0));
+}
+
+#[test]
+fn trigger_point_is_where_the_time_offset_says() {
+    let mut hantek = device();
+    hantek.awg_start().unwrap();
+    for position in [100, 200] {
+        hantek.set_time_offset(position).unwrap();
+        for _ in 0..3 {
+            let samples = samples(&mut hantek);
+            let at = position as usize;
+            assert!(samples[at].abs() <= 1, "{:?}", &samples[at - 2..at + 3]);
+            assert!(samples[at - 2] < 0 && samples[at + 2] > 0);
+        }
+    }
+}
+
+#[test]
+fn normal_trigger_holds_the_capture_without_a_trigger() {
+    let mut hantek = device();
+    hantek.usb.set_noise(1.0);
+    hantek.awg_start().unwrap();
+    hantek.set_trigger_mode(TriggerMode::Normal).unwrap();
+    let triggered = samples(&mut hantek);
+    assert_ne!(triggered, samples(&mut hantek));
+
+    // Above anything the sine reaches.
+    hantek.set_trigger_level(190).unwrap();
+    let held = samples(&mut hantek);
+    assert_eq!(held, samples(&mut hantek));
+}
+
+#[test]
+fn single_trigger_stops_the_scope() {
+    let mut hantek = device();
+    hantek.usb.set_noise(1.0);
+    hantek.awg_start().unwrap();
+    hantek.set_trigger_mode(TriggerMode::Single).unwrap();
+    let triggered = samples(&mut hantek);
+    assert_eq!(hantek.usb.state().running_status, Some(RunningStatus::Stop));
+    assert_eq!(triggered, samples(&mut hantek));
+
+    hantek.start().unwrap();
+    assert_ne!(triggered, samples(&mut hantek));
+}